@@ -1,6 +1,9 @@
 use graphics::player::world_renderer::WorldRenderer;
 use model::args::Args;
 use model::game::player::Player;
+use model::game::crafting::{CraftingManager, RECIPES_FILE};
+use model::game::item_registry::{ItemRegistry, ITEMS_FILE};
+use model::world::block_registry::{BlockRegistry, BLOCKS_FILE};
 use model::world::world::World;
 use network::proxy::Proxy;
 use network::tcp_proxy::TcpProxy;
@@ -8,11 +11,14 @@ use network::tcp_proxy::TcpProxy;
 pub fn main() {
     tracing_subscriber::fmt::init();
     let args = Args::from_args();
+    BlockRegistry::install_from_file(BLOCKS_FILE);
+    ItemRegistry::install_from_file(ITEMS_FILE);
+    CraftingManager::install_from_file(RECIPES_FILE);
     let url = args.url();
 
     // The proxy currently holds the server,
     let proxy = TcpProxy::new(&url);
-    proxy.lock().unwrap().login(args.name);
+    proxy.lock().unwrap().login(args.name, args.view_distance);
 
     // The client is initialized with an empty world, as it will be the responsibility of the server
     // to provide it with the chunks.
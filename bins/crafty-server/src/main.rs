@@ -1,8 +1,14 @@
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use model::args::{Args, WorldInitializer};
-use model::server::game_server::{handle_entity_thread, GameServer};
+use model::game::crafting::{CraftingManager, RECIPES_FILE};
+use model::game::item_registry::{ItemRegistry, ITEMS_FILE};
+use model::world::block_registry::{BlockRegistry, BLOCKS_FILE};
+use model::server::game_server::{handle_autosave_thread, handle_entity_thread, GameServer};
+use model::server::monster_spawner::SpawningRules;
 use model::world::generation::world_generator::WorldGenerator;
 use model::world::world::World;
+use model::world::world_storage::WorldStorage;
 use network::tcp_server::TcpServer;
 use tracing::info;
 
@@ -10,25 +16,53 @@ fn main() {
     tracing_subscriber::fmt::init();
 
     let args = Args::from_args();
+    BlockRegistry::install_from_file(BLOCKS_FILE);
+    ItemRegistry::install_from_file(ITEMS_FILE);
+    CraftingManager::install_from_file(RECIPES_FILE);
 
-    // Create the initial world
-    info!("[Server] Creating a world ...");
-    let world = match args.init {
-        WorldInitializer::RANDOM => WorldGenerator::create_new_random_world(10),
-        WorldInitializer::FLAT => WorldGenerator::create_new_flat_world(10),
-        WorldInitializer::DISK => World::from_file("map.json").unwrap(),
+    // Create the initial world.
+    // The chunks are generated on demand by the server, as the players explore the world.
+    info!("[Server] Creating a world with seed {} ...", args.seed);
+    let (world, generator) = match args.init {
+        WorldInitializer::RANDOM => (World::empty(), WorldGenerator::random(args.seed)),
+        WorldInitializer::FLAT => (World::empty(), WorldGenerator::flat()),
+        WorldInitializer::DISK => (World::empty(), WorldGenerator::random(args.seed)),
     };
     info!("                          ... Finished !");
 
     // Create the game model of the server.
     // It holds the 'full' world
     // It is put inside an ARC to be shared across each thread, and inside a Mute to have interior mutability.
-    let game = Arc::new(Mutex::new(GameServer::new(world)));
+    let mut server = GameServer::with_generator(world, generator);
+    server.set_max_view_distance(args.view_distance);
+    server.set_keep_inventory(args.keep_inventory);
+    server.set_spawning_rules(SpawningRules {
+        max_per_player: args.max_monsters,
+        ..SpawningRules::default()
+    });
+    // Whatever the initializer, the world is saved in its directory. The chunks are loaded from
+    // the disk on demand, and generated if they were never saved.
+    server.attach_storage(WorldStorage::new(&args.world_dir).unwrap());
+    // The spawn point given by the user replaces the one saved with the world
+    if let Some(spawn_point) = args.spawn_point {
+        server.set_spawn_point(spawn_point);
+    }
+    let game = Arc::new(Mutex::new(server));
 
     // Spawn the entity thead
     let ref1 = game.clone();
     std::thread::spawn(move || handle_entity_thread(ref1));
 
+    // Spawn the autosave thread
+    let ref2 = game.clone();
+    let period = Duration::from_secs(args.autosave);
+    std::thread::spawn(move || handle_autosave_thread(ref2, period));
+
+    // On SIGINT / SIGTERM, the world is saved and the connections are closed
+    let ref3 = game.clone();
+    ctrlc::set_handler(move || ref3.lock().unwrap().shutdown())
+        .expect("Cannot set the handler of the termination signals");
+
     // Starts the TCP server
     TcpServer::start(&args.url(), game)
 }
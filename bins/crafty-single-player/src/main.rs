@@ -1,41 +1,73 @@
 use graphics::player::world_renderer::WorldRenderer;
-use model::args::WorldInitializer;
+use model::args::{
+    keep_inventory_from_args, max_monsters_from_args, seed_from_args, spawn_point_from_args,
+    view_distance_from_args, world_dir_from_args, WorldInitializer, DEFAULT_AUTOSAVE_PERIOD,
+};
 use model::game::player::Player;
-use model::server::game_server::{handle_entity_thread, GameServer};
+use model::game::crafting::{CraftingManager, RECIPES_FILE};
+use model::game::item_registry::{ItemRegistry, ITEMS_FILE};
+use model::world::block_registry::{BlockRegistry, BLOCKS_FILE};
+use model::server::game_server::{handle_autosave_thread, handle_entity_thread, GameServer};
+use model::server::monster_spawner::SpawningRules;
 use model::world::generation::world_generator::WorldGenerator;
 use model::world::world::World;
+use model::world::world_storage::WorldStorage;
 use network::proxy::Proxy;
 use network::single_player_proxy::SinglePlayerProxy;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tracing::info;
 
 fn main() {
     tracing_subscriber::fmt::init();
+    BlockRegistry::install_from_file(BLOCKS_FILE);
+    ItemRegistry::install_from_file(ITEMS_FILE);
+    CraftingManager::install_from_file(RECIPES_FILE);
 
     // Create the initial world
     let init = WorldInitializer::from_args();
+    let seed = seed_from_args();
 
     info!("Loading world using : {:?}", init);
-    info!("[Server] Creating a world ...");
-    let world = match init {
-        WorldInitializer::RANDOM => WorldGenerator::create_new_random_world(5),
-        WorldInitializer::FLAT => WorldGenerator::create_new_flat_world(10),
-        WorldInitializer::DISK => {
-            World::from_file("map.json").unwrap_or(WorldGenerator::create_new_random_world(10))
-        }
+    info!("[Server] Creating a world with seed {seed} ...");
+    // The chunks are generated on demand by the server, as the player explores the world.
+    let (world, generator) = match init {
+        WorldInitializer::RANDOM => (World::empty(), WorldGenerator::random(seed)),
+        WorldInitializer::FLAT => (World::empty(), WorldGenerator::flat()),
+        WorldInitializer::DISK => (World::empty(), WorldGenerator::random(seed)),
     };
     info!("                          ... Finished !");
 
     // The server holds the 'full' world
-    let server = Arc::new(Mutex::new(GameServer::new(world)));
+    let view_distance = view_distance_from_args();
+    let mut server = GameServer::with_generator(world, generator);
+    server.set_max_view_distance(view_distance);
+    server.set_keep_inventory(keep_inventory_from_args());
+    server.set_spawning_rules(SpawningRules {
+        max_per_player: max_monsters_from_args(),
+        ..SpawningRules::default()
+    });
+    // Whatever the initializer, the world is saved in its directory. The chunks are loaded from
+    // the disk on demand, and generated if they were never saved.
+    server.attach_storage(WorldStorage::new(world_dir_from_args()).unwrap());
+    // The spawn point given by the user replaces the one saved with the world
+    if let Some(spawn_point) = spawn_point_from_args() {
+        server.set_spawn_point(spawn_point);
+    }
+    let server = Arc::new(Mutex::new(server));
 
     // Spawn the entity thead
     let ref1 = server.clone();
     std::thread::spawn(move || handle_entity_thread(ref1));
 
+    // Spawn the autosave thread
+    let ref2 = server.clone();
+    let period = Duration::from_secs(DEFAULT_AUTOSAVE_PERIOD);
+    std::thread::spawn(move || handle_autosave_thread(ref2, period));
+
     // The proxy currently holds the server,
     let mut proxy = SinglePlayerProxy::new(server);
-    proxy.login("local_client".to_string());
+    proxy.login("local_client".to_string(), view_distance);
 
     // The client is initialized with an empty world, as it will be the responsibility of the server
     // to provide it with the chunks.
@@ -354,6 +354,8 @@ pub fn winit_keycode_to_custom(winit_key: winit::keyboard::KeyCode) -> KeyCode {
         winit::keyboard::KeyCode::Digit8 => KeyCode::Digit8,
         winit::keyboard::KeyCode::Digit9 => KeyCode::Digit9,
         winit::keyboard::KeyCode::KeyP => KeyCode::KeyP,
+        winit::keyboard::KeyCode::KeyQ => KeyCode::KeyQ,
+        winit::keyboard::KeyCode::KeyR => KeyCode::KeyR,
         winit::keyboard::KeyCode::KeyX => KeyCode::KeyX,
         winit::keyboard::KeyCode::F3 => KeyCode::F3,
         winit::keyboard::KeyCode::F10 => KeyCode::F10,
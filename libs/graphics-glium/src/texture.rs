@@ -3,19 +3,19 @@ use glium::texture::Texture2dArray;
 use glium::{Texture2d, glutin::surface::WindowSurface};
 use image::{GenericImageView, ImageBuffer, Rgba};
 use model::entity::humanoid::{HUMANOID_TEXTURES_PATH, ImageCut, PLAYER_CUT_TEMPLATE};
-use model::world::block_kind::Block;
+use model::game::item::Item;
 use primitives::math;
 use tracing;
 
-/// Builds the array of 2D textures using all the blocks
-/// Each block is associated with 3 textures: side, top and bottom
+/// Builds the array of 2D textures using all the blocks and items
+/// Each block is associated with 3 textures: side, top and bottom, followed by the icons of the items
 /// All these textures are loaded into one single texture array, that is fed to OpenGL.
 /// The fragment shader responsible for the cubes is then in charge of selecting the correct element of this array.
 pub fn build_textures_array(display: &Display<WindowSurface>) -> Texture2dArray {
     // Get the path of the block textures
-    let root = "./resources/block/";
+    let root = "./resources/";
     let extension = ".png";
-    let all_textures = Block::get_texture_files();
+    let all_textures = Item::get_texture_files();
     let source = all_textures
         .iter()
         .map(|name| {
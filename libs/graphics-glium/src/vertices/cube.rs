@@ -22,6 +22,10 @@ pub const CUBE_VERTEX_SHADER: &str = r#"
         in int is_selected;
         flat out int is_selected_s;
 
+        // Texture used for all the faces, if positive
+        in int texture_id;
+        flat out int texture_id_s;
+
         // Where is the vertex located on the face ?
         in vec2 tex_coords;
         out vec2 v_tex_coords;
@@ -35,6 +39,7 @@ pub const CUBE_VERTEX_SHADER: &str = r#"
             face_s = face;
             block_id_s = block_id;
             is_selected_s = is_selected;
+            texture_id_s = texture_id;
         }
     "#;
 
@@ -46,6 +51,7 @@ pub const CUBE_FRAGMENT_SHADER: &str = r#"
         flat in int face_s;
         flat in int block_id_s;
         flat in int is_selected_s;
+        flat in int texture_id_s;
         in vec2 v_tex_coords;
 
         out vec4 color ;
@@ -60,7 +66,10 @@ pub const CUBE_FRAGMENT_SHADER: &str = r#"
             // Each block has 3 types of faces
             int idx = block_id_s * 3;
 
-            if (face_s == 5) {
+            if (texture_id_s >= 0) {
+                // same texture on all the faces
+                color = texture(textures, vec3(v_tex_coords, float(texture_id_s)));
+            } else if (face_s == 5) {
                 // bottom
                 color = texture(textures, vec3(v_tex_coords, idx + 2));
             } else if (face_s == 4) {
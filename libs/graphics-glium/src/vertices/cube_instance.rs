@@ -8,17 +8,30 @@ pub struct CubeInstance {
     block_id: u8,
     /// We use an integer, since booleans are not supported
     is_selected: u8,
+    /// Layer of the texture array drawn on all the faces, or -1 to use the textures of the block
+    texture_id: i32,
     position: Vector3,
 }
 
-implement_vertex!(CubeInstance, world_matrix, block_id, is_selected);
+implement_vertex!(CubeInstance, world_matrix, block_id, is_selected, texture_id);
 
 impl CubeInstance {
     pub fn new(position: Vector3, block_id: u8) -> Self {
+        Self::new_scaled(position, block_id, 1., None)
+    }
+
+    /// Creates a cube of the given size, whose lowest corner is at `position`
+    pub fn new_scaled(
+        position: Vector3,
+        block_id: u8,
+        scale: f32,
+        texture_id: Option<i32>,
+    ) -> Self {
         Self {
-            world_matrix: Self::model_matrix(&position),
+            world_matrix: Self::model_matrix_scaled(&position, scale),
             block_id,
             is_selected: false as u8,
+            texture_id: texture_id.unwrap_or(-1),
             position,
         }
     }
@@ -32,6 +45,7 @@ impl CubeInstance {
             world_matrix: Self::model_matrix_inflated(&position),
             block_id,
             is_selected: true as u8,
+            texture_id: -1,
             position,
         }
     }
@@ -41,6 +55,7 @@ impl CubeInstance {
             world_matrix: [[0.; 4]; 4],
             block_id: 0,
             is_selected: 0,
+            texture_id: -1,
             position: Vector3::empty(),
         }
     }
@@ -54,17 +69,21 @@ impl CubeInstance {
     }
 
     pub fn model_matrix(position: &Vector3) -> [[f32; 4]; 4] {
+        Self::model_matrix_scaled(position, 1.)
+    }
+
+    pub fn model_matrix_scaled(position: &Vector3, scale: f32) -> [[f32; 4]; 4] {
         // TODO As you can see, I added 0.5 at each cube model
         //      It's because I was lazy to edit all the values in `VERTICES` of +0.5, but
         //      it would be nice to do it eventually :)
         [
-            [1.00, 0.0, 0.0, 0.0],
-            [0.0, 1.00, 0.0, 0.0],
-            [0.0, 0.0, 1.00, 0.0],
+            [scale, 0.0, 0.0, 0.0],
+            [0.0, scale, 0.0, 0.0],
+            [0.0, 0.0, scale, 0.0],
             [
-                position[0] + 0.5,
-                position[1] + 0.5,
-                position[2] + 0.5,
+                position[0] + 0.5 * scale,
+                position[1] + 0.5 * scale,
+                position[2] + 0.5 * scale,
                 1.0f32,
             ],
         ]
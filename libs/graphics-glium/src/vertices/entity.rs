@@ -7,9 +7,10 @@ pub struct EntityCube {
     world_matrix: [[f32; 4]; 4],
     body_part_id: u8,
     monster_type: u8,
+    is_hurt: u8,
 }
 
-implement_vertex!(EntityCube, world_matrix, body_part_id, monster_type);
+implement_vertex!(EntityCube, world_matrix, body_part_id, monster_type, is_hurt);
 
 impl EntityCube {
     /// Build a rendered cube center around position (and not around position + (0.5,0.5,0.5) as for CubeAttr !!!!)
@@ -23,6 +24,7 @@ impl EntityCube {
             // body part_id correspond to the [6*body_part_id,6*body_part_id+5] texture loaded
             body_part_id,
             monster_type,
+            is_hurt: 0,
         }
     }
 
@@ -38,9 +40,15 @@ impl EntityCube {
             // body part_id correspond to the [6*body_part_id,6*body_part_id+5] texture loaded
             body_part_id,
             monster_type,
+            is_hurt: 0,
         }
     }
 
+    /// Draws the cube tinted in red, for an entity which was just hurt
+    pub fn set_hurt(&mut self, is_hurt: bool) {
+        self.is_hurt = is_hurt as u8;
+    }
+
     /// Generate a world matrix with a scaling over each direction
     /// a rotation around y then
     /// a rotation around local x then
@@ -15,7 +15,7 @@ impl From<CubeRenderData> for CubeInstance {
         if data.is_selected {
             CubeInstance::new_selected(data.position, data.block_id)
         } else {
-            CubeInstance::new(data.position, data.block_id)
+            CubeInstance::new_scaled(data.position, data.block_id, data.scale, data.texture_id)
         }
     }
 }
@@ -23,10 +23,12 @@ impl From<CubeRenderData> for CubeInstance {
 impl From<EntityRenderData> for EntityCube {
     fn from(data: EntityRenderData) -> Self {
         // Body part 0 (head) needs full rotation, others use yaw only
-        match data.body_part_id {
+        let mut cube = match data.body_part_id {
             0 => EntityCube::new(&data.position, data.body_part_id, data.monster_type, data.scale),
             _ => EntityCube::new_only_yaw(&data.position, data.body_part_id, data.monster_type, data.scale),
-        }
+        };
+        cube.set_hurt(data.is_hurt);
+        cube
     }
 }
 
@@ -41,8 +43,8 @@ impl From<RectRenderData> for RectInstance {
             )
         } else {
             let mut rect = RectInstance::new(data.u, data.v, data.w, data.h, data.color);
-            if let Some(block_id) = data.block_id {
-                rect.set_block_id(block_id);
+            if let Some(texture_id) = data.texture_id {
+                rect.set_texture_id(texture_id);
             }
             rect
         }
@@ -7,7 +7,7 @@ pub const RECT_VERTEX_SHADER: &str = r"
     // Attributes of each vertex
     in vec3 position;
     in vec2 tex_coords;
-    in int block_id;
+    in int texture_id;
 
     // Atributes of each tile
     in mat4 transformation;
@@ -20,7 +20,7 @@ pub const RECT_VERTEX_SHADER: &str = r"
     out vec2 tex_coords_s;
     out vec2 font_coords_s;
     flat out int is_font_s;
-    flat out int block_id_s;
+    flat out int texture_id_s;
 
     void main()
     {
@@ -29,7 +29,7 @@ pub const RECT_VERTEX_SHADER: &str = r"
        tex_coords_s = tex_coords;
        font_coords_s = font_coords;
        is_font_s = is_font;
-       block_id_s = block_id;
+       texture_id_s = texture_id;
     }
     ";
 
@@ -41,7 +41,7 @@ pub const RECT_FRAGMENT_SHADER: &str = r"
     in vec2 tex_coords_s;
     in vec2 font_coords_s;
     flat in int is_font_s;
-    flat in int block_id_s;
+    flat in int texture_id_s;
 
     // Contains all the unicode characters
     uniform sampler2D font_atlas;
@@ -62,8 +62,8 @@ pub const RECT_FRAGMENT_SHADER: &str = r"
             // * `tex_coords_s` : coordinates within the char rect
             // * `font_offsets` : dimensions of each character 
             FragColor = texture(font_atlas, vec2(font_coords_s[0] + font_offsets[0] * tex_coords_s[0], font_coords_s[1] + font_offsets[1] * tex_coords_s[1]));
-        } else if (block_id_s >= 0) {
-            int idx = block_id_s * 3;
+        } else if (texture_id_s >= 0) {
+            int idx = texture_id_s;
             FragColor = texture(textures, vec3(tex_coords_s, float(idx)));
         } else {
             // If the tile is not a font, then we just use the background color.
@@ -118,9 +118,9 @@ pub struct RectInstance {
     is_font: u8,
     /// Coordinates of the font in the texture atlas
     font_coords: [f32; 2],
-    /// The id of the block whose' side texture will be drawn
+    /// The layer of the texture array drawn in the rectangle
     /// If the tile should not have a cube texture, then just put -1
-    block_id: i8,
+    texture_id: i32,
 }
 
 implement_vertex!(
@@ -129,7 +129,7 @@ implement_vertex!(
     color,
     is_font,
     font_coords,
-    block_id
+    texture_id
 );
 
 impl RectInstance {
@@ -157,7 +157,7 @@ impl RectInstance {
             color: c.rgba(),
             is_font: false as u8,
             font_coords: [0., 0.],
-            block_id: -1,
+            texture_id: -1,
         }
     }
 
@@ -173,12 +173,12 @@ impl RectInstance {
             color: [0., 0., 0., 0.],
             is_font: true as u8,
             font_coords: c.get_index(),
-            block_id: -1,
+            texture_id: -1,
         }
     }
 
-    pub fn set_block_id(&mut self, block_id: i8) {
-        self.block_id = block_id;
+    pub fn set_texture_id(&mut self, texture_id: i32) {
+        self.texture_id = texture_id;
     }
 
     /// Creates a new rectangle that draws a font character using font coordinates directly
@@ -193,7 +193,7 @@ impl RectInstance {
             color: [0., 0., 0., 0.],
             is_font: true as u8,
             font_coords,
-            block_id: -1,
+            texture_id: -1,
         }
     }
 }
@@ -3,16 +3,16 @@ use primitives::render_data::{CubeRenderData, EntityRenderData, RectRenderData};
 use primitives::vector::Vector3;
 use crate::vertices::{CubeInstance, EntityInstance, RectInstance};
 
-/// Compute cube model matrix
-pub fn cube_model_matrix(position: &Vector3) -> [[f32; 4]; 4] {
+/// Compute cube model matrix, for a cube of the given size whose lowest corner is at `position`
+pub fn cube_model_matrix(position: &Vector3, scale: f32) -> [[f32; 4]; 4] {
     [
-        [1.00, 0.0, 0.0, 0.0],
-        [0.0, 1.00, 0.0, 0.0],
-        [0.0, 0.0, 1.00, 0.0],
+        [scale, 0.0, 0.0, 0.0],
+        [0.0, scale, 0.0, 0.0],
+        [0.0, 0.0, scale, 0.0],
         [
-            position[0] + 0.5,
-            position[1] + 0.5,
-            position[2] + 0.5,
+            position[0] + 0.5 * scale,
+            position[1] + 0.5 * scale,
+            position[2] + 0.5 * scale,
             1.0f32,
         ],
     ]
@@ -72,9 +72,9 @@ impl From<CubeRenderData> for CubeInstance {
         let matrix = if data.is_selected {
             cube_model_matrix_inflated(&data.position)
         } else {
-            cube_model_matrix(&data.position)
+            cube_model_matrix(&data.position, data.scale)
         };
-        CubeInstance::from_matrix_and_ids(matrix, data.block_id, data.is_selected)
+        CubeInstance::from_matrix_and_ids(matrix, data.block_id, data.is_selected, data.texture_id)
     }
 }
 
@@ -85,7 +85,12 @@ impl From<EntityRenderData> for EntityInstance {
             0 => entity_model_matrix_rot_yx(&data.position, data.scale),
             _ => entity_model_matrix_rot_y(&data.position, data.scale),
         };
-        EntityInstance::from_matrix_and_ids(matrix, data.body_part_id, data.monster_type)
+        EntityInstance::from_matrix_and_ids(
+            matrix,
+            data.body_part_id,
+            data.monster_type,
+            data.is_hurt,
+        )
     }
 }
 
@@ -115,7 +120,7 @@ impl From<RectRenderData> for RectInstance {
             data.color.rgba(),
             data.is_font,
             data.font_coords.unwrap_or([0.0, 0.0]),
-            data.block_id,
+            data.texture_id,
         )
     }
 }
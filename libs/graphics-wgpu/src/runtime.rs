@@ -562,6 +562,13 @@ impl Renderer for WgpuRenderer {
                                 shader_location: 8,
                                 format: wgpu::VertexFormat::Uint32,
                             },
+                            wgpu::VertexAttribute {
+                                offset: (std::mem::size_of::<[f32; 4]>() * 4
+                                    + std::mem::size_of::<u32>() * 2)
+                                    as u64,
+                                shader_location: 9,
+                                format: wgpu::VertexFormat::Sint32,
+                            },
                         ],
                     },
                 ],
@@ -676,6 +683,13 @@ impl Renderer for WgpuRenderer {
                                 shader_location: 8,
                                 format: wgpu::VertexFormat::Uint32,
                             },
+                            wgpu::VertexAttribute {
+                                offset: (std::mem::size_of::<[f32; 4]>() * 4
+                                    + std::mem::size_of::<u32>() * 2)
+                                    as u64,
+                                shader_location: 9,
+                                format: wgpu::VertexFormat::Uint32,
+                            },
                         ],
                     },
                 ],
@@ -1228,6 +1242,8 @@ fn winit_keycode_to_custom(winit_key: winit::keyboard::KeyCode) -> KeyCode {
         winit::keyboard::KeyCode::Digit8 => KeyCode::Digit8,
         winit::keyboard::KeyCode::Digit9 => KeyCode::Digit9,
         winit::keyboard::KeyCode::KeyP => KeyCode::KeyP,
+        winit::keyboard::KeyCode::KeyQ => KeyCode::KeyQ,
+        winit::keyboard::KeyCode::KeyR => KeyCode::KeyR,
         winit::keyboard::KeyCode::KeyX => KeyCode::KeyX,
         winit::keyboard::KeyCode::F3 => KeyCode::F3,
         winit::keyboard::KeyCode::F10 => KeyCode::F10,
@@ -25,6 +25,7 @@ struct InstanceInput {
     @location(6) world_matrix_3: vec4<f32>,
     @location(7) block_id: u32,
     @location(8) is_selected: u32,
+    @location(9) texture_id: i32,
 }
 
 struct VertexOutput {
@@ -33,6 +34,7 @@ struct VertexOutput {
     @location(1) face: u32,
     @location(2) block_id: u32,
     @location(3) is_selected: u32,
+    @location(4) texture_id: i32,
 }
 
 @vertex
@@ -52,6 +54,7 @@ fn vs_main(
     out.face = model.face;
     out.block_id = instance.block_id;
     out.is_selected = instance.is_selected;
+    out.texture_id = instance.texture_id;
     return out;
 }
 "#;
@@ -78,6 +81,7 @@ struct FragmentInput {
     @location(1) face: u32,
     @location(2) block_id: u32,
     @location(3) is_selected: u32,
+    @location(4) texture_id: i32,
 }
 
 @fragment
@@ -85,7 +89,10 @@ fn fs_main(in: FragmentInput) -> @location(0) vec4<f32> {
     let base_idx = in.block_id * 3u;
     var texture_idx: u32;
     
-    if (in.face == 5u) {
+    if (in.texture_id >= 0) {
+        // same texture on all the faces
+        texture_idx = u32(in.texture_id);
+    } else if (in.face == 5u) {
         // bottom
         texture_idx = base_idx + 2u;
     } else if (in.face == 4u) {
@@ -130,6 +137,7 @@ struct InstanceInput {
     @location(6) world_matrix_3: vec4<f32>,
     @location(7) body_part_id: u32,
     @location(8) monster_type: u32,
+    @location(9) is_hurt: u32,
 }
 
 struct VertexOutput {
@@ -138,6 +146,7 @@ struct VertexOutput {
     @location(1) face: u32,
     @location(2) body_part_id: u32,
     @location(3) monster_type: u32,
+    @location(4) is_hurt: u32,
 }
 
 @vertex
@@ -157,6 +166,7 @@ fn vs_main(
     out.face = model.face;
     out.body_part_id = instance.body_part_id;
     out.monster_type = instance.monster_type;
+    out.is_hurt = instance.is_hurt;
     return out;
 }
 "#;
@@ -170,12 +180,20 @@ struct FragmentInput {
     @location(1) face: u32,
     @location(2) body_part_id: u32,
     @location(3) monster_type: u32,
+    @location(4) is_hurt: u32,
 }
 
 @fragment
 fn fs_main(in: FragmentInput) -> @location(0) vec4<f32> {
     let texture_idx = in.face + in.body_part_id * 6u + in.monster_type * 24u;
-    return textureSample(entity_textures, entity_textures_sampler, in.tex_coords, texture_idx);
+    var color = textureSample(entity_textures, entity_textures_sampler, in.tex_coords, texture_idx);
+
+    // The entities which were just hurt flash in red
+    if (in.is_hurt != 0u) {
+        color = vec4<f32>(mix(color.rgb, vec3<f32>(1.0, 0.0, 0.0), 0.5), color.a);
+    }
+
+    return color;
 }
 "#;
 
@@ -198,7 +216,7 @@ struct InstanceInput {
     @location(6) color: vec4<f32>,
     @location(7) is_font: u32,
     @location(8) font_coords: vec2<f32>,
-    @location(9) block_id: i32,
+    @location(9) texture_id: i32,
 }
 
 struct VertexOutput {
@@ -207,7 +225,7 @@ struct VertexOutput {
     @location(1) tex_coords: vec2<f32>,
     @location(2) font_coords: vec2<f32>,
     @location(3) is_font: u32,
-    @location(4) block_id: i32,
+    @location(4) texture_id: i32,
 }
 
 @vertex
@@ -227,7 +245,7 @@ fn vs_main(
     out.tex_coords = model.tex_coords;
     out.font_coords = instance.font_coords;
     out.is_font = instance.is_font;
-    out.block_id = instance.block_id;
+    out.texture_id = instance.texture_id;
     return out;
 }
 "#;
@@ -245,7 +263,7 @@ struct FragmentInput {
     @location(1) tex_coords: vec2<f32>,
     @location(2) font_coords: vec2<f32>,
     @location(3) is_font: u32,
-    @location(4) block_id: i32,
+    @location(4) texture_id: i32,
 }
 
 @fragment
@@ -253,8 +271,8 @@ fn fs_main(in: FragmentInput) -> @location(0) vec4<f32> {
     if (in.is_font != 0u) {
         let font_uv = in.font_coords + font_offsets * in.tex_coords;
         return textureSample(font_atlas, font_atlas_sampler, font_uv);
-    } else if (in.block_id >= 0) {
-        let texture_idx = u32(in.block_id) * 3u;
+    } else if (in.texture_id >= 0) {
+        let texture_idx = u32(in.texture_id);
         return textureSample(block_textures, block_textures_sampler, in.tex_coords, texture_idx);
     } else {
         return in.color;
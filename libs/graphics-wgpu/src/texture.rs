@@ -1,19 +1,19 @@
 use image::GenericImageView;
 use model::entity::humanoid::{HUMANOID_TEXTURES_PATH, PLAYER_CUT_TEMPLATE};
-use model::world::block_kind::Block;
+use model::game::item::Item;
 use primitives::math;
 use tracing;
 use wgpu::{Device, Queue, Texture, TextureFormat, TextureView, TextureViewDescriptor};
 
-/// Builds a 2D array texture containing all block textures
-/// Each block has 3 textures: side, top, bottom
+/// Builds a 2D array texture containing all block and item textures
+/// Each block has 3 textures: side, top, bottom. They are followed by the icon of each item.
 pub fn build_block_textures_array(
     device: &Device,
     queue: &Queue,
 ) -> (Texture, TextureView) {
-    let root = "./resources/block/";
+    let root = "./resources/";
     let extension = ".png";
-    let all_textures = Block::get_texture_files();
+    let all_textures = Item::get_texture_files();
     
     let mut texture_images = Vec::new();
     let mut max_width = 0u32;
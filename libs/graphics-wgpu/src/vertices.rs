@@ -20,10 +20,16 @@ pub struct CubeInstance {
     pub world_matrix_3: [f32; 4], // Fourth row of matrix
     pub block_id: u32,
     pub is_selected: u32,
+    pub texture_id: i32,
 }
 
 impl CubeInstance {
-    pub fn from_matrix_and_ids(matrix: [[f32; 4]; 4], block_id: u8, is_selected: bool) -> Self {
+    pub fn from_matrix_and_ids(
+        matrix: [[f32; 4]; 4],
+        block_id: u8,
+        is_selected: bool,
+        texture_id: Option<i32>,
+    ) -> Self {
         Self {
             world_matrix_0: matrix[0],
             world_matrix_1: matrix[1],
@@ -31,6 +37,7 @@ impl CubeInstance {
             world_matrix_3: matrix[3],
             block_id: block_id as u32,
             is_selected: if is_selected { 1 } else { 0 },
+            texture_id: texture_id.unwrap_or(-1),
         }
     }
 }
@@ -45,10 +52,16 @@ pub struct EntityInstance {
     pub world_matrix_3: [f32; 4],
     pub body_part_id: u32,
     pub monster_type: u32,
+    pub is_hurt: u32,
 }
 
 impl EntityInstance {
-    pub fn from_matrix_and_ids(matrix: [[f32; 4]; 4], body_part_id: u8, monster_type: u8) -> Self {
+    pub fn from_matrix_and_ids(
+        matrix: [[f32; 4]; 4],
+        body_part_id: u8,
+        monster_type: u8,
+        is_hurt: bool,
+    ) -> Self {
         Self {
             world_matrix_0: matrix[0],
             world_matrix_1: matrix[1],
@@ -56,6 +69,7 @@ impl EntityInstance {
             world_matrix_3: matrix[3],
             body_part_id: body_part_id as u32,
             monster_type: monster_type as u32,
+            is_hurt: if is_hurt { 1 } else { 0 },
         }
     }
 }
@@ -79,7 +93,7 @@ pub struct RectInstance {
     pub color: [f32; 4],
     pub is_font: u32,
     pub font_coords: [f32; 2],
-    pub block_id: i32,
+    pub texture_id: i32,
 }
 
 impl RectInstance {
@@ -88,7 +102,7 @@ impl RectInstance {
         color: [f32; 4],
         is_font: bool,
         font_coords: [f32; 2],
-        block_id: Option<i8>,
+        texture_id: Option<i32>,
     ) -> Self {
         Self {
             transformation_0: matrix[0],
@@ -98,7 +112,7 @@ impl RectInstance {
             color,
             is_font: if is_font { 1 } else { 0 },
             font_coords,
-            block_id: block_id.map(|id| id as i32).unwrap_or(-1),
+            texture_id: texture_id.unwrap_or(-1),
         }
     }
 }
@@ -0,0 +1,42 @@
+use crate::core::string_rect::StringRect;
+use crate::renderer::RectRenderData;
+use primitives::color::Color::LightCoral;
+
+/// Screen shown when the player is dead, until they ask to respawn
+pub struct DeathScreen {
+    rects: Vec<RectRenderData>,
+}
+
+impl DeathScreen {
+    pub fn new() -> Self {
+        // A red veil on the whole screen
+        let mut rects = vec![RectRenderData {
+            u: 0.,
+            v: 0.,
+            w: 1.,
+            h: 1.,
+            color: LightCoral,
+            is_font: false,
+            font_coords: None,
+            texture_id: None,
+        }];
+        StringRect::write_string_centered(0.2, 0.05, &"you died".to_string(), &mut rects);
+        StringRect::write_string_centered(
+            -0.1,
+            0.025,
+            &"press r to respawn".to_string(),
+            &mut rects,
+        );
+        Self { rects }
+    }
+
+    pub fn rects(&self) -> &Vec<RectRenderData> {
+        &self.rects
+    }
+}
+
+impl Default for DeathScreen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -21,6 +21,9 @@ pub const ENTITY_VERTEX_SHADER: &str = r#"
         in int monster_type;
         flat out int monster_type_s;
 
+        in int is_hurt;
+        flat out int is_hurt_s;
+
         uniform mat4 perspective;
         uniform mat4 view;
 
@@ -30,6 +33,7 @@ pub const ENTITY_VERTEX_SHADER: &str = r#"
             face_s = face;
             body_part_id_s = body_part_id;
             monster_type_s = monster_type;
+            is_hurt_s = is_hurt;
         }
     "#;
 
@@ -41,6 +45,7 @@ pub const ENTITY_FRAGMENT_SHADER: &str = r#"
         flat in int face_s;
         flat in int body_part_id_s;
         flat in int monster_type_s;
+        flat in int is_hurt_s;
         in vec2 v_tex_coords;
 
         out vec4 color ;
@@ -52,6 +57,10 @@ pub const ENTITY_FRAGMENT_SHADER: &str = r#"
             // There is 4 different block building a monster
             int idx = face_s + body_part_id_s * 6 + monster_type_s * 4 * 6;
             color = texture(entity_textures, vec3(v_tex_coords, idx));
+            // The entities which were just hurt flash in red
+            if (is_hurt_s != 0) {
+                color = vec4(mix(color.rgb, vec3(1.0, 0.0, 0.0), 0.5), color.a);
+            }
          }
     "#;
 
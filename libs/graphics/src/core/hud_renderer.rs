@@ -1,10 +1,12 @@
 use super::menu_help;
 use super::menu_help::HelpMenu;
 use super::menu_help::HelpMenuData;
+use crate::core::death_screen::DeathScreen;
 use crate::core::health_bar::HealthBar;
 use crate::core::inventory_event::InventoryEvent;
 use crate::core::items_bar::ItemBar;
 use crate::core::update_status::UpdateStatus;
+use model::game::crafting::CraftingGrid;
 use model::game::health::Health;
 use model::game::player_items::{ItemStack, PlayerItems};
 use primitives::color::Color::Red;
@@ -39,6 +41,9 @@ pub struct HUDRenderer {
     health_bar: HealthBar,
 
     inventory_menu: Option<InventoryMenu>,
+
+    death_screen: DeathScreen,
+    show_death_screen: bool,
 }
 
 impl HUDRenderer {
@@ -58,6 +63,8 @@ impl HUDRenderer {
             items_bar: ItemBar::new(),
             health_bar: HealthBar::new(10, 1.),
             inventory_menu: None,
+            death_screen: DeathScreen::new(),
+            show_death_screen: false,
         };
 
         hud.add_cross();
@@ -78,7 +85,7 @@ impl HUDRenderer {
             color: Red,
             is_font: false,
             font_coords: None,
-            block_id: None,
+            texture_id: None,
         });
         self.base.push(RectRenderData {
             u: 0.,
@@ -88,7 +95,7 @@ impl HUDRenderer {
             color: Red,
             is_font: false,
             font_coords: None,
-            block_id: None,
+            texture_id: None,
         });
     }
 
@@ -104,7 +111,7 @@ impl HUDRenderer {
             color: primitives::color::Color::Transparent,
             is_font: true,
             font_coords: Some(GLChar::C.get_webgl_altas_coordinate()),
-            block_id: None,
+            texture_id: None,
         });
         self.base.push(RectRenderData {
             u: x0 + 1. * s * 3.,
@@ -114,7 +121,7 @@ impl HUDRenderer {
             color: primitives::color::Color::Transparent,
             is_font: true,
             font_coords: Some(GLChar::R.get_webgl_altas_coordinate()),
-            block_id: None,
+            texture_id: None,
         });
         self.base.push(RectRenderData {
             u: x0 + 2. * s * 3.,
@@ -124,7 +131,7 @@ impl HUDRenderer {
             color: primitives::color::Color::Transparent,
             is_font: true,
             font_coords: Some(GLChar::A.get_webgl_altas_coordinate()),
-            block_id: None,
+            texture_id: None,
         });
         self.base.push(RectRenderData {
             u: x0 + 3. * s * 3.,
@@ -134,7 +141,7 @@ impl HUDRenderer {
             color: primitives::color::Color::Transparent,
             is_font: true,
             font_coords: Some(GLChar::F.get_webgl_altas_coordinate()),
-            block_id: None,
+            texture_id: None,
         });
         self.base.push(RectRenderData {
             u: x0 + 4. * s * 3.,
@@ -144,7 +151,7 @@ impl HUDRenderer {
             color: primitives::color::Color::Transparent,
             is_font: true,
             font_coords: Some(GLChar::T.get_webgl_altas_coordinate()),
-            block_id: None,
+            texture_id: None,
         });
         self.base.push(RectRenderData {
             u: x0 + 5. * s * 3.,
@@ -154,7 +161,7 @@ impl HUDRenderer {
             color: primitives::color::Color::Transparent,
             is_font: true,
             font_coords: Some(GLChar::Y.get_webgl_altas_coordinate()),
-            block_id: None,
+            texture_id: None,
         });
     }
 
@@ -170,6 +177,12 @@ impl HUDRenderer {
         self.update();
     }
 
+    /// Shows or hides the screen telling the player that they are dead
+    pub fn set_death_screen(&mut self, show: bool) {
+        self.show_death_screen = show;
+        self.update();
+    }
+
     /// Update the vector of RectVertexAttr to be shown
     fn update(&mut self) {
         // We first clone and append the Vec in each menu
@@ -192,6 +205,9 @@ impl HUDRenderer {
             self.rects
                 .append(&mut self.inventory_menu.as_mut().unwrap().rects().clone());
         }
+        if self.show_death_screen {
+            self.rects.append(&mut self.death_screen.rects().clone());
+        }
     }
 
     pub fn set_debug(&mut self, debug_data: DebugData) {
@@ -241,9 +257,10 @@ impl HUDRenderer {
         self.update();
     }
 
-    /// Close inventory. This function may fail, because there are still items
-    /// in the crafting grid, and we do not want to loose them
-    pub fn close_inventory(&mut self) -> Option<PlayerItems> {
+    /// Close inventory, returning the items and the grids of the recipes crafted with them.
+    /// This function may fail, because there are still items in the crafting grid, and we do not
+    /// want to loose them
+    pub fn close_inventory(&mut self) -> Option<(PlayerItems, Vec<CraftingGrid>)> {
         if self.inventory_menu.as_ref().unwrap().can_be_closed_safely() {
             let items = self.inventory_menu.take().unwrap().take_items();
             self.update();
@@ -4,9 +4,8 @@ use crate::core::inventory_space;
 use crate::core::inventory_space::{InventoryPosition, InventoryRect};
 use crate::core::update_status::UpdateStatus;
 use crate::renderer::PressedOrReleased;
-use model::game::crafting::{CraftingGrid, CraftingManager};
-use model::game::player_items::PlayerItems;
-use model::world::block_kind::Block;
+use model::game::crafting::{CraftingGrid, CraftingManager, CraftingResult};
+use model::game::player_items::{ItemStack, PlayerItems};
 use primitives::color::Color::{LightGray, Red};
 use crate::renderer::RectRenderData;
 
@@ -26,9 +25,12 @@ pub struct InventoryMenu {
     crafting_slots: [[InventorySlot; 3]; 3],
     crafting_output_slot: InventorySlot,
 
-    carried_item: Option<Block>,
-    crafting_items: CraftingGrid,
-    crafting_output_items: Option<Block>,
+    carried_item: Option<ItemStack>,
+    /// The items placed in the crafting grid, one per slot, which keep their durability
+    crafting_items: [[Option<ItemStack>; 3]; 3],
+    crafting_output: Option<CraftingResult>,
+    /// The grids of the recipes crafted since the menu was opened, that the server crafts as well
+    crafts: Vec<CraftingGrid>,
 }
 
 impl InventoryMenu {
@@ -47,7 +49,8 @@ impl InventoryMenu {
 
             carried_item: None,
             crafting_items: [[None; 3]; 3],
-            crafting_output_items: None,
+            crafting_output: None,
+            crafts: Vec::new(),
         };
         inventory.update();
 
@@ -63,8 +66,9 @@ impl InventoryMenu {
         &self.rects
     }
 
-    pub fn take_items(self) -> PlayerItems {
-        self.items
+    /// Returns the items, and the grids of the recipes crafted with them
+    pub fn take_items(self) -> (PlayerItems, Vec<CraftingGrid>) {
+        (self.items, self.crafts)
     }
 
     /// Inventory can be closed safely if there is no item in the crafting grid
@@ -136,11 +140,15 @@ impl InventoryMenu {
             for row in 0..3 {
                 for col in 0..3 {
                     if self.crafting_slots[row][col].is_in(&self.cursor_pos) {
-                        if let None = self.crafting_items[row][col] {
-                            std::mem::swap(
-                                &mut self.crafting_items[row][col],
-                                &mut self.carried_item,
-                            );
+                        if let (None, Some(stack)) =
+                            (self.crafting_items[row][col], self.carried_item)
+                        {
+                            // each slot of the grid holds a single item
+                            self.crafting_items[row][col] = Some(ItemStack { count: 1, ..stack });
+                            self.carried_item = (stack.count > 1).then_some(ItemStack {
+                                count: stack.count - 1,
+                                ..stack
+                            });
                             update_craft = true;
                         }
                     }
@@ -155,16 +163,16 @@ impl InventoryMenu {
                         // grab it
                         if row == 0 {
                             // for item bar
-                            if let Some(block) = self.items.take_bar_item(col) {
-                                self.carried_item = Some(block);
+                            if let Some(stack) = self.items.take_bar_item(col) {
+                                self.carried_item = Some(stack);
                             }
                         } else {
                             // for inventory
-                            if let Some(block) = self
+                            if let Some(stack) = self
                                 .items
                                 .take_inventory_item((row - 1) * INVENTORY_NCOLS + col)
                             {
-                                self.carried_item = Some(block);
+                                self.carried_item = Some(stack);
                             }
                         }
                     }
@@ -176,10 +184,7 @@ impl InventoryMenu {
                 for col in 0..3 {
                     if self.crafting_slots[row][col].is_in(&self.cursor_pos) {
                         if let Some(_) = self.crafting_items[row][col] {
-                            std::mem::swap(
-                                &mut self.crafting_items[row][col],
-                                &mut self.carried_item,
-                            );
+                            self.carried_item = self.crafting_items[row][col].take();
                             update_craft = true;
                         }
                     }
@@ -188,13 +193,12 @@ impl InventoryMenu {
 
             // and for the crafting output
             if self.crafting_output_slot.is_in(&self.cursor_pos) {
-                if let Some(_) = self.crafting_output_items {
-                    std::mem::swap(&mut self.crafting_output_items, &mut self.carried_item);
+                if let Some(result) = self.crafting_output.take() {
+                    self.carried_item = Some(result.output);
+                    self.crafts.push(self.crafting_grid());
                     // also remove the crafting components !
-                    for row in 0..3 {
-                        for col in 0..3 {
-                            let _ = &mut self.crafting_items[row][col].take();
-                        }
+                    for (row, col) in result.consumed {
+                        self.crafting_items[row][col] = None;
                     }
                     update_craft = true;
                 }
@@ -267,7 +271,7 @@ impl InventoryMenu {
                     );
                     self.crafting_slots[row][col] = slot;
 
-                    let item = self.crafting_items[row][col].map(|block| (block, 1));
+                    let item = self.crafting_items[row][col];
                     self.rects.append(&mut slot.rects(
                         &self.ui_rect,
                         item,
@@ -287,7 +291,7 @@ impl InventoryMenu {
                 );
                 self.crafting_output_slot = slot;
 
-                let item = self.crafting_output_items.map(|block| (block, 1));
+                let item = self.crafting_output.as_ref().map(|result| result.output);
                 self.rects.append(&mut slot.rects(
                     &self.ui_rect,
                     item,
@@ -296,13 +300,13 @@ impl InventoryMenu {
             }
 
             // carried item
-            if let Some(block) = self.carried_item {
+            if let Some(stack) = self.carried_item {
                 let (x, y, w, h) = inventory_space::from_ui_to_ndc_rect(
                     &self.ui_rect,
                     &InventoryRect::new(self.cursor_pos.x, self.cursor_pos.y, item_size, item_size),
                 );
                 let mut carried_rect = RectRenderData::new_from_corner(x, y, w, h, Red);
-                carried_rect.block_id = Some(block as u8 as i8);
+                carried_rect.texture_id = Some(stack.item.texture_id() as i32);
                 self.rects.push(carried_rect);
             }
         }
@@ -312,7 +316,12 @@ impl InventoryMenu {
     /// crafting slot. This only updates the logic, need to call update
     /// afterwards for the graphics
     fn update_craft(&mut self) {
-        self.crafting_output_items = CraftingManager::recipe(&self.crafting_items)
+        self.crafting_output = CraftingManager::global().recipe(&self.crafting_grid())
+    }
+
+    /// Returns the items of the crafting grid, to match them with the recipes
+    fn crafting_grid(&self) -> CraftingGrid {
+        self.crafting_items.map(|row| row.map(|slot| slot.map(|stack| stack.item)))
     }
 
     /// Same as the function in `inventory_space.rs`, but using the UI rect from
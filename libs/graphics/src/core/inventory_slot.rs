@@ -40,13 +40,13 @@ impl InventorySlot {
         rects.push(slot);
 
         // draw the item as well
-        if let Some((block, count)) = item {
+        if let Some(stack) = item {
             let mut item_rect = RectRenderData::new_from_corner(x, y, w, h, LighterGray);
-            item_rect.block_id = Some(block as u8 as i8);
+            item_rect.texture_id = Some(stack.item.texture_id() as i32);
             rects.push(item_rect);
 
             // and the count
-            let text = format!("{count}");
+            let text = format!("{}", stack.count);
             let quantity = StringRect::new(&text, x, y, 0.03);
             rects.append(&mut quantity.rects().clone());
         }
@@ -44,7 +44,7 @@ impl ItemBar {
         let mut x0 = -W / 2. + PADDING;
 
         const ITEM_SIDE: f32 = H * 0.9;
-        for (i, &(kind, quantity)) in self.items.iter().enumerate() {
+        for (i, stack) in self.items.iter().enumerate() {
             if i == self.selected_item {
                 // Add an indication that this is the selected item
                 const DX: f32 = 0.015;
@@ -58,11 +58,11 @@ impl ItemBar {
             let item_u = x0;
             let item_v = BOTTOM - 1. + 2. * PADDING;
             let mut item_rect = RectRenderData::square_from_corner(item_u, item_v, ITEM_SIDE, self.aspect_ratio, Red);
-            item_rect.block_id = Some(kind as u8 as i8);
+            item_rect.texture_id = Some(stack.item.texture_id() as i32);
             rects.push(item_rect);
 
             // And we want to print the number of remaining items
-            let text = format!("{quantity}");
+            let text = format!("{}", stack.count);
             let quantity =
                 StringRect::new(&text, x0 + ITEM_SIDE / 5., BOTTOM - 1. + 0. * PADDING, 0.03);
             rects.append(&mut quantity.rects().clone());
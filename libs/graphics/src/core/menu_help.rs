@@ -2,13 +2,15 @@ use crate::renderer::RectRenderData;
 use crate::core::string_rect::StringRect;
 
 /// Data in the help menu
-pub const HELP_MENU_DATA: [HelpMenuItem; 9] = [
+pub const HELP_MENU_DATA: [HelpMenuItem; 11] = [
     //HelpMenuItem{command: &str "move forward",key: String::from("z")},
     HelpMenuItem::new("move forward", "z"),
     HelpMenuItem::new("move backward", "s"),
     HelpMenuItem::new("move left", "q"),
     HelpMenuItem::new("move right", "d"),
     HelpMenuItem::new("jump", "space"),
+    HelpMenuItem::new("throw item", "a"),
+    HelpMenuItem::new("respawn", "r"),
     HelpMenuItem::new("debug menu", "f3"),
     HelpMenuItem::new("help menu", "f12"),
     HelpMenuItem::new("save map", "f10"),
@@ -34,7 +34,7 @@ impl StringRect {
                 color: primitives::color::Color::Transparent,
                 is_font: true,
                 font_coords: Some(gl_char.get_webgl_altas_coordinate()),
-                block_id: None,
+                texture_id: None,
             });
         }
         u + st.len() as f32 * w * 3.
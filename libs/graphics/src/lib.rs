@@ -1,6 +1,7 @@
 pub mod renderer;
 
 pub mod core {
+    pub mod death_screen;
     pub mod entity;
     pub mod health_bar;
     pub mod hud_renderer;
@@ -5,15 +5,17 @@ use crate::player::fps::FpsManager;
 use crate::renderer::{
     KeyCode, KeyEvent, MouseButton, MouseEvent, Renderer, RendererBackend, ToDraw, WindowAction,
 };
+use model::entity::entity::EntityId;
 use model::entity::entity_manager::EntityManager;
 use model::game::actions::Action;
 use model::game::actions::Action::{Add, Destroy};
-use model::game::health::Health;
+use model::game::health::{Health, MAX_HEALTH};
 use model::game::input::MotionState;
-use model::game::player::{Player, CLICK_TIME_TO_BREAK};
+use model::game::mining;
+use model::game::player::Player;
 use model::game::player_items::PlayerItems;
+use model::server::action_validation::ActionRejection;
 use model::server::server_update::ServerUpdate;
-use model::world::block_kind::Block::{COBBELSTONE, OAKLOG, SWORD};
 use model::world::chunk::CHUNK_FLOOR;
 use model::world::world::World;
 use network::proxy::Proxy;
@@ -27,6 +29,9 @@ pub struct WorldRenderer {
     /// The proxy needs to be already logged-in
     proxy: Arc<Mutex<dyn Proxy>>,
 
+    /// Identifier given by the server when logging in
+    player_id: Option<EntityId>,
+
     /// Currently displayed world
     world: World,
 
@@ -56,15 +61,19 @@ impl RendererBackend for WorldRenderer {
     fn update(&mut self, dt: Duration) -> ToDraw {
         // Step the camera with the elapsed time
         // Try to break the selected cube
-        if self.player.is_time_to_break_over(dt.as_secs_f32()) {
+        let alive = self.health.alive();
+        if alive && self.player.is_time_to_break_over(dt.as_secs_f32(), self.time_to_break()) {
             self.apply_action(Destroy {
                 at: self.player.selected_cube().unwrap().to_cube_coordinates(),
             });
         }
 
-        // Step
+        // Step. A dead player does not move until they respawn.
         self.fps_manager.step(dt);
-        self.player.step(dt, &self.world);
+        if alive {
+            self.player.step(dt, &self.world);
+        }
+        self.entity_manager.step(dt.as_secs_f32());
 
         // Server updates
         self.proxy
@@ -83,14 +92,15 @@ impl RendererBackend for WorldRenderer {
         };
 
         // Compute the buffers to be rendered
-        let cubes_buffer = self.world.get_cubes_buffer(self.player.selected_cube());
+        let mut cubes_buffer = self.world.get_cubes_buffer(self.player.selected_cube());
+        cubes_buffer.append(&mut self.entity_manager.get_cubes());
         let entity_buffer = self.entity_manager.get_opengl_entities();
         let hud_buffer = self.hud_renderer.rects();
 
         ToDraw {
             player_view_matrix: self.player.view_matrix(),
             selected_intensity: if self.player.left_click() {
-                self.player.left_click_time() / CLICK_TIME_TO_BREAK
+                self.player.left_click_time() / self.time_to_break()
             } else {
                 0.2
             },
@@ -105,20 +115,22 @@ impl RendererBackend for WorldRenderer {
     }
 
     fn handle_mouse_event(&mut self, event: MouseEvent) {
-        if !self.hud_renderer.is_inventory_open() {
+        if !self.hud_renderer.is_inventory_open() && self.health.alive() {
             match event.button {
                 MouseButton::Left => {
                     if self.player.is_selecting_cube() {
                         self.player
                             .toggle_state(MotionState::LeftClick, event.state.is_pressed());
                     } else if event.state.is_pressed() {
-                        if let Some(mut attack) = self
+                        if let Some(attack) = self
                             .entity_manager
                             .attack(self.player.position().pos(), self.player.direction())
                         {
                             // Forward the attack to the server
-                            attack.set_strength(self.items.attack_strength());
                             self.proxy.lock().unwrap().on_new_attack(attack);
+                            // The weapon wears out
+                            self.items.use_current_item();
+                            self.update_items_bar();
                         }
                     }
                 }
@@ -162,6 +174,11 @@ impl RendererBackend for WorldRenderer {
         let mut actions = Vec::new();
         if self.hud_renderer.is_inventory_open() {
             self.handle_inventory_key_event(key_event, &mut actions);
+        } else if !self.health.alive() {
+            // The only thing a dead player can do is to ask to respawn
+            if key_event.state.is_pressed() && key_event.key == KeyCode::KeyR {
+                self.proxy.lock().unwrap().respawn();
+            }
         } else {
             self.handle_game_key_event(key_event, &mut actions)
         }
@@ -189,9 +206,10 @@ impl WorldRenderer {
     pub fn new(proxy: Arc<Mutex<dyn Proxy>>, world: World, player: Player) -> Self {
         Self {
             proxy,
+            player_id: None,
             world,
             player,
-            health: Health::new(10),
+            health: Health::new(MAX_HEALTH),
             hud_renderer: HUDRenderer::new(),
             fps_manager: FpsManager::new(),
             items: PlayerItems::empty(),
@@ -201,18 +219,6 @@ impl WorldRenderer {
     }
 
     pub fn run<R: Renderer>(&mut self) {
-        // Add a few items
-        self.items.collect(SWORD);
-        for _ in 0..16 {
-            self.items.collect(COBBELSTONE);
-        }
-        for _ in 0..8 {
-            self.items.collect(OAKLOG);
-        }
-
-        // Last details before running
-        self.update_items_bar();
-
         // Initially, ask for server updates
         self.proxy
             .lock()
@@ -232,9 +238,18 @@ impl WorldRenderer {
         if event.state.is_pressed() {
             match event.key {
                 KeyCode::KeyE => {
-                    if let Some(items) = self.hud_renderer.close_inventory() {
+                    if let Some((items, crafts)) = self.hud_renderer.close_inventory() {
                         self.items = items;
                         actions.push(WindowAction::SetCursor(false));
+                        // The server crafts the same recipes, and checks that the items were
+                        // only moved between the slots
+                        {
+                            let mut proxy = self.proxy.lock().unwrap();
+                            for grid in crafts {
+                                proxy.craft(grid);
+                            }
+                            proxy.arrange_items(self.items.clone());
+                        }
                         self.update_items_bar();
                     }
                 }
@@ -270,48 +285,22 @@ impl WorldRenderer {
                     actions.push(WindowAction::SetCursor(true))
                 }
                 // Item bar shortcuts
-                KeyCode::Digit1 => {
-                    self.items.set_current_item(0);
-                    self.update_items_bar();
-                }
-                KeyCode::Digit2 => {
-                    self.items.set_current_item(1);
-                    self.update_items_bar();
-                }
-                KeyCode::Digit3 => {
-                    self.items.set_current_item(2);
-                    self.update_items_bar();
-                }
-                KeyCode::Digit4 => {
-                    self.items.set_current_item(3);
-                    self.update_items_bar();
-                }
-                KeyCode::Digit5 => {
-                    self.items.set_current_item(4);
-                    self.update_items_bar();
-                }
-                KeyCode::Digit6 => {
-                    self.items.set_current_item(5);
-                    self.update_items_bar();
-                }
-                KeyCode::Digit7 => {
-                    self.items.set_current_item(6);
-                    self.update_items_bar();
-                }
-                KeyCode::Digit8 => {
-                    self.items.set_current_item(7);
-                    self.update_items_bar();
-                }
-                KeyCode::Digit9 => {
-                    self.items.set_current_item(8);
-                    self.update_items_bar();
-                }
+                KeyCode::Digit1 => self.select_item(0),
+                KeyCode::Digit2 => self.select_item(1),
+                KeyCode::Digit3 => self.select_item(2),
+                KeyCode::Digit4 => self.select_item(3),
+                KeyCode::Digit5 => self.select_item(4),
+                KeyCode::Digit6 => self.select_item(5),
+                KeyCode::Digit7 => self.select_item(6),
+                KeyCode::Digit8 => self.select_item(7),
+                KeyCode::Digit9 => self.select_item(8),
                 KeyCode::KeyP => {
                     tracing::debug!("=================");
                     tracing::debug!("Debug Information");
                     tracing::debug!("=================");
                     self.player.debug();
                 }
+                KeyCode::KeyQ => self.throw_item(),
                 KeyCode::KeyX => {
                     tracing::info!("Ask to spawn a monster");
                     let mut monster_pos =
@@ -321,19 +310,34 @@ impl WorldRenderer {
                 }
                 KeyCode::F3 => self.hud_renderer.toggle_debug_menu(),
                 KeyCode::F10 => self.toggle_fullscreen(actions),
-                KeyCode::F11 => self.world.save_to_file("map.json"),
+                KeyCode::F11 => self.save_world(),
                 KeyCode::F12 => self.hud_renderer.toggle_help_menu(),
                 _ => {}
             }
         }
     }
 
+    /// Returns the time [s] needed to break the selected cube with the current item
+    fn time_to_break(&self) -> f32 {
+        self.player.selected_cube().map_or(f32::INFINITY, |cube| {
+            mining::break_time(*cube.block(), self.items.get_current_item())
+        })
+    }
+
+    /// Asks the server to save its world, which it owns: the client only has the chunks around
+    /// the player
+    fn save_world(&self) {
+        tracing::info!("Asking the server to save the world");
+        self.proxy.lock().unwrap().save_world();
+    }
+
     fn apply_action(&mut self, action: Action) {
         // Handle items
         match action {
             Destroy { at } => {
+                // The harvested block is dropped by the server, and picked up later
                 if let Some(block) = self.world.block_at(&at) {
-                    self.items.collect(block.block_dropped());
+                    self.items.on_block_destroyed(block);
                 }
             }
             Add { at, block } => {
@@ -348,18 +352,34 @@ impl WorldRenderer {
                     }
                 }
 
-                self.items.consume(block);
+                self.items.consume(block.into());
             }
         }
 
-        // Currently, all actions end up editing the items.
-        self.update_items_bar();
-
         // Handle cubes
         self.world.apply_action(&action);
 
-        // Forward to server
+        // Forward to server, which updates its copy of our items the same way
         self.proxy.lock().unwrap().on_new_action(action);
+
+        // Currently, all actions end up editing the items.
+        self.update_items_bar();
+    }
+
+    /// Holds another slot of the item bar
+    fn select_item(&mut self, index: usize) {
+        self.items.set_current_item(index);
+        self.proxy.lock().unwrap().select_item(index);
+        self.update_items_bar();
+    }
+
+    /// Throws the held item, if any
+    fn throw_item(&mut self) {
+        // The server drops the same item from its copy of our items
+        if self.items.drop_current_item().is_some() {
+            self.proxy.lock().unwrap().throw_item();
+            self.update_items_bar();
+        }
     }
 
     fn update_items_bar(&mut self) {
@@ -381,22 +401,91 @@ impl WorldRenderer {
         let updates = self.proxy.lock().unwrap().consume_server_updates();
         for update in updates {
             match update {
-                ServerUpdate::LoadChunk(chunk) => self.world.add_chunk(chunk),
-                ServerUpdate::LoggedIn(client_id, position) => {
-                    tracing::info!("Client registered ID: {client_id} with position: {position:?}");
-                    self.player.set_position(position)
+                ServerUpdate::LoadChunk(chunk) => self.world.add_chunk(*chunk),
+                ServerUpdate::UnloadChunk(corner) => {
+                    self.world.remove_chunk(corner);
+                }
+                ServerUpdate::LoggedIn(state) => {
+                    tracing::info!(
+                        "Client registered ID: {} with position: {:?}",
+                        state.id,
+                        state.pos
+                    );
+                    self.player_id = Some(state.id);
+                    self.player.set_position(state.pos);
+                    self.items = state.items;
+                    self.health = state.health;
+                    self.hud_renderer.set_health(&self.health);
+                    // The player may have logged out while being dead
+                    self.hud_renderer.set_death_screen(!self.health.alive());
+                    self.update_items_bar();
                 }
                 ServerUpdate::SendAction(action) => self.world.apply_action(&action),
                 ServerUpdate::RegisterEntity(id, entity_kind, pos) => self
                     .entity_manager
                     .register_new_entity(id, entity_kind, pos),
                 ServerUpdate::UpdatePosition(id, pos) => self.entity_manager.set_position(id, pos),
-                ServerUpdate::Attack(attack) => {
-                    self.health.damage(attack.strength());
+                ServerUpdate::KnockBack(velocity) => self.player.knock_back(velocity),
+                ServerUpdate::UpdateHealth(id, health) => {
+                    // The health of the other entities is not displayed, they only flash when hurt
+                    if self.player_id == Some(id) {
+                        self.health = Health::new(health);
+                        self.hud_renderer.set_health(&self.health);
+                    } else {
+                        self.entity_manager.set_health(id, health);
+                    }
+                }
+                ServerUpdate::RemoveEntity(id) => self.entity_manager.remove_entity(id),
+                ServerUpdate::RejectAction(rejection) => self.undo_action(rejection),
+                ServerUpdate::Death(items) => {
+                    tracing::info!("The player died");
+                    self.health = Health::new(0);
+                    self.hud_renderer.set_health(&self.health);
+                    self.hud_renderer.set_death_screen(true);
+                    self.items = items;
+                    self.update_items_bar();
+                }
+                ServerUpdate::Respawn(state) => {
+                    tracing::info!("The player respawned at {:?}", state.pos);
+                    // Forget the motion of the dead player
+                    self.player = Player::new();
+                    self.player.set_position(state.pos);
+                    self.items = state.items;
+                    self.health = state.health;
                     self.hud_renderer.set_health(&self.health);
+                    self.hud_renderer.set_death_screen(false);
+                    self.update_items_bar();
+                }
+                ServerUpdate::PickUpItem(stack) => {
+                    self.items.collect_stack(stack);
+                    self.update_items_bar();
+                }
+                ServerUpdate::SetItems(items) => {
+                    tracing::warn!("The server corrected our items");
+                    self.items = items;
+                    self.update_items_bar();
                 }
-                ServerUpdate::RemoveEntity(id) => self.entity_manager.remove_entity(id as u8),
             }
         }
     }
+
+    /// Puts back the cube and the items as the server knows them, after one of our actions was
+    /// refused
+    fn undo_action(&mut self, rejection: ActionRejection) {
+        tracing::warn!("The server refused an action at {:?}", rejection.at);
+        let at = rejection.at;
+        let current = self.world.block_at(&at);
+        if current != rejection.block && self.world.chunk_at(&at).is_some() {
+            if current.is_some() {
+                self.world.apply_action(&Destroy { at });
+            }
+            if let Some(block) = rejection.block {
+                self.world.apply_action(&Add { at, block });
+            }
+        }
+
+        // The server did not update its copy of our items: they must be corrected as well
+        self.items = rejection.items;
+        self.update_items_bar();
+    }
 }
@@ -87,6 +87,8 @@ pub enum KeyCode {
     Digit8,
     Digit9,
     KeyP,
+    KeyQ,
+    KeyR,
     KeyX,
     F3,
     F10,
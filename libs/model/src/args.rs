@@ -1,5 +1,10 @@
+use crate::server::monster_spawner::DEFAULT_MAX_MONSTERS_PER_PLAYER;
+use crate::server::world_dispatcher::DEFAULT_VIEW_DISTANCE;
+use crate::world::generation::world_generator::DEFAULT_SEED;
 use clap::{Parser, ValueEnum};
 use std::env;
+use std::fmt::Display;
+use std::str::FromStr;
 use tracing::debug;
 
 #[allow(dead_code)]
@@ -26,6 +31,91 @@ impl WorldInitializer {
     }
 }
 
+/// Directory in which the world is stored, when none is provided by the user
+pub const DEFAULT_WORLD_DIR: &str = "world";
+
+/// Period between two automatic saves of the world, in seconds
+pub const DEFAULT_AUTOSAVE_PERIOD: u64 = 60;
+
+/// Parses a value with its `FromStr` implementation
+pub fn parse_value<T: FromStr>(value: &str) -> Result<T, String>
+where
+    T::Err: Display,
+{
+    value.parse().map_err(|err: T::Err| err.to_string())
+}
+
+/// Returns the value following the option `name` in `args`, parsed with `parse`, or `None` if the
+/// option is not given. A missing or invalid value is an error.
+pub fn parse_option<T>(
+    args: &[String],
+    name: &str,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<Option<T>, String> {
+    let Some(i) = args.iter().position(|arg| arg == name) else {
+        return Ok(None);
+    };
+    let value = args
+        .get(i + 1)
+        .ok_or_else(|| format!("a value is required for `{name}`"))?;
+    parse(value)
+        .map(Some)
+        .map_err(|err| format!("invalid value `{value}` for `{name}`: {err}"))
+}
+
+/// Returns the value of the option `name` in the arguments of the program, if it is given.
+/// Exits with an error when the value is missing or invalid, as the parser of clap does.
+fn option_from_args<T>(name: &str, parse: impl Fn(&str) -> Result<T, String>) -> Option<T> {
+    let args: Vec<String> = env::args().collect();
+    parse_option(&args, name, parse).unwrap_or_else(|err| {
+        eprintln!("error: {err}");
+        std::process::exit(2)
+    })
+}
+
+/// Returns the directory provided with `--world-dir <path>`, or the default one.
+pub fn world_dir_from_args() -> String {
+    option_from_args("--world-dir", parse_value).unwrap_or(DEFAULT_WORLD_DIR.to_string())
+}
+
+/// Returns the seed provided with `--seed <value>`, or the default seed.
+pub fn seed_from_args() -> u64 {
+    option_from_args("--seed", parse_value).unwrap_or(DEFAULT_SEED)
+}
+
+/// Returns the view distance provided with `--view-distance <chunks>`, or the default one.
+pub fn view_distance_from_args() -> u8 {
+    option_from_args("--view-distance", parse_value).unwrap_or(DEFAULT_VIEW_DISTANCE)
+}
+
+/// Parses a spawn point given as `x,z`
+pub fn parse_spawn_point(value: &str) -> Result<[f32; 2], String> {
+    let coordinates: Vec<f32> = value
+        .split(',')
+        .map(|c| c.trim().parse().map_err(|_| format!("invalid coordinate: {c}")))
+        .collect::<Result<_, _>>()?;
+    match coordinates[..] {
+        [x, z] => Ok([x, z]),
+        _ => Err(format!("expected `x,z`, got {value}")),
+    }
+}
+
+/// Returns the spawn point provided with `--spawn-point <x,z>`, if any.
+pub fn spawn_point_from_args() -> Option<[f32; 2]> {
+    option_from_args("--spawn-point", parse_spawn_point)
+}
+
+/// Returns true if the players keep their items when they die, with `--keep-inventory`.
+pub fn keep_inventory_from_args() -> bool {
+    env::args().any(|arg| arg == "--keep-inventory")
+}
+
+/// Returns the maximum number of monsters around each player provided with
+/// `--max-monsters <n>`, or the default one.
+pub fn max_monsters_from_args() -> usize {
+    option_from_args("--max-monsters", parse_value).unwrap_or(DEFAULT_MAX_MONSTERS_PER_PLAYER)
+}
+
 const ABOUT: &str = r#"
 
   |==========================|   
@@ -59,6 +149,27 @@ pub struct Args {
         default_value = "random"
     )]
     pub init: WorldInitializer,
+
+    #[arg(long, help = "Seed used to generate the world", default_value_t = DEFAULT_SEED)]
+    pub seed: u64,
+
+    #[arg(long, help = "Directory in which the world is stored", default_value_t = String::from(DEFAULT_WORLD_DIR))]
+    pub world_dir: String,
+
+    #[arg(long, help = "Period between two automatic saves of the world, in seconds", default_value_t = DEFAULT_AUTOSAVE_PERIOD)]
+    pub autosave: u64,
+
+    #[arg(long, help = "Number of chunks visible in each direction (for a server, the maximum allowed to the players)", default_value_t = DEFAULT_VIEW_DISTANCE)]
+    pub view_distance: u8,
+
+    #[arg(long, help = "Coordinates `x,z` around which the players spawn (by default, the one saved with the world)", value_parser = parse_spawn_point)]
+    pub spawn_point: Option<[f32; 2]>,
+
+    #[arg(long, help = "The players keep their items when they die")]
+    pub keep_inventory: bool,
+
+    #[arg(long, help = "Maximum number of monsters spawning around each player (0 to disable the spawning)", default_value_t = DEFAULT_MAX_MONSTERS_PER_PLAYER)]
+    pub max_monsters: usize,
 }
 
 impl Args {
@@ -70,3 +181,38 @@ impl Args {
         self.server.clone() + ":" + self.port.as_str()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::args::{parse_option, parse_spawn_point, parse_value};
+
+    #[test]
+    fn test_parse_spawn_point() {
+        assert_eq!(Ok([12., -3.5]), parse_spawn_point("12,-3.5"));
+        assert_eq!(Ok([0., 4.]), parse_spawn_point(" 0 , 4 "));
+        assert!(parse_spawn_point("12").is_err());
+        assert!(parse_spawn_point("1,2,3").is_err());
+        assert!(parse_spawn_point("a,2").is_err());
+    }
+
+    #[test]
+    fn test_parse_option() {
+        let args: Vec<String> = ["crafty", "--flat", "--seed", "7", "--spawn-point", "1,2"]
+            .map(String::from)
+            .to_vec();
+        assert_eq!(Ok(Some(7)), parse_option::<u64>(&args, "--seed", parse_value));
+        assert_eq!(Ok(None), parse_option::<u8>(&args, "--view-distance", parse_value));
+        assert_eq!(
+            Ok(Some([1., 2.])),
+            parse_option(&args, "--spawn-point", parse_spawn_point)
+        );
+
+        // The values that do not parse are not replaced by the default ones
+        let args: Vec<String> = ["crafty", "--seed", "abc"].map(String::from).to_vec();
+        assert!(parse_option::<u64>(&args, "--seed", parse_value).is_err());
+        let args: Vec<String> = ["crafty", "--view-distance", "300"].map(String::from).to_vec();
+        assert!(parse_option::<u8>(&args, "--view-distance", parse_value).is_err());
+        let args: Vec<String> = ["crafty", "--seed"].map(String::from).to_vec();
+        assert!(parse_option::<u64>(&args, "--seed", parse_value).is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::collision::aabb::AABB;
+use crate::game::player::PLAYER_MARGIN;
 use primitives::position::Position;
 use primitives::vector::Vector3;
 
@@ -31,3 +32,46 @@ pub trait Collidable {
         velocity: &Vector3,
     ) -> Option<CollisionData>;
 }
+
+/// Integrates the velocity of an entity whose bounding box is given by `aabb`, and stops it at the
+/// first collision with the obstacles. The velocity loses its component along the normal of the
+/// colliding surface. Returns the time (in s) during which the entity moved, which is smaller than
+/// `dt` if there is a collision.
+pub fn move_with_collision(
+    obstacles: &impl Collidable,
+    position: &mut Position,
+    velocity: &mut Vector3,
+    aabb: fn(&Position) -> AABB,
+    dt: f32,
+) -> f32 {
+    let target = aabb(&(&*position + *velocity * dt));
+
+    let collision = obstacles
+        .collision_time(position, &aabb(position), &target, velocity)
+        .unwrap_or(CollisionData {
+            time: f32::MAX,
+            normal: Vector3::empty(),
+        });
+
+    if collision.time >= dt {
+        // can move straight away
+        *position += *velocity * dt;
+
+        dt
+    } else {
+        // The margin is between the entity and the block we colide with
+        // need the projection of velocity onto the normal
+        let mut dtmargin: f32 = 0.0;
+        if velocity.norm() >= 1e-10 {
+            dtmargin = PLAYER_MARGIN / collision.normal.dot(velocity).abs();
+        }
+        // we want to put a margin, to avoid collision even with floats rounding
+        *position += *velocity * (collision.time - dtmargin);
+
+        // remove component of velocity along the normal
+        let vnormal = collision.normal * collision.normal.dot(velocity);
+        *velocity -= vnormal;
+
+        collision.time
+    }
+}
@@ -1,13 +1,12 @@
-use super::monster::{MonsterAction, TransitionState};
+use super::entity::EntityId;
+use super::humanoid::humanoid_feet;
+use super::monster::{MonsterAction, MonsterStats, TransitionState};
+use super::pathfinding::PathFollower;
 use primitives::position::Position;
 use primitives::vector::Vector3;
 use crate::server::server_state::PlayerState;
 use crate::world::world::World;
 
-const CHASING_DISTANCE: f32 = 10.;
-const CHASER_ATTACK_COOLDOWN: f32 = 2.; // Time in second before new attack
-const ATTACK_RANGE: f32 = 1.8;
-
 /// Internal state of the monster,
 /// name are for convinience, they are not forced to a particular action before the action function
 #[derive(Clone)]
@@ -20,10 +19,12 @@ pub enum MonsterStateEnum {
     Jump,
 }
 
+/// A hostile monster, which chases the players that it sees and attacks them
 pub struct Chaser {
     state: MonsterStateEnum,
-    chasing: Option<usize>,
-    attack_cooldown: f32,
+    chasing: Option<EntityId>,
+    /// The path to the chased player
+    path: PathFollower,
 }
 
 impl TransitionState for Chaser {
@@ -43,22 +44,21 @@ impl TransitionState for Chaser {
         position: &Position,
         world: &World,
         player_list: &Vec<PlayerState>,
+        stats: &MonsterStats,
     ) {
-        // Update the timer for attack
-        self.attack_cooldown -= dt;
         // If we have a lock, then keep pursuing the current locked player
         if let Some(player_id) = self.chasing {
             if let Some(player_pos) = player_list
                 .iter()
                 .find(|p| p.id == player_id)
-                .map(|p| p.pos.pos())
+                .map(|p| p.pos.clone())
             {
                 // Condition to leave the lock: too far from player
-                if player_pos.distance_to(&position.pos()) > CHASING_DISTANCE {
+                if player_pos.distance_to(&position.pos()) > stats.sight_distance {
                     self.chasing = None;
                     self.state = MonsterStateEnum::Idle;
                 } else {
-                    self.go_to_target(position, player_pos, world)
+                    self.go_to_target(dt, position, &player_pos, world, stats)
                 }
             } else {
                 self.chasing = None;
@@ -71,29 +71,45 @@ impl TransitionState for Chaser {
             // Try to find any player that is in range
             if let Some(next_target) = player_list
                 .iter()
-                .find(|player| player.pos.distance_to(&position.pos()) < CHASING_DISTANCE)
+                .find(|player| player.pos.distance_to(&position.pos()) < stats.sight_distance)
             {
                 self.chasing = Some(next_target.id);
             }
         }
     }
 
-    fn new() -> Self {
+}
+
+impl Chaser {
+    pub fn new(_stats: &MonsterStats) -> Self {
         Self {
             state: MonsterStateEnum::Idle,
             chasing: None,
-            attack_cooldown: CHASER_ATTACK_COOLDOWN,
+            path: PathFollower::new(),
         }
     }
-}
 
-impl Chaser {
-    // Go to a target position by first rotating then going forward
-    fn go_to_target(&mut self, position: &Position, target: Vector3, world: &World) {
+    // Go to a target position along a path, by first rotating then going forward
+    fn go_to_target(
+        &mut self,
+        dt: f32,
+        position: &Position,
+        target: &Position,
+        world: &World,
+        stats: &MonsterStats,
+    ) {
         let forward = position.ground_direction_forward();
         let side = position.ground_direction_right();
-        let direction_target = target - position.pos();
-        let mut direction_target_normalize = direction_target.clone();
+        let direction_target = target.pos() - position.pos();
+
+        // Head to the next cell of the path, or straight to the target without path
+        let waypoint = self.path.next_waypoint(dt, position, target, world);
+        let mut direction_target_normalize = match waypoint {
+            Some(waypoint) => {
+                Vector3::new(waypoint[0] - position.x(), 0., waypoint[2] - position.z())
+            }
+            None => direction_target,
+        };
         direction_target_normalize.normalize();
 
         // Compute the dot product between the direction and the side
@@ -113,23 +129,24 @@ impl Chaser {
                 return;
             }
 
-            // Quit if close enought and try to attack
-            if direction_target.norm() < ATTACK_RANGE {
-                if self.attack_cooldown < 0. {
-                    self.state = MonsterStateEnum::Attack;
-                    self.attack_cooldown = CHASER_ATTACK_COOLDOWN;
-                } else {
-                    self.state = MonsterStateEnum::Idle;
-                }
+            // Quit if close enought and try to attack, the monster waits for its cooldown
+            if direction_target.norm() < stats.attack_range {
+                self.state = MonsterStateEnum::Attack;
                 return;
             }
 
             // If here, we have a target, we are facing it, time to move toward it !
-            // Check if there is a block on the way if so jump !
-            let mut pos = position.pos() + forward;
-            // The block is on the ground, not facing the eyes
-            pos[1] -= 1.;
-            if world.cube_at(pos).is_none() {
+            // Jump if the next cell of the path is higher, or if there is a block on the way
+            let must_jump = match waypoint {
+                Some(waypoint) => waypoint[1] > humanoid_feet(position)[1] + 0.5,
+                None => {
+                    let mut pos = position.pos() + forward;
+                    // The block is on the ground, not facing the eyes
+                    pos[1] -= 1.;
+                    world.cube_at(pos).is_some()
+                }
+            };
+            if !must_jump {
                 // Otherwise go toward the target !
                 self.state = MonsterStateEnum::Forward;
             } else {
@@ -138,3 +155,59 @@ impl Chaser {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::entity::entity::EntityKind;
+    use crate::entity::monster_registry::MonsterRegistry;
+    use crate::game::actions::Action;
+    use crate::game::player::{FOREHEAD, PLAYER_HEIGHT, PLAYER_MARGIN};
+    use crate::server::server_state::ServerState;
+    use crate::world::block_kind::Block;
+    use crate::world::chunk::{Chunk, CHUNK_SIZE};
+    use crate::world::world::World;
+    use primitives::position::Position;
+    use primitives::vector::Vector3;
+
+    #[test]
+    fn test_chaser_goes_around_walls() {
+        // A flat world, with a wall between the monster and the player
+        let s = CHUNK_SIZE as f32;
+        let mut chunks = Vec::new();
+        for i in 0..3 {
+            for j in 0..3 {
+                let mut chunk = Chunk::new([i as f32 * s, j as f32 * s]);
+                chunk.fill_layer(0, Block::STONE);
+                chunks.push(chunk);
+            }
+        }
+        let mut world = World::new(chunks);
+        for z in 0..=6 {
+            for y in 1..=2 {
+                world.apply_action(&Action::Add {
+                    at: Vector3::newi(5, y, z),
+                    block: Block::STONE,
+                });
+            }
+        }
+
+        let eyes = 1. + PLAYER_HEIGHT - FOREHEAD + PLAYER_MARGIN;
+        let mut player = ServerState::new().login("arthur".to_string());
+        player.pos = Position::new_vec(8.5, eyes, 2.5);
+        let players = vec![player];
+        let start = Position::new_vec(2.5, eyes, 2.5);
+        let mut monster = MonsterRegistry::default()
+            .spawn(50, EntityKind::Monster1, start)
+            .unwrap();
+
+        for _ in 0..2000 {
+            monster.update(&world, 0.015, &players);
+            if monster.attack().is_some() {
+                // The monster never went through the wall
+                assert!(monster.position().x() > 5.);
+                return;
+            }
+        }
+        panic!("The monster is stuck at {:?}", monster.position());
+    }
+}
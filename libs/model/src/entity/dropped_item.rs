@@ -0,0 +1,257 @@
+use crate::collision::aabb::AABB;
+use crate::collision::collidable::{self, Collidable};
+use crate::entity::entity::{EntityId, EntityKind};
+use crate::game::item::Item;
+use crate::game::player::{FOREHEAD, GRAVITY_ACCELERATION_VECTOR, PLAYER_HEIGHT, PLAYER_MARGIN};
+use crate::game::player_items::ItemStack;
+use crate::world::world::World;
+use primitives::position::Position;
+use primitives::render_data::CubeRenderData;
+use primitives::vector::Vector3;
+
+/// Length of the edges of the cube drawn for a dropped item
+pub const DROPPED_ITEM_SIZE: f32 = 0.25;
+
+/// Time [s] after which an item that nobody picked up disappears
+pub const DESPAWN_TIME: f32 = 300.;
+
+/// Time [s] before an item dropped by a destroyed block can be picked up
+pub const PICKUP_DELAY: f32 = 0.5;
+
+/// Time [s] before a thrown item can be picked up, so that the player who threw it does not pick it
+/// up right away
+pub const THROW_PICKUP_DELAY: f32 = 2.;
+
+/// Speed [cube/s] of an item thrown by a player
+pub const THROW_SPEED: f32 = 5.;
+
+/// Maximum distance between an item and the middle of the body of a player who picks it up
+const PICKUP_DISTANCE: f32 = 1.2;
+
+/// Number of collisions handled in one step, after which the item stops moving until the next step
+const MAX_COLLISIONS: usize = 3;
+
+/// An item lying in the world, which falls until it reaches the ground and can be picked up by
+/// the players walking over it
+pub struct DroppedItem {
+    id: EntityId,
+    stack: ItemStack,
+    /// Center of the item
+    position: Position,
+    velocity: Vector3,
+    /// Time [s] since the item was dropped
+    age: f32,
+    /// Age [s] from which the item can be picked up
+    pickup_delay: f32,
+}
+
+impl DroppedItem {
+    pub fn new(
+        id: EntityId,
+        stack: ItemStack,
+        position: Vector3,
+        velocity: Vector3,
+        pickup_delay: f32,
+    ) -> Self {
+        Self {
+            id,
+            stack,
+            position: Position::from_pos(position),
+            velocity,
+            age: 0.,
+            pickup_delay,
+        }
+    }
+
+    /// Makes the item fall, with collision. Returns true if the item moved.
+    pub fn step(&mut self, dt: f32, world: &World) -> bool {
+        self.age += dt;
+
+        // The item waits for the chunk below it to be loaded
+        if world.chunk_at(&self.position.pos()).is_none() {
+            return false;
+        }
+
+        // A cube was placed on the item: it is pushed on top of it
+        if world.collides(&dropped_item_aabb(&self.position)) {
+            let bottom = self.position.y() - DROPPED_ITEM_SIZE / 2.;
+            let top_of_cube = bottom.floor() + 1. + DROPPED_ITEM_SIZE / 2. + PLAYER_MARGIN;
+            self.position.raise(top_of_cube - self.position.y());
+            self.velocity = Vector3::empty();
+            return true;
+        }
+
+        // The ground is checked at each step, since the cube below the item may be destroyed
+        let displacement = Vector3::new(0., -2.0 * PLAYER_MARGIN, 0.);
+        let in_air = !world.collides(&dropped_item_aabb(&(&self.position + displacement)));
+        if in_air {
+            self.velocity += GRAVITY_ACCELERATION_VECTOR * dt;
+        } else {
+            // Once on the ground, the item does not slide
+            self.velocity = Vector3::empty();
+            return false;
+        }
+
+        let mut remaining = dt;
+        for _ in 0..MAX_COLLISIONS {
+            remaining -= collidable::move_with_collision(
+                world,
+                &mut self.position,
+                &mut self.velocity,
+                dropped_item_aabb,
+                remaining,
+            );
+            if remaining <= 0. {
+                break;
+            }
+        }
+        true
+    }
+
+    pub fn id(&self) -> EntityId {
+        self.id
+    }
+
+    pub fn stack(&self) -> &ItemStack {
+        &self.stack
+    }
+
+    pub fn position(&self) -> &Position {
+        &self.position
+    }
+
+    pub fn entity_kind(&self) -> EntityKind {
+        EntityKind::DroppedItem(self.stack.item)
+    }
+
+    /// Returns true if the item has been lying in the world for too long, and must disappear
+    pub fn is_expired(&self) -> bool {
+        self.age >= DESPAWN_TIME
+    }
+
+    /// Returns true if the player, whose eyes are at the given position, can pick up the item
+    pub fn can_be_picked_up_by(&self, player: &Position) -> bool {
+        let body = player.pos() + Vector3::new(0., FOREHEAD - PLAYER_HEIGHT / 2., 0.);
+        self.age >= self.pickup_delay && body.distance_to(&self.position.pos()) <= PICKUP_DISTANCE
+    }
+}
+
+/// Returns the bounding box around the item
+pub fn dropped_item_aabb(position: &Position) -> AABB {
+    let half = DROPPED_ITEM_SIZE / 2.;
+    AABB::new(
+        position.z() + half,
+        position.z() - half,
+        position.y() + half,
+        position.y() - half,
+        position.x() + half,
+        position.x() - half,
+    )
+    .unwrap()
+}
+
+/// Returns the small cube drawn for an item whose center is at the given position. A block is
+/// drawn with its own textures, the other items with their icon on every face.
+pub fn dropped_item_render_data(position: &Position, item: Item) -> CubeRenderData {
+    let (block_id, texture_id) = match item {
+        Item::Block(block) => (block.id(), None),
+        Item::Other(_) => (0, Some(item.texture_id() as i32)),
+    };
+    let half = DROPPED_ITEM_SIZE / 2.;
+    CubeRenderData {
+        position: position.pos() - Vector3::new(half, half, half),
+        block_id,
+        is_selected: false,
+        scale: DROPPED_ITEM_SIZE,
+        texture_id,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entity::dropped_item::{DroppedItem, DESPAWN_TIME, DROPPED_ITEM_SIZE, PICKUP_DELAY};
+    use crate::game::actions::Action;
+    use crate::game::player_items::ItemStack;
+    use crate::world::block_kind::Block;
+    use crate::world::chunk::Chunk;
+    use crate::world::world::World;
+    use primitives::position::Position;
+    use primitives::vector::Vector3;
+
+    #[test]
+    fn test_item_falls_on_the_ground() {
+        let mut world = World::empty();
+        let mut chunk = Chunk::new([0., 0.]);
+        chunk.fill_layer(0, Block::DIRT);
+        world.add_chunk(chunk);
+
+        let stack = ItemStack::new(Block::SAND.into());
+        let start = Vector3::new(4.5, 5.5, 4.5);
+        let mut item = DroppedItem::new(0, stack, start, Vector3::empty(), PICKUP_DELAY);
+
+        // Nobody can pick up the item right away
+        let player = Position::new_vec(4.5, 2.6, 4.5);
+        assert!(!item.can_be_picked_up_by(&player));
+
+        for _ in 0..200 {
+            item.step(0.01, &world);
+        }
+
+        // It lies on the ground, on top of the first layer
+        let y = item.position().y();
+        assert!((y - 1. - DROPPED_ITEM_SIZE / 2.).abs() < 1e-3, "{y}");
+        assert!(!item.step(0.01, &world));
+        assert!(item.can_be_picked_up_by(&player));
+        assert!(!item.can_be_picked_up_by(&Position::new_vec(10., 2.6, 4.5)));
+
+        // A cube placed on it pushes it above
+        world.apply_action(&Action::Add {
+            at: Vector3::new(4., 1., 4.),
+            block: Block::STONE,
+        });
+        assert!(item.step(0.01, &world));
+        assert!(item.position().y() > 2.);
+    }
+
+    #[test]
+    fn test_item_falls_when_its_support_is_destroyed() {
+        let mut world = World::empty();
+        let mut chunk = Chunk::new([0., 0.]);
+        chunk.fill_layer(0, Block::DIRT);
+        chunk.fill_layer(1, Block::DIRT);
+        world.add_chunk(chunk);
+
+        let stack = ItemStack::new(Block::SAND.into());
+        let start = Vector3::new(4.5, 2.5, 4.5);
+        let mut item = DroppedItem::new(0, stack, start, Vector3::empty(), PICKUP_DELAY);
+        for _ in 0..100 {
+            item.step(0.01, &world);
+        }
+        assert!(!item.step(0.01, &world));
+
+        // The cube below the item is mined: the item falls on the first layer
+        world.apply_action(&Action::Destroy {
+            at: Vector3::new(4., 1., 4.),
+        });
+        assert!(item.step(0.01, &world));
+        for _ in 0..100 {
+            item.step(0.01, &world);
+        }
+        let y = item.position().y();
+        assert!((y - 1. - DROPPED_ITEM_SIZE / 2.).abs() < 1e-3, "{y}");
+    }
+
+    #[test]
+    fn test_item_disappears_after_a_while() {
+        // The item does not fall out of the loaded chunks
+        let stack = ItemStack::new(Block::SAND.into());
+        let start = Vector3::new(4.5, 5.5, 4.5);
+        let mut item = DroppedItem::new(0, stack, start, Vector3::empty(), PICKUP_DELAY);
+        assert!(!item.step(DESPAWN_TIME / 2., &World::empty()));
+        assert_eq!(start, item.position().pos());
+        assert!(!item.is_expired());
+
+        item.step(DESPAWN_TIME / 2., &World::empty());
+        assert!(item.is_expired());
+    }
+}
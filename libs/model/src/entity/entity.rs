@@ -1,32 +1,57 @@
 use crate::collision::aabb::AABB;
+use crate::entity::dropped_item::{dropped_item_aabb, dropped_item_render_data};
+use crate::entity::falling_block::{falling_block_aabb, falling_block_render_data};
 use crate::entity::humanoid;
 use crate::entity::humanoid::humanoid_aabb;
-use primitives::render_data::EntityRenderData;
+use crate::game::item::Item;
+use crate::world::block_kind::Block;
+use primitives::render_data::{CubeRenderData, EntityRenderData};
 use primitives::position::Position;
 
+/// Identifies an entity (player, monster, dropped item...) for the server and all the clients.
+/// The ids are given by the server, and are never shared by two entities.
+pub type EntityId = u32;
+
+/// Time [s] during which an entity is drawn in red after being hurt
+const HURT_FLASH_TIME: f32 = 0.3;
+
 #[derive(Debug, PartialEq, Clone)]
 /// Enum for the different types of entity
 pub enum EntityKind {
     Player,
     Monster1,
     Monster2,
+    /// An item lying in the world
+    DroppedItem(Item),
+    /// A block falling until it lands on the ground
+    FallingBlock(Block),
 }
 
 impl EntityKind {
-    pub fn to_u8(&self) -> u8 {
+    /// Returns the binary representation of the kind: its code, followed by the dropped item or
+    /// the id of the falling block if there is one
+    pub fn to_bytes(&self) -> Vec<u8> {
         match self {
-            Self::Player => 0,
-            Self::Monster1 => 1,
-            Self::Monster2 => 2,
+            Self::Player => vec![0],
+            Self::Monster1 => vec![1],
+            Self::Monster2 => vec![2],
+            Self::DroppedItem(item) => [&[3], item.to_bytes().as_slice()].concat(),
+            Self::FallingBlock(block) => vec![4, block.id()],
         }
     }
 
-    pub fn from_u8(entity_code: u8) -> Self {
-        match entity_code {
-            0 => Self::Player,
-            1 => Self::Monster1,
-            2 => Self::Monster2,
-            _ => Self::Monster1,
+    /// Parses the kind at the start of the bytes, and returns it with the remaining bytes.
+    /// Returns `None` if the bytes do not contain a valid kind.
+    pub fn from_bytes(bytes: &[u8]) -> Option<(Self, &[u8])> {
+        match bytes {
+            [0, rest @ ..] => Some((Self::Player, rest)),
+            [2, rest @ ..] => Some((Self::Monster2, rest)),
+            [3, b0, b1, rest @ ..] => {
+                Some((Self::DroppedItem(Item::from_bytes([*b0, *b1])?), rest))
+            }
+            [4, id, rest @ ..] => Some((Self::FallingBlock(Block::from_id(*id)?), rest)),
+            [3, ..] | [4, ..] | [] => None,
+            [_, rest @ ..] => Some((Self::Monster1, rest)),
         }
     }
 
@@ -36,24 +61,52 @@ impl EntityKind {
             _ => false,
         }
     }
+
+    /// Returns true if the players can attack this entity
+    pub fn is_attackable(&self) -> bool {
+        !matches!(self, Self::DroppedItem(_) | Self::FallingBlock(_))
+    }
 }
 
 /// Contain the data of an entity
 pub struct Entity {
-    id: usize,
+    id: EntityId,
     entity_type: EntityKind,
     position: Position,
+    /// Last health sent by the server, if any
+    health: Option<u8>,
+    /// Time [s] left during which the entity is drawn as hurt
+    hurt_flash: f32,
 }
 
 impl Entity {
-    pub fn new(id: usize, entity_type: EntityKind, position: Position) -> Self {
+    pub fn new(id: EntityId, entity_type: EntityKind, position: Position) -> Self {
         Self {
             id,
             entity_type,
             position,
+            health: None,
+            hurt_flash: 0.,
         }
     }
 
+    /// Updates the health of the entity, which flashes if it went down
+    pub fn set_health(&mut self, health: u8) {
+        if self.health.is_none_or(|previous| health < previous) {
+            self.hurt_flash = HURT_FLASH_TIME;
+        }
+        self.health = Some(health);
+    }
+
+    /// Lets the time pass, for the flash to fade
+    pub fn step(&mut self, dt: f32) {
+        self.hurt_flash = (self.hurt_flash - dt).max(0.);
+    }
+
+    pub fn is_hurt(&self) -> bool {
+        self.hurt_flash > 0.
+    }
+
     pub fn set_position(&mut self, position: Position) {
         self.position = position;
     }
@@ -62,7 +115,7 @@ impl Entity {
         &self.position
     }
 
-    pub fn id(&self) -> usize {
+    pub fn id(&self) -> EntityId {
         self.id
     }
 
@@ -72,10 +125,24 @@ impl Entity {
 
     /// Draw the entity and return a Vec of EntityRenderData
     pub fn get_opengl_entities(&self) -> Vec<EntityRenderData> {
+        let position = self.position.clone();
+        match self.entity_type {
+            EntityKind::Player => humanoid::get_opengl_entities(position, 0, self.is_hurt()),
+            EntityKind::Monster1 => humanoid::get_opengl_entities(position, 1, self.is_hurt()),
+            EntityKind::Monster2 => humanoid::get_opengl_entities(position, 2, self.is_hurt()),
+            // Drawn as a cube (see `get_cube`)
+            EntityKind::DroppedItem(_) | EntityKind::FallingBlock(_) => Vec::new(),
+        }
+    }
+
+    /// Returns the cube to draw for the entity, if it is drawn as a cube
+    pub fn get_cube(&self) -> Option<CubeRenderData> {
         match self.entity_type {
-            EntityKind::Player => humanoid::get_opengl_entities(self.position.clone(), 0),
-            EntityKind::Monster1 => humanoid::get_opengl_entities(self.position.clone(), 1),
-            EntityKind::Monster2 => humanoid::get_opengl_entities(self.position.clone(), 2),
+            EntityKind::DroppedItem(item) => Some(dropped_item_render_data(&self.position, item)),
+            EntityKind::FallingBlock(block) => {
+                Some(falling_block_render_data(&self.position, block))
+            }
+            _ => None,
         }
     }
 
@@ -84,6 +151,8 @@ impl Entity {
             EntityKind::Player | EntityKind::Monster1 | EntityKind::Monster2 => {
                 humanoid_aabb(&self.position)
             }
+            EntityKind::DroppedItem(_) => dropped_item_aabb(&self.position),
+            EntityKind::FallingBlock(_) => falling_block_aabb(&self.position),
         }
     }
 }
@@ -1,7 +1,7 @@
-use crate::entity::entity::{Entity, EntityKind};
+use crate::entity::entity::{Entity, EntityId, EntityKind};
 use crate::game::attack::EntityAttack;
 use crate::world::cube::Cube;
-use primitives::render_data::EntityRenderData;
+use primitives::render_data::{CubeRenderData, EntityRenderData};
 use primitives::position::Position;
 use primitives::vector::Vector3;
 use std::collections::HashMap;
@@ -9,7 +9,7 @@ use tracing::{debug, info};
 
 /// Contain all the entities
 pub struct EntityManager {
-    entities: HashMap<u8, Entity>,
+    entities: HashMap<EntityId, Entity>,
 }
 
 impl EntityManager {
@@ -20,26 +20,40 @@ impl EntityManager {
     }
 
     /// Register another player, provided its id and initial position
-    pub fn register_new_entity(&mut self, id: u8, entity_kind: EntityKind, pos: Position) {
-        info!("New player has joined the game: {id}");
-        let entity = Entity::new(id as usize, entity_kind, pos.clone());
+    pub fn register_new_entity(&mut self, id: EntityId, entity_kind: EntityKind, pos: Position) {
+        if entity_kind.is_player() {
+            info!("New player has joined the game: {id}");
+        }
+        let entity = Entity::new(id, entity_kind, pos.clone());
         self.entities.insert(id, entity);
     }
 
     /// Remove an entity from the Manager
-    pub fn remove_entity(&mut self, id: u8) {
+    pub fn remove_entity(&mut self, id: EntityId) {
         if !self.entities.contains_key(&id) {
             panic!("Trying to remove an id that do not exist")
         }
         self.entities.remove(&id);
     }
 
-    pub fn set_position(&mut self, id: u8, position: Position) {
+    pub fn set_position(&mut self, id: EntityId, position: Position) {
         self.entities
             .get_mut(&id)
             .map(|entity| entity.set_position(position));
     }
 
+    /// Updates the health of an entity, which flashes when it is hurt
+    pub fn set_health(&mut self, id: EntityId, health: u8) {
+        if let Some(entity) = self.entities.get_mut(&id) {
+            entity.set_health(health);
+        }
+    }
+
+    /// Lets the time pass for all the entities
+    pub fn step(&mut self, dt: f32) {
+        self.entities.values_mut().for_each(|entity| entity.step(dt));
+    }
+
     /// Returns the list of OpenGL attributes to be rendered
     pub fn get_opengl_entities(&self) -> Vec<EntityRenderData> {
         self.entities
@@ -49,10 +63,19 @@ impl EntityManager {
             .concat()
     }
 
+    /// Returns the cubes to be rendered for the entities drawn as cubes (i.e. the dropped items)
+    pub fn get_cubes(&self) -> Vec<CubeRenderData> {
+        self.entities
+            .values()
+            .filter_map(|entity| entity.get_cube())
+            .collect()
+    }
+
     pub fn attack(&self, position: Vector3, direction: Vector3) -> Option<EntityAttack> {
         if let Some((id, _)) = self
             .entities
             .iter()
+            .filter(|(_, entity)| entity.entity_type().is_attackable())
             .map(|(id, entity)| (id, entity.aabb().faces()))
             .find(|(_, faces)| Cube::intersection_with_faces(&faces, position, direction).is_some())
         {
@@ -107,6 +130,27 @@ mod tests {
         assert_eq!(None, mgr.attack(Vector3::unit_x(), Vector3::unit_z()));
     }
 
+    #[test]
+    fn test_hurt_entities_flash() {
+        let mut mgr = EntityManager::new();
+        mgr.register_new_entity(50, EntityKind::Monster1, Position::from_pos(Vector3::empty()));
+        let is_hurt = |mgr: &EntityManager| mgr.get_opengl_entities().iter().all(|e| e.is_hurt);
+        assert!(!is_hurt(&mgr));
+
+        mgr.set_health(50, 8);
+        assert!(is_hurt(&mgr));
+        mgr.step(1.);
+        assert!(!is_hurt(&mgr));
+
+        // Healing does not flash, and the flash fades after a while
+        mgr.set_health(50, 9);
+        assert!(!is_hurt(&mgr));
+        mgr.set_health(50, 5);
+        assert!(is_hurt(&mgr));
+        mgr.step(1.);
+        assert!(!is_hurt(&mgr));
+    }
+
     #[test]
     #[should_panic]
     fn test_remove() {
@@ -0,0 +1,123 @@
+use crate::collision::aabb::AABB;
+use crate::entity::entity::{EntityId, EntityKind};
+use crate::game::player::GRAVITY_ACCELERATION_VECTOR;
+use crate::world::block_kind::Block;
+use crate::world::cube::Cube;
+use crate::world::world::World;
+use primitives::position::Position;
+use primitives::render_data::CubeRenderData;
+use primitives::vector::Vector3;
+
+/// A block that lost the cube below it. It falls until it lands on a solid cube, where it becomes
+/// part of the world again.
+pub struct FallingBlock {
+    id: EntityId,
+    block: Block,
+    /// Lowest corner of the block, which stays aligned with the cubes of the world horizontally
+    position: Position,
+    /// Speed [cube/s] of the fall
+    speed: f32,
+}
+
+impl FallingBlock {
+    /// Creates the block that starts falling from the cube at `at`
+    pub fn new(id: EntityId, block: Block, at: Vector3) -> Self {
+        Self {
+            id,
+            block,
+            position: Position::from_pos(at),
+            speed: 0.,
+        }
+    }
+
+    /// Makes the block fall. Returns the position of the cube where it lands, if it reached the
+    /// ground during this step.
+    pub fn step(&mut self, dt: f32, world: &World) -> Option<Vector3> {
+        // The block waits for the chunk below it to be loaded
+        world.chunk_at(&self.position.pos())?;
+
+        self.speed -= GRAVITY_ACCELERATION_VECTOR[1] * dt;
+        let start = self.position.y();
+        let end = start - self.speed * dt;
+
+        // The first solid cube that the block reaches stops it, starting with the cube that the
+        // bottom of the block is in, which may have landed right before. The bottom of the world
+        // stops it too.
+        let mut below = start.floor();
+        while below + 1. >= end {
+            let at = Vector3::new(self.position.x(), below, self.position.z());
+            let is_solid = world.block_at(&at).is_some_and(|block| !block.is_fluid());
+            if is_solid || below < 0. {
+                return Some(at + Vector3::unit_y());
+            }
+            below -= 1.;
+        }
+
+        self.position.raise(end - start);
+        None
+    }
+
+    pub fn id(&self) -> EntityId {
+        self.id
+    }
+
+    pub fn block(&self) -> Block {
+        self.block
+    }
+
+    pub fn position(&self) -> &Position {
+        &self.position
+    }
+
+    pub fn entity_kind(&self) -> EntityKind {
+        EntityKind::FallingBlock(self.block)
+    }
+}
+
+/// Returns the bounding box of a falling block whose lowest corner is at the given position
+pub fn falling_block_aabb(position: &Position) -> AABB {
+    Cube::cube_aabb(position.pos())
+}
+
+/// Returns the cube drawn for a falling block whose lowest corner is at the given position
+pub fn falling_block_render_data(position: &Position, block: Block) -> CubeRenderData {
+    CubeRenderData {
+        position: position.pos(),
+        block_id: block.id(),
+        is_selected: false,
+        scale: 1.,
+        texture_id: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entity::falling_block::FallingBlock;
+    use crate::world::block_kind::Block;
+    use crate::world::chunk::Chunk;
+    use crate::world::world::World;
+    use primitives::vector::Vector3;
+
+    #[test]
+    fn test_block_lands_on_the_first_solid_cube() {
+        let mut chunk = Chunk::new([0., 0.]);
+        chunk.fill_layer(0, Block::STONE);
+        chunk.fill_layer(1, Block::WATER);
+        let world = World::new(vec![chunk]);
+
+        // The sand goes through the water
+        let mut sand = FallingBlock::new(0, Block::SAND, Vector3::new(3., 6., 2.));
+        let mut landing = None;
+        for _ in 0..200 {
+            landing = sand.step(0.01, &world);
+            if landing.is_some() {
+                break;
+            }
+        }
+        assert_eq!(Some(Vector3::new(3., 1., 2.)), landing);
+
+        // Even a fast block does not go through the ground
+        let mut sand = FallingBlock::new(0, Block::SAND, Vector3::new(3., 6., 2.));
+        assert_eq!(Some(Vector3::new(3., 1., 2.)), sand.step(1., &world));
+    }
+}
@@ -72,7 +72,11 @@ pub const PLAYER_CUT_TEMPLATE: [ImageCut; 24] = [
 pub const HUMANOID_TEXTURES_PATH: [&str; 2] = ["player.png", "monster.png"];
 
 /// Return a vector of EntityRenderData forming a humanoid
-pub fn get_opengl_entities(mut position: Position, monster_type: u8) -> Vec<EntityRenderData> {
+pub fn get_opengl_entities(
+    mut position: Position,
+    monster_type: u8,
+    is_hurt: bool,
+) -> Vec<EntityRenderData> {
     let mut ent = Vec::new();
 
     // Head
@@ -84,6 +88,7 @@ pub fn get_opengl_entities(mut position: Position, monster_type: u8) -> Vec<Enti
         position: position.clone(),
         body_part_id: 0,
         monster_type,
+        is_hurt,
         scale: [PLAYER_HEAD_SIZE; 3],
     });
     position += Vector3::newf(PLAYER_HEAD_OFFSET)
@@ -97,6 +102,7 @@ pub fn get_opengl_entities(mut position: Position, monster_type: u8) -> Vec<Enti
         position: position.clone(),
         body_part_id: 2,
         monster_type,
+        is_hurt,
         scale: PLAYER_BODY_SCALE,
     });
 
@@ -106,6 +112,7 @@ pub fn get_opengl_entities(mut position: Position, monster_type: u8) -> Vec<Enti
         position: position.clone(),
         body_part_id: 3,
         monster_type,
+        is_hurt,
         scale: PLAYER_ARM_SCALE,
     });
     position += Vector3::newf(PLAYER_ARM_OFFSET).rotation_y(position.yaw()) * -2.;
@@ -113,6 +120,7 @@ pub fn get_opengl_entities(mut position: Position, monster_type: u8) -> Vec<Enti
         position: position.clone(),
         body_part_id: 3,
         monster_type,
+        is_hurt,
         scale: PLAYER_ARM_SCALE,
     });
     position += Vector3::newf(PLAYER_ARM_OFFSET).rotation_y(position.yaw());
@@ -123,6 +131,7 @@ pub fn get_opengl_entities(mut position: Position, monster_type: u8) -> Vec<Enti
         position: position.clone(),
         body_part_id: 1,
         monster_type,
+        is_hurt,
         scale: PLAYER_LEG_SCALE,
     });
     position += Vector3::new(0., 0., -2. * PLAYER_LEG_WIDTH_SHIFT).rotation_y(position.yaw());
@@ -130,6 +139,7 @@ pub fn get_opengl_entities(mut position: Position, monster_type: u8) -> Vec<Enti
         position: position.clone(),
         body_part_id: 1,
         monster_type,
+        is_hurt,
         scale: PLAYER_LEG_SCALE,
     });
 
@@ -148,3 +158,8 @@ pub fn humanoid_aabb(eye_position: &Position) -> AABB {
     )
     .unwrap()
 }
+
+/// Returns the position of the feet of the player, below the eyes
+pub fn humanoid_feet(eye_position: &Position) -> Vector3 {
+    eye_position.pos() - Vector3::unit_y() * (PLAYER_HEIGHT - FOREHEAD)
+}
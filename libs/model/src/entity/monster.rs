@@ -1,14 +1,14 @@
-use crate::collision::collidable::{Collidable, CollisionData};
-use crate::entity::entity::EntityKind;
+use crate::collision::collidable::{self, Collidable};
+use crate::entity::entity::{EntityId, EntityKind};
 use crate::game::attack::EntityAttack;
+use crate::game::health::INVULNERABILITY_TIME;
 use crate::game::player::{GRAVITY_ACCELERATION_VECTOR, JUMP_VELOCITY, PLAYER_MARGIN};
 use primitives::position::Position;
 use primitives::vector::Vector3;
 use crate::server::server_state::PlayerState;
+use crate::world::fluid::{swim, FLUID_SPEED_FACTOR};
 use crate::world::world::World;
 use super::humanoid::humanoid_aabb;
-const MONSTER1_SPEED: f32 = 2.;
-const MONSTER1_ROTATION_SPEED: f32 = 0.10;
 
 #[derive(Clone)]
 /// Action that the monster can realize
@@ -16,11 +16,30 @@ pub enum MonsterAction {
     Forward,
     LeftRot,
     RightRot,
-    Attack(usize),
+    Attack(EntityId),
     Jump,
     Idle,
 }
 
+/// Characteristics of a kind of monster
+#[derive(Clone, Debug)]
+pub struct MonsterStats {
+    /// Speed [cube/s] at which it walks
+    pub speed: f32,
+    /// Angle [rad] by which it turns at each step
+    pub rotation_speed: f32,
+    /// Health points with which it spawns
+    pub health: u8,
+    /// Health points that its attacks remove
+    pub damage: u8,
+    /// Distance [cube] to a player below which it attacks them
+    pub attack_range: f32,
+    /// Time [s] between two attacks
+    pub attack_cooldown: f32,
+    /// Distance [cube] below which it notices the players
+    pub sight_distance: f32,
+}
+
 /// This trait implement a state machine for the internal logic of monster
 pub trait TransitionState {
     /// Return the monster's action
@@ -32,32 +51,45 @@ pub trait TransitionState {
         position: &Position,
         world: &World,
         player_list: &Vec<PlayerState>,
+        stats: &MonsterStats,
     );
-    fn new() -> Self;
 }
 
 /// Contain the data of a monster
-pub struct Monster<T> {
-    id: usize,
+pub struct Monster {
+    id: EntityId,
     entity_type: EntityKind,
     position: Position,
-    transition: T,
+    transition: Box<dyn TransitionState + Send>,
+    stats: MonsterStats,
+    health: u8,
+    /// Time [s] before the monster can be hurt again
+    invulnerability: f32,
+    /// Time [s] before the monster can attack again
+    attack_cooldown: f32,
     in_air: bool,
     velocity: Vector3,
     /// If the monster is attacking, if so give the id of the victim
     attack: Option<EntityAttack>,
 }
 
-impl<T> Monster<T>
-where
-    T: TransitionState,
-{
-    pub fn new(id: usize, entity_type: EntityKind, position: Position) -> Self {
+impl Monster {
+    pub fn new(
+        id: EntityId,
+        entity_type: EntityKind,
+        position: Position,
+        stats: MonsterStats,
+        transition: Box<dyn TransitionState + Send>,
+    ) -> Self {
         Self {
             id,
             entity_type,
             position,
-            transition: TransitionState::new(),
+            transition,
+            health: stats.health,
+            invulnerability: 0.,
+            attack_cooldown: stats.attack_cooldown,
+            stats,
             in_air: true,
             velocity: Vector3::empty(),
             attack: None,
@@ -66,38 +98,90 @@ where
 
     /// Update the state of the monster and do an action (move, attack)
     pub fn update(&mut self, world: &World, dt: f32, player_list: &Vec<PlayerState>) {
+        self.invulnerability = (self.invulnerability - dt).max(0.);
+        self.attack_cooldown -= dt;
+
+        // The monster waits for its chunk to be loaded
+        if world.chunk_at(&self.position.pos()).is_none() {
+            return;
+        }
+
         // Update the internal state of transition
         self.transition
-            .update(dt, &self.position, world, player_list);
+            .update(dt, &self.position, world, player_list, &self.stats);
 
         // Apply the action return by transition
-        self.apply_action(self.transition.action(), dt, world);
+        self.apply_action(self.transition.action(), dt, world, player_list);
+    }
+
+    /// Hurts the monster, unless it was hurt too recently. Returns true if the monster lost
+    /// health.
+    pub fn damage(&mut self, strength: u8) -> bool {
+        if strength == 0 || self.invulnerability > 0. || !self.alive() {
+            return false;
+        }
+        self.health = self.health.saturating_sub(strength);
+        self.invulnerability = INVULNERABILITY_TIME;
+        true
+    }
+
+    pub fn alive(&self) -> bool {
+        self.health > 0
+    }
+
+    pub fn health(&self) -> u8 {
+        self.health
     }
 
     /// Apply the action of the monster
-    fn apply_action(&mut self, action: MonsterAction, mut dt: f32, world: &World) {
+    fn apply_action(
+        &mut self,
+        action: MonsterAction,
+        mut dt: f32,
+        world: &World,
+        player_list: &[PlayerState],
+    ) {
         self.attack = None;
+        let in_fluid = world.is_in_fluid(&humanoid_aabb(&self.position));
         match action {
             MonsterAction::Forward => {
-                let velocity_hor = self.position.ground_direction_forward() * MONSTER1_SPEED;
+                let speed = if in_fluid {
+                    self.stats.speed * FLUID_SPEED_FACTOR
+                } else {
+                    self.stats.speed
+                };
+                let velocity_hor = self.position.ground_direction_forward() * speed;
                 self.velocity[0] = velocity_hor[0];
                 self.velocity[2] = velocity_hor[2];
             }
-            MonsterAction::LeftRot => self.position.rotate_yaw(MONSTER1_ROTATION_SPEED),
-            MonsterAction::RightRot => self.position.rotate_yaw(-MONSTER1_ROTATION_SPEED),
+            MonsterAction::LeftRot => self.position.rotate_yaw(self.stats.rotation_speed),
+            MonsterAction::RightRot => self.position.rotate_yaw(-self.stats.rotation_speed),
             MonsterAction::Jump => self.jump(),
             MonsterAction::Idle => {
                 self.velocity[0] = 0.;
                 self.velocity[2] = 0.;
             }
             MonsterAction::Attack(attacked) => {
-                self.attack = Some(EntityAttack::new(attacked as u8));
+                // The monster only hits the victims in its range, once per cooldown
+                let in_range = player_list.iter().any(|player| {
+                    player.id == attacked
+                        && player.pos.distance_to(&self.position.pos()) <= self.stats.attack_range
+                });
+                if in_range && self.attack_cooldown <= 0. {
+                    let mut attack = EntityAttack::new(attacked);
+                    attack.set_strength(self.stats.damage);
+                    self.attack = Some(attack);
+                    self.attack_cooldown = self.stats.attack_cooldown;
+                }
                 self.velocity[0] = 0.;
                 self.velocity[2] = 0.;
             }
         }
 
-        if self.in_air {
+        // The monsters float in the fluids
+        if in_fluid {
+            swim(&mut self.velocity, dt, true);
+        } else if self.in_air {
             self.velocity += GRAVITY_ACCELERATION_VECTOR * dt;
         }
 
@@ -117,7 +201,7 @@ where
         &self.position
     }
 
-    pub fn id(&self) -> usize {
+    pub fn id(&self) -> EntityId {
         self.id
     }
 
@@ -125,6 +209,10 @@ where
         &self.entity_type
     }
 
+    pub fn stats(&self) -> &MonsterStats {
+        &self.stats
+    }
+
     pub fn attack(&self) -> &Option<EntityAttack> {
         &self.attack
     }
@@ -132,43 +220,13 @@ where
     /// Integrate the velocity to move the camera, with collision. Returns the
     /// dt (in seconds), which can be smaller than `dt` if there is a collision.
     fn move_with_collision(&mut self, dt: f32, world: &World) -> f32 {
-        let target = humanoid_aabb(&(&self.position + self.velocity * dt));
-
-        let collision = world
-            .collision_time(
-                &self.position,
-                &humanoid_aabb(&self.position),
-                &target,
-                &self.velocity,
-            )
-            .unwrap_or(CollisionData {
-                time: f32::MAX,
-                normal: Vector3::empty(),
-            });
-
-        if collision.time >= dt {
-            // TO DO : do we also want a margin here ???????????
-
-            // can move straight away
-            self.position += self.velocity * dt;
-
-            dt
-        } else {
-            // The margin is between the player and the block we colide with
-            // need the projection of velocity onto the normal
-            let mut dtmargin: f32 = 0.0;
-            if self.velocity.norm() >= 1e-10 {
-                dtmargin = PLAYER_MARGIN / collision.normal.dot(&self.velocity).abs();
-            }
-            // we want to put a margin, to avoid collision even with floats rounding
-            self.position += self.velocity * (collision.time - dtmargin);
-
-            // remove component of velocity along the normal
-            let vnormal = collision.normal * collision.normal.dot(&self.velocity);
-            self.velocity = self.velocity - vnormal;
-
-            collision.time
-        }
+        collidable::move_with_collision(
+            world,
+            &mut self.position,
+            &mut self.velocity,
+            humanoid_aabb,
+            dt,
+        )
     }
 
     pub fn jump(&mut self) {
@@ -177,3 +235,76 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::entity::entity::EntityKind;
+    use crate::entity::monster_registry::MonsterRegistry;
+    use crate::game::health::INVULNERABILITY_TIME;
+    use crate::game::player::{FOREHEAD, PLAYER_HEIGHT, PLAYER_MARGIN};
+    use crate::server::server_state::ServerState;
+    use crate::world::block_kind::Block;
+    use crate::world::chunk::{Chunk, CHUNK_SIZE};
+    use crate::world::world::World;
+    use primitives::position::Position;
+
+    fn flat_world() -> World {
+        let s = CHUNK_SIZE as f32;
+        let mut chunks = Vec::new();
+        for i in 0..2 {
+            for j in 0..2 {
+                let mut chunk = Chunk::new([i as f32 * s, j as f32 * s]);
+                chunk.fill_layer(0, Block::STONE);
+                chunks.push(chunk);
+            }
+        }
+        World::new(chunks)
+    }
+
+    #[test]
+    fn test_attacks_follow_the_cooldown() {
+        let world = flat_world();
+        let eyes = 1. + PLAYER_HEIGHT - FOREHEAD + PLAYER_MARGIN;
+        let mut player = ServerState::new().login("arthur".to_string());
+        player.pos = Position::new_vec(5.5, eyes, 4.5);
+        let mut players = vec![player];
+        let mut monster = MonsterRegistry::default()
+            .spawn(50, EntityKind::Monster1, Position::new_vec(4.5, eyes, 4.5))
+            .unwrap();
+        let cooldown = monster.stats().attack_cooldown;
+
+        // The monster stays next to the player, and hits them once per cooldown
+        let dt = 0.015;
+        let mut attacks = Vec::new();
+        for i in 0..1000 {
+            monster.update(&world, dt, &players);
+            if let Some(attack) = monster.attack() {
+                assert_eq!(monster.stats().damage, attack.strength());
+                attacks.push(i as f32 * dt);
+            }
+        }
+        assert!(attacks.len() >= 3);
+        assert!(attacks.windows(2).all(|t| t[1] - t[0] >= cooldown - dt));
+
+        // Nothing happens once the player is out of range
+        players[0].pos = Position::new_vec(15.5, eyes, 15.5);
+        monster.update(&world, 2. * cooldown, &players);
+        assert!(monster.attack().is_none());
+    }
+
+    #[test]
+    fn test_monsters_lose_health() {
+        let mut monster = MonsterRegistry::default()
+            .spawn(50, EntityKind::Monster1, Position::new_vec(4.5, 10., 4.5))
+            .unwrap();
+        let health = monster.stats().health;
+        assert!(monster.damage(3));
+        assert_eq!(health - 3, monster.health());
+
+        // The second hit comes too early
+        assert!(!monster.damage(3));
+        monster.update(&flat_world(), INVULNERABILITY_TIME, &vec![]);
+        assert!(monster.damage(100));
+        assert!(!monster.alive());
+    }
+}
@@ -0,0 +1,122 @@
+use crate::entity::chaser::Chaser;
+use crate::entity::entity::{EntityId, EntityKind};
+use crate::entity::monster::{Monster, MonsterStats, TransitionState};
+use crate::entity::wanderer::Wanderer;
+use crate::game::item::Item;
+use crate::game::player_items::ItemStack;
+use crate::world::block_kind::Block;
+use primitives::position::Position;
+use rand::Rng;
+
+/// Creates the behaviour of a new monster, given its stats
+pub type BehaviourFactory = fn(&MonsterStats) -> Box<dyn TransitionState + Send>;
+
+/// Items that a monster drops when it dies, in a random quantity between `min` and `max`
+#[derive(Clone, Debug)]
+pub struct Loot {
+    pub item: Item,
+    pub min: u8,
+    pub max: u8,
+}
+
+impl Loot {
+    /// Draws the quantity of the item, and returns the stack to drop if there is any
+    pub fn roll(&self, rng: &mut impl Rng) -> Option<ItemStack> {
+        let count = rng.gen_range(self.min..=self.max.max(self.min));
+        (count > 0).then(|| ItemStack {
+            count: count as usize,
+            ..ItemStack::new(self.item)
+        })
+    }
+}
+
+/// What the monsters of a kind are: how they behave, their stats, and what they drop
+#[derive(Clone)]
+pub struct MonsterDefinition {
+    pub stats: MonsterStats,
+    pub behaviour: BehaviourFactory,
+    pub loot: Vec<Loot>,
+}
+
+/// Maps the kinds of the monsters to their definitions. A new creature is added by registering
+/// its definition, without touching the monster manager.
+pub struct MonsterRegistry {
+    definitions: Vec<(EntityKind, MonsterDefinition)>,
+}
+
+impl Default for MonsterRegistry {
+    /// The builtin monsters: the first kind chases the players, the second one is a passive animal
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register(
+            EntityKind::Monster1,
+            MonsterDefinition {
+                stats: MonsterStats {
+                    speed: 2.,
+                    rotation_speed: 0.10,
+                    health: 10,
+                    damage: 1,
+                    attack_range: 1.8,
+                    attack_cooldown: 2.,
+                    sight_distance: 10.,
+                },
+                behaviour: |stats| Box::new(Chaser::new(stats)),
+                loot: vec![Loot {
+                    item: Item::Block(Block::COBBELSTONE),
+                    min: 0,
+                    max: 2,
+                }],
+            },
+        );
+        registry.register(
+            EntityKind::Monster2,
+            MonsterDefinition {
+                stats: MonsterStats {
+                    speed: 3.,
+                    rotation_speed: 0.15,
+                    health: 6,
+                    damage: 0,
+                    attack_range: 0.,
+                    attack_cooldown: 0.,
+                    sight_distance: 6.,
+                },
+                behaviour: |stats| Box::new(Wanderer::new(stats)),
+                loot: vec![Loot {
+                    item: Item::Block(Block::OAKLOG),
+                    min: 0,
+                    max: 1,
+                }],
+            },
+        );
+        registry
+    }
+}
+
+impl MonsterRegistry {
+    /// Returns a registry without any monster
+    pub fn new() -> Self {
+        Self {
+            definitions: Vec::new(),
+        }
+    }
+
+    /// Defines the monsters of the given kind, replacing their previous definition
+    pub fn register(&mut self, kind: EntityKind, definition: MonsterDefinition) {
+        self.definitions.retain(|(registered, _)| *registered != kind);
+        self.definitions.push((kind, definition));
+    }
+
+    pub fn get(&self, kind: &EntityKind) -> Option<&MonsterDefinition> {
+        self.definitions
+            .iter()
+            .find(|(registered, _)| registered == kind)
+            .map(|(_, definition)| definition)
+    }
+
+    /// Creates a monster of the given kind, or returns None if the kind is not a monster
+    pub fn spawn(&self, id: EntityId, kind: EntityKind, position: Position) -> Option<Monster> {
+        let definition = self.get(&kind)?;
+        let behaviour = (definition.behaviour)(&definition.stats);
+        Some(Monster::new(id, kind, position, definition.stats.clone(), behaviour))
+    }
+}
@@ -0,0 +1,336 @@
+use crate::entity::humanoid::humanoid_feet;
+use crate::world::world::World;
+use primitives::position::Position;
+use primitives::vector::Vector3;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+
+/// Maximum number of cells explored when planning a path, which bounds the time spent on it
+const MAX_EXPLORED_CELLS: usize = 2000;
+
+/// Highest fall [cube] that a path goes through
+pub const MAX_FALL_HEIGHT: i32 = 3;
+
+/// Costs of the moves between two cells
+const WALK_COST: u32 = 1;
+const JUMP_COST: u32 = 2;
+
+/// Minimum time [s] between two plannings of the path
+const REPLANNING_PERIOD: f32 = 0.5;
+
+/// Distance [cube] that the target moves from the end of the path before it is planned again
+const TARGET_MOVED_DISTANCE: i32 = 2;
+
+/// Horizontal distance [cube] to the centre of a cell below which it is reached
+const WAYPOINT_RADIUS: f32 = 0.3;
+
+/// Horizontal distance [cube] to the next cell of the path above which the entity left it
+const OFF_PATH_DISTANCE: f32 = 2.;
+
+/// Cell of the grid of the world, given by the coordinates of its lowest corner
+pub type Cell = (i32, i32, i32);
+
+/// Returns the cell that contains the position
+pub fn cell_of(pos: Vector3) -> Cell {
+    (pos[0].floor() as i32, pos[1].floor() as i32, pos[2].floor() as i32)
+}
+
+/// Returns the point at the centre of the bottom of the cell, where the feet stand
+pub fn cell_position(cell: Cell) -> Vector3 {
+    Vector3::new(cell.0 as f32 + 0.5, cell.1 as f32, cell.2 as f32 + 0.5)
+}
+
+/// Returns true if an entity can go through the cell: it is loaded, and empty or filled with a
+/// fluid
+fn is_passable(world: &World, cell: Cell) -> bool {
+    let corner = Vector3::newi(cell.0, cell.1, cell.2);
+    world.is_loaded(&corner) && world.block_at(&corner).is_none_or(|block| block.is_fluid())
+}
+
+/// Returns true if a humanoid can stand with its feet in the cell: there is room for its height
+/// (2 cubes), and a solid cube holds it, unless it swims.
+pub fn is_standable(world: &World, cell: Cell) -> bool {
+    let (x, y, z) = cell;
+    if !is_passable(world, cell) || !is_passable(world, (x, y + 1, z)) {
+        return false;
+    }
+    let is_solid = |cell: Cell| {
+        let corner = Vector3::newi(cell.0, cell.1, cell.2);
+        world.block_at(&corner).is_some_and(|block| !block.is_fluid())
+    };
+    let in_fluid = world
+        .block_at(&Vector3::newi(x, y, z))
+        .is_some_and(|block| block.is_fluid());
+    is_solid((x, y - 1, z)) || in_fluid
+}
+
+/// Returns the cell where a humanoid whose feet are at `feet` stands, or lands if it is in the
+/// air, if it is not too high
+pub fn ground_cell(world: &World, feet: Vector3) -> Option<Cell> {
+    // The feet on the ground may be slightly below its top, because of the rounding errors
+    let (x, y, z) = cell_of(feet + Vector3::unit_y() * 0.5);
+    (0..=MAX_FALL_HEIGHT)
+        .map(|drop| (x, y - drop, z))
+        .find(|cell| is_standable(world, *cell))
+}
+
+/// Returns the cells that a humanoid standing in the cell reaches in one move, with the cost of
+/// the move. It walks to the 4 cells around it, jumps on a cube 1 cube higher if there is room
+/// above its head, and falls from at most `MAX_FALL_HEIGHT` cubes.
+fn neighbors(world: &World, cell: Cell) -> Vec<(Cell, u32)> {
+    let (x, y, z) = cell;
+    let mut neighbors = Vec::new();
+    for (dx, dz) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+        let (nx, nz) = (x + dx, z + dz);
+        if is_standable(world, (nx, y, nz)) {
+            neighbors.push(((nx, y, nz), WALK_COST));
+        } else if is_standable(world, (nx, y + 1, nz)) {
+            if is_passable(world, (x, y + 2, z)) {
+                neighbors.push(((nx, y + 1, nz), JUMP_COST));
+            }
+        } else if is_passable(world, (nx, y, nz)) && is_passable(world, (nx, y + 1, nz)) {
+            let landing = (1..=MAX_FALL_HEIGHT)
+                .map(|drop| (nx, y - drop, nz))
+                .take_while(|below| is_passable(world, *below))
+                .find(|below| is_standable(world, *below));
+            if let Some(landing) = landing {
+                neighbors.push((landing, WALK_COST + (y - landing.1) as u32));
+            }
+        }
+    }
+    neighbors
+}
+
+/// Plans the shortest walkable path between two cells with A*.
+/// Returns the cells to go through after `start`, ending with `goal`, or None if the goal cannot
+/// be reached, or is too far to be found after exploring `MAX_EXPLORED_CELLS` cells.
+pub fn find_path(world: &World, start: Cell, goal: Cell) -> Option<Vec<Cell>> {
+    // The distance never overestimates the cost of the path, which keeps the path the shortest
+    let heuristic = |cell: Cell| {
+        ((cell.0 - goal.0).abs() + (cell.1 - goal.1).abs() + (cell.2 - goal.2).abs()) as u32
+    };
+
+    let mut to_explore = BinaryHeap::from([Reverse((heuristic(start), start))]);
+    let mut costs = HashMap::from([(start, 0)]);
+    let mut came_from: HashMap<Cell, Cell> = HashMap::new();
+    let mut n_explored = 0;
+
+    while let Some(Reverse((_, cell))) = to_explore.pop() {
+        if cell == goal {
+            let mut path = vec![cell];
+            while let Some(previous) = came_from.get(path.last().unwrap()) {
+                path.push(*previous);
+            }
+            path.pop();
+            path.reverse();
+            return Some(path);
+        }
+
+        n_explored += 1;
+        if n_explored > MAX_EXPLORED_CELLS {
+            return None;
+        }
+
+        let cost = costs[&cell];
+        for (next, move_cost) in neighbors(world, cell) {
+            let next_cost = cost + move_cost;
+            if costs.get(&next).is_none_or(|known| next_cost < *known) {
+                costs.insert(next, next_cost);
+                came_from.insert(next, cell);
+                to_explore.push(Reverse((next_cost + heuristic(next), next)));
+            }
+        }
+    }
+    None
+}
+
+/// Leads a humanoid to a target that may move, along a path which is kept while it stays valid.
+/// The path is planned again when the target moves away from its end, when the humanoid leaves
+/// it, or when the world blocks it, but not more often than every `REPLANNING_PERIOD`.
+#[derive(Default)]
+pub struct PathFollower {
+    /// The cells left to go through, the next one first
+    path: VecDeque<Cell>,
+    /// The cell of the target when the path was planned
+    goal: Option<Cell>,
+    /// Time [s] until the path can be planned again
+    cooldown: f32,
+}
+
+impl PathFollower {
+    pub fn new() -> Self {
+        Self {
+            path: VecDeque::new(),
+            goal: None,
+            cooldown: 0.,
+        }
+    }
+
+    /// Returns the point where the humanoid whose eyes are at `position` must go next to reach the
+    /// humanoid whose eyes are at `target`: the bottom centre of the next cell of the path.
+    /// Returns None if there is no path, or if the humanoid is in the cell of the target.
+    pub fn next_waypoint(
+        &mut self,
+        dt: f32,
+        position: &Position,
+        target: &Position,
+        world: &World,
+    ) -> Option<Vector3> {
+        self.cooldown -= dt;
+        let feet = humanoid_feet(position);
+        let (Some(start), Some(goal)) = (
+            ground_cell(world, feet),
+            ground_cell(world, humanoid_feet(target)),
+        ) else {
+            // In the air, the humanoid keeps going
+            return self.path.front().copied().map(cell_position);
+        };
+
+        // Skip the cells already reached
+        if let Some(index) = self.path.iter().position(|cell| *cell == start) {
+            self.path.drain(..index);
+            let centre = cell_position(start);
+            let horizontal = Vector3::new(centre[0] - feet[0], 0., centre[2] - feet[2]);
+            if horizontal.norm() < WAYPOINT_RADIUS {
+                self.path.pop_front();
+            }
+        }
+
+        if self.cooldown <= 0. && self.must_replan(start, goal, feet, world) {
+            self.path = find_path(world, start, goal).unwrap_or_default().into();
+            self.goal = Some(goal);
+            self.cooldown = REPLANNING_PERIOD;
+        }
+        self.path.front().copied().map(cell_position)
+    }
+
+    /// Returns true if the current path does not lead from `start` to `goal` anymore
+    fn must_replan(&self, start: Cell, goal: Cell, feet: Vector3, world: &World) -> bool {
+        let Some(previous_goal) = self.goal else {
+            return true;
+        };
+        let target_moved = (previous_goal.0 - goal.0).abs()
+            + (previous_goal.1 - goal.1).abs()
+            + (previous_goal.2 - goal.2).abs();
+        if target_moved >= TARGET_MOVED_DISTANCE {
+            return true;
+        }
+        match self.path.front() {
+            None => start != goal && previous_goal != goal,
+            Some(next) => {
+                let centre = cell_position(*next);
+                let horizontal = Vector3::new(centre[0] - feet[0], 0., centre[2] - feet[2]);
+                horizontal.norm() > OFF_PATH_DISTANCE || !is_standable(world, *next)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entity::pathfinding::{find_path, PathFollower, MAX_FALL_HEIGHT};
+    use crate::game::actions::Action;
+    use crate::game::player::{FOREHEAD, PLAYER_HEIGHT};
+    use crate::world::block_kind::Block;
+    use crate::world::chunk::{Chunk, CHUNK_SIZE};
+    use crate::world::world::World;
+    use primitives::position::Position;
+    use primitives::vector::Vector3;
+
+    /// Returns a flat world of 3 x 3 chunks, whose ground is at y = 0
+    fn flat_world() -> World {
+        let s = CHUNK_SIZE as f32;
+        let mut chunks = Vec::new();
+        for i in 0..3 {
+            for j in 0..3 {
+                let mut chunk = Chunk::new([i as f32 * s, j as f32 * s]);
+                chunk.fill_layer(0, Block::STONE);
+                chunks.push(chunk);
+            }
+        }
+        World::new(chunks)
+    }
+
+    fn add(world: &mut World, x: i32, y: i32, z: i32) {
+        world.apply_action(&Action::Add {
+            at: Vector3::newi(x, y, z),
+            block: Block::STONE,
+        });
+    }
+
+    /// Builds a wall of the given height along x = 5, from z = 0 to z = 10
+    fn build_wall(world: &mut World, height: i32) {
+        for z in 0..=10 {
+            for y in 1..=height {
+                add(world, 5, y, z);
+            }
+        }
+    }
+
+    #[test]
+    fn test_path_goes_around_walls() {
+        let mut world = flat_world();
+        build_wall(&mut world, 2);
+
+        let path = find_path(&world, (2, 1, 2), (8, 1, 2)).unwrap();
+        assert_eq!(Some(&(8, 1, 2)), path.last());
+        // The path goes around the end of the wall, without going through it
+        assert!(path.iter().all(|cell| cell.0 != 5 || cell.2 > 10));
+        assert!(path.iter().all(|cell| cell.1 == 1));
+        // Each move goes to a cell next to the previous one
+        assert!(path
+            .windows(2)
+            .all(|pair| (pair[0].0 - pair[1].0).abs() + (pair[0].2 - pair[1].2).abs() == 1));
+        assert_eq!(6 + 2 * 9, path.len());
+    }
+
+    #[test]
+    fn test_path_jumps_on_cubes_and_falls() {
+        let mut world = flat_world();
+        build_wall(&mut world, 1);
+
+        // A wall of 1 cube is climbed by a jump
+        let path = find_path(&world, (2, 1, 2), (8, 1, 2)).unwrap();
+        assert_eq!(vec![(3, 1, 2), (4, 1, 2), (5, 2, 2), (6, 1, 2), (7, 1, 2), (8, 1, 2)], path);
+
+        // There is no room to jump under a ceiling: the humanoid jumps next to it
+        add(&mut world, 4, 3, 2);
+        let path = find_path(&world, (2, 1, 2), (8, 1, 2)).unwrap();
+        assert!(!path.windows(2).any(|pair| pair == [(4, 1, 2), (5, 2, 2)]));
+        assert_eq!(8, path.len());
+
+        // A tower is too high to be climbed, but the humanoid falls from it
+        for y in 1..=MAX_FALL_HEIGHT {
+            add(&mut world, 12, y, 12);
+        }
+        let top = (12, MAX_FALL_HEIGHT + 1, 12);
+        assert_eq!(None, find_path(&world, (12, 1, 13), top));
+        assert_eq!(Some(vec![(12, 1, 13)]), find_path(&world, top, (12, 1, 13)));
+
+        // But not from higher
+        add(&mut world, 12, MAX_FALL_HEIGHT + 1, 12);
+        assert_eq!(None, find_path(&world, (12, MAX_FALL_HEIGHT + 2, 12), (12, 1, 13)));
+    }
+
+    #[test]
+    fn test_follower_replans_when_the_world_changes() {
+        let mut world = flat_world();
+        let feet_to_eyes = Vector3::unit_y() * (PLAYER_HEIGHT - FOREHEAD);
+        let position = Position::from_pos(Vector3::new(2.5, 1., 2.5) + feet_to_eyes);
+        let target = Position::from_pos(Vector3::new(8.5, 1., 2.5) + feet_to_eyes);
+
+        let mut follower = PathFollower::new();
+        let waypoint = follower.next_waypoint(0.01, &position, &target, &world);
+        assert_eq!(Some(Vector3::new(3.5, 1., 2.5)), waypoint);
+
+        // The path is kept while it stays valid
+        assert_eq!(waypoint, follower.next_waypoint(1., &position, &target, &world));
+
+        // A cube blocks the path: the follower goes around it
+        add(&mut world, 3, 1, 2);
+        add(&mut world, 3, 2, 2);
+        let waypoint = follower.next_waypoint(1., &position, &target, &world).unwrap();
+        assert_eq!(1., waypoint.distance_to(&Vector3::new(2.5, 1., 2.5)));
+        assert_ne!(Vector3::new(3.5, 1., 2.5), waypoint);
+    }
+}
@@ -1,4 +1,4 @@
-use super::monster::{MonsterAction, TransitionState};
+use super::monster::{MonsterAction, MonsterStats, TransitionState};
 use primitives::position::Position;
 use crate::server::server_state::PlayerState;
 use crate::world::world::World;
@@ -17,13 +17,16 @@ pub struct WalkInCercle {
     timer: f32,
 }
 
-impl TransitionState for WalkInCercle {
-    fn new() -> Self {
+impl WalkInCercle {
+    pub fn new(_stats: &MonsterStats) -> Self {
         Self {
             state: MonsterStateEnum::Idle,
             timer: 0.,
         }
     }
+}
+
+impl TransitionState for WalkInCercle {
     fn action(&self) -> MonsterAction {
         match self.state {
             MonsterStateEnum::Forward => MonsterAction::Forward,
@@ -38,6 +41,7 @@ impl TransitionState for WalkInCercle {
         _position: &Position,
         _world: &World,
         _player_list: &Vec<PlayerState>,
+        _stats: &MonsterStats,
     ) {
         if self.timer - dt < 0. {
             match self.state {
@@ -0,0 +1,167 @@
+use super::monster::{MonsterAction, MonsterStats, TransitionState};
+use crate::server::server_state::PlayerState;
+use crate::world::world::World;
+use primitives::position::Position;
+use primitives::vector::Vector3;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+/// Internal state of the animal,
+/// name are for convinience, they are not forced to a particular action
+#[derive(Clone, Debug, PartialEq)]
+pub enum WandererStateEnum {
+    Idle,
+    Forward,
+    TurnLeft,
+    TurnRight,
+    Jump,
+}
+
+/// A passive animal, which never attacks. It wanders randomly, and runs away from the players
+/// that come too close.
+pub struct Wanderer {
+    state: WandererStateEnum,
+    /// Time [s] left before the animal changes what it does while wandering
+    timer: f32,
+    /// Set while the animal runs away from a player
+    fleeing: bool,
+    rng: SmallRng,
+}
+
+impl TransitionState for Wanderer {
+    fn action(&self) -> MonsterAction {
+        match self.state {
+            WandererStateEnum::Idle => MonsterAction::Idle,
+            WandererStateEnum::Forward => MonsterAction::Forward,
+            WandererStateEnum::TurnLeft => MonsterAction::LeftRot,
+            WandererStateEnum::TurnRight => MonsterAction::RightRot,
+            WandererStateEnum::Jump => MonsterAction::Jump,
+        }
+    }
+
+    fn update(
+        &mut self,
+        dt: f32,
+        position: &Position,
+        world: &World,
+        player_list: &Vec<PlayerState>,
+        stats: &MonsterStats,
+    ) {
+        // Run away from the closest player in sight
+        let closest = player_list
+            .iter()
+            .map(|player| player.pos.pos())
+            .filter(|player| player.distance_to(&position.pos()) < stats.sight_distance)
+            .min_by(|a, b| {
+                let distance = |p: &Vector3| p.distance_to(&position.pos());
+                distance(a).total_cmp(&distance(b))
+            });
+        if let Some(player) = closest {
+            self.fleeing = true;
+            self.flee(position, player, world);
+            return;
+        }
+        if self.fleeing {
+            // Safe again, the animal calms down
+            self.fleeing = false;
+            self.state = WandererStateEnum::Idle;
+            self.timer = 0.;
+        }
+        self.wander(dt);
+    }
+}
+
+impl Wanderer {
+    pub fn new(_stats: &MonsterStats) -> Self {
+        Self {
+            state: WandererStateEnum::Idle,
+            timer: 0.,
+            fleeing: false,
+            rng: SmallRng::from_entropy(),
+        }
+    }
+
+    /// Walks, turns and stops randomly
+    fn wander(&mut self, dt: f32) {
+        self.timer -= dt;
+        if self.timer > 0. {
+            return;
+        }
+        (self.state, self.timer) = match self.rng.gen_range(0..4) {
+            0 => (WandererStateEnum::Idle, self.rng.gen_range(1. ..4.)),
+            1 => (WandererStateEnum::TurnLeft, self.rng.gen_range(0.2..1.)),
+            2 => (WandererStateEnum::TurnRight, self.rng.gen_range(0.2..1.)),
+            _ => (WandererStateEnum::Forward, self.rng.gen_range(1. ..3.)),
+        };
+    }
+
+    /// Turns its back to the player, then runs, jumping over the cubes on its way
+    fn flee(&mut self, position: &Position, player: Vector3, world: &World) {
+        let mut away = position.pos() - player;
+        away[1] = 0.;
+        away.normalize();
+        let sangle = away.dot(&position.ground_direction_right());
+        let cangle = away.dot(&position.ground_direction_forward());
+
+        self.state = if cangle < 0. && sangle.abs() < 0.1 {
+            // The player is in front of it: any side will do
+            WandererStateEnum::TurnRight
+        } else if sangle > 0.1 {
+            WandererStateEnum::TurnRight
+        } else if sangle < -0.1 {
+            WandererStateEnum::TurnLeft
+        } else {
+            // The block is on the ground, not facing the eyes
+            let mut ahead = position.pos() + position.ground_direction_forward();
+            ahead[1] -= 1.;
+            if world.cube_at(ahead).is_some() {
+                WandererStateEnum::Jump
+            } else {
+                WandererStateEnum::Forward
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entity::entity::EntityKind;
+    use crate::entity::monster_registry::MonsterRegistry;
+    use crate::game::player::{FOREHEAD, PLAYER_HEIGHT, PLAYER_MARGIN};
+    use crate::server::server_state::ServerState;
+    use crate::world::block_kind::Block;
+    use crate::world::chunk::{Chunk, CHUNK_SIZE};
+    use crate::world::world::World;
+    use primitives::position::Position;
+
+    #[test]
+    fn test_animal_flees_from_the_players() {
+        let s = CHUNK_SIZE as f32;
+        let mut chunks = Vec::new();
+        for i in 0..4 {
+            for j in 0..4 {
+                let mut chunk = Chunk::new([i as f32 * s, j as f32 * s]);
+                chunk.fill_layer(0, Block::STONE);
+                chunks.push(chunk);
+            }
+        }
+        let world = World::new(chunks);
+
+        let eyes = 1. + PLAYER_HEIGHT - FOREHEAD + PLAYER_MARGIN;
+        let start = Position::new_vec(12.5, eyes, 12.5);
+        let mut animal = MonsterRegistry::default()
+            .spawn(50, EntityKind::Monster2, start)
+            .unwrap();
+        let mut player = ServerState::new().login("arthur".to_string());
+        player.pos = Position::new_vec(10.5, eyes, 12.5);
+        let players = vec![player.clone()];
+
+        for _ in 0..200 {
+            animal.update(&world, 0.015, &players);
+            assert!(animal.attack().is_none());
+        }
+        // It ran away from the player
+        let distance = |animal_pos: &Position| animal_pos.distance_to(&player.pos.pos());
+        assert!(distance(animal.position()) > 4.);
+    }
+}
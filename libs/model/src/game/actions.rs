@@ -18,22 +18,64 @@ pub enum Action {
 }
 
 impl Action {
+    /// Returns the position of the cube edited by the action
+    pub fn position(&self) -> Vector3 {
+        match self {
+            Action::Destroy { at } | Action::Add { at, .. } => *at,
+        }
+    }
+
+    /// Encodes the action: a byte for the kind of action, the position, and for `Add` the id of
+    /// the block.
     pub fn to_bytes(&self) -> Vec<u8> {
-        let as_json = serde_json::to_string(self).unwrap();
-        as_json.into_bytes()
+        match self {
+            Action::Destroy { at } => [vec![0], at.to_bytes()].concat(),
+            Action::Add { at, block } => [vec![1], at.to_bytes(), vec![block.id()]].concat(),
+        }
     }
 
-    pub fn from_str(text: &str) -> Self {
-        serde_json::from_str(text).unwrap()
+    /// Decodes the representation computed by `to_bytes`.
+    /// Returns `None` if the bytes do not represent a valid action.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let at = Vector3::from_bytes(bytes.get(1..)?)?;
+        match bytes[0] {
+            0 => Some(Action::Destroy { at }),
+            1 => Some(Action::Add {
+                at,
+                block: Block::from_id(*bytes.get(13)?)?,
+            }),
+            _ => None,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use primitives::vector::Vector3;
+    use crate::game::actions::Action;
     use crate::world::block_kind::Block;
     use crate::world::cube::Cube;
 
+    #[test]
+    fn test_bytes_representation() {
+        let actions = [
+            Action::Destroy {
+                at: Vector3::new(1., 4., -3.),
+            },
+            Action::Add {
+                at: Vector3::new(-10., 12., 5.),
+                block: Block::OAKLOG,
+            },
+        ];
+        for action in actions {
+            let bytes = action.to_bytes();
+            assert_eq!(Some(action), Action::from_bytes(&bytes));
+            assert_eq!(None, Action::from_bytes(&bytes[..bytes.len() - 1]));
+        }
+        assert_eq!(None, Action::from_bytes(&[]));
+        assert_eq!(None, Action::from_bytes(&[7; 13]));
+    }
+
     #[test]
     fn test_computation_of_new_cube_position() {
         let cube = Cube::new([0., 0., 0.], Block::COBBELSTONE, 0);
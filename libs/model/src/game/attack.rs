@@ -1,26 +1,33 @@
 
+use crate::entity::entity::EntityId;
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct EntityAttack {
-    attacked: u8,
+    attacked: EntityId,
     strength: u8,
 }
 
 impl EntityAttack {
-    pub fn new(attacked: u8) -> Self {
+    pub fn new(attacked: EntityId) -> Self {
         Self {
             attacked,
             strength: 1,
         }
     }
 
+    /// Returns the id of the victim, followed by the strength
     pub fn to_bytes(&self) -> Vec<u8> {
-        vec![self.attacked, self.strength]
+        [self.attacked.to_be_bytes().as_slice(), &[self.strength]].concat()
     }
 
-    pub fn from_bytes(bytes_to_parse: &[u8]) -> Self {
-        Self {
-            attacked: bytes_to_parse[0],
-            strength: bytes_to_parse[1],
+    /// Returns `None` if there are not enough bytes
+    pub fn from_bytes(bytes_to_parse: &[u8]) -> Option<Self> {
+        match bytes_to_parse {
+            [b0, b1, b2, b3, strength, ..] => Some(Self {
+                attacked: EntityId::from_be_bytes([*b0, *b1, *b2, *b3]),
+                strength: *strength,
+            }),
+            _ => None,
         }
     }
 
@@ -32,7 +39,7 @@ impl EntityAttack {
         self.strength
     }
 
-    pub fn victim_id(&self) -> u8 {
+    pub fn victim_id(&self) -> EntityId {
         self.attacked
     }
 }
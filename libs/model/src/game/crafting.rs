@@ -1,28 +1,453 @@
+use crate::game::definitions::digest_of;
+use crate::game::item::Item;
+use crate::game::player_items::ItemStack;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use crate::world::block_kind::Block;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::sync::OnceLock;
+use tracing::{info, warn};
 
-/// A grid to encode the input of a crafting recipe
-pub type CraftingGrid = [[Option<Block>; 3]; 3];
+/// File from which the recipes are loaded at startup, relative to the working directory
+pub const RECIPES_FILE: &str = "./resources/recipes.json";
 
-/// Structure responsible for the crafting recipes. For now, only its static
-/// methods will be used, but in the end it will load configuration from a file,
-/// so will be stateful
-pub struct CraftingManager {}
+/// Recipes used when no file could be loaded. This is a copy of `resources/recipes.json` taken at
+/// compile time.
+const BUILTIN_RECIPES: &str = include_str!("../../../../resources/recipes.json");
+
+static MANAGER: OnceLock<CraftingManager> = OnceLock::new();
+
+/// A grid to encode the input of a crafting recipe.
+/// `grid[0]` is the bottom row of the grid, and `grid[row][0]` its left column.
+pub type CraftingGrid = [[Option<Item>; 3]; 3];
+
+/// Encodes a grid, sent by a client to the server when crafting its recipe
+pub fn grid_to_bytes(grid: &CraftingGrid) -> Vec<u8> {
+    serde_json::to_vec(grid).unwrap()
+}
+
+pub fn grid_from_bytes(bytes: &[u8]) -> Result<CraftingGrid, serde_json::Error> {
+    serde_json::from_slice(bytes)
+}
+
+/// Output of a recipe, as written in the recipe file
+#[derive(Deserialize)]
+struct OutputDefinition {
+    item: String,
+    #[serde(default = "default_count")]
+    count: usize,
+}
+
+fn default_count() -> usize {
+    1
+}
+
+/// A recipe, as written in the recipe file
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum RecipeDefinition {
+    Shaped {
+        /// The rows of the recipe, from top to bottom, each character being a key of `key`.
+        /// Spaces are empty slots.
+        pattern: Vec<String>,
+        key: HashMap<String, String>,
+        #[serde(default)]
+        mirrored: bool,
+        output: OutputDefinition,
+    },
+    Shapeless {
+        ingredients: Vec<String>,
+        output: OutputDefinition,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Recipe {
+    /// The ingredients must be placed with this shape, anywhere in the grid
+    Shaped {
+        /// The rows of the shape from top to bottom, without empty rows or columns on the sides
+        pattern: Vec<Vec<Option<Item>>>,
+        /// Whether the shape flipped horizontally is also accepted
+        mirrored: bool,
+        output: ItemStack,
+    },
+    /// The ingredients can be placed anywhere in the grid
+    Shapeless {
+        ingredients: Vec<Item>,
+        output: ItemStack,
+    },
+}
+
+impl Recipe {
+    pub fn output(&self) -> ItemStack {
+        match self {
+            Recipe::Shaped { output, .. } | Recipe::Shapeless { output, .. } => *output,
+        }
+    }
+
+    fn matches(&self, grid: &CraftingGrid) -> bool {
+        match self {
+            Recipe::Shaped {
+                pattern, mirrored, ..
+            } => {
+                let shape = shape_of(grid);
+                if shape == *pattern {
+                    return true;
+                }
+                *mirrored
+                    && shape.len() == pattern.len()
+                    && shape
+                        .iter()
+                        .zip(pattern)
+                        .all(|(row, expected)| row.iter().eq(expected.iter().rev()))
+            }
+            Recipe::Shapeless { ingredients, .. } => {
+                let mut remaining = ingredients.clone();
+                for item in grid.iter().flatten().flatten() {
+                    match remaining.iter().position(|ingredient| ingredient == item) {
+                        Some(i) => {
+                            remaining.swap_remove(i);
+                        }
+                        None => return false,
+                    }
+                }
+                remaining.is_empty()
+            }
+        }
+    }
+}
+
+/// Returns the rows of the smallest rectangle of the grid that contains all its items, from top
+/// to bottom
+fn shape_of(grid: &CraftingGrid) -> Vec<Vec<Option<Item>>> {
+    let rows: Vec<usize> = (0..3).filter(|&row| grid[row].iter().any(Option::is_some)).collect();
+    let cols: Vec<usize> = (0..3)
+        .filter(|&col| grid.iter().any(|row| row[col].is_some()))
+        .collect();
+    let (Some(&bottom), Some(&top), Some(&left), Some(&right)) =
+        (rows.first(), rows.last(), cols.first(), cols.last())
+    else {
+        return Vec::new();
+    };
+    (bottom..=top)
+        .rev()
+        .map(|row| grid[row][left..=right].to_vec())
+        .collect()
+}
+
+/// The result of a recipe matching the crafting grid
+#[derive(Clone, Debug, PartialEq)]
+pub struct CraftingResult {
+    pub output: ItemStack,
+    /// The slots of the grid, as `(row, col)`, whose items are used up when taking the output
+    pub consumed: Vec<(usize, usize)>,
+}
+
+#[derive(Debug)]
+pub enum CraftingError {
+    Io(std::io::Error),
+    Parse(serde_json::Error),
+    /// The name is not the name of a block nor of an item
+    UnknownItem(String),
+    InvalidPattern(String),
+    /// More items are produced than what fits in one slot
+    InvalidCount { item: String, count: usize },
+}
+
+impl Display for CraftingError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CraftingError::Io(err) => write!(f, "Cannot read the recipes: {err}"),
+            CraftingError::Parse(err) => write!(f, "Invalid recipes: {err}"),
+            CraftingError::UnknownItem(name) => write!(f, "Unknown item {name} in a recipe"),
+            CraftingError::InvalidPattern(reason) => write!(f, "Invalid recipe pattern: {reason}"),
+            CraftingError::InvalidCount { item, count } => {
+                write!(f, "A recipe cannot produce {count} {item}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CraftingError {}
+
+/// Structure responsible for the crafting recipes.
+///
+/// The recipes are defined in a JSON file (see `resources/recipes.json`), loaded at startup with
+/// `CraftingManager::install_from_file`, after the blocks and the items.
+pub struct CraftingManager {
+    recipes: Vec<Recipe>,
+}
 
 impl CraftingManager {
-    pub fn recipe(grid: &CraftingGrid) -> Option<Block> {
-        let mut recipes: HashMap<CraftingGrid, Block> = HashMap::new();
-
-        // TODO the rows are in the wrong order, be careful
-        recipes.insert(
-            [
-                [None, Some(Block::OAKLOG), None],
-                [None, Some(Block::COBBELSTONE), None],
-                [None, Some(Block::COBBELSTONE), None],
-            ],
-            Block::SWORD,
-        );
-
-        recipes.get(grid).copied()
+    /// Parses and validates a list of recipes
+    pub fn from_json(json: &str) -> Result<Self, CraftingError> {
+        let definitions: Vec<RecipeDefinition> =
+            serde_json::from_str(json).map_err(CraftingError::Parse)?;
+        let recipes = definitions
+            .into_iter()
+            .map(Self::parse_recipe)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { recipes })
+    }
+
+    fn parse_recipe(definition: RecipeDefinition) -> Result<Recipe, CraftingError> {
+        let item = |name: &str| {
+            Item::from_name(name).ok_or_else(|| CraftingError::UnknownItem(name.to_string()))
+        };
+        let output = |output: OutputDefinition| {
+            let stack = ItemStack::new(item(&output.item)?);
+            if output.count == 0 || output.count > stack.item.max_stack() {
+                return Err(CraftingError::InvalidCount {
+                    item: output.item,
+                    count: output.count,
+                });
+            }
+            Ok(ItemStack {
+                count: output.count,
+                ..stack
+            })
+        };
+
+        match definition {
+            RecipeDefinition::Shaped {
+                pattern,
+                key,
+                mirrored,
+                output: output_definition,
+            } => {
+                let mut grid: CraftingGrid = [[None; 3]; 3];
+                if pattern.len() > 3 {
+                    return Err(CraftingError::InvalidPattern("more than 3 rows".to_string()));
+                }
+                for (i, line) in pattern.iter().enumerate() {
+                    if line.chars().count() > 3 {
+                        let reason = format!("row {line:?} is too long");
+                        return Err(CraftingError::InvalidPattern(reason));
+                    }
+                    for (col, symbol) in line.chars().enumerate() {
+                        if symbol == ' ' {
+                            continue;
+                        }
+                        let name = key.get(&symbol.to_string()).ok_or_else(|| {
+                            CraftingError::InvalidPattern(format!("{symbol:?} is not in the key"))
+                        })?;
+                        // The pattern is written from top to bottom
+                        grid[2 - i][col] = Some(item(name)?);
+                    }
+                }
+                let pattern = shape_of(&grid);
+                if pattern.is_empty() {
+                    return Err(CraftingError::InvalidPattern("no ingredient".to_string()));
+                }
+                Ok(Recipe::Shaped {
+                    pattern,
+                    mirrored,
+                    output: output(output_definition)?,
+                })
+            }
+            RecipeDefinition::Shapeless {
+                ingredients,
+                output: output_definition,
+            } => {
+                if ingredients.is_empty() || ingredients.len() > 9 {
+                    return Err(CraftingError::InvalidPattern(format!(
+                        "{} ingredients",
+                        ingredients.len()
+                    )));
+                }
+                Ok(Recipe::Shapeless {
+                    ingredients: ingredients
+                        .iter()
+                        .map(|name| item(name))
+                        .collect::<Result<_, _>>()?,
+                    output: output(output_definition)?,
+                })
+            }
+        }
+    }
+
+    /// Loads the recipes from a file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CraftingError> {
+        let json = std::fs::read_to_string(path).map_err(CraftingError::Io)?;
+        Self::from_json(&json)
+    }
+
+    /// The recipes that are part of the game, used when no file is provided
+    pub fn builtin() -> Self {
+        Self::from_json(BUILTIN_RECIPES).expect("The built-in recipes are invalid")
+    }
+
+    /// Loads the recipes from the given file, and makes them the recipes of the game.
+    /// If the file cannot be loaded, the built-in recipes are used.
+    /// This must be called after `ItemRegistry::install_from_file`.
+    pub fn install_from_file(path: impl AsRef<Path>) {
+        let manager = match Self::load(&path) {
+            Ok(manager) => {
+                info!("Loaded {} recipes from {:?}", manager.len(), path.as_ref());
+                manager
+            }
+            Err(err) => {
+                warn!("{err}, using the built-in recipes");
+                Self::builtin()
+            }
+        };
+        if MANAGER.set(manager).is_err() {
+            warn!("The recipes are already in use, they cannot be replaced");
+        }
+    }
+
+    /// Returns the recipes used by the game, which are the built-in ones if none were installed
+    pub fn global() -> &'static Self {
+        MANAGER.get_or_init(Self::builtin)
+    }
+
+    pub fn len(&self) -> usize {
+        self.recipes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recipes.is_empty()
+    }
+
+    /// Returns a digest of the recipes (see `definitions_digest`)
+    pub fn digest(&self) -> u64 {
+        digest_of(&self.recipes)
+    }
+
+    pub fn recipes(&self) -> &[Recipe] {
+        &self.recipes
+    }
+
+    /// Returns the output of the first recipe matching the grid, with the slots it consumes
+    pub fn recipe(&self, grid: &CraftingGrid) -> Option<CraftingResult> {
+        let recipe = self.recipes.iter().find(|recipe| recipe.matches(grid))?;
+        // All the items of the grid are part of the recipe
+        let consumed = (0..3)
+            .flat_map(|row| (0..3).map(move |col| (row, col)))
+            .filter(|&(row, col)| grid[row][col].is_some())
+            .collect();
+        Some(CraftingResult {
+            output: recipe.output(),
+            consumed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::crafting::{CraftingError, CraftingGrid, CraftingManager};
+    use crate::game::item::{Item, ItemKind};
+    use crate::world::block_kind::Block;
+
+    const LOG: Option<Item> = Some(Item::Block(Block::OAKLOG));
+    const COBBLE: Option<Item> = Some(Item::Block(Block::COBBELSTONE));
+    const LEAVES: Option<Item> = Some(Item::Block(Block::OAKLEAVES));
+    const DIRT: Option<Item> = Some(Item::Block(Block::DIRT));
+
+    fn output(manager: &CraftingManager, grid: &CraftingGrid) -> Option<(Item, usize)> {
+        manager
+            .recipe(grid)
+            .map(|result| (result.output.item, result.output.count))
+    }
+
+    #[test]
+    fn test_shaped_recipe_anywhere_in_the_grid() {
+        let manager = CraftingManager::builtin();
+        let sword = Some((Item::Other(ItemKind::STONE_SWORD), 1));
+
+        // The bottom row is the first one
+        let grid = [[None, LOG, None], [None, COBBLE, None], [None, COBBLE, None]];
+        assert_eq!(sword, output(&manager, &grid));
+        let grid = [[None, None, LOG], [None, None, COBBLE], [None, None, COBBLE]];
+        assert_eq!(sword, output(&manager, &grid));
+
+        // Upside down or with an extra item, it is not a sword anymore
+        let grid = [[None, COBBLE, None], [None, COBBLE, None], [None, LOG, None]];
+        assert_eq!(None, output(&manager, &grid));
+        let grid = [[None, LOG, DIRT], [None, COBBLE, None], [None, COBBLE, None]];
+        assert_eq!(None, output(&manager, &grid));
+
+        // A smaller shape can be moved both horizontally and vertically
+        let stones = Some((Item::Block(Block::STONE), 4));
+        let grid = [[COBBLE, COBBLE, None], [COBBLE, COBBLE, None], [None; 3]];
+        assert_eq!(stones, output(&manager, &grid));
+        let grid = [[None; 3], [None, COBBLE, COBBLE], [None, COBBLE, COBBLE]];
+        assert_eq!(stones, output(&manager, &grid));
+    }
+
+    #[test]
+    fn test_mirrored_recipe() {
+        let json = r#"[
+            {"type": "shaped", "pattern": ["LL", "L "], "key": {"L": "oak_leaves"},
+             "output": {"item": "oak_log"}},
+            {"type": "shaped", "pattern": ["DD", "D "], "key": {"D": "dirt"}, "mirrored": true,
+             "output": {"item": "grass"}}
+        ]"#;
+        let manager = CraftingManager::from_json(json).unwrap();
+
+        let grid = [[LEAVES, None, None], [LEAVES, LEAVES, None], [None; 3]];
+        assert_eq!(Some((Item::Block(Block::OAKLOG), 1)), output(&manager, &grid));
+        let grid = [[None, LEAVES, None], [LEAVES, LEAVES, None], [None; 3]];
+        assert_eq!(None, output(&manager, &grid));
+
+        let grid = [[DIRT, None, None], [DIRT, DIRT, None], [None; 3]];
+        assert_eq!(Some((Item::Block(Block::GRASS), 1)), output(&manager, &grid));
+        let grid = [[None, None, DIRT], [None, DIRT, DIRT], [None; 3]];
+        assert_eq!(Some((Item::Block(Block::GRASS), 1)), output(&manager, &grid));
+    }
+
+    #[test]
+    fn test_shapeless_recipe() {
+        let manager = CraftingManager::builtin();
+        let grass = Some((Item::Block(Block::GRASS), 2));
+
+        let grid = [[DIRT, None, None], [None; 3], [None, None, LEAVES]];
+        assert_eq!(grass, output(&manager, &grid));
+        let grid = [[None; 3], [LEAVES, DIRT, None], [None; 3]];
+        assert_eq!(grass, output(&manager, &grid));
+
+        // Every ingredient is needed exactly once
+        let grid = [[DIRT, None, None], [None; 3], [None; 3]];
+        assert_eq!(None, output(&manager, &grid));
+        let grid = [[DIRT, DIRT, LEAVES], [None; 3], [None; 3]];
+        assert_eq!(None, output(&manager, &grid));
+    }
+
+    #[test]
+    fn test_consumed_ingredients() {
+        let manager = CraftingManager::builtin();
+        let grid = [[None; 3], [None, COBBLE, COBBLE], [None, COBBLE, COBBLE]];
+        let result = manager.recipe(&grid).unwrap();
+        assert_eq!(4, result.output.count);
+        assert_eq!(vec![(1, 1), (1, 2), (2, 1), (2, 2)], result.consumed);
+    }
+
+    #[test]
+    fn test_invalid_recipes_are_rejected() {
+        let recipe = |pattern: &str, output: &str| {
+            CraftingManager::from_json(&format!(
+                r#"[{{"type": "shaped", "pattern": {pattern}, "key": {{"D": "dirt"}},
+                      "output": {output}}}]"#
+            ))
+        };
+        let grass = r#"{"item": "grass"}"#;
+
+        assert!(recipe(r#"["D"]"#, grass).is_ok());
+        assert!(matches!(
+            recipe(r#"["D"]"#, r#"{"item": "diamond"}"#),
+            Err(CraftingError::UnknownItem(_))
+        ));
+        assert!(matches!(
+            recipe(r#"["X"]"#, grass),
+            Err(CraftingError::InvalidPattern(_))
+        ));
+        assert!(matches!(
+            recipe(r#"["DDDD"]"#, grass),
+            Err(CraftingError::InvalidPattern(_))
+        ));
+        assert!(matches!(
+            recipe(r#"["D"]"#, r#"{"item": "stone_sword", "count": 2}"#),
+            Err(CraftingError::InvalidCount { .. })
+        ));
     }
 }
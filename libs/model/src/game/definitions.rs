@@ -0,0 +1,46 @@
+use crate::game::crafting::CraftingManager;
+use crate::game::item_registry::ItemRegistry;
+use crate::world::block_registry::BlockRegistry;
+use serde::Serialize;
+
+/// Returns a digest of the blocks, items and recipes installed. The client and the server must have
+/// the same one, since they exchange the blocks and the items by id.
+pub fn definitions_digest() -> u64 {
+    let digests = [
+        BlockRegistry::global().digest(),
+        ItemRegistry::global().digest(),
+        CraftingManager::global().digest(),
+    ];
+    fnv1a(digests.iter().flat_map(|digest| digest.to_le_bytes()))
+}
+
+/// Returns a digest of the definitions, computed from their JSON representation. Unlike the
+/// hashers of the standard library, it is the same on every machine and with every build.
+pub(crate) fn digest_of(definitions: &impl Serialize) -> u64 {
+    let json = serde_json::to_vec(definitions).expect("The definitions cannot be serialized");
+    fnv1a(json)
+}
+
+/// The 64-bit FNV-1a hash
+fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::definitions::{digest_of, fnv1a};
+    use crate::world::block_registry::BlockRegistry;
+
+    #[test]
+    fn test_digests() {
+        // Reference values of the FNV-1a hash
+        assert_eq!(0xcbf2_9ce4_8422_2325, fnv1a(*b""));
+        assert_eq!(0xaf63_dc4c_8601_ec8c, fnv1a(*b"a"));
+
+        assert_eq!(digest_of(&[1, 2]), digest_of(&[1, 2]));
+        assert_ne!(digest_of(&[1, 2]), digest_of(&[2, 1]));
+        assert_eq!(BlockRegistry::builtin().digest(), BlockRegistry::global().digest());
+    }
+}
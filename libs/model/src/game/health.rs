@@ -1,10 +1,57 @@
+use crate::game::player::GRAVITY_ACCELERATION_VECTOR;
+use serde::{Deserialize, Serialize};
+
+/// Health of a player when joining the game
+pub const MAX_HEALTH: u8 = 10;
+
+/// Time [s] after being hurt during which a player cannot be hurt again
+pub const INVULNERABILITY_TIME: f32 = 0.5;
+
+/// Time [s] without being hurt needed to regenerate one health point
+pub const REGENERATION_PERIOD: f32 = 4.;
+
+/// Vertical speed [cube/s] at which a player can hit the ground without being hurt (a fall of
+/// about 3 cubes)
+pub const SAFE_FALL_SPEED: f32 = 11.;
+
+/// Vertical speed [cube/s] above `SAFE_FALL_SPEED` that costs one health point
+const FALL_SPEED_PER_DAMAGE: f32 = 2.;
+
+/// Returns the damage of a player hitting the ground at the given vertical speed [cube/s]
+pub fn fall_damage(speed: f32) -> u8 {
+    if speed <= SAFE_FALL_SPEED {
+        0
+    } else {
+        ((speed - SAFE_FALL_SPEED) / FALL_SPEED_PER_DAMAGE).ceil() as u8
+    }
+}
+
+/// Returns the vertical speed [cube/s] of a player hitting the ground after falling from the given
+/// height [cube]
+pub fn landing_speed(height: f32) -> f32 {
+    (-2. * GRAVITY_ACCELERATION_VECTOR.y() * height.max(0.)).sqrt()
+}
+
+/// Health points of a player. Only the points are shared with the clients: the timers are kept
+/// by the server, which is the one hurting and healing the players.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Health {
     health: u8,
+    /// Time [s] before the player can be hurt again
+    #[serde(skip)]
+    invulnerability: f32,
+    /// Time [s] since the player was last hurt or healed
+    #[serde(skip)]
+    since_last_change: f32,
 }
 
 impl Health {
     pub fn new(health: u8) -> Self {
-        Self { health }
+        Self {
+            health,
+            invulnerability: 0.,
+            since_last_change: 0.,
+        }
     }
 
     pub fn alive(&self) -> bool {
@@ -15,11 +62,94 @@ impl Health {
         self.health
     }
 
-    pub fn damage(&mut self, strength: u8) {
-        self.health = if self.health >= strength {
-            self.health - strength
-        } else {
-            0
-        };
+    /// Hurts the player, unless they were hurt too recently. Returns true if the player lost
+    /// health.
+    pub fn damage(&mut self, strength: u8) -> bool {
+        if strength == 0 || self.invulnerability > 0. || !self.alive() {
+            return false;
+        }
+        self.health = self.health.saturating_sub(strength);
+        self.invulnerability = INVULNERABILITY_TIME;
+        self.since_last_change = 0.;
+        true
+    }
+
+    /// Lets the time pass: the player regenerates slowly when they are not hurt.
+    /// Returns true if the player gained health.
+    pub fn step(&mut self, dt: f32) -> bool {
+        self.invulnerability = (self.invulnerability - dt).max(0.);
+        if !self.alive() || self.health >= MAX_HEALTH {
+            self.since_last_change = 0.;
+            return false;
+        }
+
+        self.since_last_change += dt;
+        if self.since_last_change < REGENERATION_PERIOD {
+            return false;
+        }
+        self.since_last_change = 0.;
+        self.health += 1;
+        true
+    }
+}
+
+impl PartialEq for Health {
+    fn eq(&self, other: &Self) -> bool {
+        self.health == other.health
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::health::{
+        fall_damage, landing_speed, Health, INVULNERABILITY_TIME, MAX_HEALTH, REGENERATION_PERIOD,
+        SAFE_FALL_SPEED,
+    };
+
+    #[test]
+    fn test_invulnerability_after_damage() {
+        let mut health = Health::new(MAX_HEALTH);
+        assert!(health.damage(3));
+        assert_eq!(MAX_HEALTH - 3, health.health());
+
+        // The second hit comes too early
+        assert!(!health.damage(3));
+        health.step(INVULNERABILITY_TIME);
+        assert!(health.damage(30));
+        assert_eq!(0, health.health());
+        assert!(!health.alive());
+    }
+
+    #[test]
+    fn test_regeneration() {
+        let mut health = Health::new(MAX_HEALTH - 2);
+        assert!(!health.step(REGENERATION_PERIOD / 2.));
+        assert!(health.step(REGENERATION_PERIOD / 2.));
+        assert_eq!(MAX_HEALTH - 1, health.health());
+
+        // Being hurt resets the regeneration
+        health.step(REGENERATION_PERIOD / 2.);
+        health.damage(1);
+        assert!(!health.step(REGENERATION_PERIOD / 2.));
+        assert!(health.step(REGENERATION_PERIOD / 2.));
+
+        // The health never exceeds the maximum, and the dead do not regenerate
+        let mut health = Health::new(MAX_HEALTH);
+        assert!(!health.step(10. * REGENERATION_PERIOD));
+        let mut health = Health::new(0);
+        assert!(!health.step(10. * REGENERATION_PERIOD));
+    }
+
+    #[test]
+    fn test_fall_damage() {
+        assert_eq!(0, fall_damage(0.));
+        assert_eq!(0, fall_damage(SAFE_FALL_SPEED));
+        assert_eq!(1, fall_damage(SAFE_FALL_SPEED + 0.5));
+        assert!(fall_damage(40.) >= MAX_HEALTH);
+
+        // A fall of 3 cubes is safe, not a fall of 4
+        assert_eq!(0, fall_damage(landing_speed(3.)));
+        assert_eq!(1, fall_damage(landing_speed(4.)));
+        assert_eq!(0., landing_speed(-1.));
     }
 }
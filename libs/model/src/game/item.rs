@@ -0,0 +1,221 @@
+use crate::game::item_registry::{
+    ItemCategory, ItemDefinition, ItemRegistry, ToolKind, DEFAULT_MAX_STACK,
+};
+use crate::world::block_kind::Block;
+use crate::world::block_registry::BlockRegistry;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt::{Debug, Formatter};
+
+/// A kind of item that is not a block, defined in the `ItemRegistry`
+#[derive(Clone, Copy, PartialEq, Serialize, Hash, Eq)]
+pub struct ItemKind(u8);
+
+impl ItemKind {
+    // The items that the game itself relies on (crafting, starting items, ...).
+    // They must be defined in the registry with these ids.
+    pub const STONE_SWORD: ItemKind = ItemKind(0);
+
+    /// The items used by the game, with their name in the registry
+    pub(crate) const BUILTIN: [(ItemKind, &'static str); 1] = [(ItemKind::STONE_SWORD, "stone_sword")];
+
+    /// Returns the item with the given id, if it is defined in the registry
+    pub fn from_id(id: u8) -> Option<Self> {
+        ItemRegistry::global().get(id).map(|_| ItemKind(id))
+    }
+
+    pub(crate) fn from_raw_id(id: u8) -> Self {
+        ItemKind(id)
+    }
+
+    pub fn id(&self) -> u8 {
+        self.0
+    }
+
+    /// Returns the properties of this item
+    pub fn definition(&self) -> &'static ItemDefinition {
+        ItemRegistry::global()
+            .get(self.0)
+            .expect("Items are only created from the registry")
+    }
+}
+
+impl<'de> Deserialize<'de> for ItemKind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = u8::deserialize(deserializer)?;
+        ItemKind::from_id(id).ok_or_else(|| serde::de::Error::custom(format!("unknown item {id}")))
+    }
+}
+
+impl Debug for ItemKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match ItemRegistry::global().get(self.0) {
+            Some(definition) => write!(f, "{}", definition.name),
+            None => write!(f, "ItemKind({})", self.0),
+        }
+    }
+}
+
+/// Anything that a player can hold: either a block, that can be placed in the world, or another
+/// kind of item (tool, weapon, ...).
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Hash, Eq, Debug)]
+pub enum Item {
+    Block(Block),
+    Other(ItemKind),
+}
+
+impl Item {
+    /// Returns the block or the item with the given name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Block::from_name(name)
+            .map(Item::Block)
+            .or_else(|| ItemRegistry::global().by_name(name).map(Item::Other))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Item::Block(block) => block.name(),
+            Item::Other(kind) => &kind.definition().name,
+        }
+    }
+
+    pub fn category(&self) -> ItemCategory {
+        match self {
+            Item::Block(_) => ItemCategory::Block,
+            Item::Other(kind) => kind.definition().category,
+        }
+    }
+
+    /// Returns the block that this item places in the world, if any
+    pub fn as_block(&self) -> Option<Block> {
+        match self {
+            Item::Block(block) => Some(*block),
+            Item::Other(_) => None,
+        }
+    }
+
+    /// Maximum number of items of this kind in a slot
+    pub fn max_stack(&self) -> usize {
+        match self {
+            Item::Block(_) => DEFAULT_MAX_STACK,
+            Item::Other(kind) => kind.definition().max_stack,
+        }
+    }
+
+    /// Number of times a new item of this kind can be used, if it wears out
+    pub fn max_durability(&self) -> Option<u16> {
+        match self {
+            Item::Block(_) => None,
+            Item::Other(kind) => kind.definition().durability,
+        }
+    }
+
+    pub fn attack_strength(&self) -> u8 {
+        match self {
+            Item::Block(_) => 1,
+            Item::Other(kind) => kind.definition().attack_strength,
+        }
+    }
+
+    /// Returns the kind of tool of this item, if it is one
+    pub fn tool(&self) -> Option<ToolKind> {
+        match self {
+            Item::Block(_) => None,
+            Item::Other(kind) => kind.definition().tool,
+        }
+    }
+
+    /// How many times faster this item mines the blocks made for its tool
+    pub fn efficiency(&self) -> f32 {
+        match self {
+            Item::Block(_) => 1.,
+            Item::Other(kind) => kind.definition().efficiency,
+        }
+    }
+
+    /// Returns the binary representation of the item: whether it is a block, and its id
+    pub fn to_bytes(&self) -> [u8; 2] {
+        match self {
+            Item::Block(block) => [0, block.id()],
+            Item::Other(kind) => [1, kind.id()],
+        }
+    }
+
+    /// Returns `None` if the item is not defined in the registries
+    pub fn from_bytes(bytes: [u8; 2]) -> Option<Self> {
+        match bytes {
+            [0, id] => Block::from_id(id).map(Item::Block),
+            [1, id] => ItemKind::from_id(id).map(Item::Other),
+            _ => None,
+        }
+    }
+
+    /// Returns the index of the texture representing this item in the texture array (see
+    /// `Item::get_texture_files`)
+    pub fn texture_id(&self) -> usize {
+        match self {
+            // The side of the block
+            Item::Block(block) => 3 * block.id() as usize,
+            Item::Other(kind) => 3 * BlockRegistry::global().len() + kind.id() as usize,
+        }
+    }
+
+    /// Returns all the textures to be loaded, in the proper order, as paths relative to the
+    /// `resources` directory: the three textures of each block, followed by the icon of each item.
+    pub fn get_texture_files() -> Vec<String> {
+        let blocks = Block::get_texture_files()
+            .into_iter()
+            .map(|name| format!("block/{name}"));
+        let items = ItemRegistry::global()
+            .icon_files()
+            .into_iter()
+            .map(|name| format!("item/{name}"));
+        blocks.chain(items).collect()
+    }
+}
+
+impl From<Block> for Item {
+    fn from(block: Block) -> Self {
+        Item::Block(block)
+    }
+}
+
+impl From<ItemKind> for Item {
+    fn from(kind: ItemKind) -> Self {
+        Item::Other(kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::item::{Item, ItemKind};
+    use crate::game::item_registry::ItemRegistry;
+    use crate::world::block_kind::Block;
+    use crate::world::block_registry::BlockRegistry;
+
+    #[test]
+    fn test_texture_ids() {
+        let files = Item::get_texture_files();
+        let sand = Item::Block(Block::SAND);
+        let sword = Item::Other(ItemKind::STONE_SWORD);
+        assert_eq!("block/sand_side", files[sand.texture_id()]);
+        assert_eq!("item/stone_sword", files[sword.texture_id()]);
+        assert_eq!(3 * BlockRegistry::global().len() + ItemRegistry::global().len(), files.len());
+    }
+
+    #[test]
+    fn test_items_by_name() {
+        assert_eq!(Some(Item::Block(Block::DIRT)), Item::from_name("dirt"));
+        assert_eq!(Some(Item::Other(ItemKind::STONE_SWORD)), Item::from_name("stone_sword"));
+        assert_eq!(None, Item::from_name("diamond"));
+        assert_eq!("stone_sword", Item::Other(ItemKind::STONE_SWORD).name());
+    }
+
+    #[test]
+    fn test_bytes_representation() {
+        for item in [Item::Block(Block::SAND), Item::Other(ItemKind::STONE_SWORD)] {
+            assert_eq!(Some(item), Item::from_bytes(item.to_bytes()));
+        }
+        assert_eq!(None, Item::from_bytes([1, 255]));
+        assert_eq!(None, Item::from_bytes([2, 0]));
+    }
+}
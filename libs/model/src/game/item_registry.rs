@@ -0,0 +1,279 @@
+use crate::game::definitions::digest_of;
+use crate::game::item::ItemKind;
+use crate::world::block_registry::BlockRegistry;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::sync::OnceLock;
+use tracing::{info, warn};
+
+/// File from which the items are loaded at startup, relative to the working directory
+pub const ITEMS_FILE: &str = "./resources/items.json";
+
+/// Definitions used when no file could be loaded. This is a copy of `resources/items.json` taken
+/// at compile time.
+const BUILTIN_ITEMS: &str = include_str!("../../../../resources/items.json");
+
+/// Maximum number of items in a slot, for the items that do not define it
+pub const DEFAULT_MAX_STACK: usize = 64;
+
+static REGISTRY: OnceLock<ItemRegistry> = OnceLock::new();
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemCategory {
+    /// A block, which can be placed in the world
+    Block,
+    /// Something only used for crafting
+    Material,
+    Tool,
+    Weapon,
+    /// Something that is used up when used
+    Consumable,
+}
+
+/// The kinds of tools, each one mining some blocks faster (see `BlockDefinition::tool`)
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolKind {
+    Pickaxe,
+    Axe,
+    Shovel,
+}
+
+/// Properties of a kind of item, as written in the definition file
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ItemDefinition {
+    pub id: u8,
+    pub name: String,
+    pub category: ItemCategory,
+    /// Name of the texture shown in the inventory, in `resources/item`
+    pub icon: String,
+    /// Maximum number of items in a slot
+    #[serde(default = "default_max_stack")]
+    pub max_stack: usize,
+    /// Number of times the item can be used before breaking, if it wears out
+    #[serde(default)]
+    pub durability: Option<u16>,
+    /// Damages done when attacking while holding this item
+    #[serde(default = "default_attack_strength")]
+    pub attack_strength: u8,
+    /// The kind of tool of this item, if it is one
+    #[serde(default)]
+    pub tool: Option<ToolKind>,
+    /// How many times faster this tool mines the blocks it is made for
+    #[serde(default = "default_efficiency")]
+    pub efficiency: f32,
+}
+
+fn default_max_stack() -> usize {
+    DEFAULT_MAX_STACK
+}
+
+fn default_attack_strength() -> u8 {
+    1
+}
+
+fn default_efficiency() -> f32 {
+    1.
+}
+
+#[derive(Debug)]
+pub enum ItemRegistryError {
+    Io(std::io::Error),
+    Parse(serde_json::Error),
+    /// The ids must go from 0 to the number of items, in the order of the file
+    UnexpectedId { expected: u8, found: u8 },
+    /// The name is already used by another item or by a block
+    DuplicateName(String),
+    /// An item that wears out cannot be stacked
+    StackableWithDurability(String),
+    /// One of the items used by the game itself is not defined with the expected id
+    MissingBuiltin { id: u8, name: &'static str },
+}
+
+impl Display for ItemRegistryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ItemRegistryError::Io(err) => write!(f, "Cannot read the items: {err}"),
+            ItemRegistryError::Parse(err) => write!(f, "Invalid item definitions: {err}"),
+            ItemRegistryError::UnexpectedId { expected, found } => {
+                write!(f, "Expected an item with id {expected}, found {found}")
+            }
+            ItemRegistryError::DuplicateName(name) => write!(f, "{name} is defined twice"),
+            ItemRegistryError::StackableWithDurability(name) => {
+                write!(f, "Item {name} has a durability, its max stack must be 1")
+            }
+            ItemRegistryError::MissingBuiltin { id, name } => {
+                write!(f, "Item {name} must be defined with id {id}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ItemRegistryError {}
+
+/// All the kinds of items that are not blocks: tools, weapons, materials and consumables.
+///
+/// Like the blocks (see `BlockRegistry`), the items are defined in a JSON file
+/// (`resources/items.json`) loaded at startup with `ItemRegistry::install_from_file`.
+pub struct ItemRegistry {
+    /// The definitions, indexed by id
+    definitions: Vec<ItemDefinition>,
+    /// The id of each item, from its name
+    ids: HashMap<String, u8>,
+}
+
+impl ItemRegistry {
+    /// Parses and validates a list of item definitions.
+    /// The names of the items must be different from the ones of the blocks.
+    pub fn from_json(json: &str, blocks: &BlockRegistry) -> Result<Self, ItemRegistryError> {
+        let definitions: Vec<ItemDefinition> =
+            serde_json::from_str(json).map_err(ItemRegistryError::Parse)?;
+
+        let mut ids = HashMap::new();
+        for (expected, definition) in definitions.iter().enumerate() {
+            if definition.id as usize != expected || expected > u8::MAX as usize {
+                return Err(ItemRegistryError::UnexpectedId {
+                    expected: expected as u8,
+                    found: definition.id,
+                });
+            }
+            let name = &definition.name;
+            if blocks.by_name(name).is_some() || ids.insert(name.clone(), definition.id).is_some() {
+                return Err(ItemRegistryError::DuplicateName(name.clone()));
+            }
+            if definition.durability.is_some() && definition.max_stack != 1 {
+                return Err(ItemRegistryError::StackableWithDurability(name.clone()));
+            }
+        }
+
+        for (kind, name) in ItemKind::BUILTIN {
+            if definitions.get(kind.id() as usize).map(|d| d.name.as_str()) != Some(name) {
+                return Err(ItemRegistryError::MissingBuiltin { id: kind.id(), name });
+            }
+        }
+
+        Ok(Self { definitions, ids })
+    }
+
+    /// Loads the item definitions from a file
+    pub fn load(path: impl AsRef<Path>, blocks: &BlockRegistry) -> Result<Self, ItemRegistryError> {
+        let json = std::fs::read_to_string(path).map_err(ItemRegistryError::Io)?;
+        Self::from_json(&json, blocks)
+    }
+
+    /// The definitions that are part of the game, used when no file is provided
+    pub fn builtin() -> Self {
+        Self::from_json(BUILTIN_ITEMS, BlockRegistry::global())
+            .expect("The built-in items are invalid")
+    }
+
+    /// Loads the items from the given file, and makes them the items of the game.
+    /// If the file cannot be loaded, the built-in items are used.
+    /// This must be called after `BlockRegistry::install_from_file`, and before any item is used.
+    pub fn install_from_file(path: impl AsRef<Path>) {
+        let registry = match Self::load(&path, BlockRegistry::global()) {
+            Ok(registry) => {
+                info!("Loaded {} items from {:?}", registry.len(), path.as_ref());
+                registry
+            }
+            Err(err) => {
+                warn!("{err}, using the built-in items");
+                Self::builtin()
+            }
+        };
+        if REGISTRY.set(registry).is_err() {
+            warn!("The items are already in use, they cannot be replaced");
+        }
+    }
+
+    /// Returns the registry used by the game, which is the built-in one if none was installed
+    pub fn global() -> &'static Self {
+        REGISTRY.get_or_init(Self::builtin)
+    }
+
+    pub fn len(&self) -> usize {
+        self.definitions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.definitions.is_empty()
+    }
+
+    /// Returns a digest of the definitions (see `definitions_digest`)
+    pub fn digest(&self) -> u64 {
+        digest_of(&self.definitions)
+    }
+
+    /// Returns the definition of the item with the given id
+    pub fn get(&self, id: u8) -> Option<&ItemDefinition> {
+        self.definitions.get(id as usize)
+    }
+
+    /// Returns the item with the given name
+    pub fn by_name(&self, name: &str) -> Option<ItemKind> {
+        self.ids.get(name).map(|id| ItemKind::from_raw_id(*id))
+    }
+
+    /// Returns the names of the icons of the items, ordered by id
+    pub fn icon_files(&self) -> Vec<String> {
+        self.definitions
+            .iter()
+            .map(|definition| definition.icon.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::item::ItemKind;
+    use crate::game::item_registry::{
+        ItemCategory, ItemRegistry, ItemRegistryError, ToolKind, BUILTIN_ITEMS,
+    };
+    use crate::world::block_registry::BlockRegistry;
+
+    #[test]
+    fn test_builtin_items() {
+        let registry = ItemRegistry::builtin();
+        let sword = registry.get(ItemKind::STONE_SWORD.id()).unwrap();
+        assert_eq!(ItemCategory::Weapon, sword.category);
+        assert_eq!(1, sword.max_stack);
+        assert!(sword.durability.is_some());
+        assert_eq!(Some(ItemKind::STONE_SWORD), registry.by_name("stone_sword"));
+        assert_eq!("stone_sword", registry.icon_files()[0]);
+        let pickaxe = registry.get(registry.by_name("stone_pickaxe").unwrap().id()).unwrap();
+        assert_eq!(Some(ToolKind::Pickaxe), pickaxe.tool);
+    }
+
+    #[test]
+    fn test_invalid_definitions_are_rejected() {
+        let blocks = BlockRegistry::builtin();
+        let with = |item: &str| {
+            let json = BUILTIN_ITEMS.trim_end().trim_end_matches(']').to_string();
+            ItemRegistry::from_json(&(json + "," + item + "]"), &blocks)
+        };
+
+        let stick = r#"{"id": 4, "name": "stick", "category": "material", "icon": "stick"}"#;
+        assert!(with(stick).is_ok());
+
+        let dirt = r#"{"id": 4, "name": "dirt", "category": "material", "icon": "dirt"}"#;
+        assert!(matches!(with(dirt), Err(ItemRegistryError::DuplicateName(_))));
+
+        let pickaxe = r#"{"id": 4, "name": "pickaxe", "category": "tool", "icon": "pickaxe",
+                          "durability": 50}"#;
+        assert!(matches!(with(pickaxe), Err(ItemRegistryError::StackableWithDurability(_))));
+
+        let apple = r#"{"id": 6, "name": "apple", "category": "consumable", "icon": "apple"}"#;
+        assert!(matches!(
+            with(apple),
+            Err(ItemRegistryError::UnexpectedId { expected: 4, found: 6 })
+        ));
+
+        assert!(matches!(
+            ItemRegistry::from_json("[]", &blocks),
+            Err(ItemRegistryError::MissingBuiltin { .. })
+        ));
+    }
+}
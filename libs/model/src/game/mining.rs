@@ -0,0 +1,80 @@
+use crate::game::item::Item;
+use crate::world::block_kind::Block;
+
+/// Time [s] needed to break a block of hardness 1 without tool
+pub const SECONDS_PER_HARDNESS: f32 = 1.5;
+
+/// How much longer it takes to break a block that requires a tool without this tool
+const MISSING_TOOL_PENALTY: f32 = 10. / 3.;
+
+/// Returns true if the held item is the tool that the block is made for
+fn is_right_tool(block: Block, held: Option<Item>) -> bool {
+    match (block.definition().tool, held.and_then(|item| item.tool())) {
+        (Some(expected), Some(tool)) => expected == tool,
+        _ => false,
+    }
+}
+
+/// Returns the time [s] needed to break the block while holding the given item
+pub fn break_time(block: Block, held: Option<Item>) -> f32 {
+    let time = block.hardness() * SECONDS_PER_HARDNESS;
+    if is_right_tool(block, held) {
+        time / held.map_or(1., |item| item.efficiency())
+    } else if block.definition().requires_tool {
+        time * MISSING_TOOL_PENALTY
+    } else {
+        time
+    }
+}
+
+/// Returns the block collected when breaking the block while holding the given item, if any
+pub fn harvested_block(block: Block, held: Option<Item>) -> Option<Block> {
+    if block.definition().requires_tool && !is_right_tool(block, held) {
+        None
+    } else {
+        Some(block.block_dropped())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::item::{Item, ItemKind};
+    use crate::game::mining::{break_time, harvested_block, SECONDS_PER_HARDNESS};
+    use crate::world::block_kind::Block;
+
+    #[test]
+    fn test_break_time_depends_on_the_tool() {
+        let pickaxe = Item::from_name("stone_pickaxe");
+        let axe = Item::from_name("stone_axe");
+        let sword = Some(Item::Other(ItemKind::STONE_SWORD));
+        let stone = Some(Item::Block(Block::STONE));
+
+        // Without the right tool, any item is like the bare hand
+        let by_hand = break_time(Block::OAKLOG, None);
+        assert_eq!(Block::OAKLOG.hardness() * SECONDS_PER_HARDNESS, by_hand);
+        assert_eq!(by_hand, break_time(Block::OAKLOG, sword));
+        assert_eq!(by_hand, break_time(Block::OAKLOG, pickaxe));
+        assert_eq!(by_hand, break_time(Block::OAKLOG, stone));
+        assert!(break_time(Block::OAKLOG, axe) < by_hand);
+
+        // Breaking a block that requires a tool without it is even slower
+        let time = Block::STONE.hardness() * SECONDS_PER_HARDNESS;
+        assert!(break_time(Block::STONE, None) > time);
+        assert!(break_time(Block::STONE, pickaxe) < time);
+
+        assert!(break_time(Block::OAKLEAVES, None) < break_time(Block::DIRT, None));
+    }
+
+    #[test]
+    fn test_some_blocks_require_a_tool() {
+        let pickaxe = Item::from_name("stone_pickaxe");
+        let shovel = Item::from_name("stone_shovel");
+
+        assert_eq!(None, harvested_block(Block::STONE, None));
+        assert_eq!(None, harvested_block(Block::STONE, shovel));
+        assert_eq!(Some(Block::COBBELSTONE), harvested_block(Block::STONE, pickaxe));
+
+        assert_eq!(Some(Block::DIRT), harvested_block(Block::GRASS, None));
+        assert_eq!(Some(Block::DIRT), harvested_block(Block::GRASS, pickaxe));
+    }
+}
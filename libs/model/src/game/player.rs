@@ -6,10 +6,9 @@ use std::time::Duration;
 use crate::collision::collidable::{Collidable, CollisionData};
 use crate::game::input::{MotionState, PlayerInputStatus};
 use crate::world::cube::Cube;
+use crate::world::fluid::{swim, FLUID_SPEED_FACTOR};
 use crate::world::world::World;
 
-pub const CLICK_TIME_TO_BREAK: f32 = 2.0;
-
 /// Travel speed [m/s] or [cube/s]
 const SPEED: f32 = 4.0;
 // TODO for some obscure reason, actual speed is lower than that. Perhaps the dt
@@ -27,6 +26,12 @@ pub const PLAYER_HEIGHT: f32 = 1.8;
 pub const DIAMETER: f32 = 0.5;
 pub const FOREHEAD: f32 = 0.1;
 
+/// Maximum distance [cube] between the player and the cubes that they can select
+pub const REACH: f32 = 6.;
+
+/// Rate [1/s] at which the horizontal speed of a player pushed away by a hit decreases
+const KNOCK_BACK_DAMPING: f32 = 5.;
+
 /// Represents the physical state of a player, on the client side.
 /// This means:
 /// - position
@@ -52,6 +57,9 @@ pub struct Player {
     touched_cube: Option<Cube>,
 
     in_air: bool,
+
+    /// Horizontal velocity given by the last hit, which fades away
+    knock_back: Vector3,
 }
 
 impl Player {
@@ -63,25 +71,38 @@ impl Player {
             input_status: PlayerInputStatus::new(),
             touched_cube: None,
             in_air: true, // will be updated every frame anyway
+            knock_back: Vector3::empty(),
         }
     }
 
     pub fn step(&mut self, elapsed: Duration, world: &World) {
+        // The player waits for the chunk below them to be received, not to fall through it
+        if world.chunk_at(&self.position.pos()).is_none() {
+            return;
+        }
+
         // Compute the next position
         let dt = elapsed.as_secs_f32();
 
-        // add gravity
-        if self.in_air {
+        // add gravity, or swim
+        let in_fluid = world.is_in_fluid(&humanoid_aabb(&self.position));
+        if in_fluid {
+            swim(&mut self.velocity, dt, self.input_status.jump());
+        } else if self.in_air {
             self.velocity += GRAVITY_ACCELERATION_VECTOR * dt;
         }
 
         {
-            let controls_vel = self.controls_velocity();
-            self.velocity[0] = controls_vel[0];
-            self.velocity[2] = controls_vel[2];
+            let mut controls_vel = self.controls_velocity();
+            if in_fluid {
+                controls_vel = controls_vel * FLUID_SPEED_FACTOR;
+            }
+            self.velocity[0] = controls_vel[0] + self.knock_back[0];
+            self.velocity[2] = controls_vel[2] + self.knock_back[2];
+            self.knock_back = self.knock_back * (1. - KNOCK_BACK_DAMPING * dt).max(0.);
         }
 
-        if self.input_status.jump() {
+        if self.input_status.jump() && !in_fluid {
             self.jump();
         }
 
@@ -130,6 +151,13 @@ impl Player {
         }
     }
 
+    /// Pushes the player with the given velocity, after they were hit
+    pub fn knock_back(&mut self, velocity: Vector3) {
+        self.knock_back = Vector3::new(velocity[0], 0., velocity[2]);
+        self.velocity[1] = velocity[1];
+        self.in_air = true;
+    }
+
     pub fn up(&mut self) {
         self.position.translate_y(5.);
     }
@@ -232,8 +260,8 @@ impl Player {
         for cube in world
             .cubes_near_player(position)
             .filter_map(|c| *c)
-            .filter(|c| c.is_visible())
-            .filter(|c| c.position().distance_to(&position) < 6.)
+            .filter(|c| c.is_visible() && !c.block().is_fluid())
+            .filter(|c| c.position().distance_to(&position) < REACH)
         {
             if let Some(result) = cube.intersection_with(position, direction) {
                 if current_best.is_none() || result < current_best.unwrap().0 {
@@ -289,18 +317,15 @@ impl Player {
 
     /// Returns the normalized direction vector
     pub fn direction(&self) -> Vector3 {
-        Vector3::new(
-            self.position.yaw().cos() * self.position.pitch().cos(),
-            self.position.pitch().sin(),
-            self.position.yaw().sin() * self.position.pitch().cos(),
-        )
+        self.position.direction()
     }
 
-    /// Returns true if the player is asking to break a cube
-    pub fn is_time_to_break_over(&mut self, dt: f32) -> bool {
+    /// Returns true if the player is asking to break a cube, and has been clicking on it for
+    /// the given time [s]
+    pub fn is_time_to_break_over(&mut self, dt: f32, time_to_break: f32) -> bool {
         if self.is_selecting_cube() && self.left_click() {
             self.add_click_time(dt);
-            if self.left_click_time() >= CLICK_TIME_TO_BREAK {
+            if self.left_click_time() >= time_to_break {
                 self.reset_click_time();
                 return true;
             }
@@ -308,3 +333,50 @@ impl Player {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::game::player::Player;
+    use crate::world::block_kind::Block;
+    use crate::world::chunk::Chunk;
+    use crate::world::world::World;
+    use primitives::position::Position;
+    use std::time::Duration;
+
+    #[test]
+    fn test_player_waits_for_the_chunk() {
+        let mut player = Player::new();
+        let start = Position::new_vec(4.5, 5., 4.5);
+        player.set_position(start.clone());
+        player.step(Duration::from_millis(100), &World::empty());
+        assert_eq!(start, *player.position());
+
+        // Once the chunk is there, the player falls on the ground
+        let mut world = World::empty();
+        let mut chunk = Chunk::new([0., 0.]);
+        chunk.fill_layer(0, Block::DIRT);
+        world.add_chunk(chunk);
+        for _ in 0..100 {
+            player.step(Duration::from_millis(10), &world);
+        }
+        assert!(player.position().y() < 3.);
+    }
+
+    #[test]
+    fn test_falling_into_water() {
+        let mut world = World::empty();
+        let mut chunk = Chunk::new([0., 0.]);
+        chunk.fill_layer(0, Block::DIRT);
+        chunk.fill_layer(1, Block::WATER);
+        chunk.fill_layer(2, Block::WATER);
+        world.add_chunk(chunk);
+
+        let mut player = Player::new();
+        player.set_position(Position::new_vec(4.5, 14., 4.5));
+        for _ in 0..300 {
+            player.step(Duration::from_millis(10), &world);
+        }
+        // The player went through the water, down to the ground
+        assert!(player.position().y() < 3.);
+    }
+}
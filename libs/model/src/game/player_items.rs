@@ -1,12 +1,50 @@
+use crate::game::crafting::{CraftingGrid, CraftingManager};
+use crate::game::item::{Item, ItemKind};
+use crate::game::mining;
 use crate::world::block_kind::Block;
-
-pub type ItemStack = (Block, usize);
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 const CURRENT_ITEMS_SIZE: usize = 8;
-const MAX_ITEMS_IN_SLOT: usize = 64;
+
+/// Several items of the same kind, held in one slot
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ItemStack {
+    pub item: Item,
+    pub count: usize,
+    /// Remaining uses of the item, if it wears out. Such items are never stacked.
+    pub durability: Option<u16>,
+}
+
+impl ItemStack {
+    /// Returns a stack with one new item
+    pub fn new(item: Item) -> Self {
+        Self {
+            item,
+            count: 1,
+            durability: item.max_durability(),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, serde_json::Error> {
+        serde_json::from_slice(bytes)
+    }
+
+    /// Returns true if the given stack can be added to this one
+    fn can_merge(&self, other: &ItemStack) -> bool {
+        self.item == other.item
+            && self.durability.is_none()
+            && other.durability.is_none()
+            && self.count + other.count <= self.item.max_stack()
+    }
+}
 
 /// Holds the items of a player.
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct PlayerItems {
     /// The items always displayed on the item bars
     bar_items: [Option<ItemStack>; CURRENT_ITEMS_SIZE],
@@ -24,9 +62,22 @@ impl PlayerItems {
         }
     }
 
+    /// Returns the items given to a player joining the game for the first time
+    pub fn starting_items() -> Self {
+        let mut items = Self::empty();
+        items.collect(ItemKind::STONE_SWORD.into());
+        for _ in 0..16 {
+            items.collect(Block::COBBELSTONE.into());
+        }
+        for _ in 0..8 {
+            items.collect(Block::OAKLOG.into());
+        }
+        items
+    }
+
     pub fn new(
-        inventory_items: [Option<(Block, usize)>; CURRENT_ITEMS_SIZE * 3],
-        current_items: [Option<(Block, usize)>; CURRENT_ITEMS_SIZE],
+        inventory_items: [Option<ItemStack>; CURRENT_ITEMS_SIZE * 3],
+        current_items: [Option<ItemStack>; CURRENT_ITEMS_SIZE],
     ) -> Self {
         Self {
             bar_items: current_items,
@@ -35,6 +86,14 @@ impl PlayerItems {
         }
     }
 
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, serde_json::Error> {
+        serde_json::from_slice(bytes)
+    }
+
     pub fn get_bar_items(&self) -> Vec<ItemStack> {
         self.bar_items
             .iter()
@@ -51,61 +110,113 @@ impl PlayerItems {
         self.inventory_items[index]
     }
 
-    pub fn take_bar_item(&mut self, index: usize) -> Option<Block> {
+    /// Takes one item from a slot of the bar
+    pub fn take_bar_item(&mut self, index: usize) -> Option<ItemStack> {
         Self::take_item(&mut self.bar_items[index])
     }
 
-    pub fn take_inventory_item(&mut self, index: usize) -> Option<Block> {
+    /// Takes one item from a slot of the inventory
+    pub fn take_inventory_item(&mut self, index: usize) -> Option<ItemStack> {
         Self::take_item(&mut self.inventory_items[index])
     }
 
-    pub fn put_bar_item(&mut self, index: usize, block: Block) -> bool {
-        Self::put_item(&mut self.bar_items[index], block)
+    pub fn put_bar_item(&mut self, index: usize, stack: ItemStack) -> bool {
+        Self::put_item(&mut self.bar_items[index], stack)
     }
 
-    pub fn put_inventory_item(&mut self, index: usize, block: Block) -> bool {
-        Self::put_item(&mut self.inventory_items[index], block)
+    pub fn put_inventory_item(&mut self, index: usize, stack: ItemStack) -> bool {
+        Self::put_item(&mut self.inventory_items[index], stack)
     }
 
-    pub fn get_current_block(&self) -> Option<Block> {
-        if let Some((_, Some((block, _)))) = self
+    /// Returns the stack currently held by the player
+    pub fn get_current_stack(&self) -> Option<ItemStack> {
+        if let Some((_, Some(stack))) = self
             .bar_items
             .iter()
             .filter(|item| item.is_some())
             .enumerate()
             .find(|(i, _)| *i == self.current_item)
         {
-            Some(block.clone())
+            Some(*stack)
         } else {
             None
         }
     }
 
+    /// Returns the item currently held by the player
+    pub fn get_current_item(&self) -> Option<Item> {
+        self.get_current_stack().map(|stack| stack.item)
+    }
+
+    /// Returns the block currently held by the player, if the current item is a block
+    pub fn get_current_block(&self) -> Option<Block> {
+        self.get_current_item().and_then(|item| item.as_block())
+    }
+
     pub fn attack_strength(&self) -> u8 {
-        if let Some(b) = self.get_current_block() {
-            b.attack_strength()
+        if let Some(item) = self.get_current_item() {
+            item.attack_strength()
         } else {
             1
         }
     }
 
-    pub fn collect(&mut self, block: Block) {
-        fn place_in_collection(list: &mut [Option<ItemStack>], block: Block) -> bool {
+    /// Wears out the item currently held, which is removed when it breaks.
+    /// Does nothing if this item does not wear out.
+    pub fn use_current_item(&mut self) {
+        let Some(current) = self.get_current_stack() else {
+            return;
+        };
+        let Some(durability) = current.durability else {
+            return;
+        };
+        if let Some(slot) = self
+            .bar_items
+            .iter_mut()
+            .filter(|slot| slot.is_some())
+            .nth(self.current_item)
+        {
+            *slot = if durability > 1 {
+                Some(ItemStack {
+                    durability: Some(durability - 1),
+                    ..current
+                })
+            } else {
+                None
+            };
+        }
+    }
+
+    /// Wears out the item used to destroy a block, and returns the block that it drops, if any.
+    /// The dropped block is not collected: it falls in the world, where it can be picked up.
+    pub fn on_block_destroyed(&mut self, block: Block) -> Option<Block> {
+        let harvested = mining::harvested_block(block, self.get_current_item());
+        if block.hardness() > 0. {
+            self.use_current_item();
+        }
+        harvested
+    }
+
+    pub fn collect(&mut self, item: Item) {
+        self.collect_stack(ItemStack::new(item));
+    }
+
+    /// Adds the stack to the items. Returns false if there is no room for it.
+    pub fn collect_stack(&mut self, new: ItemStack) -> bool {
+        fn place_in_collection(list: &mut [Option<ItemStack>], new: ItemStack) -> bool {
             // First check if the item already exists in the list
             // If so, simply increase the counter
-            for i in 0..list.len() {
-                if let Some((b, count)) = list[i] {
-                    if b == block && count < MAX_ITEMS_IN_SLOT {
-                        list[i] = Some((b, count + 1));
-                        return true;
-                    }
+            for stack in list.iter_mut().flatten() {
+                if stack.can_merge(&new) {
+                    stack.count += new.count;
+                    return true;
                 }
             }
 
             // Second, try to place the item in the first remaining slot
-            for i in 0..list.len() {
-                if list[i].is_none() {
-                    list[i] = Some((block, 1));
+            for slot in list.iter_mut() {
+                if slot.is_none() {
+                    *slot = Some(new);
                     return true;
                 }
             }
@@ -113,19 +224,40 @@ impl PlayerItems {
             false
         }
 
-        if !place_in_collection(&mut self.bar_items, block) {
-            place_in_collection(&mut self.inventory_items, block);
-        }
+        place_in_collection(&mut self.bar_items, new)
+            || place_in_collection(&mut self.inventory_items, new)
+    }
+
+    /// Removes one item from the stack currently held, to throw it
+    pub fn drop_current_item(&mut self) -> Option<ItemStack> {
+        let slot = self
+            .bar_items
+            .iter_mut()
+            .filter(|slot| slot.is_some())
+            .nth(self.current_item)?;
+        Self::take_item(slot)
     }
 
-    pub fn consume(&mut self, block: Block) {
+    /// Removes all the items, and returns them
+    pub fn take_all(&mut self) -> Vec<ItemStack> {
+        self.bar_items
+            .iter_mut()
+            .chain(self.inventory_items.iter_mut())
+            .filter_map(|slot| slot.take())
+            .collect()
+    }
+
+    pub fn consume(&mut self, item: Item) {
         for i in 0..CURRENT_ITEMS_SIZE {
-            if let Some((b, c)) = self.bar_items[i] {
-                if b == block {
-                    if c == 1 {
+            if let Some(stack) = self.bar_items[i] {
+                if stack.item == item {
+                    if stack.count == 1 {
                         self.bar_items[i] = None
                     } else {
-                        self.bar_items[i] = Some((b, c - 1))
+                        self.bar_items[i] = Some(ItemStack {
+                            count: stack.count - 1,
+                            ..stack
+                        })
                     }
                     return;
                 }
@@ -133,10 +265,45 @@ impl PlayerItems {
         }
     }
 
-    pub fn has_block(&self, block: Block) -> bool {
+    pub fn has_item(&self, item: Item) -> bool {
         self.bar_items
             .iter()
-            .any(|item| item.is_some_and(|(b, _)| b == block))
+            .any(|stack| stack.is_some_and(|stack| stack.item == item))
+    }
+
+    /// Crafts the recipe of the grid with the ingredients found anywhere in the items, and collects
+    /// its output. Returns the output, or nothing if no recipe matches, if an ingredient is
+    /// missing or if there is no room for the output; the items are then left untouched.
+    pub fn craft(&mut self, grid: &CraftingGrid) -> Option<ItemStack> {
+        let result = CraftingManager::global().recipe(grid)?;
+        let mut items = self.clone();
+        for (row, col) in result.consumed {
+            let slot = items
+                .bar_items
+                .iter_mut()
+                .chain(items.inventory_items.iter_mut())
+                .find(|slot| slot.is_some_and(|stack| Some(stack.item) == grid[row][col]))?;
+            Self::take_item(slot);
+        }
+        if !items.collect_stack(result.output) {
+            return None;
+        }
+        *self = items;
+        Some(result.output)
+    }
+
+    /// Returns true if both hold the same items, whatever the slots they are in
+    pub fn same_items_as(&self, other: &PlayerItems) -> bool {
+        self.counts() == other.counts()
+    }
+
+    /// Returns the number of items of each kind, the worn ones being counted apart
+    fn counts(&self) -> HashMap<(Item, Option<u16>), usize> {
+        let mut counts = HashMap::new();
+        for stack in self.bar_items.iter().chain(self.inventory_items.iter()).flatten() {
+            *counts.entry((stack.item, stack.durability)).or_insert(0) += stack.count;
+        }
+        counts
     }
 
     pub fn debug(&self) {
@@ -154,75 +321,181 @@ impl PlayerItems {
         self.current_item
     }
 
-    fn take_item(itemstack: &mut Option<ItemStack>) -> Option<Block> {
-        let mut ret: Option<Block> = None;
-
-        *itemstack = if let Some((block, count)) = itemstack {
-            if *count > 0 {
-                ret = Some(*block);
-            }
-
-            if *count > 1 {
-                Some((*block, *count - 1))
-            } else {
-                None
-            }
+    fn take_item(itemstack: &mut Option<ItemStack>) -> Option<ItemStack> {
+        let stack = (*itemstack)?;
+        *itemstack = if stack.count > 1 {
+            Some(ItemStack {
+                count: stack.count - 1,
+                ..stack
+            })
         } else {
             None
         };
-
-        ret
+        Some(ItemStack { count: 1, ..stack })
     }
 
-    fn put_item(itemstack: &mut Option<ItemStack>, block: Block) -> bool {
-        let mut success: bool = false;
-        *itemstack = match itemstack {
-            Some((block2, count)) => {
-                if *block2 == block {
-                    // TODO put stack limit
-                    success = true;
-                    Some((*block2, *count + 1))
-                } else {
-                    Some((*block2, *count))
-                }
+    fn put_item(itemstack: &mut Option<ItemStack>, new: ItemStack) -> bool {
+        match itemstack {
+            Some(stack) if stack.can_merge(&new) => {
+                stack.count += new.count;
+                true
             }
+            Some(_) => false,
             None => {
-                success = true;
-                Some((block, 1))
+                *itemstack = Some(new);
+                true
             }
-        };
-
-        success
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::game::player_items::PlayerItems;
-    use crate::world::block_kind::Block::COBBELSTONE;
+    use crate::game::item::{Item, ItemKind};
+    use crate::game::player_items::{ItemStack, PlayerItems};
+    use crate::world::block_kind::Block;
 
     #[test]
     fn test_basic_operations() {
         let mut items = PlayerItems::empty();
+        let cobblestone = Item::Block(Block::COBBELSTONE);
 
         // At first, there is simply no block to add
         assert_eq!(items.get_current_block(), None);
 
         // The player collects 2 rocks
-        items.collect(COBBELSTONE);
+        items.collect(cobblestone);
         items.debug();
 
-        items.collect(COBBELSTONE);
+        items.collect(cobblestone);
 
         // He can place two (and only two) cobbelstones
-        assert_eq!(items.get_current_block(), Some(COBBELSTONE));
-        items.consume(COBBELSTONE);
-        assert_eq!(items.get_current_block(), Some(COBBELSTONE));
-        items.consume(COBBELSTONE);
+        assert_eq!(items.get_current_block(), Some(Block::COBBELSTONE));
+        items.consume(cobblestone);
+        assert_eq!(items.get_current_block(), Some(Block::COBBELSTONE));
+        items.consume(cobblestone);
 
         items.debug();
 
         // After consuming 2 stones, we don't have anymore cubes to place
         assert_eq!(items.get_current_block(), None);
     }
+
+    #[test]
+    fn test_stack_sizes() {
+        let mut items = PlayerItems::empty();
+        let sword = Item::Other(ItemKind::STONE_SWORD);
+
+        // Swords are not stacked
+        items.collect(sword);
+        items.collect(sword);
+        assert_eq!(2, items.get_bar_items().len());
+        assert!(!items.put_bar_item(0, items.get_bar_item(1).unwrap()));
+
+        // Blocks are stacked up to 64
+        for _ in 0..65 {
+            items.collect(Block::DIRT.into());
+        }
+        let bar = items.get_bar_items();
+        assert_eq!(4, bar.len());
+        assert_eq!((Item::Block(Block::DIRT), 64), (bar[2].item, bar[2].count));
+        assert_eq!(1, bar[3].count);
+
+        // A sword is not a block
+        assert_eq!(None, items.get_current_block());
+        assert_eq!(Some(sword), items.get_current_item());
+        assert_eq!(2, items.attack_strength());
+    }
+
+    #[test]
+    fn test_durability() {
+        let mut items = PlayerItems::empty();
+        items.collect(ItemKind::STONE_SWORD.into());
+        let durability = items.get_current_stack().unwrap().durability.unwrap();
+
+        // The durability is kept when moving the item
+        items.use_current_item();
+        let sword = items.take_bar_item(0).unwrap();
+        assert_eq!(Some(durability - 1), sword.durability);
+        assert!(items.put_inventory_item(3, sword));
+        let sword = items.take_inventory_item(3).unwrap();
+        assert!(items.put_bar_item(0, sword));
+
+        // Until it breaks
+        for _ in 1..durability {
+            items.use_current_item();
+        }
+        assert_eq!(None, items.get_current_item());
+    }
+
+    #[test]
+    fn test_drop_and_pick_up() {
+        let mut items = PlayerItems::starting_items();
+        let sword = items.get_current_stack().unwrap();
+
+        // The items are thrown one by one
+        assert_eq!(Some(sword), items.drop_current_item());
+        let cobblestone = items.drop_current_item().unwrap();
+        assert_eq!((Item::Block(Block::COBBELSTONE), 1), (cobblestone.item, cobblestone.count));
+        assert_eq!(15, items.get_current_stack().unwrap().count);
+
+        // A picked up stack keeps its durability
+        assert!(items.collect_stack(ItemStack { count: 3, ..cobblestone }));
+        assert!(items.collect_stack(sword));
+        assert_eq!(Some(sword), items.get_bar_item(0));
+        assert_eq!(18, items.get_bar_item(1).unwrap().count);
+
+        // There is no room left in full items
+        let all = items.take_all();
+        assert_eq!(3, all.len());
+        assert_eq!(PlayerItems::empty(), items);
+        for _ in 0..32 {
+            assert!(items.collect_stack(sword));
+        }
+        assert!(!items.collect_stack(sword));
+    }
+
+    #[test]
+    fn test_crafting_from_the_items() {
+        let log = Some(Item::Block(Block::OAKLOG));
+        let cobble = Some(Item::Block(Block::COBBELSTONE));
+        let mut items = PlayerItems::empty();
+        items.collect(Block::COBBELSTONE.into());
+        items.collect(Block::COBBELSTONE.into());
+        let before = items.clone();
+
+        // Nothing is crafted from missing ingredients, or from a grid matching no recipe
+        let sword_grid = [[log, None, None], [cobble, None, None], [cobble, None, None]];
+        assert_eq!(None, items.craft(&[[cobble, cobble, None], [None; 3], [None; 3]]));
+        assert_eq!(None, items.craft(&sword_grid));
+        assert_eq!(before, items);
+
+        // The ingredients are taken from the inventory as well
+        items.put_inventory_item(5, ItemStack::new(Block::OAKLOG.into()));
+        let sword = items.craft(&sword_grid).unwrap();
+        assert_eq!(Item::Other(ItemKind::STONE_SWORD), sword.item);
+        assert_eq!(None, items.get_inventory_item(5));
+        assert_eq!(vec![sword], items.get_bar_items());
+    }
+
+    #[test]
+    fn test_same_items() {
+        let items = PlayerItems::starting_items();
+
+        // The items can be moved and split between the slots
+        let mut moved = items.clone();
+        let sword = moved.take_bar_item(0).unwrap();
+        assert!(moved.put_inventory_item(7, sword));
+        let cobblestone = moved.take_bar_item(1).unwrap();
+        assert!(moved.put_bar_item(5, cobblestone));
+        assert!(items.same_items_as(&moved));
+
+        // But not created, or repaired
+        let mut created = items.clone();
+        created.collect(Block::OAKLOG.into());
+        assert!(!items.same_items_as(&created));
+        let mut worn = items.clone();
+        worn.use_current_item();
+        assert!(!items.same_items_as(&worn));
+    }
 }
@@ -1,15 +1,25 @@
 pub mod entity {
     pub mod chaser;
+    pub mod dropped_item;
     pub mod entity;
+    pub mod falling_block;
     pub mod entity_manager;
     pub mod humanoid;
     pub mod monster;
+    pub mod monster_registry;
+    pub mod pathfinding;
     pub mod walker_in_circle;
+    pub mod wanderer;
 }
 
 pub mod server {
+    pub mod action_validation;
+    pub mod dropped_item_manager;
+    pub mod entity_ids;
+    pub mod falling_block_manager;
     pub mod game_server;
     pub mod monster_manager;
+    pub mod monster_spawner;
     pub mod server_state;
     pub mod server_update;
     pub mod world_dispatcher;
@@ -17,20 +27,26 @@ pub mod server {
 
 pub mod world {
     pub mod block_kind;
+    pub mod block_registry;
     pub mod chunk;
     pub mod cube;
     pub mod cubes_to_draw;
+    pub mod fluid;
     pub mod generation;
     pub mod world;
-    pub mod world_serializer;
+    pub mod world_storage;
 }
 
 pub mod game {
     pub mod actions;
     pub mod attack;
     pub mod crafting;
+    pub mod definitions;
     pub mod health;
     pub mod input;
+    pub mod item;
+    pub mod item_registry;
+    pub mod mining;
     pub mod player;
     pub mod player_items;
 }
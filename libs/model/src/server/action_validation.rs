@@ -0,0 +1,224 @@
+use crate::game::actions::Action;
+use crate::game::player::REACH;
+use crate::game::player_items::PlayerItems;
+use crate::server::server_state::PlayerState;
+use crate::world::block_kind::Block;
+use crate::world::world::World;
+use primitives::vector::Vector3;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// Distance [cube] accepted beyond the reach of the players, since the server only knows the last
+/// position that they sent
+const REACH_TOLERANCE: f32 = 2.;
+
+/// Reasons for the server to refuse an action of a player
+#[derive(Debug, PartialEq)]
+pub enum ActionError {
+    /// The coordinates of the cube are not whole numbers
+    NotOnGrid,
+    /// The cube or the entity is further from the player than this distance
+    TooFar(f32),
+    /// The cube is in a chunk that the server does not have, or above or below the world
+    NotLoaded,
+    /// A cube is added where there is already one, which is not a fluid
+    PositionOccupied,
+    /// A cube is destroyed where there is none, or only a fluid
+    NothingToDestroy,
+    /// The player does not hold the block they placed
+    MissingBlock(Block),
+    NotPlaceable(Block),
+}
+
+impl Display for ActionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ActionError::NotOnGrid => write!(f, "the cube is not on the grid"),
+            ActionError::TooFar(distance) => write!(f, "the target is too far ({distance:.1})"),
+            ActionError::NotLoaded => write!(f, "the cube is not loaded"),
+            ActionError::PositionOccupied => write!(f, "there is already a cube"),
+            ActionError::NothingToDestroy => write!(f, "there is no cube to destroy"),
+            ActionError::MissingBlock(block) => write!(f, "the player does not hold {block:?}"),
+            ActionError::NotPlaceable(block) => write!(f, "{block:?} cannot be placed"),
+        }
+    }
+}
+
+impl std::error::Error for ActionError {}
+
+/// Checks that the player is allowed to do the action in the world, given what the server knows
+/// about them.
+pub fn validate_action(
+    world: &World,
+    player: &PlayerState,
+    action: &Action,
+) -> Result<(), ActionError> {
+    let at = action.position();
+    if at.as_array().iter().any(|c| c.fract() != 0.) {
+        return Err(ActionError::NotOnGrid);
+    }
+    let center = at + Vector3::new(0.5, 0.5, 0.5);
+    let distance = center.distance_to(&player.pos.pos());
+    if distance > REACH + REACH_TOLERANCE {
+        return Err(ActionError::TooFar(distance));
+    }
+    if !world.is_loaded(&at) {
+        return Err(ActionError::NotLoaded);
+    }
+
+    match action {
+        Action::Destroy { at } => {
+            if world.block_at(at).is_none_or(|block| block.is_fluid()) {
+                return Err(ActionError::NothingToDestroy);
+            }
+        }
+        Action::Add { at, block } => {
+            if world.block_at(at).is_some_and(|block| !block.is_fluid()) {
+                return Err(ActionError::PositionOccupied);
+            }
+            if !block.can_be_placed() {
+                return Err(ActionError::NotPlaceable(*block));
+            }
+            if player.items.get_current_block() != Some(*block) {
+                return Err(ActionError::MissingBlock(*block));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the player is close enough to hit the entity whose eyes are at `victim`
+pub fn validate_attack(player: &PlayerState, victim: Vector3) -> Result<(), ActionError> {
+    let distance = victim.distance_to(&player.pos.pos());
+    if distance > REACH + REACH_TOLERANCE {
+        return Err(ActionError::TooFar(distance));
+    }
+    Ok(())
+}
+
+/// Sent to a player whose action was refused, so that they can undo it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ActionRejection {
+    /// Position of the cube edited by the action
+    pub at: Vector3,
+    /// The block that the server has at this position
+    pub block: Option<Block>,
+    /// The items that the server knows the player has
+    pub items: PlayerItems,
+}
+
+impl ActionRejection {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, serde_json::Error> {
+        serde_json::from_slice(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::actions::Action;
+    use crate::game::player_items::PlayerItems;
+    use crate::server::action_validation::{validate_action, validate_attack, ActionError};
+    use crate::server::server_state::ServerState;
+    use crate::world::block_kind::Block;
+    use crate::world::chunk::{Chunk, CHUNK_HEIGHT};
+    use crate::world::world::World;
+    use primitives::position::Position;
+    use primitives::vector::Vector3;
+
+    #[test]
+    fn test_actions_are_validated() {
+        let mut world = World::empty();
+        let mut chunk = Chunk::new([0., 0.]);
+        chunk.fill_layer(0, Block::DIRT);
+        world.add_chunk(chunk);
+
+        let mut player = ServerState::new().login("arthur".to_string());
+        player.pos = Position::new_vec(4., 3., 4.);
+        player.items = PlayerItems::empty();
+        player.items.collect(Block::SAND.into());
+        player.items.collect(Block::DIRT.into());
+
+        let ground = Vector3::new(4., 0., 4.);
+        let above = Vector3::new(4., 1., 4.);
+        let destroy = |at| Action::Destroy { at };
+        let add = |at, block| Action::Add { at, block };
+
+        assert_eq!(Ok(()), validate_action(&world, &player, &destroy(ground)));
+        assert_eq!(Ok(()), validate_action(&world, &player, &add(above, Block::SAND)));
+
+        assert_eq!(
+            Err(ActionError::NothingToDestroy),
+            validate_action(&world, &player, &destroy(above))
+        );
+        assert_eq!(
+            Err(ActionError::PositionOccupied),
+            validate_action(&world, &player, &add(ground, Block::SAND))
+        );
+        // The dirt is on the item bar, but the player holds the sand
+        assert_eq!(
+            Err(ActionError::MissingBlock(Block::DIRT)),
+            validate_action(&world, &player, &add(above, Block::DIRT))
+        );
+        assert!(matches!(
+            validate_action(&world, &player, &destroy(Vector3::new(4., 0., 50.))),
+            Err(ActionError::TooFar(_))
+        ));
+
+        // The cubes are only added and destroyed on the grid
+        assert_eq!(
+            Err(ActionError::NotOnGrid),
+            validate_action(&world, &player, &add(Vector3::new(4., 1.5, 4.), Block::SAND))
+        );
+        assert_eq!(
+            Err(ActionError::NotOnGrid),
+            validate_action(&world, &player, &destroy(Vector3::new(4.2, 0., 4.)))
+        );
+        assert_eq!(
+            Err(ActionError::NotOnGrid),
+            validate_action(&world, &player, &destroy(Vector3::new(f32::NAN, 0., 4.)))
+        );
+
+        // The blocks replace the fluids, which cannot be destroyed
+        world.apply_action(&add(above, Block::WATER));
+        assert_eq!(Ok(()), validate_action(&world, &player, &add(above, Block::SAND)));
+        assert_eq!(
+            Err(ActionError::NothingToDestroy),
+            validate_action(&world, &player, &destroy(above))
+        );
+
+        // Out of the known world
+        player.pos = Position::new_vec(-4., 3., 4.);
+        assert_eq!(
+            Err(ActionError::NotLoaded),
+            validate_action(&world, &player, &destroy(Vector3::new(-4., 0., 4.)))
+        );
+
+        // Above and below the world
+        let top = CHUNK_HEIGHT as f32;
+        player.pos = Position::new_vec(4., top, 4.);
+        assert_eq!(
+            Err(ActionError::NotLoaded),
+            validate_action(&world, &player, &add(Vector3::new(4., top, 4.), Block::SAND))
+        );
+        player.pos = Position::new_vec(4., 0., 4.);
+        assert_eq!(
+            Err(ActionError::NotLoaded),
+            validate_action(&world, &player, &add(Vector3::new(4., -1., 4.), Block::SAND))
+        );
+    }
+
+    #[test]
+    fn test_attacks_are_validated() {
+        let mut player = ServerState::new().login("arthur".to_string());
+        player.pos = Position::new_vec(4., 3., 4.);
+        assert_eq!(Ok(()), validate_attack(&player, Vector3::new(6., 3., 4.)));
+        assert!(matches!(
+            validate_attack(&player, Vector3::new(4., 3., 20.)),
+            Err(ActionError::TooFar(_))
+        ));
+    }
+}
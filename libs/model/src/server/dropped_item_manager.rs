@@ -0,0 +1,188 @@
+use std::sync::{Arc, Mutex};
+
+use crate::entity::dropped_item::DroppedItem;
+use crate::entity::entity::EntityId;
+use crate::game::player_items::ItemStack;
+use crate::server::entity_ids::EntityIdAllocator;
+use crate::server::server_update::ServerUpdate;
+use crate::world::world::World;
+use primitives::position::Position;
+use primitives::vector::Vector3;
+
+/// Maximum number of items lying in the world
+const MAX_ITEMS: usize = 128;
+
+/// Holds the items lying in the world, on the server
+pub struct DroppedItemManager {
+    world: Arc<Mutex<World>>,
+    items: Vec<DroppedItem>,
+    buffer_update: Vec<ServerUpdate>,
+    entity_ids: EntityIdAllocator,
+}
+
+impl DroppedItemManager {
+    /// Creates a manager whose items get their ids from the given allocator
+    pub fn new(world: Arc<Mutex<World>>, entity_ids: EntityIdAllocator) -> Self {
+        Self {
+            world,
+            items: Vec::new(),
+            buffer_update: Vec::new(),
+            entity_ids,
+        }
+    }
+
+    /// Drops a stack at the given position (the center of the item), thrown with the given
+    /// velocity. The item can be picked up after `pickup_delay` seconds.
+    /// Returns the id of the new item.
+    pub fn drop_item(
+        &mut self,
+        stack: ItemStack,
+        position: Vector3,
+        velocity: Vector3,
+        pickup_delay: f32,
+    ) -> EntityId {
+        // When there are too many items, the oldest one disappears
+        if self.items.len() >= MAX_ITEMS {
+            let oldest = self.items.remove(0);
+            self.buffer_update.push(ServerUpdate::RemoveEntity(oldest.id()));
+        }
+
+        let id = self.entity_ids.allocate();
+        let item = DroppedItem::new(id, stack, position, velocity, pickup_delay);
+        // Inform the players that a new item lies in the world
+        self.buffer_update.push(ServerUpdate::RegisterEntity(
+            id,
+            item.entity_kind(),
+            item.position().clone(),
+        ));
+        self.items.push(item);
+        id
+    }
+
+    /// Makes the items fall, and removes the ones that were not picked up in time
+    pub fn step(&mut self, dt: f32) {
+        let world = self.world.lock().unwrap();
+        for item in self.items.iter_mut() {
+            if item.step(dt, &world) {
+                self.buffer_update.push(ServerUpdate::UpdatePosition(
+                    item.id(),
+                    item.position().clone(),
+                ));
+            }
+        }
+
+        self.items.retain(|item| {
+            if item.is_expired() {
+                self.buffer_update.push(ServerUpdate::RemoveEntity(item.id()));
+            }
+            !item.is_expired()
+        });
+    }
+
+    /// Removes the items close enough to the player, whose eyes are at the given position, and
+    /// that `collect` accepts. Returns the stacks that were picked up.
+    pub fn pick_up(
+        &mut self,
+        player: &Position,
+        mut collect: impl FnMut(ItemStack) -> bool,
+    ) -> Vec<ItemStack> {
+        let mut picked = Vec::new();
+        self.items.retain(|item| {
+            if item.can_be_picked_up_by(player) && collect(*item.stack()) {
+                picked.push(*item.stack());
+                self.buffer_update.push(ServerUpdate::RemoveEntity(item.id()));
+                false
+            } else {
+                true
+            }
+        });
+        picked
+    }
+
+    /// Returns the updates to be sent to all the players
+    pub fn take_server_updates(&mut self) -> Vec<ServerUpdate> {
+        std::mem::take(&mut self.buffer_update)
+    }
+
+    /// Return the ServerUpdate with all the items
+    /// Used to register all the items to a new player
+    pub fn get_items(&self) -> Vec<ServerUpdate> {
+        self.items
+            .iter()
+            .map(|item| {
+                ServerUpdate::RegisterEntity(
+                    item.id(),
+                    item.entity_kind(),
+                    item.position().clone(),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{DroppedItemManager, MAX_ITEMS};
+    use crate::entity::dropped_item::DESPAWN_TIME;
+    use crate::game::player_items::ItemStack;
+    use crate::server::entity_ids::EntityIdAllocator;
+    use crate::server::server_update::ServerUpdate;
+    use crate::world::block_kind::Block;
+    use crate::world::world::World;
+    use primitives::position::Position;
+    use primitives::vector::Vector3;
+
+    #[test]
+    fn test_items_are_picked_up_and_despawn() {
+        let world = Arc::new(Mutex::new(World::empty()));
+        let mut manager = DroppedItemManager::new(world, EntityIdAllocator::new());
+        let stack = ItemStack::new(Block::SAND.into());
+        let at = Vector3::new(0.5, 10.5, 0.5);
+
+        let id = manager.drop_item(stack, at, Vector3::empty(), 0.);
+        manager.drop_item(stack, Vector3::new(20., 10.5, 0.5), Vector3::empty(), 0.);
+        assert_eq!(2, manager.get_items().len());
+        assert_eq!(2, manager.take_server_updates().len());
+
+        // A player without room for the item does not pick it up
+        let player = Position::new_vec(0.5, 11.5, 0.5);
+        assert!(manager.pick_up(&player, |_| false).is_empty());
+
+        // The player only picks up the item close to them
+        assert_eq!(vec![stack], manager.pick_up(&player, |_| true));
+        assert_eq!(1, manager.get_items().len());
+        match manager.take_server_updates().as_slice() {
+            [ServerUpdate::RemoveEntity(removed)] => assert_eq!(id, *removed),
+            other => panic!("Unexpected updates: {other:?}"),
+        }
+
+        // The other one disappears after a while
+        manager.step(DESPAWN_TIME);
+        assert!(manager.get_items().is_empty());
+        assert_eq!(1, manager.take_server_updates().len());
+    }
+
+    #[test]
+    fn test_ids_are_not_reused() {
+        let world = Arc::new(Mutex::new(World::empty()));
+        let mut manager = DroppedItemManager::new(world, EntityIdAllocator::new());
+        let stack = ItemStack::new(Block::SAND.into());
+
+        let ids: Vec<u32> = (0..MAX_ITEMS)
+            .map(|_| manager.drop_item(stack, Vector3::empty(), Vector3::empty(), 0.))
+            .collect();
+        assert_eq!((0..MAX_ITEMS as u32).collect::<Vec<u32>>(), ids);
+
+        // Once there are too many items, the oldest one is replaced by an item with a new id
+        manager.take_server_updates();
+        let id = manager.drop_item(stack, Vector3::empty(), Vector3::empty(), 0.);
+        assert_eq!(MAX_ITEMS as u32, id);
+        assert_eq!(MAX_ITEMS, manager.get_items().len());
+        assert!(matches!(
+            manager.take_server_updates()[..],
+            [ServerUpdate::RemoveEntity(0), ServerUpdate::RegisterEntity(new, _, _)] if new == id
+        ));
+    }
+}
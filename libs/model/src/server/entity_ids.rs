@@ -0,0 +1,51 @@
+use crate::entity::entity::EntityId;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+/// Gives the ids of all the entities of the server: players, monsters, dropped items and falling
+/// blocks. It is shared by all the managers of entities, so that two entities never get the same
+/// id, and an id is never given twice, so that a client never mistakes a new entity for one that
+/// was removed.
+#[derive(Clone, Debug, Default)]
+pub struct EntityIdAllocator {
+    next: Arc<AtomicU32>,
+}
+
+impl EntityIdAllocator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns an id which was never given before
+    pub fn allocate(&self) -> EntityId {
+        let id = self.next.fetch_add(1, Ordering::Relaxed);
+        assert!(id < EntityId::MAX, "No entity id left");
+        id
+    }
+
+    /// Marks an id as used, when it was given in a previous session (i.e. to a player who is
+    /// restored from the storage), so that it is never given again
+    pub fn reserve(&self, id: EntityId) {
+        self.next.fetch_max(id.saturating_add(1), Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::entity_ids::EntityIdAllocator;
+
+    #[test]
+    fn test_ids_are_never_given_twice() {
+        let ids = EntityIdAllocator::new();
+        let shared = ids.clone();
+        assert_eq!(0, ids.allocate());
+        assert_eq!(1, shared.allocate());
+        assert_eq!(2, ids.allocate());
+
+        // The reserved ids are skipped, and reserving an old id changes nothing
+        ids.reserve(10);
+        assert_eq!(11, shared.allocate());
+        ids.reserve(3);
+        assert_eq!(12, ids.allocate());
+    }
+}
@@ -0,0 +1,173 @@
+use std::sync::{Arc, Mutex};
+
+use crate::entity::falling_block::FallingBlock;
+use crate::game::actions::Action;
+use crate::server::entity_ids::EntityIdAllocator;
+use crate::server::server_update::ServerUpdate;
+use crate::world::world::World;
+use primitives::vector::Vector3;
+
+/// Maximum number of blocks falling at the same time
+const MAX_FALLING_BLOCKS: usize = 28;
+
+/// Holds the blocks falling in the world, on the server
+pub struct FallingBlockManager {
+    world: Arc<Mutex<World>>,
+    blocks: Vec<FallingBlock>,
+    buffer_update: Vec<ServerUpdate>,
+    entity_ids: EntityIdAllocator,
+}
+
+impl FallingBlockManager {
+    /// Creates a manager whose blocks get their ids from the given allocator
+    pub fn new(world: Arc<Mutex<World>>, entity_ids: EntityIdAllocator) -> Self {
+        Self {
+            world,
+            blocks: Vec::new(),
+            buffer_update: Vec::new(),
+            entity_ids,
+        }
+    }
+
+    /// The cube at `at` changed: the block at this position and the one above it start falling if
+    /// nothing holds them anymore. Returns the actions that remove them from the world, which the
+    /// caller applies.
+    pub fn on_world_changed(&mut self, at: Vector3) -> Vec<Action> {
+        let world = self.world.lock().unwrap();
+        let mut actions = Vec::new();
+        for pos in [at, at + Vector3::unit_y()] {
+            let Some(block) = world.block_at(&pos).filter(|block| block.falls()) else {
+                continue;
+            };
+            let below = pos - Vector3::unit_y();
+            let is_held = world.block_at(&below).is_some_and(|block| !block.is_fluid());
+            if is_held || !world.is_loaded(&below) {
+                continue;
+            }
+            // When too many blocks are falling, the block waits for another one to land
+            if self.blocks.len() >= MAX_FALLING_BLOCKS {
+                continue;
+            }
+
+            let id = self.entity_ids.allocate();
+            let falling = FallingBlock::new(id, block, pos);
+            self.buffer_update.push(ServerUpdate::RegisterEntity(
+                id,
+                falling.entity_kind(),
+                falling.position().clone(),
+            ));
+            self.blocks.push(falling);
+            actions.push(Action::Destroy { at: pos });
+        }
+        actions
+    }
+
+    /// Makes the blocks fall. Returns the actions that put the blocks that landed back in the
+    /// world, which the caller applies.
+    pub fn step(&mut self, dt: f32) -> Vec<Action> {
+        let world = self.world.lock().unwrap();
+        let mut actions = Vec::new();
+        self.blocks.retain_mut(|falling| match falling.step(dt, &world) {
+            Some(mut at) => {
+                // The blocks of a column that land together pile up
+                while actions.iter().any(|action: &Action| action.position() == at) {
+                    at += Vector3::unit_y();
+                }
+                self.buffer_update.push(ServerUpdate::RemoveEntity(falling.id()));
+                actions.push(Action::Add {
+                    at,
+                    block: falling.block(),
+                });
+                false
+            }
+            None => {
+                self.buffer_update.push(ServerUpdate::UpdatePosition(
+                    falling.id(),
+                    falling.position().clone(),
+                ));
+                true
+            }
+        });
+        actions
+    }
+
+    /// Returns the updates to be sent to all the players
+    pub fn take_server_updates(&mut self) -> Vec<ServerUpdate> {
+        std::mem::take(&mut self.buffer_update)
+    }
+
+    /// Return the ServerUpdate with all the falling blocks
+    /// Used to register all the blocks to a new player
+    pub fn get_blocks(&self) -> Vec<ServerUpdate> {
+        self.blocks
+            .iter()
+            .map(|falling| {
+                ServerUpdate::RegisterEntity(
+                    falling.id(),
+                    falling.entity_kind(),
+                    falling.position().clone(),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::FallingBlockManager;
+    use crate::game::actions::Action;
+    use crate::server::entity_ids::EntityIdAllocator;
+    use crate::server::server_update::ServerUpdate;
+    use crate::world::block_kind::Block;
+    use crate::world::chunk::Chunk;
+    use crate::world::world::World;
+    use primitives::vector::Vector3;
+
+    #[test]
+    fn test_sand_falls_when_its_support_is_destroyed() {
+        let mut chunk = Chunk::new([0., 0.]);
+        chunk.fill_layer(0, Block::STONE);
+        chunk.fill_layer(1, Block::DIRT);
+        chunk.fill_layer(2, Block::SAND);
+        chunk.fill_layer(3, Block::SAND);
+        let world = Arc::new(Mutex::new(World::new(vec![chunk])));
+        let mut manager = FallingBlockManager::new(Arc::clone(&world), EntityIdAllocator::new());
+
+        // The sand is held by the dirt
+        let dirt = Vector3::new(2., 1., 2.);
+        assert!(manager.on_world_changed(dirt).is_empty());
+
+        // Without the dirt, the sand above starts falling
+        world.lock().unwrap().apply_action(&Action::Destroy { at: dirt });
+        let sand = dirt + Vector3::unit_y();
+        assert_eq!(vec![Action::Destroy { at: sand }], manager.on_world_changed(dirt));
+        assert!(matches!(
+            manager.take_server_updates()[..],
+            [ServerUpdate::RegisterEntity(_, _, _)]
+        ));
+
+        // Once removed from the world, it is the turn of the sand above it
+        world.lock().unwrap().apply_action(&Action::Destroy { at: sand });
+        let top = sand + Vector3::unit_y();
+        assert_eq!(vec![Action::Destroy { at: top }], manager.on_world_changed(sand));
+        world.lock().unwrap().apply_action(&Action::Destroy { at: top });
+        assert_eq!(2, manager.get_blocks().len());
+
+        // The first one lands where the dirt was, the other one on top of it
+        let mut landed = Vec::new();
+        for _ in 0..100 {
+            for action in manager.step(0.01) {
+                world.lock().unwrap().apply_action(&action);
+                landed.push(action);
+            }
+        }
+        let add = |at| Action::Add {
+            at,
+            block: Block::SAND,
+        };
+        assert_eq!(vec![add(dirt), add(sand)], landed);
+        assert!(manager.get_blocks().is_empty());
+    }
+}
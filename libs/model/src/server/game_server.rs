@@ -1,17 +1,50 @@
-use crate::entity::entity::EntityKind;
+use crate::collision::collidable::Collidable;
+use crate::entity::entity::{EntityId, EntityKind};
+use crate::entity::humanoid::humanoid_aabb;
 use primitives::position::Position;
+use crate::entity::dropped_item::{PICKUP_DELAY, THROW_PICKUP_DELAY, THROW_SPEED};
+use crate::server::action_validation::{validate_action, validate_attack, ActionRejection};
+use crate::server::dropped_item_manager::DroppedItemManager;
+use crate::server::falling_block_manager::FallingBlockManager;
 use crate::server::monster_manager::MonsterManager;
+use crate::server::monster_spawner::{MonsterSpawner, SpawningRules};
 use crate::server::server_state::ServerState;
-use crate::server::world_dispatcher::WorldDispatcher;
+use crate::server::world_dispatcher::{WorldDispatcher, DEFAULT_VIEW_DISTANCE};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use crate::game::actions::Action;
 use crate::game::attack::EntityAttack;
+use crate::game::crafting::CraftingGrid;
+use crate::game::health::{fall_damage, landing_speed};
+use crate::game::player_items::{ItemStack, PlayerItems};
 use crate::server::server_update::ServerUpdate;
-use crate::server::server_update::ServerUpdate::{Attack, LoggedIn, RegisterEntity, RemoveEntity, SendAction, UpdatePosition};
+use crate::server::server_update::ServerUpdate::{
+    Death, KnockBack, LoggedIn, PickUpItem, RegisterEntity, RejectAction, RemoveEntity, Respawn,
+    SendAction, SetItems, UpdateHealth, UpdatePosition,
+};
+use crate::world::chunk::{Chunk, CHUNK_SIZE};
+use crate::world::fluid::FluidSimulator;
+use crate::world::generation::world_generator::WorldGenerator;
 use crate::world::world::World;
-use tracing::{debug, info};
+use crate::world::world_storage::WorldStorage;
+use primitives::vector::Vector3;
+use tracing::{debug, error, info, warn};
+
+/// Angle [rad] between the directions in which the items of a dead player or monster are thrown
+const DEATH_SCATTER_ANGLE: f32 = 2.4;
+
+/// Maximum distance [cube] between the spawn point and the ground on which a player spawns
+const SPAWN_SEARCH_RADIUS: i32 = CHUNK_SIZE as i32;
+
+/// Horizontal speed [cube/s] of a player pushed away by a hit
+const KNOCK_BACK_SPEED: f32 = 8.;
+
+/// Vertical speed [cube/s] of a player pushed away by a hit
+const KNOCK_BACK_LIFT: f32 = 4.;
+
+/// Distance [cube] below the feet of a player within which they stand on the ground
+const GROUND_TOLERANCE: f32 = 0.05;
 
 /// Main function of the thread in charge of entities
 pub fn handle_entity_thread(server: Arc<Mutex<GameServer>>) {
@@ -24,15 +57,46 @@ pub fn handle_entity_thread(server: Arc<Mutex<GameServer>>) {
         t = Instant::now();
 
         if let Ok(mut server) = server.lock() {
-            let player_list = server.state.connected_players().cloned().collect();
+            // The monsters ignore the dead players
+            let player_list = server
+                .state
+                .connected_players()
+                .filter(|player| player.health.alive())
+                .cloned()
+                .collect();
             server.monster_manager.step(dt, &player_list);
+            server.spawn_monsters_naturally(dt);
             server.add_monster_updates();
+            server.step_dropped_items(dt);
+            server.regenerate_players(dt);
+            server.step_fluids(dt);
+            server.step_falling_blocks(dt);
         }
 
         std::thread::sleep(sleep_time);
     }
 }
 
+/// Main function of the thread in charge of saving the world periodically.
+/// Returns when the server shuts down.
+pub fn handle_autosave_thread(server: Arc<Mutex<GameServer>>, period: Duration) {
+    let sleep_time = Duration::from_millis(100);
+
+    let mut last_save = Instant::now();
+    loop {
+        std::thread::sleep(sleep_time);
+
+        let mut server = server.lock().unwrap();
+        if server.is_shutting_down() {
+            return;
+        }
+        if last_save.elapsed() >= period {
+            server.save_world();
+            last_save = Instant::now();
+        }
+    }
+}
+
 /// The GameServer is the model of the server
 pub struct GameServer {
     /// The full world
@@ -42,69 +106,212 @@ pub struct GameServer {
     world_dispatcher: WorldDispatcher,
 
     /// Buffer of updates to be sent to each player
-    server_updates_buffer: HashMap<usize, Vec<ServerUpdate>>,
+    server_updates_buffer: HashMap<EntityId, Vec<ServerUpdate>>,
 
     /// In charge of handling the entities
     monster_manager: MonsterManager,
 
+    /// Spawns the monsters around the players, and despawns the ones far from them
+    spawner: MonsterSpawner,
+
+    /// In charge of the items lying in the world
+    dropped_items: DroppedItemManager,
+
+    /// Makes the fluids flow
+    fluids: FluidSimulator,
+
+    /// In charge of the blocks that fall when nothing holds them
+    falling_blocks: FallingBlockManager,
+
     /// Internal state of the server (expect the entities)
     state: ServerState,
+
+    /// Highest height [cube] reached by each player in the air since they left the ground
+    fall_tops: HashMap<EntityId, f32>,
+
+    /// Generates the chunks that are requested but not yet part of the world.
+    /// If there is none, the world is limited to the chunks it was created with.
+    generator: Option<WorldGenerator>,
+
+    /// Where the chunks are persisted. Chunks found on the disk are preferred over generated ones.
+    storage: Option<WorldStorage>,
+
+    /// Set when the server is asked to stop: the connections must be closed.
+    shutting_down: bool,
+
+    /// Maximum number of chunks that a player can see in each direction
+    max_view_distance: u8,
+
+    /// When set, the players keep their items when they die instead of dropping them
+    keep_inventory: bool,
 }
 
 impl GameServer {
     pub fn new(world: World) -> Self {
         let ref_to_world = Arc::new(Mutex::new(world));
+        // All the entities get their ids from the allocator of the players
+        let state = ServerState::new();
+        let entity_ids = state.entity_ids();
         Self {
             world: Arc::clone(&ref_to_world),
             world_dispatcher: WorldDispatcher::new(),
             server_updates_buffer: HashMap::new(),
-            monster_manager: MonsterManager::new(ref_to_world),
-            state: ServerState::new(),
+            monster_manager: MonsterManager::new(Arc::clone(&ref_to_world), entity_ids.clone()),
+            spawner: MonsterSpawner::new(SpawningRules::default()),
+            dropped_items: DroppedItemManager::new(Arc::clone(&ref_to_world), entity_ids.clone()),
+            fluids: FluidSimulator::new(),
+            falling_blocks: FallingBlockManager::new(ref_to_world, entity_ids),
+            state,
+            fall_tops: HashMap::new(),
+            generator: None,
+            storage: None,
+            shutting_down: false,
+            max_view_distance: DEFAULT_VIEW_DISTANCE,
+            keep_inventory: false,
+        }
+    }
+
+    /// Creates a server whose world is extended on demand with the provided generator, as the
+    /// players explore it.
+    pub fn with_generator(world: World, generator: WorldGenerator) -> Self {
+        let mut server = Self::new(world);
+        server.generator = Some(generator);
+        server
+    }
+
+    /// Persists the world in the provided storage: the chunks are read from it before being
+    /// generated, and the modified chunks are written to it by `save_world`.
+    /// The state of the players is restored from the storage. A world that was already stored
+    /// keeps its seed and kind of terrain, which replace the ones of the generator of the server.
+    pub fn attach_storage(&mut self, storage: WorldStorage) {
+        info!("[SERVER] World stored at {:?}", storage.dir());
+        match storage.load_level() {
+            Ok(Some(level)) => {
+                if self.generator.as_ref().is_some_and(|g| g.level() != level) {
+                    warn!("[SERVER] The stored world is generated with {level:?}");
+                }
+                self.generator = Some(WorldGenerator::from_level(&level));
+            }
+            Ok(None) => {
+                // A new world is stored with the seed and the kind of terrain of its generator
+                if let Some(generator) = &self.generator {
+                    if let Err(err) = storage.save_level(&generator.level()) {
+                        error!("[SERVER] Could not save the seed of the world: {err}");
+                    }
+                }
+            }
+            Err(err) => error!("[SERVER] Could not load the seed of the world: {err}"),
+        }
+        match storage.load_server_state() {
+            Ok(Some(mut state)) => {
+                // The restored players keep their ids, which the other entities do not take
+                state.set_entity_ids(self.state.entity_ids());
+                self.state = state;
+            }
+            Ok(None) => {}
+            Err(err) => error!("[SERVER] Could not load the state of the players: {err}"),
+        }
+        self.storage = Some(storage);
+    }
+
+    /// Writes the chunks modified since the last save and the state of the players to the
+    /// storage, if there is one.
+    pub fn save_world(&mut self) {
+        let Some(storage) = &mut self.storage else {
+            return;
+        };
+        match storage.save_modified_chunks(&mut self.world.lock().unwrap()) {
+            Ok(n) => debug!("[SERVER] Saved {n} chunks"),
+            Err(err) => error!("[SERVER] Error while saving the world: {err}"),
+        }
+        if let Err(err) = storage.save_server_state(&self.state) {
+            error!("[SERVER] Error while saving the state of the players: {err}")
         }
     }
 
-    /// Logins a new player into the server
+    /// Saves the world and asks all the connections to be closed.
+    pub fn shutdown(&mut self) {
+        info!("[SERVER] Shutting down...");
+        self.save_world();
+        self.shutting_down = true;
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down
+    }
+
+    /// Caps the view distance that the players can request, to limit the bandwidth of the server
+    pub fn set_max_view_distance(&mut self, max_view_distance: u8) {
+        self.max_view_distance = max_view_distance.max(1);
+    }
+
+    /// Sets the horizontal coordinates (x, z) around which the players spawn and respawn
+    pub fn set_spawn_point(&mut self, spawn_point: [f32; 2]) {
+        self.state.set_spawn_point(spawn_point);
+    }
+
+    pub fn set_keep_inventory(&mut self, keep_inventory: bool) {
+        self.keep_inventory = keep_inventory;
+    }
+
+    /// Sets the rules of the natural spawning of the monsters
+    pub fn set_spawning_rules(&mut self, rules: SpawningRules) {
+        self.spawner = MonsterSpawner::new(rules);
+    }
+
+    /// Logins a new player into the server, who would like to see `view_distance` chunks around
+    /// them. The view distance is capped by the maximum of the server.
     /// Returns the ID of the registered player
-    pub fn login(&mut self, name: String) -> usize {
+    pub fn login(&mut self, name: String, view_distance: u8) -> EntityId {
         // Create the new ID
-        let player = self.state.login(name.clone());
+        let is_new = !self.state.is_known(&name);
+        let mut player = self.state.login(name.clone());
+        self.fall_tops.remove(&player.id);
+        if is_new {
+            // A new player starts on the ground next to the spawn point
+            player.pos = self.find_spawn_position();
+            self.state.set_player_pos(player.id, player.pos.clone());
+        }
+        let view_distance = view_distance.clamp(1, self.max_view_distance);
         info!(
-            "[SERVER] New player registered: {name} (ID={}, pos={:?})",
+            "[SERVER] New player registered: {name} (ID={}, pos={:?}, view distance={view_distance})",
             player.id, player.pos
         );
         info!("Connected players: {}", self.state.n_players_connected());
 
         // Create a new buffer of updates for this client,
-        let mut initial_updates = vec![LoggedIn(player.id as u8, player.pos.clone())];
+        let mut initial_updates = vec![LoggedIn(player.clone())];
 
         // Initialize it directly with a LoggedIn message and the position of the other players
-        for (i, connected) in self.state.connected_players().enumerate() {
+        for connected in self.state.connected_players() {
             if connected.id != player.id {
                 initial_updates.push(RegisterEntity(
-                    i as u8,
+                    connected.id,
                     EntityKind::Player,
-                    player.pos.clone(),
+                    connected.pos.clone(),
                 ))
             }
         }
 
         let monster_entry = self.monster_manager.get_monsters();
         initial_updates.append(&mut monster_entry.clone());
+        initial_updates.append(&mut self.dropped_items.get_items());
+        initial_updates.append(&mut self.falling_blocks.get_blocks());
 
         self.server_updates_buffer
             .insert(player.id, initial_updates);
 
         // Register the player in the dispatcher
-        self.world_dispatcher.register_player(player.id);
+        self.world_dispatcher.register_player(player.id, view_distance);
 
-        // Register the new player to other players of the game.
+        // Register the new player to other players of the game, unless they logged out while dead
         for other_player in self.state.connected_players() {
-            if player.id != other_player.id {
+            if player.id != other_player.id && player.health.alive() {
                 self.server_updates_buffer
                     .get_mut(&other_player.id)
                     .unwrap()
                     .push(RegisterEntity(
-                        player.id as u8,
+                        player.id,
                         EntityKind::Player,
                         player.pos.clone(),
                     ));
@@ -114,40 +321,47 @@ impl GameServer {
         player.id
     }
 
-    pub fn logout(&mut self, id: usize) {
+    pub fn logout(&mut self, id: EntityId) {
         info!("Logging out user: {id}");
         // The world dispatcher must be informed that this client loose all of its chunks
         self.state.logout(id);
+        self.fall_tops.remove(&id);
         self.world_dispatcher.logout(id);
+        self.unload_unseen_chunks();
         // Inform the other players
         for player in self.state.connected_players() {
             self.server_updates_buffer
                 .get_mut(&player.id)
                 .unwrap()
-                .push(RemoveEntity(id as u32));
+                .push(RemoveEntity(id));
         }
     }
 
     // Implementation of the 'callbacks': entry points of the server
 
     /// Called when receiving the position of a new player
-    pub fn on_new_position_update(&mut self, player_id: usize, position: Position) {
+    pub fn on_new_position_update(&mut self, player_id: EntityId, position: Position) {
         // Update the world dispatcher. to compute if the player needs to be sent new chunks
-        if let Some((chunks_to_send, _chunks_to_delete)) = self
+        if let Some((chunks_to_send, chunks_to_delete)) = self
             .world_dispatcher
             .update_position(player_id, (position.x(), position.z()))
         {
             for corner in chunks_to_send {
-                // Find the correct chunk
-                if let Some(to_send) = self.world.lock().unwrap().get_chunk(corner) {
+                // Find the correct chunk, or generate it if it does not exist yet
+                if let Some(to_send) = self.get_or_generate_chunk(corner) {
                     self.server_updates_buffer
                         .get_mut(&player_id)
                         .unwrap()
-                        .push(ServerUpdate::LoadChunk(to_send))
-                } else {
-                    // TODO generate chunk !!!
+                        .push(ServerUpdate::LoadChunk(Box::new(to_send)))
                 }
             }
+            let updates = self.server_updates_buffer.get_mut(&player_id).unwrap();
+            for corner in &chunks_to_delete {
+                updates.push(ServerUpdate::UnloadChunk(*corner));
+            }
+            if !chunks_to_delete.is_empty() {
+                self.unload_unseen_chunks();
+            }
         }
 
         // Update other players
@@ -156,70 +370,569 @@ impl GameServer {
                 self.server_updates_buffer
                     .get_mut(&player.id)
                     .unwrap()
-                    .push(UpdatePosition(player_id as u8, position.clone()))
+                    .push(UpdatePosition(player_id, position.clone()))
             }
         }
 
         // Update internal state
         self.state.set_player_pos(player_id, position.clone());
+        self.track_fall(player_id, &position);
     }
 
-    pub fn on_new_action(&mut self, player_id: usize, action: Action) {
-        // Edit the world of the server
-        self.world.lock().unwrap().apply_action(&action);
-
-        // Forward the action to all the other connected players
-        for player in self.state.connected_players() {
-            if player.id != player_id {
-                self.server_updates_buffer
-                    .get_mut(&player.id)
-                    .unwrap()
-                    .push(SendAction(action.clone()))
+    /// Follows the height of the player between their positions: a player who lands after falling
+    /// from too high is hurt, unless they fell into a fluid.
+    fn track_fall(&mut self, player_id: EntityId, position: &Position) {
+        let (on_ground, in_fluid) = {
+            let world = self.world.lock().unwrap();
+            let below = position + Vector3::new(0., -GROUND_TOLERANCE, 0.);
+            (
+                world.collides(&humanoid_aabb(&below)),
+                world.is_in_fluid(&humanoid_aabb(position)),
+            )
+        };
+        let y = position.y();
+        if in_fluid {
+            self.fall_tops.remove(&player_id);
+        } else if !on_ground {
+            let top = self.fall_tops.entry(player_id).or_insert(y);
+            *top = top.max(y);
+        } else if let Some(top) = self.fall_tops.remove(&player_id) {
+            let speed = landing_speed(top - y);
+            let damage = fall_damage(speed);
+            if damage > 0 {
+                debug!("[SERVER] Player {player_id} fell from {top} to {y}");
+                self.hurt_player(player_id, damage, None);
             }
         }
     }
 
-    pub fn on_new_attack(&mut self, attack: EntityAttack) {
-        debug!("Attacked received: {attack:?}");
-        let victim = attack.victim_id() as usize;
+    /// Called when a player edits the world. The action is applied and forwarded to the other
+    /// players only if the player is allowed to do it, otherwise the player is asked to undo it.
+    pub fn on_new_action(&mut self, player_id: EntityId, action: Action) {
+        let Some(player) = self.state.player(player_id) else {
+            return;
+        };
+        if !player.health.alive() {
+            return;
+        }
+        let mut items = player.items.clone();
+        {
+            let world = self.world.lock().unwrap();
+            if let Err(err) = validate_action(&world, player, &action) {
+                warn!("[SERVER] Rejected {action:?} of player {player_id}: {err}");
+                let at = action.position();
+                let rejection = ActionRejection {
+                    at,
+                    block: world.block_at(&at),
+                    items,
+                };
+                if let Some(updates) = self.server_updates_buffer.get_mut(&player_id) {
+                    updates.push(RejectAction(rejection));
+                }
+                return;
+            }
 
-        // Communicate the attack to victim, if the victim is a player.
-        for player in self.state.connected_players() {
-            if player.id == victim {
-                if let Some(buf) = self.server_updates_buffer.get_mut(&player.id) {
-                    buf.push(Attack(attack));
-                    return;
+            // Update the items of the player the same way the client does
+            match &action {
+                Action::Destroy { at } => {
+                    let harvested = world
+                        .block_at(at)
+                        .and_then(|block| items.on_block_destroyed(block));
+                    if let Some(block) = harvested {
+                        // The harvested block falls where the destroyed one was
+                        let center = *at + Vector3::new(0.5, 0.5, 0.5);
+                        let stack = ItemStack::new(block.into());
+                        self.dropped_items
+                            .drop_item(stack, center, Vector3::empty(), PICKUP_DELAY);
+                    }
                 }
+                Action::Add { block, .. } => items.consume((*block).into()),
             }
         }
+        self.state.set_player_items(player_id, items);
+
+        // Edit the world of the server, and forward the action to all the other connected players
+        self.apply_world_action(action, Some(player_id));
+    }
+
+    /// The player holds another slot of their item bar
+    pub fn on_select_item(&mut self, player_id: EntityId, index: usize) {
+        let Some(player) = self.state.player(player_id) else {
+            return;
+        };
+        let mut items = player.items.clone();
+        items.set_current_item(index);
+        self.state.set_player_items(player_id, items);
+    }
+
+    /// The player crafted the recipe of the grid, with ingredients taken from their items
+    pub fn on_craft(&mut self, player_id: EntityId, grid: CraftingGrid) {
+        let Some(player) = self.state.player(player_id) else {
+            return;
+        };
+        if !player.health.alive() {
+            return;
+        }
+        let mut items = player.items.clone();
+        if items.craft(&grid).is_none() {
+            warn!("[SERVER] Rejected the crafting of player {player_id}: {grid:?}");
+            self.send_items(player_id, items);
+            return;
+        }
+        self.state.set_player_items(player_id, items);
+    }
+
+    /// The player moved their items between the slots. The new arrangement is only accepted if it
+    /// holds the same items as the ones the server knows, otherwise the player gets them back.
+    pub fn on_arrange_items(&mut self, player_id: EntityId, arranged: PlayerItems) {
+        let Some(player) = self.state.player(player_id) else {
+            return;
+        };
+        if !player.items.same_items_as(&arranged) {
+            warn!("[SERVER] Player {player_id} arranged items that they do not have");
+            self.send_items(player_id, player.items.clone());
+            return;
+        }
+        self.state.set_player_items(player_id, arranged);
+    }
+
+    /// Sends the items of the player as the server knows them, to correct the ones of the client
+    fn send_items(&mut self, player_id: EntityId, items: PlayerItems) {
+        if let Some(updates) = self.server_updates_buffer.get_mut(&player_id) {
+            updates.push(SetItems(items));
+        }
+    }
+
+    /// The player throws one item of the stack they hold, in the direction they look at
+    pub fn on_throw_item(&mut self, player_id: EntityId) {
+        let Some(player) = self.state.player(player_id) else {
+            return;
+        };
+        if !player.health.alive() {
+            return;
+        }
+        let mut items = player.items.clone();
+        let Some(stack) = items.drop_current_item() else {
+            return;
+        };
+        let velocity = player.pos.direction() * THROW_SPEED;
+        self.dropped_items
+            .drop_item(stack, player.pos.pos(), velocity, THROW_PICKUP_DELAY);
+        self.state.set_player_items(player_id, items);
+    }
+
+    /// Called when a player hits an entity. The damage depends on the item that the attacker holds
+    /// according to the server, whatever the strength asked by the client. An entity out of the
+    /// reach of the attacker is not hurt, and the attacker gets back the items the server knows.
+    pub fn on_new_attack(&mut self, attacker_id: EntityId, attack: EntityAttack) {
+        debug!("Attacked received from player {attacker_id}: {attack:?}");
+        let Some(attacker) = self.state.player(attacker_id) else {
+            return;
+        };
+        if !attacker.health.alive() {
+            return;
+        }
+        let strength = attacker.items.attack_strength();
+        let from = attacker.pos.pos();
+        let victim = attack.victim_id();
+
+        let victim_position = self
+            .state
+            .connected_players()
+            .find(|player| player.id == victim)
+            .map(|player| player.pos.pos())
+            .or_else(|| {
+                let monsters = self.monster_manager.positions();
+                monsters.into_iter().find_map(|(id, pos)| (id == victim).then_some(pos))
+            });
+        let checked = match victim_position {
+            Some(position) => validate_attack(attacker, position).map_err(|err| err.to_string()),
+            None => Err(format!("entity {victim} is unknown")),
+        };
+        if let Err(err) = checked {
+            warn!("[SERVER] Rejected the attack of player {attacker_id}: {err}");
+            let items = attacker.items.clone();
+            self.send_items(attacker_id, items);
+            return;
+        }
+
+        // The weapon wears out, as on the client
+        let mut items = attacker.items.clone();
+        items.use_current_item();
+        self.state.set_player_items(attacker_id, items);
+
+        if self.state.connected_players().any(|player| player.id == victim) {
+            self.hurt_player(victim, strength, Some(from));
+            return;
+        }
 
         // If we arrive here, it means the victim is not one of the connected player.
-        // Therefore, it must be a monster.
+        // Therefore, it must be a monster: it loses health, and dies without any left.
+        let Some(health) = self.monster_manager.hurt_monster(victim, strength) else {
+            return;
+        };
+        self.broadcast_health(victim, health);
+        if health == 0 {
+            self.kill_monster(victim);
+        }
+    }
 
-        // In this case, we are attacking a monster. Let's kill the monster and forward the update to all other players.
-        self.monster_manager.remove_monster(victim);
+    /// Removes a dead monster, and scatters its loot where it died
+    fn kill_monster(&mut self, id: EntityId) {
+        let Some((position, loot)) = self.monster_manager.kill_monster(id) else {
+            return;
+        };
+        debug!("[SERVER] Monster {id} died, dropping {} stacks", loot.len());
+        for (i, stack) in loot.into_iter().enumerate() {
+            let angle = i as f32 * DEATH_SCATTER_ANGLE;
+            let velocity = Vector3::new(angle.cos(), 2., angle.sin());
+            self.dropped_items
+                .drop_item(stack, position, velocity, PICKUP_DELAY);
+        }
+        for player in self.state.connected_players() {
+            if let Some(buffer) = self.server_updates_buffer.get_mut(&player.id) {
+                buffer.push(RemoveEntity(id));
+            }
+        }
+    }
 
-        // Forward to the other players that the monster was killed.
+    /// Removes a monster from the world, and tells all the players
+    fn remove_monster(&mut self, id: EntityId) {
+        self.monster_manager.remove_monster(id);
         for player in self.state.connected_players() {
             self.server_updates_buffer
                 .get_mut(&player.id)
                 .unwrap()
-                .push(RemoveEntity(victim as u32));
+                .push(RemoveEntity(id));
+        }
+    }
+
+    /// Called when a dead player asks to come back to life. They respawn next to the spawn point.
+    pub fn on_respawn(&mut self, player_id: EntityId) {
+        if self.state.player(player_id).is_none_or(|player| player.health.alive()) {
+            warn!("[SERVER] Player {player_id} asked to respawn, but is not dead");
+            return;
+        }
+        let pos = self.find_spawn_position();
+        let Some(player) = self.state.respawn_player(player_id, pos) else {
+            return;
+        };
+        self.fall_tops.remove(&player_id);
+        info!("[SERVER] Player {player_id} respawned at {:?}", player.pos);
+
+        for other in self.state.connected_players() {
+            let update = if other.id == player_id {
+                Respawn(player.clone())
+            } else {
+                RegisterEntity(player_id, EntityKind::Player, player.pos.clone())
+            };
+            if let Some(buffer) = self.server_updates_buffer.get_mut(&other.id) {
+                buffer.push(update);
+            }
         }
     }
 
     pub fn spawn_monster(&mut self, position: Position) {
+        if self.monster_manager.is_full() {
+            warn!("[SERVER] Too many monsters, none spawns at {position:?}");
+            return;
+        }
         self.monster_manager
             .spawn_new_monster(position, EntityKind::Monster1);
     }
 
+    /// Spawns the monsters on the ground around the living players, according to the biomes, and
+    /// despawns the ones that are far from all the players
+    fn spawn_monsters_naturally(&mut self, dt: f32) {
+        let players: Vec<Vector3> = self
+            .state
+            .connected_players()
+            .filter(|player| player.health.alive())
+            .map(|player| player.pos.pos())
+            .collect();
+        let monsters = self.monster_manager.positions();
+        for id in self.spawner.monsters_to_despawn(&players, &monsters) {
+            debug!("[SERVER] Monster {id} despawns");
+            self.remove_monster(id);
+        }
+
+        let monsters: Vec<Vector3> = self
+            .monster_manager
+            .positions()
+            .into_iter()
+            .map(|(_, position)| position)
+            .collect();
+        let generator = self.generator.as_ref();
+        let biome_at = |x, z| generator.and_then(|generator| generator.biome_at(x, z));
+        let spawned = self.spawner.step(
+            dt,
+            &self.world.lock().unwrap(),
+            &players,
+            &monsters,
+            biome_at,
+        );
+        for (position, kind) in spawned {
+            if self.monster_manager.is_full() {
+                return;
+            }
+            debug!("[SERVER] A monster {kind:?} spawns at {position:?}");
+            self.monster_manager.spawn_new_monster(position, kind);
+        }
+    }
+
     /// Returns the list of updates that the server sends to the client.
-    pub fn consume_updates(&mut self, player_id: usize) -> Vec<ServerUpdate> {
+    pub fn consume_updates(&mut self, player_id: EntityId) -> Vec<ServerUpdate> {
         self.server_updates_buffer
             .insert(player_id, Vec::new())
             .unwrap()
     }
 
+    /// Returns the chunk at the given corner. If the world does not have it yet, it is loaded from
+    /// the storage or, if it was never saved, created by the generator. It is then added to the world.
+    fn get_or_generate_chunk(&mut self, corner: (i32, i32)) -> Option<Chunk> {
+        let mut world = self.world.lock().unwrap();
+        if let Some(chunk) = world.get_chunk(corner) {
+            return Some(chunk);
+        }
+
+        if let Some(storage) = &mut self.storage {
+            match storage.load_chunk(corner) {
+                Ok(Some(chunk)) => {
+                    world.add_chunk(chunk.clone());
+                    return Some(chunk);
+                }
+                Ok(None) => {}
+                Err(err) => error!("[SERVER] Could not load the chunk at {corner:?}: {err}"),
+            }
+        }
+
+        let generator = self.generator.as_mut()?;
+        debug!("Generating chunk at {corner:?}");
+        world.add_generated_chunk(generator.generate_chunk(corner));
+        world.get_chunk(corner)
+    }
+
+    /// Saves and forgets the chunks that no player sees anymore, so that the server only keeps the
+    /// chunks around the players in memory. Without a storage, the chunks edited by the players
+    /// are kept, since they could not be generated again.
+    fn unload_unseen_chunks(&mut self) {
+        let seen = self.world_dispatcher.seen_chunks();
+        let mut world = self.world.lock().unwrap();
+        let unseen: Vec<(i32, i32)> = world
+            .chunks()
+            .map(Chunk::integer_corner)
+            .filter(|corner| !seen.contains(corner))
+            .filter(|corner| self.storage.is_some() || !world.is_edited(*corner))
+            .collect();
+
+        if let Some(storage) = &mut self.storage {
+            let modified: Vec<Chunk> = unseen
+                .iter()
+                .filter(|corner| world.is_modified(**corner))
+                .filter_map(|corner| world.get_chunk(*corner))
+                .collect();
+            if let Err(err) = storage.save_chunks(&modified) {
+                error!("[SERVER] Could not save the chunks out of sight: {err}");
+                return;
+            }
+        }
+        for corner in &unseen {
+            world.remove_chunk(*corner);
+        }
+        if !unseen.is_empty() {
+            debug!("[SERVER] Unloaded {} chunks out of sight", unseen.len());
+        }
+    }
+
+    /// Returns where a player spawns: on the ground close to the spawn point or, if there is none,
+    /// high in the sky above it.
+    fn find_spawn_position(&mut self) -> Position {
+        let [x, z] = self.state.spawn_point();
+        let r = SPAWN_SEARCH_RADIUS;
+
+        // The chunks around the spawn point must be part of the world
+        for i in [-r, 0, r] {
+            for j in [-r, 0, r] {
+                let at = Vector3::new(x + i as f32, 0., z + j as f32);
+                self.get_or_generate_chunk(Chunk::corner_of(&at));
+            }
+        }
+
+        let ground = self.world.lock().unwrap().safe_spawn_position(x, z, r);
+        ground.unwrap_or_else(|| self.state.sky_spawn_position())
+    }
+
+    /// Hurts a connected player, who is pushed away from the attacker at `from` if there is one.
+    /// The player dies when they have no health left.
+    fn hurt_player(&mut self, player_id: EntityId, strength: u8, from: Option<Vector3>) {
+        let is_connected = self.state.connected_players().any(|p| p.id == player_id);
+        if !is_connected || !self.state.damage_player(player_id, strength) {
+            return;
+        }
+        let Some(player) = self.state.player(player_id) else {
+            return;
+        };
+        let (pos, health) = (player.pos.pos(), player.health.health());
+
+        if let Some(from) = from {
+            let mut direction = Vector3::new(pos.x() - from.x(), 0., pos.z() - from.z());
+            if direction.norm() > 1e-3 {
+                direction.normalize();
+            }
+            let velocity = direction * KNOCK_BACK_SPEED + Vector3::new(0., KNOCK_BACK_LIFT, 0.);
+            if let Some(buffer) = self.server_updates_buffer.get_mut(&player_id) {
+                buffer.push(KnockBack(velocity));
+            }
+        }
+
+        self.broadcast_health(player_id, health);
+        if health == 0 {
+            self.on_player_death(player_id);
+        }
+    }
+
+    /// The players who were not hurt for a while regain health
+    fn regenerate_players(&mut self, dt: f32) {
+        for id in self.state.regenerate_players(dt) {
+            if let Some(player) = self.state.player(id) {
+                self.broadcast_health(id, player.health.health());
+            }
+        }
+    }
+
+    /// Informs all the players of the new health of a player or a monster
+    fn broadcast_health(&mut self, entity_id: EntityId, health: u8) {
+        for player in self.state.connected_players() {
+            if let Some(buffer) = self.server_updates_buffer.get_mut(&player.id) {
+                buffer.push(UpdateHealth(entity_id, health));
+            }
+        }
+    }
+
+    /// Unless the server keeps the inventories, the items of the dead player are scattered where
+    /// they died. The other players stop seeing them until they respawn.
+    fn on_player_death(&mut self, player_id: EntityId) {
+        info!("[SERVER] Player {player_id} died");
+        if !self.keep_inventory {
+            self.drop_all_items(player_id);
+        }
+        let Some(player) = self.state.player(player_id) else {
+            return;
+        };
+
+        for other in self.state.connected_players() {
+            let update = if other.id == player_id {
+                Death(player.items.clone())
+            } else {
+                RemoveEntity(player_id)
+            };
+            if let Some(buffer) = self.server_updates_buffer.get_mut(&other.id) {
+                buffer.push(update);
+            }
+        }
+    }
+
+    /// Scatters all the items of the player around them, when they die
+    fn drop_all_items(&mut self, player_id: EntityId) {
+        let Some(player) = self.state.player(player_id) else {
+            return;
+        };
+        let mut items = player.items.clone();
+        let stacks = items.take_all();
+        info!("[SERVER] Player {player_id} dropped {} stacks", stacks.len());
+        for (i, stack) in stacks.into_iter().enumerate() {
+            let angle = i as f32 * DEATH_SCATTER_ANGLE;
+            let velocity = Vector3::new(angle.cos(), 2., angle.sin());
+            self.dropped_items
+                .drop_item(stack, player.pos.pos(), velocity, PICKUP_DELAY);
+        }
+        self.state.set_player_items(player_id, items);
+    }
+
+    /// Moves the dropped items, gives them to the players walking over them, and informs all the
+    /// players
+    fn step_dropped_items(&mut self, dt: f32) {
+        self.dropped_items.step(dt);
+
+        let ids: Vec<EntityId> = self.state.connected_players().map(|p| p.id).collect();
+        for id in ids {
+            let Some(player) = self.state.player(id) else {
+                continue;
+            };
+            // A dead player does not pick up anything
+            if !player.health.alive() {
+                continue;
+            }
+            let mut items = player.items.clone();
+            let picked = self
+                .dropped_items
+                .pick_up(&player.pos, |stack| items.collect_stack(stack));
+            if picked.is_empty() {
+                continue;
+            }
+            self.state.set_player_items(id, items);
+            if let Some(buffer) = self.server_updates_buffer.get_mut(&id) {
+                buffer.extend(picked.into_iter().map(PickUpItem));
+            }
+        }
+
+        let item_updates = self.dropped_items.take_server_updates();
+        self.server_updates_buffer
+            .iter_mut()
+            .for_each(|(_, buffer)| buffer.append(&mut item_updates.clone()));
+    }
+
+    /// Edits the world of the server, and sends the action to all the players except its author,
+    /// who already did it. The world reacts to the change: the fluids flow around it, and the
+    /// blocks that nothing holds anymore start falling, which is sent to all the players.
+    fn apply_world_action(&mut self, action: Action, author: Option<EntityId>) {
+        let mut author = author;
+        let mut to_apply = vec![action];
+        while let Some(action) = to_apply.pop() {
+            self.world.lock().unwrap().apply_action(&action);
+            self.fluids.wake(action.position());
+            to_apply.append(&mut self.falling_blocks.on_world_changed(action.position()));
+
+            for player in self.state.connected_players() {
+                if Some(player.id) == author {
+                    continue;
+                }
+                if let Some(buffer) = self.server_updates_buffer.get_mut(&player.id) {
+                    buffer.push(SendAction(action.clone()));
+                }
+            }
+            // The consequences of the action are sent to the author as well
+            author = None;
+        }
+        self.add_falling_block_updates();
+    }
+
+    /// Makes the fluids flow
+    fn step_fluids(&mut self, dt: f32) {
+        let actions = self.fluids.step(dt, &self.world.lock().unwrap());
+        for action in actions {
+            self.apply_world_action(action, None);
+        }
+    }
+
+    /// Makes the blocks fall, and puts the ones that landed back in the world
+    fn step_falling_blocks(&mut self, dt: f32) {
+        for action in self.falling_blocks.step(dt) {
+            self.apply_world_action(action, None);
+        }
+        self.add_falling_block_updates();
+    }
+
+    fn add_falling_block_updates(&mut self) {
+        let updates = self.falling_blocks.take_server_updates();
+        if updates.is_empty() {
+            return;
+        }
+        self.server_updates_buffer
+            .iter_mut()
+            .for_each(|(_, buffer)| buffer.append(&mut updates.clone()));
+    }
+
     fn add_monster_updates(&mut self) {
         // Add to these updates the ones that the entity manager also provides
         let monster_updates = self.monster_manager.take_server_updates();
@@ -227,18 +940,45 @@ impl GameServer {
             .iter_mut()
             .for_each(|(_, buffer)| buffer.append(&mut monster_updates.clone()));
 
-        for attack in self.monster_manager.take_attack_buffer() {
-            self.on_new_attack(attack)
+        for (attack, monster) in self.monster_manager.take_attack_buffer() {
+            let victim = attack.victim_id();
+            self.hurt_player(victim, attack.strength(), Some(monster.pos()));
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::entity::entity::{EntityId, EntityKind};
+    use crate::entity::monster_registry::{Loot, MonsterRegistry};
     use crate::game::attack::EntityAttack;
+    use crate::game::health::{
+        fall_damage, landing_speed, INVULNERABILITY_TIME, MAX_HEALTH, REGENERATION_PERIOD,
+    };
+    use crate::game::item::Item;
+    use crate::game::player_items::{ItemStack, PlayerItems};
     use crate::server::game_server::GameServer;
+    use crate::server::monster_manager::MonsterManager;
+    use crate::server::monster_spawner::SpawningRules;
     use crate::server::server_update::ServerUpdate;
+    use crate::server::world_dispatcher::DEFAULT_VIEW_DISTANCE;
+    use crate::world::generation::world_generator::WorldGenerator;
+    use crate::game::actions::Action;
+    use crate::world::block_kind::Block;
+    use crate::world::fluid::FLUID_TICK;
     use crate::world::world::World;
+    use crate::world::world_storage::WorldStorage;
+    use primitives::position::Position;
+    use primitives::vector::Vector3;
+    use std::sync::Arc;
+
+    /// The player falls from `height` cubes above the place where they stand
+    fn fall(server: &mut GameServer, id: EntityId, height: f32) {
+        let pos = server.state.player(id).unwrap().pos.clone();
+        for y in [pos.y() + height, pos.y()] {
+            server.on_new_position_update(id, Position::new_vec(pos.x(), y, pos.z()));
+        }
+    }
 
     #[test]
     fn test_two_clients_connecting() {
@@ -246,18 +986,18 @@ mod tests {
         let mut server = GameServer::new(World::empty());
 
         // first client logins
-        let id1 = server.login("arthur".to_string());
+        let id1 = server.login("arthur".to_string(), DEFAULT_VIEW_DISTANCE);
 
         // We expect 1 update: the login message
         let updates = server.consume_updates(id1);
         assert_eq!(1, updates.len());
-        assert!(matches!(updates[0], ServerUpdate::LoggedIn(_, _)));
+        assert!(matches!(updates[0], ServerUpdate::LoggedIn(_)));
 
         // Once the update has been consumed, there is nothing anymore to be sent
         assert_eq!(0, server.consume_updates(id1).len());
 
         // Second client logins
-        let id2 = server.login("johan".to_string());
+        let id2 = server.login("johan".to_string(), DEFAULT_VIEW_DISTANCE);
 
         // We expect 1 new update for the first player: the register message
         let updates = server.consume_updates(id1);
@@ -267,18 +1007,388 @@ mod tests {
         // The second player must have 2 messages: LoggedIn and Register
         let updates = server.consume_updates(id2);
         assert_eq!(2, updates.len());
-        assert!(matches!(updates[0], ServerUpdate::LoggedIn(_, _)));
+        assert!(matches!(updates[0], ServerUpdate::LoggedIn(_)));
         assert!(matches!(updates[1], ServerUpdate::RegisterEntity(_, _, _)));
     }
 
+    #[test]
+    fn test_entity_ids_are_unique() {
+        let mut server = GameServer::with_generator(World::empty(), WorldGenerator::flat());
+        let id1 = server.login("arthur".to_string(), DEFAULT_VIEW_DISTANCE);
+        let pos1 = Position::new_vec(3., 11., 1.);
+        server.on_new_position_update(id1, pos1.clone());
+
+        // The monsters and the items do not take the ids of the players, even the new ones
+        server.spawn_monster(Position::new_vec(1., 11., 1.));
+        let stack = ItemStack::new(Block::STONE.into());
+        server
+            .dropped_items
+            .drop_item(stack, Vector3::new(1., 11., 1.), Vector3::empty(), 0.);
+        let id2 = server.login("johan".to_string(), DEFAULT_VIEW_DISTANCE);
+        let (monster, _) = server.monster_manager.positions()[0];
+        let mut ids = vec![id1, id2, monster];
+        ids.extend(server.dropped_items.get_items().iter().map(|update| match update {
+            ServerUpdate::RegisterEntity(id, _, _) => *id,
+            other => panic!("Unexpected update: {other:?}"),
+        }));
+        ids.sort();
+        ids.dedup();
+        assert_eq!(4, ids.len());
+
+        // The new player sees arthur with his own id, where he stands
+        let registered = server
+            .consume_updates(id2)
+            .into_iter()
+            .find_map(|update| match update {
+                ServerUpdate::RegisterEntity(id, EntityKind::Player, pos) => Some((id, pos)),
+                _ => None,
+            });
+        assert_eq!(Some((id1, pos1)), registered);
+    }
+
+    #[test]
+    fn test_players_with_large_ids() {
+        let mut server = GameServer::with_generator(World::empty(), WorldGenerator::flat());
+
+        // Many entities came and went before the players logged in
+        let entity_ids = server.state.entity_ids();
+        for _ in 0..300 {
+            entity_ids.allocate();
+        }
+        let id1 = server.login("arthur".to_string(), DEFAULT_VIEW_DISTANCE);
+        let id2 = server.login("johan".to_string(), DEFAULT_VIEW_DISTANCE);
+        assert!(id1 > 255 && id2 > 255);
+
+        let pos = Position::new_vec(3., 11., 1.);
+        server.on_new_position_update(id2, pos.clone());
+        let moved = server.consume_updates(id1).into_iter().any(|update| {
+            matches!(update, ServerUpdate::UpdatePosition(id, p) if id == id2 && p == pos)
+        });
+        assert!(moved);
+        assert!(!server.consume_updates(id2).is_empty());
+    }
+
+    #[test]
+    fn test_chunks_are_generated_on_demand() {
+        let mut server = GameServer::with_generator(World::empty(), WorldGenerator::flat());
+        let id = server.login("arthur".to_string(), DEFAULT_VIEW_DISTANCE);
+        server.consume_updates(id);
+
+        // Walking far away from the origin must still provide chunks
+        server.on_new_position_update(id, Position::new_vec(1000., 50., -1000.));
+        let updates = server.consume_updates(id);
+        assert!(!updates.is_empty());
+        assert!(updates
+            .iter()
+            .all(|update| matches!(update, ServerUpdate::LoadChunk(_))));
+    }
+
+    #[test]
+    fn test_chunks_out_of_sight_are_unloaded() {
+        let mut server = GameServer::with_generator(World::empty(), WorldGenerator::flat());
+        let id = server.login("arthur".to_string(), DEFAULT_VIEW_DISTANCE);
+        server.consume_updates(id);
+        server.on_new_position_update(id, Position::new_vec(0., 50., 0.));
+        let loaded = server.consume_updates(id).len();
+
+        // When going back to the origin, all the chunks that were sent far away are unloaded
+        server.on_new_position_update(id, Position::new_vec(1000., 50., -1000.));
+        server.consume_updates(id);
+        server.on_new_position_update(id, Position::new_vec(0., 50., 0.));
+        let updates = server.consume_updates(id);
+        let count = |f: fn(&ServerUpdate) -> bool| updates.iter().filter(|u| f(u)).count();
+        assert!(loaded > 0);
+        assert_eq!(loaded, count(|u| matches!(u, ServerUpdate::LoadChunk(_))));
+        assert_eq!(loaded, count(|u| matches!(u, ServerUpdate::UnloadChunk(_))));
+    }
+
+    #[test]
+    fn test_server_forgets_the_chunks_out_of_sight() {
+        let dir = std::env::temp_dir().join(format!("crafty_forget_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for with_storage in [true, false] {
+            let mut server = GameServer::with_generator(World::empty(), WorldGenerator::flat());
+            if with_storage {
+                server.attach_storage(WorldStorage::new(&dir).unwrap());
+            }
+            let id = server.login("arthur".to_string(), DEFAULT_VIEW_DISTANCE);
+            server.on_new_position_update(id, Position::new_vec(1., 11., 1.));
+            let at = Vector3::new(1., 9., 1.);
+            server.on_new_action(id, Action::Destroy { at });
+            let world = Arc::clone(&server.world);
+            let n_chunks = || world.lock().unwrap().chunks().count();
+            let seen = n_chunks();
+
+            // Far away, the server only keeps the chunks that the player sees
+            server.on_new_position_update(id, Position::new_vec(1000., 11., -1000.));
+            let edited = world.lock().unwrap().get_chunk((0, 0));
+            if with_storage {
+                assert_eq!(seen, n_chunks());
+                assert!(edited.is_none());
+            } else {
+                // Without a storage, the edited chunk cannot be forgotten
+                assert_eq!(seen + 1, n_chunks());
+                assert!(edited.is_some());
+            }
+
+            // Coming back, the edited chunk is found again
+            server.on_new_position_update(id, Position::new_vec(1., 11., 1.));
+            let chunk = server.get_or_generate_chunk((0, 0)).unwrap();
+            assert!(chunk.cube_at(&at).is_none());
+            assert_eq!(seen, n_chunks());
+
+            // Nobody sees any chunk when the player leaves
+            server.logout(id);
+            assert_eq!(usize::from(!with_storage), n_chunks());
+        }
+    }
+
+    #[test]
+    fn test_view_distance_is_capped_by_the_server() {
+        let mut server = GameServer::with_generator(World::empty(), WorldGenerator::flat());
+        server.set_max_view_distance(2);
+        let id1 = server.login("arthur".to_string(), 100);
+        let id2 = server.login("johan".to_string(), 1);
+        server.consume_updates(id1);
+        server.consume_updates(id2);
+
+        server.on_new_position_update(id1, Position::new_vec(0., 50., 0.));
+        server.on_new_position_update(id2, Position::new_vec(0., 50., 0.));
+        let chunks = |updates: Vec<ServerUpdate>| {
+            updates
+                .iter()
+                .filter(|update| matches!(update, ServerUpdate::LoadChunk(_)))
+                .count()
+        };
+        assert_eq!(16, chunks(server.consume_updates(id1)));
+        assert_eq!(4, chunks(server.consume_updates(id2)));
+    }
+
+    #[test]
+    fn test_world_survives_a_restart() {
+        let dir = std::env::temp_dir().join(format!("crafty_restart_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let start_server = || {
+            let mut server = GameServer::with_generator(World::empty(), WorldGenerator::flat());
+            server.attach_storage(WorldStorage::new(&dir).unwrap());
+            server
+        };
+
+        // A player destroys a cube, and the server is stopped
+        let mut server = start_server();
+        let id = server.login("arthur".to_string(), DEFAULT_VIEW_DISTANCE);
+        server.on_new_position_update(id, Position::new_vec(1., 11., 1.));
+        let at = Vector3::new(1., 9., 1.);
+        server.on_new_action(id, Action::Destroy { at });
+        server.shutdown();
+        assert!(server.is_shutting_down());
+
+        // The cube is still destroyed after the restart, and the player is known
+        let mut server = start_server();
+        assert_eq!(id, server.login("arthur".to_string(), DEFAULT_VIEW_DISTANCE));
+        let corner = (0, 0);
+        let chunk = server.get_or_generate_chunk(corner).unwrap();
+        assert!(chunk.cube_at(&at).is_none());
+        assert!(chunk.cube_at(&Vector3::new(2., 9., 1.)).is_some());
+
+        // The world stays flat, even if the server is started with another generator
+        let mut server = GameServer::with_generator(World::empty(), WorldGenerator::random(3));
+        server.attach_storage(WorldStorage::new(&dir).unwrap());
+        let level = server.generator.as_ref().map(WorldGenerator::level);
+        assert_eq!(Some(WorldGenerator::flat().level()), level);
+    }
+
+    #[test]
+    fn test_actions_are_validated_by_the_server() {
+        let mut server = GameServer::with_generator(World::empty(), WorldGenerator::flat());
+        let id1 = server.login("arthur".to_string(), DEFAULT_VIEW_DISTANCE);
+        let id2 = server.login("johan".to_string(), DEFAULT_VIEW_DISTANCE);
+        server.on_new_position_update(id1, Position::new_vec(1., 11., 1.));
+        server.consume_updates(id1);
+        server.consume_updates(id2);
+        let cobblestones = |server: &GameServer| {
+            let items = &server.state.player(id1).unwrap().items;
+            items
+                .get_bar_items()
+                .iter()
+                .filter(|stack| stack.item == Block::COBBELSTONE.into())
+                .map(|stack| stack.count)
+                .sum::<usize>()
+        };
+        let before = cobblestones(&server);
+
+        // The player holds their sword: they cannot place a cube, even if they have some
+        let at = Vector3::new(2., 10., 1.);
+        server.on_new_action(id1, Action::Add { at, block: Block::COBBELSTONE });
+        assert!(server.consume_updates(id2).is_empty());
+        assert!(matches!(server.consume_updates(id1)[..], [ServerUpdate::RejectAction(_)]));
+        assert_eq!(before, cobblestones(&server));
+
+        // A cube placed next to the player is forwarded, and taken from the items of the player
+        server.on_select_item(id1, 1);
+        server.on_new_action(id1, Action::Add { at, block: Block::COBBELSTONE });
+        assert!(server.consume_updates(id1).is_empty());
+        assert!(matches!(server.consume_updates(id2)[..], [ServerUpdate::SendAction(_)]));
+        assert_eq!(before - 1, cobblestones(&server));
+
+        // A cube too far away is refused
+        let far = Vector3::new(1., 9., 30.);
+        server.on_new_action(id1, Action::Destroy { at: far });
+        assert!(server.consume_updates(id2).is_empty());
+        match &server.consume_updates(id1)[..] {
+            [ServerUpdate::RejectAction(rejection)] => {
+                assert_eq!(far, rejection.at);
+                assert!(rejection.block.is_some());
+                assert_eq!(server.state.player(id1).unwrap().items, rejection.items);
+            }
+            other => panic!("Unexpected updates: {other:?}"),
+        }
+        let chunk = server.get_or_generate_chunk((0, 24)).unwrap();
+        assert!(chunk.cube_at(&far).is_some());
+    }
+
+    #[test]
+    fn test_items_are_kept_by_the_server() {
+        let mut server = GameServer::with_generator(World::empty(), WorldGenerator::flat());
+        let id = server.login("arthur".to_string(), DEFAULT_VIEW_DISTANCE);
+        let victim = server.login("johan".to_string(), DEFAULT_VIEW_DISTANCE);
+        server.consume_updates(id);
+        let items = |server: &GameServer| server.state.player(id).unwrap().items.clone();
+        let sword = items(&server).get_current_stack().unwrap();
+
+        // The weapon wears out when attacking
+        server.on_new_attack(id, EntityAttack::new(victim));
+        server.consume_updates(id);
+        let worn = items(&server).get_current_stack().unwrap();
+        assert_eq!(sword.durability.map(|durability| durability - 1), worn.durability);
+
+        // The server crafts the recipes with the items it knows
+        let log = Some(Item::Block(Block::OAKLOG));
+        let cobble = Some(Item::Block(Block::COBBELSTONE));
+        server.on_craft(id, [[log, None, None], [cobble, None, None], [cobble, None, None]]);
+        assert!(server.consume_updates(id).is_empty());
+        let swords = items(&server)
+            .get_bar_items()
+            .iter()
+            .filter(|stack| stack.item == sword.item)
+            .count();
+        assert_eq!(2, swords);
+        let dirt = Some(Item::Block(Block::DIRT));
+        let leaves = Some(Item::Block(Block::OAKLEAVES));
+        server.on_craft(id, [[dirt, leaves, None], [None; 3], [None; 3]]);
+        assert!(matches!(server.consume_updates(id)[..], [ServerUpdate::SetItems(_)]));
+
+        // The items can be moved between the slots, but not created
+        let mut arranged = items(&server);
+        let stack = arranged.take_bar_item(0).unwrap();
+        assert!(arranged.put_inventory_item(4, stack));
+        server.on_arrange_items(id, arranged.clone());
+        assert!(server.consume_updates(id).is_empty());
+        assert_eq!(arranged, items(&server));
+        let mut cheated = arranged.clone();
+        cheated.collect(Block::DIRT.into());
+        server.on_arrange_items(id, cheated);
+        match &server.consume_updates(id)[..] {
+            [ServerUpdate::SetItems(sent)] => assert_eq!(arranged, *sent),
+            other => panic!("Unexpected updates: {other:?}"),
+        }
+        assert_eq!(arranged, items(&server));
+    }
+
+    #[test]
+    fn test_player_dies_and_respawns() {
+        let mut server = GameServer::with_generator(World::empty(), WorldGenerator::flat());
+        server.set_spawn_point([30., 20.]);
+        let id1 = server.login("arthur".to_string(), DEFAULT_VIEW_DISTANCE);
+        let id2 = server.login("johan".to_string(), DEFAULT_VIEW_DISTANCE);
+        server.consume_updates(id1);
+        server.consume_updates(id2);
+
+        // New players start on the ground, next to the spawn point
+        let pos = server.state.player(id1).unwrap().pos.clone();
+        assert_eq!((30.5, 20.5), (pos.x(), pos.z()));
+        assert!((pos.y() - 11.7).abs() < 1e-3);
+
+        // arthur falls from very high: his items are dropped, and johan does not see him anymore
+        fall(&mut server, id1, 100.);
+        match &server.consume_updates(id1)[..] {
+            [.., ServerUpdate::UpdateHealth(_, 0), ServerUpdate::Death(items)] => {
+                assert!(items.get_bar_items().is_empty())
+            }
+            other => panic!("Unexpected updates: {other:?}"),
+        }
+        assert!(matches!(
+            server.consume_updates(id2)[..],
+            [.., ServerUpdate::UpdateHealth(_, 0), ServerUpdate::RemoveEntity(_)]
+        ));
+        assert!(!server.dropped_items.get_items().is_empty());
+
+        // A dead player cannot be attacked anymore
+        server.on_new_attack(id2, EntityAttack::new(id1));
+        assert!(server.consume_updates(id1).is_empty());
+
+        // He comes back to life at the spawn point
+        server.on_respawn(id1);
+        match &server.consume_updates(id1)[..] {
+            [ServerUpdate::Respawn(state)] => {
+                assert_eq!(MAX_HEALTH, state.health.health());
+                assert_eq!(pos, state.pos);
+            }
+            other => panic!("Unexpected updates: {other:?}"),
+        }
+        assert!(matches!(server.consume_updates(id2)[..], [ServerUpdate::RegisterEntity(..)]));
+
+        // A living player cannot respawn
+        server.on_respawn(id1);
+        assert!(server.consume_updates(id1).is_empty());
+    }
+
+    #[test]
+    fn test_dead_players_do_nothing() {
+        let mut server = GameServer::with_generator(World::empty(), WorldGenerator::flat());
+        server.set_keep_inventory(true);
+        let id = server.login("arthur".to_string(), DEFAULT_VIEW_DISTANCE);
+        fall(&mut server, id, 100.);
+        server.consume_updates(id);
+        let items = server.state.player(id).unwrap().items.clone();
+
+        // On the death screen, the player cannot build, throw items nor craft
+        let at = Vector3::new(1., 9., 0.);
+        server.on_new_action(id, Action::Destroy { at });
+        assert!(server.world.lock().unwrap().block_at(&at).is_some());
+        server.on_throw_item(id);
+        assert!(server.dropped_items.get_items().is_empty());
+        let log = Some(Item::Block(Block::OAKLOG));
+        server.on_craft(id, [[log, None, None], [None; 3], [None; 3]]);
+        assert_eq!(items, server.state.player(id).unwrap().items);
+        assert!(server.consume_updates(id).is_empty());
+    }
+
+    #[test]
+    fn test_items_are_kept_on_death() {
+        let mut server = GameServer::with_generator(World::empty(), WorldGenerator::flat());
+        server.set_keep_inventory(true);
+        let id = server.login("arthur".to_string(), DEFAULT_VIEW_DISTANCE);
+        server.consume_updates(id);
+
+        fall(&mut server, id, 100.);
+        match &server.consume_updates(id)[..] {
+            [.., ServerUpdate::UpdateHealth(..), ServerUpdate::Death(items)] => {
+                assert_eq!(PlayerItems::starting_items(), *items)
+            }
+            other => panic!("Unexpected updates: {other:?}"),
+        }
+        assert!(server.dropped_items.get_items().is_empty());
+    }
+
     #[test]
     fn test_attack_broacasting() {
         // Create a server with an empty world
         let mut server = GameServer::new(World::empty());
 
-        let id1 = server.login("arthur".to_string());
-        let id2 = server.login("johan".to_string());
-        let id3 = server.login("arnaud".to_string());
+        let id1 = server.login("arthur".to_string(), DEFAULT_VIEW_DISTANCE);
+        let id2 = server.login("johan".to_string(), DEFAULT_VIEW_DISTANCE);
+        let id3 = server.login("arnaud".to_string(), DEFAULT_VIEW_DISTANCE);
 
         // consumes all updates
         server.consume_updates(id1);
@@ -286,11 +1396,251 @@ mod tests {
         server.consume_updates(id3);
 
         // johan attacks arnaud
-        server.on_new_attack(EntityAttack::new(id3 as u8));
+        server.on_new_attack(id2, EntityAttack::new(id3));
 
-        // only arnaud is supposed to receive a message
-        assert_eq!(0, server.consume_updates(id1).len());
-        assert_eq!(0, server.consume_updates(id2).len());
-        assert_eq!(1, server.consume_updates(id3).len());
+        // only arnaud is knocked back, and everybody knows his new health
+        assert_eq!(1, server.consume_updates(id1).len());
+        assert_eq!(1, server.consume_updates(id2).len());
+        assert_eq!(2, server.consume_updates(id3).len());
+    }
+
+    #[test]
+    fn test_server_owns_the_health() {
+        let mut server = GameServer::with_generator(World::empty(), WorldGenerator::flat());
+        let id1 = server.login("arthur".to_string(), DEFAULT_VIEW_DISTANCE);
+        let id2 = server.login("johan".to_string(), DEFAULT_VIEW_DISTANCE);
+        server.on_new_position_update(id1, Position::new_vec(0.5, 11.7, 0.5));
+        server.on_new_position_update(id2, Position::new_vec(2.5, 11.7, 0.5));
+        server.consume_updates(id1);
+        server.consume_updates(id2);
+        let health = |server: &GameServer| server.state.player(id2).unwrap().health.health();
+
+        // The damage depends on the sword held by arthur, not on the strength sent by his client
+        let mut attack = EntityAttack::new(id2);
+        attack.set_strength(100);
+        server.on_new_attack(id1, attack.clone());
+        let expected = MAX_HEALTH - PlayerItems::starting_items().attack_strength();
+        assert_eq!(expected, health(&server));
+        match &server.consume_updates(id2)[..] {
+            [ServerUpdate::KnockBack(velocity), ServerUpdate::UpdateHealth(id, points)] => {
+                // johan is pushed away from arthur
+                assert!(velocity.x() > 0. && velocity.y() > 0.);
+                assert_eq!((id2, expected), (*id, *points));
+            }
+            other => panic!("Unexpected updates: {other:?}"),
+        }
+
+        server.consume_updates(id1);
+
+        // Right after being hit, johan cannot be hurt
+        server.on_new_attack(id1, attack.clone());
+        fall(&mut server, id2, 10.);
+        assert_eq!(expected, health(&server));
+        assert!(server.consume_updates(id2).is_empty());
+
+        // A small fall does not hurt, a long one does
+        server.regenerate_players(INVULNERABILITY_TIME);
+        fall(&mut server, id2, 3.);
+        assert_eq!(expected, health(&server));
+        fall(&mut server, id2, 6.);
+        let expected = expected - fall_damage(landing_speed(6.));
+        assert_eq!(expected, health(&server));
+        server.consume_updates(id1);
+        server.consume_updates(id2);
+
+        // Falling into the water does not hurt
+        server.regenerate_players(INVULNERABILITY_TIME);
+        for y in [10., 11.] {
+            let water = Action::Add { at: Vector3::new(2., y, 0.), block: Block::WATER };
+            server.world.lock().unwrap().apply_action(&water);
+        }
+        fall(&mut server, id2, 10.);
+        assert_eq!(expected, health(&server));
+
+        // johan is too far to be hit
+        server.on_new_position_update(id2, Position::new_vec(20.5, 11.7, 0.5));
+        server.consume_updates(id1);
+        server.regenerate_players(INVULNERABILITY_TIME);
+        server.on_new_attack(id1, attack.clone());
+        assert_eq!(expected, health(&server));
+        assert!(matches!(server.consume_updates(id1)[..], [ServerUpdate::SetItems(_)]));
+
+        // After a while without being hurt, johan regenerates
+        server.regenerate_players(REGENERATION_PERIOD);
+        assert_eq!(expected + 1, health(&server));
+        assert!(matches!(server.consume_updates(id1)[..], [ServerUpdate::UpdateHealth(..)]));
+    }
+
+    #[test]
+    fn test_monsters_die_and_drop_their_loot() {
+        let mut server = GameServer::with_generator(World::empty(), WorldGenerator::flat());
+        let mut registry = MonsterRegistry::default();
+        let mut chaser = registry.get(&EntityKind::Monster1).unwrap().clone();
+        let stone = Item::Block(Block::STONE);
+        chaser.loot = vec![Loot {
+            item: stone,
+            min: 1,
+            max: 1,
+        }];
+        registry.register(EntityKind::Monster1, chaser);
+        let world = Arc::clone(&server.world);
+        server.monster_manager =
+            MonsterManager::with_registry(world, registry, server.state.entity_ids());
+
+        let id = server.login("arthur".to_string(), DEFAULT_VIEW_DISTANCE);
+        server.on_new_position_update(id, Position::new_vec(1., 11., 1.));
+        let ground = server.world.lock().unwrap().safe_spawn_position(3., 1., 0).unwrap();
+        server.spawn_monster(ground);
+        server.add_monster_updates();
+        server.consume_updates(id);
+        let (monster, _) = server.monster_manager.positions()[0];
+        let strength = PlayerItems::starting_items().attack_strength();
+        let hits = MAX_HEALTH.div_ceil(strength);
+        let attack = EntityAttack::new(monster);
+
+        // Each hit hurts the monster, which everybody is told about
+        for hit in 1..hits {
+            server.on_new_attack(id, attack.clone());
+            let expected = MAX_HEALTH - hit * strength;
+            match &server.consume_updates(id)[..] {
+                [ServerUpdate::UpdateHealth(victim, health)] => {
+                    assert_eq!((monster, expected), (*victim, *health));
+                }
+                other => panic!("Unexpected updates: {other:?}"),
+            }
+            // The hits too close to each other do nothing
+            server.on_new_attack(id, attack.clone());
+            assert!(server.consume_updates(id).is_empty());
+            for _ in 0..(INVULNERABILITY_TIME / 0.015).ceil() as usize {
+                server.monster_manager.step(0.015, &vec![]);
+            }
+            server.monster_manager.take_server_updates();
+        }
+
+        // The last one kills it, and its loot lies where it died
+        server.on_new_attack(id, attack.clone());
+        server.step_dropped_items(0.);
+        match &server.consume_updates(id)[..] {
+            [
+                ServerUpdate::UpdateHealth(_, 0),
+                ServerUpdate::RemoveEntity(removed),
+                ServerUpdate::RegisterEntity(_, EntityKind::DroppedItem(item), _),
+                ..
+            ] => {
+                assert_eq!(monster, *removed);
+                assert_eq!(stone, *item);
+            }
+            other => panic!("Unexpected updates: {other:?}"),
+        }
+        assert!(server.monster_manager.positions().is_empty());
+    }
+
+    #[test]
+    fn test_fluids_flow_on_the_server() {
+        let mut server = GameServer::with_generator(World::empty(), WorldGenerator::flat());
+        let id = server.login("arthur".to_string(), DEFAULT_VIEW_DISTANCE);
+        server.on_new_position_update(id, Position::new_vec(1., 11., 1.));
+        server.consume_updates(id);
+
+        // Some water appears on the ground: it spreads around, and the player sees it
+        let source = Vector3::new(4., 10., 4.);
+        let water = Action::Add { at: source, block: Block::WATER };
+        server.world.lock().unwrap().apply_action(&water);
+        server.fluids.wake(source);
+        server.step_fluids(FLUID_TICK / 2.);
+        assert!(server.consume_updates(id).is_empty());
+        server.step_fluids(FLUID_TICK / 2.);
+        let updates = server.consume_updates(id);
+        assert_eq!(4, updates.len());
+        for update in updates {
+            match update {
+                ServerUpdate::SendAction(Action::Add { at, block }) => {
+                    assert_eq!(1., at.distance_to(&source));
+                    assert_eq!(Block::WATER.fluid().unwrap().flows_into, Some(block));
+                    assert_eq!(Some(block), server.world.lock().unwrap().block_at(&at));
+                }
+                other => panic!("Unexpected update: {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_sand_falls_on_the_server() {
+        let mut server = GameServer::with_generator(World::empty(), WorldGenerator::flat());
+        let id = server.login("arthur".to_string(), DEFAULT_VIEW_DISTANCE);
+        server.on_new_position_update(id, Position::new_vec(1., 11., 1.));
+        server.consume_updates(id);
+
+        // Some sand stands on a cube of dirt
+        let support = Vector3::new(4., 10., 4.);
+        let sand = support + Vector3::unit_y();
+        {
+            let mut world = server.world.lock().unwrap();
+            world.apply_action(&Action::Add { at: support, block: Block::DIRT });
+            world.apply_action(&Action::Add { at: sand, block: Block::SAND });
+        }
+
+        // Without the dirt, the sand becomes an entity that the player sees falling
+        server.apply_world_action(Action::Destroy { at: support }, None);
+        let updates = server.consume_updates(id);
+        let falling_id = match &updates[..] {
+            [
+                ServerUpdate::SendAction(Action::Destroy { at: first }),
+                ServerUpdate::SendAction(Action::Destroy { at: second }),
+                ServerUpdate::RegisterEntity(falling_id, EntityKind::FallingBlock(Block::SAND), _),
+            ] if *first == support && *second == sand => *falling_id,
+            other => panic!("Unexpected updates: {other:?}"),
+        };
+        assert_eq!(None, server.world.lock().unwrap().block_at(&sand));
+
+        // It lands where the dirt was
+        for _ in 0..100 {
+            server.step_falling_blocks(0.01);
+        }
+        let updates = server.consume_updates(id);
+        assert!(updates.iter().any(|update| matches!(
+            update,
+            ServerUpdate::RemoveEntity(removed) if *removed == falling_id
+        )));
+        assert!(updates.iter().any(|update| matches!(
+            update,
+            ServerUpdate::SendAction(Action::Add { at, block: Block::SAND }) if *at == support
+        )));
+        assert_eq!(Some(Block::SAND), server.world.lock().unwrap().block_at(&support));
+    }
+
+    #[test]
+    fn test_monsters_spawn_and_despawn_naturally() {
+        let mut server = GameServer::with_generator(World::empty(), WorldGenerator::flat());
+        let rules = SpawningRules::default();
+        let id = server.login("arthur".to_string(), DEFAULT_VIEW_DISTANCE);
+        server.on_new_position_update(id, Position::new_vec(1., 11., 1.));
+        server.consume_updates(id);
+
+        // Monsters appear around the player, on the ground
+        for _ in 0..50 {
+            server.spawn_monsters_naturally(rules.period);
+        }
+        server.add_monster_updates();
+        let monsters = server.monster_manager.positions();
+        assert_eq!(rules.max_per_player, monsters.len());
+        let registered = server
+            .consume_updates(id)
+            .into_iter()
+            .filter(|update| matches!(update, ServerUpdate::RegisterEntity(_, _, _)))
+            .count();
+        assert_eq!(monsters.len(), registered);
+
+        // Once the player is far away, they despawn
+        server.on_new_position_update(id, Position::new_vec(200., 11., 1.));
+        server.consume_updates(id);
+        server.spawn_monsters_naturally(0.);
+        assert!(server.monster_manager.positions().is_empty());
+        let removed = server
+            .consume_updates(id)
+            .into_iter()
+            .filter(|update| matches!(update, ServerUpdate::RemoveEntity(_)))
+            .count();
+        assert_eq!(monsters.len(), removed);
     }
 }
@@ -1,44 +1,74 @@
 use std::sync::{Arc, Mutex};
 
-use crate::entity::chaser::Chaser;
-use crate::entity::entity::EntityKind;
+use crate::entity::entity::{EntityId, EntityKind};
 use crate::entity::monster::Monster;
+use crate::entity::monster_registry::MonsterRegistry;
 use crate::game::attack::EntityAttack;
+use crate::game::player_items::ItemStack;
+use crate::server::entity_ids::EntityIdAllocator;
 use primitives::position::Position;
+use primitives::vector::Vector3;
 use crate::server::server_update::ServerUpdate;
 use crate::world::world::World;
 use super::server_state::PlayerState;
+use rand::rngs::SmallRng;
+use rand::SeedableRng;
+
+/// Maximum number of monsters in the world
+pub const MAX_MONSTERS: usize = 50;
 
 pub struct MonsterManager {
     world: Arc<Mutex<World>>,
-    monsters: Vec<Monster<Chaser>>,
+    /// The behaviour and the stats of each kind of monster
+    registry: MonsterRegistry,
+    monsters: Vec<Monster>,
     buffer_update: Vec<ServerUpdate>,
-    attack_buffer: Vec<EntityAttack>,
+    /// The attacks of the monsters, with the position of the attacker
+    attack_buffer: Vec<(EntityAttack, Position)>,
+    /// Draws the loot of the dead monsters
+    rng: SmallRng,
+    entity_ids: EntityIdAllocator,
 }
 
 impl MonsterManager {
-    pub fn new(world: Arc<Mutex<World>>) -> Self {
+    /// Creates a manager whose monsters get their ids from the given allocator
+    pub fn new(world: Arc<Mutex<World>>, entity_ids: EntityIdAllocator) -> Self {
+        Self::with_registry(world, MonsterRegistry::default(), entity_ids)
+    }
+
+    /// Creates a manager whose monsters are defined by the given registry
+    pub fn with_registry(
+        world: Arc<Mutex<World>>,
+        registry: MonsterRegistry,
+        entity_ids: EntityIdAllocator,
+    ) -> Self {
         Self {
             world,
+            registry,
             monsters: Vec::new(),
             buffer_update: Vec::new(),
             attack_buffer: Vec::new(),
+            rng: SmallRng::from_entropy(),
+            entity_ids,
         }
     }
 
-    pub fn spawn_new_monster(&mut self, pos: Position, kind: EntityKind) -> usize {
-        let id = self.generate_id();
-        self.monsters
-            .push(Monster::new(id, kind.clone(), pos.clone()));
+    /// Spawns a monster of the given kind, and returns its id. Returns None if the kind is not a
+    /// monster of the registry.
+    pub fn spawn_new_monster(&mut self, pos: Position, kind: EntityKind) -> Option<EntityId> {
+        // No id is spent on the kinds which are not monsters
+        self.registry.get(&kind)?;
+        let id = self.entity_ids.allocate();
+        let monster = self.registry.spawn(id, kind.clone(), pos.clone())?;
+        self.monsters.push(monster);
         // Inform the player that a new entity has spawn
-        self.buffer_update
-            .push(ServerUpdate::RegisterEntity(id as u8, kind, pos));
-        id
+        self.buffer_update.push(ServerUpdate::RegisterEntity(id, kind, pos));
+        Some(id)
     }
 
     // TODO add a message to client to remove an entity !!
     /// Remove a monster with id id from the Manager
-    pub fn remove_monster(&mut self, id: usize) {
+    pub fn remove_monster(&mut self, id: EntityId) {
         for index in 0..self.monsters.len() {
             if self.monsters[index].id() == id {
                 self.monsters.swap_remove(index);
@@ -47,17 +77,42 @@ impl MonsterManager {
         }
     }
 
+    /// Hurts a monster with the given strength. Returns its remaining health if it was hurt, or
+    /// None if it does not exist or cannot be hurt yet.
+    pub fn hurt_monster(&mut self, id: EntityId, strength: u8) -> Option<u8> {
+        let monster = self.monsters.iter_mut().find(|monster| monster.id() == id)?;
+        monster.damage(strength).then(|| monster.health())
+    }
+
+    /// Removes a monster which died, and returns where it was with the loot that it drops
+    pub fn kill_monster(&mut self, id: EntityId) -> Option<(Vector3, Vec<ItemStack>)> {
+        let index = self.monsters.iter().position(|monster| monster.id() == id)?;
+        let monster = self.monsters.swap_remove(index);
+        let loot = self
+            .registry
+            .get(monster.entity_type())
+            .map(|definition| {
+                definition
+                    .loot
+                    .iter()
+                    .filter_map(|loot| loot.roll(&mut self.rng))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Some((monster.position().pos(), loot))
+    }
+
     /// Ask the monster to move
     pub fn step(&mut self, dt: f32, players: &Vec<PlayerState>) {
         self.monsters.iter_mut().for_each(|monster| {
             monster.update(&self.world.lock().unwrap(), dt, players);
             // Inform the players that the monster has moved
             self.buffer_update.push(ServerUpdate::UpdatePosition(
-                monster.id() as u8,
+                monster.id(),
                 monster.position().clone(),
             ));
             if let Some(att) = monster.attack() {
-                self.attack_buffer.push(att.clone());
+                self.attack_buffer.push((att.clone(), monster.position().clone()));
             }
         });
     }
@@ -67,27 +122,36 @@ impl MonsterManager {
         std::mem::take(&mut self.buffer_update)
     }
 
-    pub fn take_attack_buffer(&mut self) -> Vec<EntityAttack> {
+    pub fn take_attack_buffer(&mut self) -> Vec<(EntityAttack, Position)> {
         std::mem::take(&mut self.attack_buffer)
     }
 
+    /// Returns true if no more monster can spawn
+    pub fn is_full(&self) -> bool {
+        self.monsters.len() >= MAX_MONSTERS
+    }
+
+    /// Returns the ids of the monsters, with the positions of their eyes
+    pub fn positions(&self) -> Vec<(EntityId, Vector3)> {
+        self.monsters
+            .iter()
+            .map(|monster| (monster.id(), monster.position().pos()))
+            .collect()
+    }
+
     /// Return the ServerUpdate with all entities
     /// Used to register all the monster to a new player
     pub fn get_monsters(&self) -> Vec<ServerUpdate> {
         let mut vec_update = Vec::new();
         for monster in &self.monsters {
             vec_update.push(ServerUpdate::RegisterEntity(
-                monster.id() as u8,
+                monster.id(),
                 monster.entity_type().clone(),
                 monster.position().clone(),
             ));
         }
         vec_update
     }
-
-    fn generate_id(&self) -> usize {
-        50 + self.monsters.len()
-    }
 }
 
 #[cfg(test)]
@@ -95,7 +159,11 @@ mod test {
     use std::sync::{Arc, Mutex};
 
     use super::MonsterManager;
+    use crate::entity::monster_registry::{Loot, MonsterRegistry};
     use crate::entity::entity::EntityKind;
+    use crate::game::item::Item;
+    use crate::server::entity_ids::EntityIdAllocator;
+    use crate::world::block_kind::Block;
     use primitives::position::Position;
     use primitives::vector::Vector3;
     use crate::server::server_update::ServerUpdate;
@@ -104,7 +172,7 @@ mod test {
     #[test]
     fn test_add_monster() {
         let world = Arc::new(Mutex::new(World::empty()));
-        let mut monster_manager = MonsterManager::new(world);
+        let mut monster_manager = MonsterManager::new(world, EntityIdAllocator::new());
         let pos = Position::new(Vector3::empty(), 0., 0.);
 
         monster_manager.spawn_new_monster(pos.clone(), EntityKind::Monster1);
@@ -127,11 +195,12 @@ mod test {
     #[test]
     fn test_rm_monster() {
         let world = Arc::new(Mutex::new(World::empty()));
-        let mut monster_manager = MonsterManager::new(world);
+        let mut monster_manager = MonsterManager::new(world, EntityIdAllocator::new());
         let pos = Position::new(Vector3::empty(), 0., 0.);
 
         let id = monster_manager
-            .spawn_new_monster(pos.clone(), crate::entity::entity::EntityKind::Monster1);
+            .spawn_new_monster(pos.clone(), crate::entity::entity::EntityKind::Monster1)
+            .unwrap();
 
         monster_manager.take_server_updates();
 
@@ -144,18 +213,90 @@ mod test {
         assert_eq!(updates.len(), 0);
     }
 
+    #[test]
+    fn test_ids_are_not_reused() {
+        let world = Arc::new(Mutex::new(World::empty()));
+        let entity_ids = EntityIdAllocator::new();
+        let mut monster_manager = MonsterManager::new(world, entity_ids.clone());
+        let pos = Position::new(Vector3::empty(), 0., 0.);
+
+        // The ids are shared with the other entities
+        let player = entity_ids.allocate();
+        let id0 = monster_manager.spawn_new_monster(pos.clone(), EntityKind::Monster1);
+        let id1 = monster_manager.spawn_new_monster(pos.clone(), EntityKind::Monster1);
+        assert!(id0.is_some_and(|id| id != player));
+        monster_manager.remove_monster(id0.unwrap());
+
+        // The new monster takes neither the id of the removed one nor the one of the remaining one
+        let id2 = monster_manager.spawn_new_monster(pos, EntityKind::Monster2);
+        assert!(id2 != id0 && id2 != id1);
+    }
+
+    #[test]
+    fn test_only_registered_kinds_spawn() {
+        let world = Arc::new(Mutex::new(World::empty()));
+        let pos = Position::new(Vector3::empty(), 0., 0.);
+
+        let mut monster_manager = MonsterManager::new(Arc::clone(&world), EntityIdAllocator::new());
+        assert_eq!(None, monster_manager.spawn_new_monster(pos.clone(), EntityKind::Player));
+
+        // Without the passive animals, the second kind does not spawn
+        let mut registry = MonsterRegistry::new();
+        let chaser = MonsterRegistry::default().get(&EntityKind::Monster1).unwrap().clone();
+        registry.register(EntityKind::Monster1, chaser);
+        let mut monster_manager =
+            MonsterManager::with_registry(world, registry, EntityIdAllocator::new());
+        assert_eq!(None, monster_manager.spawn_new_monster(pos.clone(), EntityKind::Monster2));
+        assert!(monster_manager.spawn_new_monster(pos, EntityKind::Monster1).is_some());
+        assert_eq!(1, monster_manager.get_monsters().len());
+    }
+
+    #[test]
+    fn test_dead_monsters_drop_their_loot() {
+        let world = Arc::new(Mutex::new(World::empty()));
+        let pos = Position::new(Vector3::new(1., 2., 3.), 0., 0.);
+        let mut registry = MonsterRegistry::new();
+        let mut chaser = MonsterRegistry::default().get(&EntityKind::Monster1).unwrap().clone();
+        let stone = Item::Block(Block::STONE);
+        chaser.loot = vec![Loot {
+            item: stone,
+            min: 3,
+            max: 3,
+        }];
+        registry.register(EntityKind::Monster1, chaser);
+        let mut monster_manager =
+            MonsterManager::with_registry(world, registry, EntityIdAllocator::new());
+        let id = monster_manager
+            .spawn_new_monster(pos.clone(), EntityKind::Monster1)
+            .unwrap();
+
+        // The monster survives the first hit, and cannot be hurt again right away
+        assert_eq!(Some(6), monster_manager.hurt_monster(id, 4));
+        assert_eq!(None, monster_manager.hurt_monster(id, 4));
+        assert_eq!(None, monster_manager.hurt_monster(id + 1, 4));
+
+        let (at, loot) = monster_manager.kill_monster(id).unwrap();
+        assert_eq!(pos.pos(), at);
+        assert_eq!(1, loot.len());
+        assert_eq!((stone, 3), (loot[0].item, loot[0].count));
+        assert!(monster_manager.get_monsters().is_empty());
+        assert!(monster_manager.kill_monster(id).is_none());
+    }
+
     #[test]
     fn test_get_monsters() {
         let world = Arc::new(Mutex::new(World::empty()));
-        let mut monster_manager = MonsterManager::new(world);
+        let mut monster_manager = MonsterManager::new(world, EntityIdAllocator::new());
         let position0 = Position::new(Vector3::new(0., 0., 0.), 0., 0.);
         let entity_type0 = EntityKind::Monster1;
 
-        let id0 = monster_manager.spawn_new_monster(position0.clone(), entity_type0.clone());
+        let id0 = monster_manager
+            .spawn_new_monster(position0.clone(), entity_type0.clone())
+            .unwrap();
         let monsters_update = monster_manager.get_monsters();
         match &monsters_update[0] {
             ServerUpdate::RegisterEntity(id, entity_pos1, position1) => {
-                assert_eq!(id0, *id as usize);
+                assert_eq!(id0, *id);
                 assert_eq!(position0, *position1);
                 assert_eq!(entity_type0, *entity_pos1);
             }
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+use crate::entity::entity::{EntityId, EntityKind};
+use crate::world::chunk::Chunk;
+use crate::world::world::World;
+use primitives::position::Position;
+use primitives::vector::Vector3;
+
+/// Maximum number of monsters around each player, by default
+pub const DEFAULT_MAX_MONSTERS_PER_PLAYER: usize = 4;
+
+/// The monsters that can spawn somewhere, with their relative weights
+pub type SpawnTable = Vec<(EntityKind, u32)>;
+
+/// Rules of the natural spawning of the monsters around the players
+#[derive(Clone, Debug)]
+pub struct SpawningRules {
+    /// Time [s] between two attempts to spawn a monster around each player
+    pub period: f32,
+    /// Minimum horizontal distance [cube] between a new monster and all the players
+    pub min_distance: f32,
+    /// Maximum horizontal distance [cube] between a new monster and the player it spawns for
+    pub max_distance: f32,
+    /// Horizontal distance [cube] to all the players beyond which a monster despawns
+    pub despawn_distance: f32,
+    /// Maximum number of monsters within `max_distance` of a player. No monster spawns with 0.
+    pub max_per_player: usize,
+    /// Maximum number of monsters in a chunk
+    pub max_per_chunk: usize,
+    /// The monsters that spawn in each biome, given by its name
+    pub biomes: HashMap<String, SpawnTable>,
+    /// The monsters that spawn in the biomes without table, and in the flat worlds
+    pub default_table: SpawnTable,
+}
+
+impl Default for SpawningRules {
+    fn default() -> Self {
+        let biomes = [
+            ("Plain", vec![(EntityKind::Monster1, 1)]),
+            ("Forest", vec![(EntityKind::Monster1, 2), (EntityKind::Monster2, 1)]),
+            ("Mountain", vec![(EntityKind::Monster2, 1)]),
+            // Nothing lives in the oceans
+            ("Ocean", vec![]),
+        ];
+        Self {
+            period: 5.,
+            min_distance: 12.,
+            max_distance: 24.,
+            despawn_distance: 48.,
+            max_per_player: DEFAULT_MAX_MONSTERS_PER_PLAYER,
+            max_per_chunk: 2,
+            biomes: biomes
+                .into_iter()
+                .map(|(name, table)| (name.to_string(), table))
+                .collect(),
+            default_table: vec![(EntityKind::Monster1, 1)],
+        }
+    }
+}
+
+/// Decides where the monsters spawn naturally on the server, and which ones despawn
+pub struct MonsterSpawner {
+    rules: SpawningRules,
+    /// Time [s] since the last attempt to spawn monsters
+    since_last_attempt: f32,
+    rng: SmallRng,
+}
+
+impl MonsterSpawner {
+    pub fn new(rules: SpawningRules) -> Self {
+        Self {
+            rules,
+            since_last_attempt: 0.,
+            rng: SmallRng::from_entropy(),
+        }
+    }
+
+    /// Lets the time pass. Every `period`, tries to spawn a monster around each player whose
+    /// surroundings are not full. Returns where the monsters spawn, and their kind.
+    ///
+    /// `players` and `monsters` are the positions of the players and of the existing monsters,
+    /// and `biome_at` gives the name of the biome of a column, if any.
+    pub fn step(
+        &mut self,
+        dt: f32,
+        world: &World,
+        players: &[Vector3],
+        monsters: &[Vector3],
+        biome_at: impl Fn(f32, f32) -> Option<&'static str>,
+    ) -> Vec<(Position, EntityKind)> {
+        self.since_last_attempt += dt;
+        if self.since_last_attempt < self.rules.period {
+            return Vec::new();
+        }
+        self.since_last_attempt = 0.;
+
+        let mut monsters = monsters.to_vec();
+        let mut spawned = Vec::new();
+        for player in players {
+            let around = monsters
+                .iter()
+                .filter(|monster| horizontal_distance(monster, player) <= self.rules.max_distance)
+                .count();
+            if around >= self.rules.max_per_player {
+                continue;
+            }
+            let Some(position) = self.find_spawn_position(world, player, players) else {
+                continue;
+            };
+            let chunk = Chunk::corner_of(&position.pos());
+            let in_chunk = monsters
+                .iter()
+                .filter(|monster| Chunk::corner_of(monster) == chunk)
+                .count();
+            if in_chunk >= self.rules.max_per_chunk {
+                continue;
+            }
+            let Some(kind) = self.choose_kind(biome_at(position.x(), position.z())) else {
+                continue;
+            };
+            monsters.push(position.pos());
+            spawned.push((position, kind));
+        }
+        spawned
+    }
+
+    /// Returns the ids of the monsters which are farther than `despawn_distance` from all the
+    /// players, given their positions
+    pub fn monsters_to_despawn(
+        &self,
+        players: &[Vector3],
+        monsters: &[(EntityId, Vector3)],
+    ) -> Vec<EntityId> {
+        let is_far = |monster: &Vector3, player: &Vector3| {
+            horizontal_distance(monster, player) > self.rules.despawn_distance
+        };
+        monsters
+            .iter()
+            .filter(|(_, monster)| players.iter().all(|player| is_far(monster, player)))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// Picks a random column in the ring around the player, and returns where the eyes of a
+    /// monster standing on its ground must be. The ground must be loaded and opaque, so that no
+    /// monster spawns on the water, and far enough from all the players. The monster stands at the
+    /// center of the column, which must still be in the ring.
+    fn find_spawn_position(
+        &mut self,
+        world: &World,
+        player: &Vector3,
+        players: &[Vector3],
+    ) -> Option<Position> {
+        let angle = self.rng.gen_range(0. ..std::f32::consts::TAU);
+        let distance = self
+            .rng
+            .gen_range(self.rules.min_distance..=self.rules.max_distance);
+        let x = player[0] + distance * angle.cos();
+        let z = player[2] + distance * angle.sin();
+        let position = world.safe_spawn_position(x, z, 0)?;
+        let is_far = players
+            .iter()
+            .all(|player| horizontal_distance(&position.pos(), player) >= self.rules.min_distance);
+        let is_close = horizontal_distance(&position.pos(), player) <= self.rules.max_distance;
+        (is_far && is_close).then_some(position)
+    }
+
+    /// Picks the kind of a monster in the table of the biome, according to the weights
+    fn choose_kind(&mut self, biome: Option<&str>) -> Option<EntityKind> {
+        let table = biome
+            .and_then(|name| self.rules.biomes.get(name))
+            .unwrap_or(&self.rules.default_table);
+        let total: u32 = table.iter().map(|(_, weight)| weight).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut draw = self.rng.gen_range(0..total);
+        for (kind, weight) in table {
+            if draw < *weight {
+                return Some(kind.clone());
+            }
+            draw -= weight;
+        }
+        None
+    }
+}
+
+fn horizontal_distance(a: &Vector3, b: &Vector3) -> f32 {
+    Vector3::new(a[0] - b[0], 0., a[2] - b[2]).norm()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entity::entity::EntityKind;
+    use crate::server::monster_spawner::{horizontal_distance, MonsterSpawner, SpawningRules};
+    use crate::world::block_kind::Block;
+    use crate::world::chunk::{Chunk, CHUNK_SIZE};
+    use crate::world::world::World;
+    use primitives::vector::Vector3;
+
+    /// Returns a flat world of 10 x 10 chunks, centered on the origin
+    fn flat_world(block: Block) -> World {
+        let s = CHUNK_SIZE as f32;
+        let mut chunks = Vec::new();
+        for i in -5..5 {
+            for j in -5..5 {
+                let mut chunk = Chunk::new([i as f32 * s, j as f32 * s]);
+                chunk.fill_layer(0, Block::STONE);
+                chunk.fill_layer(1, block);
+                chunks.push(chunk);
+            }
+        }
+        World::new(chunks)
+    }
+
+    #[test]
+    fn test_monsters_spawn_around_the_players() {
+        let world = flat_world(Block::GRASS);
+        let rules = SpawningRules::default();
+        let mut spawner = MonsterSpawner::new(rules.clone());
+        let player = Vector3::new(0.5, 10., 0.5);
+
+        // Nothing happens before the period
+        let plain = |_, _| Some("Plain");
+        assert!(spawner.step(rules.period / 2., &world, &[player], &[], plain).is_empty());
+
+        let mut monsters = Vec::new();
+        for _ in 0..100 {
+            let spawned = spawner.step(rules.period, &world, &[player], &monsters, plain);
+            for (position, kind) in spawned {
+                assert_eq!(EntityKind::Monster1, kind);
+                let distance = horizontal_distance(&position.pos(), &player);
+                assert!(distance >= rules.min_distance && distance <= rules.max_distance);
+                // The monster stands on the ground
+                assert!(world.block_at(&(position.pos() - Vector3::unit_y() * 2.)).is_some());
+                monsters.push(position.pos());
+            }
+        }
+        // The population is capped
+        assert_eq!(rules.max_per_player, monsters.len());
+        assert!(monsters.iter().all(|monster| {
+            let chunk = Chunk::corner_of(monster);
+            let in_chunk = monsters.iter().filter(|m| Chunk::corner_of(m) == chunk).count();
+            in_chunk <= rules.max_per_chunk
+        }));
+    }
+
+    #[test]
+    fn test_monsters_follow_the_biomes() {
+        let rules = SpawningRules::default();
+        let mut spawner = MonsterSpawner::new(rules.clone());
+        let player = [Vector3::new(0.5, 10., 0.5)];
+
+        // Nothing spawns in an ocean, nor on the water
+        let world = flat_world(Block::GRASS);
+        let ocean = |_, _| Some("Ocean");
+        let mountain = |_, _| Some("Mountain");
+        for _ in 0..20 {
+            assert!(spawner.step(rules.period, &world, &player, &[], ocean).is_empty());
+            assert!(spawner
+                .step(rules.period, &flat_world(Block::WATER), &player, &[], mountain)
+                .is_empty());
+        }
+
+        // The monsters of the mountains are the second kind
+        let spawned = (0..20)
+            .flat_map(|_| spawner.step(rules.period, &world, &player, &[], mountain))
+            .collect::<Vec<_>>();
+        assert!(!spawned.is_empty());
+        assert!(spawned.iter().all(|(_, kind)| *kind == EntityKind::Monster2));
+    }
+
+    #[test]
+    fn test_far_monsters_despawn() {
+        let spawner = MonsterSpawner::new(SpawningRules::default());
+        let players = [Vector3::new(0., 10., 0.), Vector3::new(100., 10., 0.)];
+        let monsters = [
+            (50, Vector3::new(10., 10., 0.)),
+            (51, Vector3::new(50., 10., 0.)),
+            (52, Vector3::new(90., 10., 20.)),
+        ];
+        assert_eq!(vec![51], spawner.monsters_to_despawn(&players, &monsters));
+        assert_eq!(vec![50, 51, 52], spawner.monsters_to_despawn(&[], &monsters));
+    }
+}
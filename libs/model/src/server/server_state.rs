@@ -1,45 +1,111 @@
+use crate::entity::entity::EntityId;
+use crate::game::health::{Health, MAX_HEALTH};
+use crate::game::player_items::PlayerItems;
+use crate::server::entity_ids::EntityIdAllocator;
 use crate::world::chunk::CHUNK_FLOOR;
 use primitives::position::Position;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 
-#[derive(Clone)]
+/// Everything the server remembers about a player, even when they are not connected
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct PlayerState {
     pub pos: Position,
-    pub id: usize,
+    pub id: EntityId,
+    pub items: PlayerItems,
+    pub health: Health,
+}
+
+impl PlayerState {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, serde_json::Error> {
+        serde_json::from_slice(bytes)
+    }
 }
 
 /// Persistent state of the server
+#[derive(Serialize, Deserialize)]
 pub struct ServerState {
     players: HashMap<String, PlayerState>,
+    /// Horizontal coordinates (x, z) around which the players spawn and respawn
+    #[serde(default)]
+    spawn_point: [f32; 2],
+    /// The connections do not survive a restart of the server
+    #[serde(skip)]
     connected: HashSet<String>,
+    /// Gives the ids of the new players, shared with the other entities of the server
+    #[serde(skip)]
+    entity_ids: EntityIdAllocator,
 }
 
 impl ServerState {
     pub fn new() -> Self {
         Self {
             players: HashMap::new(),
+            spawn_point: [0., 0.],
             connected: HashSet::new(),
+            entity_ids: EntityIdAllocator::new(),
         }
     }
 
+    /// Returns the allocator which gives the ids of the players, to share it with the other
+    /// entities
+    pub fn entity_ids(&self) -> EntityIdAllocator {
+        self.entity_ids.clone()
+    }
+
+    /// Gives the ids of the new players with the provided allocator, in which the ids of the
+    /// known players are reserved
+    pub fn set_entity_ids(&mut self, entity_ids: EntityIdAllocator) {
+        for player in self.players.values() {
+            entity_ids.reserve(player.id);
+        }
+        self.entity_ids = entity_ids;
+    }
+
+    pub fn spawn_point(&self) -> [f32; 2] {
+        self.spawn_point
+    }
+
+    pub fn set_spawn_point(&mut self, spawn_point: [f32; 2]) {
+        self.spawn_point = spawn_point;
+    }
+
+    /// Returns a position high in the sky above the spawn point, from which a player falls when
+    /// no ground was found below it
+    pub fn sky_spawn_position(&self) -> Position {
+        let [x, z] = self.spawn_point;
+        Position::new_vec(x, CHUNK_FLOOR as f32 + 3. + 10. * CHUNK_FLOOR as f32, z)
+    }
+
+    /// Returns true if the player already played on this server
+    pub fn is_known(&self, name: &str) -> bool {
+        self.players.contains_key(name)
+    }
+
+    /// Logins a player. A player who already played is restored where they left, with their
+    /// items and health. A new player falls from the sky at the spawn point.
     pub fn login(&mut self, name: String) -> PlayerState {
         self.connected.insert(name.clone());
         if !self.players.contains_key(&name) {
+            let pos = self.sky_spawn_position();
             self.players.insert(
                 name.clone(),
                 PlayerState {
-                    id: self.players.len(),
-                    pos: Position::spawn_position(CHUNK_FLOOR as f32 + 3.),
+                    id: self.entity_ids.allocate(),
+                    pos,
+                    items: PlayerItems::starting_items(),
+                    health: Health::new(MAX_HEALTH),
                 },
             );
         }
-        self.players
-            .get_mut(&name)
-            .map(|player| player.pos.raise(10. * CHUNK_FLOOR as f32));
         self.players.get(&name).unwrap().clone()
     }
 
-    pub fn logout(&mut self, id: usize) {
+    pub fn logout(&mut self, id: EntityId) {
         let name = self.players.iter().find(|(_, v)| v.id == id).unwrap().0;
         self.connected.remove(name);
     }
@@ -55,21 +121,61 @@ impl ServerState {
         self.connected.len()
     }
 
-    pub fn set_player_pos(&mut self, id: usize, pos: Position) {
-        if let Some(player_state) = self
-            .players
+    pub fn set_player_pos(&mut self, id: EntityId, pos: Position) {
+        if let Some(player_state) = self.player_mut(id) {
+            player_state.pos = pos;
+        }
+    }
+
+    pub fn set_player_items(&mut self, id: EntityId, items: PlayerItems) {
+        if let Some(player_state) = self.player_mut(id) {
+            player_state.items = items;
+        }
+    }
+
+    /// Hurts the player, unless they were hurt too recently. Returns true if they lost health.
+    pub fn damage_player(&mut self, id: EntityId, strength: u8) -> bool {
+        self.player_mut(id)
+            .is_some_and(|player_state| player_state.health.damage(strength))
+    }
+
+    /// Lets the time pass for the connected players, who regenerate slowly.
+    /// Returns the ids of the players who gained health.
+    pub fn regenerate_players(&mut self, dt: f32) -> Vec<EntityId> {
+        self.players
+            .iter_mut()
+            .filter(|(name, _)| self.connected.contains(*name))
+            .filter_map(|(_, player_state)| player_state.health.step(dt).then_some(player_state.id))
+            .collect()
+    }
+
+    /// Brings a dead player back to life at the given position, with their health restored.
+    /// Returns the new state of the player.
+    pub fn respawn_player(&mut self, id: EntityId, pos: Position) -> Option<PlayerState> {
+        let player_state = self.player_mut(id)?;
+        player_state.pos = pos;
+        player_state.health = Health::new(MAX_HEALTH);
+        Some(player_state.clone())
+    }
+
+    pub fn player(&self, id: EntityId) -> Option<&PlayerState> {
+        self.players.values().find(|v| v.id == id)
+    }
+
+    fn player_mut(&mut self, id: EntityId) -> Option<&mut PlayerState> {
+        self.players
             .iter_mut()
             .find(|(_, v)| v.id == id)
             .map(|(_, v)| v)
-        {
-            player_state.pos = pos;
-        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::game::player_items::PlayerItems;
     use crate::server::server_state::{PlayerState, ServerState};
+    use crate::world::block_kind::Block;
+    use primitives::position::Position;
 
     #[test]
     fn test_client_id_assignation() {
@@ -89,4 +195,45 @@ mod tests {
         assert_eq!(1, connected.len());
         assert_eq!(1, connected[0].id)
     }
+
+    #[test]
+    fn test_player_is_restored_on_login() {
+        let mut state = ServerState::new();
+        let player = state.login("arthur".to_string());
+        assert_eq!(PlayerItems::starting_items(), player.items);
+
+        // The player moves, collects some grass and is attacked
+        let pos = Position::new_vec(12., 15., -3.);
+        let mut items = player.items.clone();
+        items.collect(Block::GRASS.into());
+        state.set_player_pos(player.id, pos.clone());
+        state.set_player_items(player.id, items.clone());
+        state.damage_player(player.id, 3);
+        state.logout(player.id);
+
+        // When they come back, everything is as they left it
+        let restored = state.login("arthur".to_string());
+        assert_eq!(player.id, restored.id);
+        assert_eq!(pos, restored.pos);
+        assert_eq!(items, restored.items);
+        assert_eq!(player.health.health() - 3, restored.health.health());
+    }
+
+    #[test]
+    fn test_dead_player_respawns() {
+        let mut state = ServerState::new();
+        state.set_spawn_point([20., -5.]);
+        let player = state.login("arthur".to_string());
+        assert_eq!(20., player.pos.x());
+        assert_eq!(-5., player.pos.z());
+
+        state.damage_player(player.id, 100);
+        assert!(!state.player(player.id).unwrap().health.alive());
+
+        let pos = Position::new_vec(20.5, 12., -4.5);
+        let respawned = state.respawn_player(player.id, pos.clone()).unwrap();
+        assert!(respawned.health.alive());
+        assert_eq!(pos, respawned.pos);
+        assert_eq!(Some(&respawned), state.player(player.id));
+    }
 }
@@ -1,8 +1,10 @@
-use crate::entity::entity::EntityKind;
+use crate::entity::entity::{EntityId, EntityKind};
 use crate::game::actions::Action;
-use crate::game::attack::EntityAttack;
+use crate::game::player_items::{ItemStack, PlayerItems};
+use crate::server::action_validation::ActionRejection;
 use primitives::position::Position;
-use crate::server::server_update::ServerUpdate::LoadChunk;
+use primitives::vector::Vector3;
+use crate::server::server_state::PlayerState;
 use crate::world::chunk::Chunk;
 
 pub const RESPONSE_OK: u8 = 100;
@@ -11,27 +13,32 @@ pub const RESPONSE_ERROR: u8 = 101;
 /// List of messages that are sent to the client from the server
 #[derive(Clone, Debug)]
 pub enum ServerUpdate {
-    /// Ask the client to load a new chunk
-    LoadChunk(Chunk),
-    /// The server forwards to the client his client id
-    LoggedIn(u8, Position),
+    /// Ask the client to load a new chunk (boxed, since it is much larger than the other updates)
+    LoadChunk(Box<Chunk>),
+    /// Ask the client to forget the chunk with the given corner, which is out of its sight
+    UnloadChunk((i32, i32)),
+    /// The server forwards to the client its state: id, position, items and health
+    LoggedIn(PlayerState),
     /// The server forwards to the client an action to be executed
     SendAction(Action),
-    /// Tell the client that a new player is part of the game
-    RegisterEntity(u8, EntityKind, Position),
+    /// Tell the client that a new entity is part of the game
+    RegisterEntity(EntityId, EntityKind, Position),
     /// Update the position of an existing entity
-    UpdatePosition(u8, Position),
-    /// Attack to suffer... :(
-    Attack(EntityAttack),
+    UpdatePosition(EntityId, Position),
+    /// The player was hit... :( and is pushed away with the given velocity
+    KnockBack(Vector3),
+    /// The health of a player or a monster changed
+    UpdateHealth(EntityId, u8),
     /// Remove an entity
-    RemoveEntity(u32),
+    RemoveEntity(EntityId),
+    /// The server refused an action of the client, who must undo it
+    RejectAction(ActionRejection),
+    /// The player walked over a dropped item, which must be added to their items
+    PickUpItem(ItemStack),
+    /// The items of the player, as the server knows them, after the client disagreed with them
+    SetItems(PlayerItems),
+    /// The player died. They keep the given items, the other ones were dropped where they died.
+    Death(PlayerItems),
+    /// The dead player comes back to life, with the given state
+    Respawn(PlayerState),
 }
-
-impl ServerUpdate {
-    /// Returns true if this update is big enough to require a special treatment when sent over the
-    /// network.
-    pub fn is_heavy(&self) -> bool {
-        matches!(self, LoadChunk(_))
-    }
-}
-
@@ -1,64 +1,86 @@
-use std::collections::HashSet;
-use crate::world::chunk::CHUNK_SIZE;
+use std::collections::{HashMap, HashSet};
+use crate::entity::entity::EntityId;
+use crate::world::chunk::{Chunk, CHUNK_SIZE};
+use primitives::vector::Vector3;
 
-const VISIBLE_CHUNKS: i32 = 4;
+/// Number of chunks visible in each direction around a player, when none is requested
+pub const DEFAULT_VIEW_DISTANCE: u8 = 4;
 
-/// A struct in charge of keeping track of the chunks loaded by each players.
-pub struct WorldDispatcher {
-    // Note for all the following attributes
-    // -------------------------------------
-    // The player ids are created SEQUENTIALLY, from 0 to N
-    // Therefore, we can access the data of 1 player by accessible its 'userid'
-    // This is done for optimization.
+/// What a single player sees of the world
+struct PlayerView {
     /// The chunks loaded by this player
-    player_chunks: Vec<HashSet<(i32, i32)>>,
+    chunks: HashSet<(i32, i32)>,
 
     /// The current position of this player
     /// We only care about the position on the 'xy' plane.
-    player_current_chunk: Vec<(i32, i32)>,
+    current_chunk: (i32, i32),
+
+    /// The number of chunks that this player sees in each direction
+    view_distance: i32,
+}
+
+/// A struct in charge of keeping track of the chunks loaded by each players.
+pub struct WorldDispatcher {
+    /// The view of each player, by id. The ids come from the allocator shared with the other
+    /// entities, so they are not contiguous.
+    players: HashMap<EntityId, PlayerView>,
 }
 
 impl WorldDispatcher {
     pub fn new() -> Self {
         Self {
-            player_chunks: Vec::new(),
-            player_current_chunk: vec![],
+            players: HashMap::new(),
         }
     }
 
-    pub fn register_player(&mut self, _id: usize) {
-        self.player_chunks.push(HashSet::new());
-        self.player_current_chunk.push((-10, -10))
+    /// Registers a player (or a player coming back) that sees `view_distance` chunks around them.
+    pub fn register_player(&mut self, id: EntityId, view_distance: u8) {
+        self.players.insert(
+            id,
+            PlayerView {
+                chunks: HashSet::new(),
+                current_chunk: (-10, -10),
+                view_distance: view_distance as i32,
+            },
+        );
+    }
+
+    pub fn logout(&mut self, id: EntityId) {
+        self.players.remove(&id);
     }
 
-    pub fn logout(&mut self, id: usize) {
-        self.player_chunks[id] = HashSet::new();
+    /// Returns the chunks seen by at least one player
+    pub fn seen_chunks(&self) -> HashSet<(i32, i32)> {
+        self.players
+            .values()
+            .flat_map(|view| view.chunks.iter().copied())
+            .collect()
     }
 
-    /// Updates the position and returns a list of new chunks to be loaded
+    /// Updates the position and returns the chunks that the player must load, and the ones that
+    /// are now out of sight and must be unloaded.
     pub fn update_position(
         &mut self,
-        id: usize,
+        id: EntityId,
         pos: (f32, f32),
     ) -> Option<(HashSet<(i32, i32)>, HashSet<(i32, i32)>)> {
+        let view = self.players.get_mut(&id)?;
+
         // Compute the current chunk
-        let s = CHUNK_SIZE as f32;
-        let current_chunk = ((pos.0 - pos.0 % s) as i32, (pos.1 - pos.1 % s) as i32);
+        let current_chunk = Chunk::corner_of(&Vector3::new(pos.0, 0., pos.1));
 
         // If the player has changed chunk, then we send the new chunks that are further away
-        if current_chunk != self.player_current_chunk[id] {
+        if current_chunk != view.current_chunk {
             // Compute the necessary chunks
-            let chunks_to_see = Self::get_visible_chunk(current_chunk);
+            let chunks_to_see = Self::get_visible_chunk(current_chunk, view.view_distance);
 
             // Compute the diff
-            let chunks_to_send = &chunks_to_see - &self.player_chunks[id];
-            let chunks_to_delete = &self.player_chunks[id] - &chunks_to_see;
+            let chunks_to_send = &chunks_to_see - &view.chunks;
+            let chunks_to_delete = &view.chunks - &chunks_to_see;
 
             // Update the information about this player...
-            self.player_current_chunk[id] = current_chunk;
-            chunks_to_send.iter().for_each(|chunk| {
-                self.player_chunks[id].insert(chunk.clone());
-            });
+            view.current_chunk = current_chunk;
+            view.chunks = chunks_to_see;
 
             return Some((chunks_to_send, chunks_to_delete));
         }
@@ -66,10 +88,10 @@ impl WorldDispatcher {
     }
 
     // TODO output of this function can be memoized
-    fn get_visible_chunk(from: (i32, i32)) -> HashSet<(i32, i32)> {
+    fn get_visible_chunk(from: (i32, i32), view_distance: i32) -> HashSet<(i32, i32)> {
         let mut chunks = HashSet::new();
-        for i in -VISIBLE_CHUNKS..VISIBLE_CHUNKS {
-            for j in -VISIBLE_CHUNKS..VISIBLE_CHUNKS {
+        for i in -view_distance..view_distance {
+            for j in -view_distance..view_distance {
                 chunks.insert((
                     from.0 + i * CHUNK_SIZE as i32,
                     from.1 + j * CHUNK_SIZE as i32,
@@ -82,19 +104,21 @@ impl WorldDispatcher {
 
 #[cfg(test)]
 mod tests {
-    use crate::server::world_dispatcher::{WorldDispatcher, VISIBLE_CHUNKS};
+    use crate::server::world_dispatcher::{WorldDispatcher, DEFAULT_VIEW_DISTANCE};
     use crate::world::chunk::CHUNK_SIZE;
 
+    const VIEW_DISTANCE: i32 = DEFAULT_VIEW_DISTANCE as i32;
+
     #[test]
     fn test_basic_scenario() {
         let mut dispatcher = WorldDispatcher::new();
-        dispatcher.register_player(0);
+        dispatcher.register_player(0, DEFAULT_VIEW_DISTANCE);
 
         // Initially, the server sends the full grid around the player
         let (to_send, to_delete) = dispatcher.update_position(0, (0., 0.)).unwrap();
         assert_eq!(
             to_send.len(),
-            (4 * VISIBLE_CHUNKS * VISIBLE_CHUNKS) as usize
+            (4 * VIEW_DISTANCE * VIEW_DISTANCE) as usize
         );
         assert_eq!(to_delete.len(), 0);
 
@@ -105,7 +129,42 @@ mod tests {
         let (to_send, to_delete) = dispatcher
             .update_position(0, (CHUNK_SIZE as f32 + 1., 1.))
             .unwrap();
-        assert_eq!(to_send.len(), 2 * VISIBLE_CHUNKS as usize);
-        assert_eq!(to_delete.len(), 2 * VISIBLE_CHUNKS as usize);
+        assert_eq!(to_send.len(), 2 * VIEW_DISTANCE as usize);
+        assert_eq!(to_delete.len(), 2 * VIEW_DISTANCE as usize);
+    }
+
+    #[test]
+    fn test_unloaded_chunks_are_sent_again() {
+        let mut dispatcher = WorldDispatcher::new();
+        dispatcher.register_player(0, DEFAULT_VIEW_DISTANCE);
+        dispatcher.update_position(0, (0., 0.)).unwrap();
+
+        // Walk far away: all the initial chunks are unloaded
+        let far = (4 * VIEW_DISTANCE * CHUNK_SIZE as i32) as f32;
+        let (_, to_delete) = dispatcher.update_position(0, (far, 0.)).unwrap();
+        assert_eq!(to_delete.len(), (4 * VIEW_DISTANCE * VIEW_DISTANCE) as usize);
+
+        // Coming back, they must be sent again
+        let (to_send, to_delete) = dispatcher.update_position(0, (0., 0.)).unwrap();
+        assert_eq!(to_send.len(), (4 * VIEW_DISTANCE * VIEW_DISTANCE) as usize);
+        assert_eq!(to_delete.len(), (4 * VIEW_DISTANCE * VIEW_DISTANCE) as usize);
+    }
+
+    #[test]
+    fn test_view_distance_per_player() {
+        let mut dispatcher = WorldDispatcher::new();
+        dispatcher.register_player(0, 2);
+        dispatcher.register_player(1, 6);
+
+        let (to_send, _) = dispatcher.update_position(0, (0., 0.)).unwrap();
+        assert_eq!(16, to_send.len());
+        let (to_send, _) = dispatcher.update_position(1, (0., 0.)).unwrap();
+        assert_eq!(144, to_send.len());
+
+        // A player coming back is sent its chunks again, with its new view distance
+        dispatcher.logout(0);
+        dispatcher.register_player(0, 1);
+        let (to_send, _) = dispatcher.update_position(0, (0., 0.)).unwrap();
+        assert_eq!(4, to_send.len());
     }
 }
@@ -1,75 +1,122 @@
-use serde::{Deserialize, Serialize};
-use strum::EnumIter;
-use strum::IntoEnumIterator;
+use crate::world::block_registry::{BlockDefinition, BlockRegistry, FluidFlow};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt::{Debug, Formatter};
 
 /// The kind of cube
 /// Each kind is associated with 3 textures: side, top & bottom.
-#[derive(Clone, Copy, EnumIter, PartialEq, Debug, Serialize, Deserialize, Hash, Eq)]
-pub enum Block {
-    GRASS = 0,
-    DIRT,
-    COBBELSTONE,
-    OAKLOG,
-    OAKLEAVES,
-    WATER,
-    STONE,
-    SAND,
-    SWORD,
-}
+///
+/// The kinds of blocks and their properties are defined in the `BlockRegistry`. A block is only
+/// the id of its definition, which is also the index of its textures.
+#[derive(Clone, Copy, PartialEq, Serialize, Hash, Eq)]
+pub struct Block(u8);
 
 impl Block {
-    fn file_name(&self) -> String {
-        match self {
-            Block::GRASS => "grass".to_string(),
-            Block::DIRT => "dirt".to_string(),
-            Block::COBBELSTONE => "cobblestone".to_string(),
-            Block::OAKLOG => "oak_log".to_string(),
-            Block::OAKLEAVES => "oak_leaves".to_string(),
-            Block::WATER => "water".to_string(),
-            Block::STONE => "stone".to_string(),
-            Block::SAND => "sand".to_string(),
-            Block::SWORD => "stone_sword".to_string(),
-        }
+    // The blocks that the game itself relies on (world generation, crafting, ...).
+    // They must be defined in the registry with these ids.
+    pub const GRASS: Block = Block(0);
+    pub const DIRT: Block = Block(1);
+    pub const COBBELSTONE: Block = Block(2);
+    pub const OAKLOG: Block = Block(3);
+    pub const OAKLEAVES: Block = Block(4);
+    pub const WATER: Block = Block(5);
+    pub const STONE: Block = Block(6);
+    pub const SAND: Block = Block(7);
+
+    /// The blocks used by the game, with their name in the registry
+    pub(crate) const BUILTIN: [(Block, &'static str); 8] = [
+        (Block::GRASS, "grass"),
+        (Block::DIRT, "dirt"),
+        (Block::COBBELSTONE, "cobblestone"),
+        (Block::OAKLOG, "oak_log"),
+        (Block::OAKLEAVES, "oak_leaves"),
+        (Block::WATER, "water"),
+        (Block::STONE, "stone"),
+        (Block::SAND, "sand"),
+    ];
+
+    /// Returns the block with the given id, if it is defined in the registry
+    pub fn from_id(id: u8) -> Option<Self> {
+        BlockRegistry::global().get(id).map(|_| Block(id))
+    }
+
+    pub(crate) fn from_raw_id(id: u8) -> Self {
+        Block(id)
+    }
+
+    /// Returns the block with the given name in the registry
+    pub fn from_name(name: &str) -> Option<Self> {
+        BlockRegistry::global().by_name(name)
+    }
+
+    /// Returns all the blocks defined in the registry
+    pub fn all() -> impl Iterator<Item = Block> {
+        BlockRegistry::global().blocks()
+    }
+
+    pub fn id(&self) -> u8 {
+        self.0
+    }
+
+    /// Returns the properties of this block
+    pub fn definition(&self) -> &'static BlockDefinition {
+        BlockRegistry::global()
+            .get(self.0)
+            .expect("Blocks are only created from the registry")
+    }
+
+    pub fn name(&self) -> &'static str {
+        &self.definition().name
     }
 
     /// Returns a list of all the textures to be loaded, in the proper order.
     pub fn get_texture_files() -> Vec<String> {
-        let mut names = Vec::new();
-        for block_kind in Block::iter() {
-            let name = block_kind.file_name();
-            names.push(name.clone() + "_side");
-            names.push(name.clone() + "_top");
-            names.push(name.clone() + "_bottom");
-        }
-        names
+        BlockRegistry::global().texture_files()
     }
 
     pub fn can_be_placed(&self) -> bool {
-        match self {
-            Block::SWORD => false,
-            _ => true,
-        }
+        self.definition().placeable
     }
 
-    pub fn attack_strength(&self) -> u8 {
-        match self {
-            Block::SWORD => 2,
-            _ => 1,
-        }
+    pub fn is_transparent(&self) -> bool {
+        self.definition().transparent
     }
 
-    pub fn is_transparent(&self) -> bool {
-        match self {
-            Block::OAKLEAVES | Block::WATER => true,
-            _ => false,
-        }
+    pub fn hardness(&self) -> f32 {
+        self.definition().hardness
     }
 
     pub fn block_dropped(&self) -> Self {
-        match self {
-            Block::GRASS => Block::DIRT,
-            Block::STONE => Block::COBBELSTONE,
-            block => *block,
+        BlockRegistry::global().drop_of(self.0).unwrap_or(*self)
+    }
+
+    /// Whether this block falls when there is nothing below it
+    pub fn falls(&self) -> bool {
+        self.definition().falls
+    }
+
+    /// Whether the entities go through this block and swim in it
+    pub fn is_fluid(&self) -> bool {
+        self.definition().fluid
+    }
+
+    /// Returns how this block spreads in the world, if it is a fluid
+    pub fn fluid(&self) -> Option<FluidFlow> {
+        BlockRegistry::global().fluid_of(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Block {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = u8::deserialize(deserializer)?;
+        Block::from_id(id).ok_or_else(|| serde::de::Error::custom(format!("unknown block {id}")))
+    }
+}
+
+impl Debug for Block {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match BlockRegistry::global().get(self.0) {
+            Some(definition) => write!(f, "{}", definition.name),
+            None => write!(f, "Block({})", self.0),
         }
     }
 }
@@ -0,0 +1,486 @@
+use crate::game::definitions::digest_of;
+use crate::game::item_registry::ToolKind;
+use crate::world::block_kind::Block;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::sync::OnceLock;
+use tracing::{info, warn};
+
+/// File from which the blocks are loaded at startup, relative to the working directory (like the
+/// textures)
+pub const BLOCKS_FILE: &str = "./resources/blocks.json";
+
+/// Definitions used when no file could be loaded. This is a copy of `resources/blocks.json` taken
+/// at compile time.
+const BUILTIN_BLOCKS: &str = include_str!("../../../../resources/blocks.json");
+
+static REGISTRY: OnceLock<BlockRegistry> = OnceLock::new();
+
+/// Largest id of a block. The chunks are encoded with 0 for the empty cells and `id + 1` for the
+/// blocks (see `Chunk::to_bytes`), which must fit in a byte.
+pub const MAX_BLOCK_ID: u8 = u8::MAX - 1;
+
+/// Names of the three textures of a block, without their extension
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct BlockTextures {
+    pub side: String,
+    pub top: String,
+    pub bottom: String,
+}
+
+/// Properties of a kind of block, as written in the definition file
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct BlockDefinition {
+    /// Identifier used in the chunks, on the network and as index in the texture array
+    pub id: u8,
+    pub name: String,
+    pub textures: BlockTextures,
+    /// Whether the cubes behind this block are visible
+    #[serde(default)]
+    pub transparent: bool,
+    /// Whether this block can be placed in the world by the players
+    #[serde(default = "default_placeable")]
+    pub placeable: bool,
+    /// Name of the block collected when this block is destroyed, if it is not the block itself
+    #[serde(default)]
+    pub drops: Option<String>,
+    /// How long this block resists to being mined
+    #[serde(default)]
+    pub hardness: f32,
+    /// The tool that mines this block faster
+    #[serde(default)]
+    pub tool: Option<ToolKind>,
+    /// Whether this block only drops something when mined with its tool
+    #[serde(default)]
+    pub requires_tool: bool,
+    /// Whether this block falls when there is nothing below it
+    #[serde(default)]
+    pub falls: bool,
+    /// Whether this block is a fluid, which the entities go through and swim in
+    #[serde(default)]
+    pub fluid: bool,
+    /// For a fluid, the name of the lower level of the same fluid, which spreads to the free cells
+    /// around this block
+    #[serde(default)]
+    pub flows_into: Option<String>,
+}
+
+/// How a fluid spreads, computed from the definitions of its levels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FluidFlow {
+    /// Number of cells over which the fluid spreads on a flat ground, including this one
+    pub level: u8,
+    /// The lower level that spreads around this one, if there is one
+    pub flows_into: Option<Block>,
+    /// The level of the fluid that falls in the free cell below this one
+    pub falls_into: Block,
+    /// Whether this level is not created by another one: it never dries up
+    pub is_source: bool,
+}
+
+fn default_placeable() -> bool {
+    true
+}
+
+#[derive(Debug)]
+pub enum BlockRegistryError {
+    Io(std::io::Error),
+    Parse(serde_json::Error),
+    /// The ids must go from 0 to the number of blocks, in the order of the file
+    UnexpectedId { expected: u8, found: u8 },
+    /// More blocks are defined than there are ids, up to `MAX_BLOCK_ID`
+    TooManyBlocks(usize),
+    DuplicateName(String),
+    UnknownDrop { block: String, drop: String },
+    /// The block requires a tool, but does not say which one
+    MissingTool(String),
+    /// One of the blocks used by the game itself is not defined with the expected id
+    MissingBuiltin { id: u8, name: &'static str },
+    /// The block flows into an unknown block, a block which is not a fluid, or itself
+    InvalidFlow { block: String, flow: String },
+}
+
+impl Display for BlockRegistryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlockRegistryError::Io(err) => write!(f, "Cannot read the blocks: {err}"),
+            BlockRegistryError::Parse(err) => write!(f, "Invalid block definitions: {err}"),
+            BlockRegistryError::UnexpectedId { expected, found } => {
+                write!(f, "Expected a block with id {expected}, found {found}")
+            }
+            BlockRegistryError::TooManyBlocks(n) => {
+                write!(f, "{n} blocks are defined, the ids stop at {MAX_BLOCK_ID}")
+            }
+            BlockRegistryError::DuplicateName(name) => write!(f, "Block {name} is defined twice"),
+            BlockRegistryError::UnknownDrop { block, drop } => {
+                write!(f, "Block {block} drops the unknown block {drop}")
+            }
+            BlockRegistryError::MissingBuiltin { id, name } => {
+                write!(f, "Block {name} must be defined with id {id}")
+            }
+            BlockRegistryError::MissingTool(name) => {
+                write!(f, "Block {name} requires a tool, which is not given")
+            }
+            BlockRegistryError::InvalidFlow { block, flow } => {
+                write!(f, "Block {block} cannot flow into {flow}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BlockRegistryError {}
+
+/// All the kinds of blocks of the game, with their properties.
+///
+/// The blocks are defined in a JSON file (see `resources/blocks.json`), which is loaded at
+/// startup with `BlockRegistry::install_from_file`, so that blocks can be added without
+/// recompiling the game. The registry is then shared by the whole program, and accessed through
+/// `Block`.
+///
+/// The client and the server must use the same definitions, since blocks are exchanged by id.
+pub struct BlockRegistry {
+    /// The definitions, indexed by id
+    definitions: Vec<BlockDefinition>,
+    /// The id of each block, from its name
+    ids: HashMap<String, u8>,
+    /// The block dropped by each block, indexed by id
+    drops: Vec<Block>,
+    /// How each block spreads if it is a fluid, indexed by id
+    fluids: Vec<Option<FluidFlow>>,
+}
+
+impl BlockRegistry {
+    /// Parses and validates a list of block definitions
+    pub fn from_json(json: &str) -> Result<Self, BlockRegistryError> {
+        let definitions: Vec<BlockDefinition> =
+            serde_json::from_str(json).map_err(BlockRegistryError::Parse)?;
+
+        if definitions.len() > MAX_BLOCK_ID as usize + 1 {
+            return Err(BlockRegistryError::TooManyBlocks(definitions.len()));
+        }
+        let mut ids = HashMap::new();
+        for (expected, definition) in definitions.iter().enumerate() {
+            if definition.id as usize != expected {
+                return Err(BlockRegistryError::UnexpectedId {
+                    expected: expected as u8,
+                    found: definition.id,
+                });
+            }
+            if ids.insert(definition.name.clone(), definition.id).is_some() {
+                return Err(BlockRegistryError::DuplicateName(definition.name.clone()));
+            }
+            if definition.requires_tool && definition.tool.is_none() {
+                return Err(BlockRegistryError::MissingTool(definition.name.clone()));
+            }
+        }
+
+        for (block, name) in Block::BUILTIN {
+            if definitions.get(block.id() as usize).map(|d| d.name.as_str()) != Some(name) {
+                return Err(BlockRegistryError::MissingBuiltin { id: block.id(), name });
+            }
+        }
+
+        let drops = definitions
+            .iter()
+            .map(|definition| match &definition.drops {
+                None => Ok(Block::from_raw_id(definition.id)),
+                Some(drop) => ids.get(drop).map(|id| Block::from_raw_id(*id)).ok_or_else(|| {
+                    BlockRegistryError::UnknownDrop {
+                        block: definition.name.clone(),
+                        drop: drop.clone(),
+                    }
+                }),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let fluids = Self::compute_fluids(&definitions, &ids)?;
+
+        Ok(Self {
+            definitions,
+            ids,
+            drops,
+            fluids,
+        })
+    }
+
+    /// Follows the levels of each fluid, from its source to the last level
+    fn compute_fluids(
+        definitions: &[BlockDefinition],
+        ids: &HashMap<String, u8>,
+    ) -> Result<Vec<Option<FluidFlow>>, BlockRegistryError> {
+        let mut flows = vec![None; definitions.len()];
+        for definition in definitions {
+            let Some(flow) = &definition.flows_into else {
+                continue;
+            };
+            let invalid = || BlockRegistryError::InvalidFlow {
+                block: definition.name.clone(),
+                flow: flow.clone(),
+            };
+            let target = *ids.get(flow).ok_or_else(invalid)?;
+            if !definition.fluid || !definitions[target as usize].fluid || target == definition.id {
+                return Err(invalid());
+            }
+            flows[definition.id as usize] = Some(target);
+        }
+
+        let is_target = |id: u8| flows.contains(&Some(id));
+        let mut fluids = vec![None; definitions.len()];
+        for source in definitions.iter().filter(|d| d.fluid && !is_target(d.id)) {
+            // The levels of the fluid, from the source
+            let mut levels = vec![source.id];
+            while let Some(next) = flows[*levels.last().unwrap() as usize] {
+                if levels.contains(&next) {
+                    return Err(BlockRegistryError::InvalidFlow {
+                        block: definitions[next as usize].name.clone(),
+                        flow: source.name.clone(),
+                    });
+                }
+                levels.push(next);
+            }
+
+            let falls_into = Block::from_raw_id(*levels.get(1).unwrap_or(&source.id));
+            let n_levels = levels.len();
+            for (i, id) in levels.into_iter().enumerate() {
+                fluids[id as usize] = Some(FluidFlow {
+                    level: (n_levels - i) as u8,
+                    flows_into: flows[id as usize].map(Block::from_raw_id),
+                    falls_into,
+                    is_source: i == 0,
+                });
+            }
+        }
+
+        // The levels of a fluid that loops on itself have no source
+        match definitions
+            .iter()
+            .find(|d| d.fluid && fluids[d.id as usize].is_none())
+        {
+            Some(definition) => Err(BlockRegistryError::InvalidFlow {
+                block: definition.name.clone(),
+                flow: definition.flows_into.clone().unwrap_or_default(),
+            }),
+            None => Ok(fluids),
+        }
+    }
+
+    /// Loads the block definitions from a file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, BlockRegistryError> {
+        let json = std::fs::read_to_string(path).map_err(BlockRegistryError::Io)?;
+        Self::from_json(&json)
+    }
+
+    /// The definitions that are part of the game, used when no file is provided
+    pub fn builtin() -> Self {
+        Self::from_json(BUILTIN_BLOCKS).expect("The built-in blocks are invalid")
+    }
+
+    /// Loads the blocks from the given file, and makes them the blocks of the game.
+    /// If the file cannot be loaded, the built-in blocks are used.
+    /// This must be called before any block is used: the blocks cannot change afterwards.
+    pub fn install_from_file(path: impl AsRef<Path>) {
+        let registry = match Self::load(&path) {
+            Ok(registry) => {
+                info!("Loaded {} blocks from {:?}", registry.len(), path.as_ref());
+                registry
+            }
+            Err(err) => {
+                warn!("{err}, using the built-in blocks");
+                Self::builtin()
+            }
+        };
+        if REGISTRY.set(registry).is_err() {
+            warn!("The blocks are already in use, they cannot be replaced");
+        }
+    }
+
+    /// Returns the registry used by the game, which is the built-in one if none was installed
+    pub fn global() -> &'static Self {
+        REGISTRY.get_or_init(Self::builtin)
+    }
+
+    pub fn len(&self) -> usize {
+        self.definitions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.definitions.is_empty()
+    }
+
+    /// Returns a digest of the definitions (see `definitions_digest`)
+    pub fn digest(&self) -> u64 {
+        digest_of(&self.definitions)
+    }
+
+    /// Returns the definition of the block with the given id
+    pub fn get(&self, id: u8) -> Option<&BlockDefinition> {
+        self.definitions.get(id as usize)
+    }
+
+    /// Returns the block with the given name
+    pub fn by_name(&self, name: &str) -> Option<Block> {
+        self.ids.get(name).map(|id| Block::from_raw_id(*id))
+    }
+
+    /// Returns the block collected when the block with the given id is destroyed
+    pub(crate) fn drop_of(&self, id: u8) -> Option<Block> {
+        self.drops.get(id as usize).copied()
+    }
+
+    /// Returns how the block with the given id spreads, if it is a fluid
+    pub(crate) fn fluid_of(&self, id: u8) -> Option<FluidFlow> {
+        self.fluids.get(id as usize).copied().flatten()
+    }
+
+    /// Returns all the blocks, ordered by id
+    pub fn blocks(&self) -> impl Iterator<Item = Block> + '_ {
+        self.definitions
+            .iter()
+            .map(|definition| Block::from_raw_id(definition.id))
+    }
+
+    /// Returns a list of all the textures to be loaded, in the proper order: for each block, its
+    /// side, top and bottom textures.
+    pub fn texture_files(&self) -> Vec<String> {
+        self.definitions
+            .iter()
+            .flat_map(|definition| {
+                let textures = &definition.textures;
+                [
+                    textures.side.clone(),
+                    textures.top.clone(),
+                    textures.bottom.clone(),
+                ]
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::block_kind::Block;
+    use crate::world::block_registry::{
+        BlockRegistry, BlockRegistryError, FluidFlow, BUILTIN_BLOCKS, MAX_BLOCK_ID,
+    };
+
+    #[test]
+    fn test_builtin_blocks() {
+        let registry = BlockRegistry::builtin();
+        assert_eq!(12, registry.len());
+        assert_eq!(Some(Block::OAKLEAVES), registry.by_name("oak_leaves"));
+        assert_eq!(Some(Block::DIRT), registry.drop_of(Block::GRASS.id()));
+        assert_eq!(Some(Block::SAND), registry.drop_of(Block::SAND.id()));
+        assert_eq!("grass_top", registry.texture_files()[1]);
+        assert_eq!(3 * registry.len(), registry.texture_files().len());
+    }
+
+    #[test]
+    fn test_new_block_can_be_defined() {
+        let mut json = BUILTIN_BLOCKS.trim_end().trim_end_matches(']').to_string();
+        json.push_str(
+            r#", {
+                "id": 12,
+                "name": "glass",
+                "textures": { "side": "glass", "top": "glass", "bottom": "glass" },
+                "transparent": true,
+                "drops": "sand"
+            }]"#,
+        );
+        let registry = BlockRegistry::from_json(&json).unwrap();
+        let glass = registry.by_name("glass").unwrap();
+        let definition = registry.get(glass.id()).unwrap();
+        assert!(definition.transparent);
+        assert!(definition.placeable);
+        assert_eq!(0., definition.hardness);
+        assert_eq!(Some(Block::SAND), registry.drop_of(glass.id()));
+    }
+
+    #[test]
+    fn test_invalid_definitions_are_rejected() {
+        let block = |id: u8, name: &str, drops: &str| {
+            format!(
+                r#"{{"id": {id}, "name": "{name}", "drops": "{drops}",
+                     "textures": {{"side": "a", "top": "b", "bottom": "c"}}}}"#
+            )
+        };
+
+        let json = format!("[{}]", block(1, "grass", "grass"));
+        assert!(matches!(
+            BlockRegistry::from_json(&json),
+            Err(BlockRegistryError::UnexpectedId { expected: 0, found: 1 })
+        ));
+
+        let json = format!("[{}, {}]", block(0, "grass", "grass"), block(1, "grass", "grass"));
+        assert!(matches!(
+            BlockRegistry::from_json(&json),
+            Err(BlockRegistryError::DuplicateName(_))
+        ));
+
+        let json = format!("[{}]", block(0, "dirt", "dirt"));
+        assert!(matches!(
+            BlockRegistry::from_json(&json),
+            Err(BlockRegistryError::MissingBuiltin { id: 0, name: "grass" })
+        ));
+
+        let json = BUILTIN_BLOCKS.replace(r#""drops": "dirt""#, r#""drops": "diamond""#);
+        assert!(matches!(
+            BlockRegistry::from_json(&json),
+            Err(BlockRegistryError::UnknownDrop { .. })
+        ));
+
+        let json = BUILTIN_BLOCKS.replace(r#""tool": "pickaxe","#, "");
+        assert!(matches!(
+            BlockRegistry::from_json(&json),
+            Err(BlockRegistryError::MissingTool(_))
+        ));
+
+        // The last id is not available, to encode the chunks on one byte per cell
+        let n_builtin = BlockRegistry::builtin().len() as u8;
+        let with_blocks_up_to = |last: u8| {
+            let extra: Vec<String> = (n_builtin..=last)
+                .map(|id| block(id, &format!("block{id}"), &format!("block{id}")))
+                .collect();
+            let builtin = BUILTIN_BLOCKS.trim().strip_suffix(']').unwrap();
+            format!("{builtin}, {}]", extra.join(", "))
+        };
+        assert!(BlockRegistry::from_json(&with_blocks_up_to(MAX_BLOCK_ID)).is_ok());
+        assert!(matches!(
+            BlockRegistry::from_json(&with_blocks_up_to(u8::MAX)),
+            Err(BlockRegistryError::TooManyBlocks(256))
+        ));
+
+        assert!(matches!(BlockRegistry::from_json("{}"), Err(BlockRegistryError::Parse(_))));
+
+        let invalid_flow = r#""flows_into": "sand""#;
+        let json = BUILTIN_BLOCKS.replace(r#""flows_into": "flowing_water_4""#, invalid_flow);
+        assert!(matches!(
+            BlockRegistry::from_json(&json),
+            Err(BlockRegistryError::InvalidFlow { .. })
+        ));
+    }
+
+    #[test]
+    fn test_levels_of_fluids() {
+        let registry = BlockRegistry::builtin();
+        let flowing_4 = registry.by_name("flowing_water_4").unwrap();
+        let flowing_3 = registry.by_name("flowing_water_3").unwrap();
+        let flowing_1 = registry.by_name("flowing_water_1").unwrap();
+
+        let water = registry.fluid_of(Block::WATER.id()).unwrap();
+        assert_eq!(
+            FluidFlow {
+                level: 5,
+                flows_into: Some(flowing_4),
+                falls_into: flowing_4,
+                is_source: true,
+            },
+            water
+        );
+        let last = registry.fluid_of(flowing_1.id()).unwrap();
+        assert_eq!((1, None, false), (last.level, last.flows_into, last.is_source));
+        assert_eq!(Some(flowing_3), registry.fluid_of(flowing_4.id()).unwrap().flows_into);
+        assert_eq!(None, registry.fluid_of(Block::SAND.id()));
+    }
+}
@@ -1,21 +1,49 @@
-use strum::IntoEnumIterator;
+use std::fmt::{Display, Formatter};
 use tracing;
 use crate::collision::aabb::AABB;
 use crate::collision::collidable::{Collidable, CollisionData};
 use primitives::position::Position;
 use primitives::vector::Vector3;
 use crate::world::block_kind::Block;
-use crate::world::block_kind::Block::{DIRT, GRASS};
 use crate::world::cube::Cube;
-use crate::world::world_serializer::{get_serialize_container, serialize_one_chunk, SerializedWorld};
 
 type ChunkData = [[[Option<Cube>; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_HEIGHT];
+
+/// Content of one position of a chunk, as encoded in the binary representations:
+/// 0 if the position is empty, otherwise the id of the block plus one, and the number of neighbors.
+type CellCode = (u8, u8);
 pub type CubeIndex = (usize, usize, usize);
 
 pub const CHUNK_SIZE: usize = 8;
-const CHUNK_HEIGHT: usize = 64;
+pub const CHUNK_HEIGHT: usize = 64;
 pub const CHUNK_FLOOR: usize = 9;
 
+/// Error raised when a chunk can not be decoded from its binary representation
+#[derive(Debug, PartialEq)]
+pub enum ChunkDecodingError {
+    /// The data ends before the chunk is complete
+    MissingData,
+    /// The data contains an id that does not match any block
+    UnknownBlock(u8),
+    /// The data refers to an entry which is not part of the palette
+    UnknownPaletteEntry(u16),
+    /// The data describes more cubes than a chunk can hold
+    TooManyCubes,
+}
+
+impl Display for ChunkDecodingError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChunkDecodingError::MissingData => write!(f, "Chunk data is incomplete"),
+            ChunkDecodingError::UnknownBlock(id) => write!(f, "Unknown block id: {id}"),
+            ChunkDecodingError::UnknownPaletteEntry(i) => write!(f, "Unknown palette entry: {i}"),
+            ChunkDecodingError::TooManyCubes => write!(f, "Chunk data describes too many cubes"),
+        }
+    }
+}
+
+impl std::error::Error for ChunkDecodingError {}
+
 /// A chunk is a (size * size * h) partition of the space that contains cubes
 ///
 /// * A chunk is described by the position of one of his corner: the one with the lowest x-z value
@@ -45,6 +73,21 @@ impl Chunk {
         self.corner
     }
 
+    /// Returns the corner of the chunk, as integers
+    pub fn integer_corner(&self) -> (i32, i32) {
+        (self.corner[0] as i32, self.corner[1] as i32)
+    }
+
+    /// Returns the corner of the chunk that contains the given position, assuming that the chunks
+    /// are aligned on a grid of `CHUNK_SIZE`.
+    pub fn corner_of(pos: &Vector3) -> (i32, i32) {
+        let s = CHUNK_SIZE as i32;
+        (
+            (pos[0].floor() as i32).div_euclid(s) * s,
+            (pos[2].floor() as i32).div_euclid(s) * s,
+        )
+    }
+
     /// Returns an iterator over all the positions of the chunk
     pub fn cubes_iter(&self) -> impl Iterator<Item = &Option<Cube>> {
         self.cubes
@@ -70,7 +113,7 @@ impl Chunk {
                         (CHUNK_FLOOR as i32 + z_offset) as f32 - 2.,
                         corner[1] + j as f32,
                     ],
-                    DIRT,
+                    Block::DIRT,
                     0,
                 ));
                 cubes[(CHUNK_FLOOR as i32 - 1 + z_offset) as usize][i][j] = Some(Cube::new(
@@ -79,7 +122,7 @@ impl Chunk {
                         (CHUNK_FLOOR as i32 + z_offset) as f32 - 1.,
                         corner[1] + j as f32,
                     ],
-                    DIRT,
+                    Block::DIRT,
                     0,
                 ));
                 cubes[(CHUNK_FLOOR as i32 + z_offset) as usize][i][j] = Some(Cube::new(
@@ -88,7 +131,7 @@ impl Chunk {
                         (CHUNK_FLOOR as i32 + z_offset) as f32,
                         corner[1] + j as f32,
                     ],
-                    GRASS,
+                    Block::GRASS,
                     0,
                 ));
             }
@@ -252,6 +295,14 @@ impl Chunk {
         self.cube_at_index(self.get_indices(pos))
     }
 
+    /// Returns the highest cube in the column of the given position, which must be in the chunk
+    pub fn highest_cube_at(&self, pos: &Vector3) -> Option<&Cube> {
+        let (_, i, j) = self.get_indices(pos);
+        (0..CHUNK_HEIGHT)
+            .rev()
+            .find_map(|k| self.cube_at_index((k, i, j)))
+    }
+
     pub fn cube_at_mut(&mut self, pos: &Vector3) -> Option<&mut Cube> {
         self.cube_at_index_mut(self.get_indices(pos))
     }
@@ -284,31 +335,152 @@ impl Chunk {
         }
     }
 
-    pub fn to_json(&self) -> String {
-        let mut all_cubes = get_serialize_container();
-        serialize_one_chunk(&mut all_cubes, self);
-        let world = SerializedWorld {
-            chunk_corners: vec![self.corner],
-            cubes_by_kind: all_cubes,
-        };
-        serde_json::to_string(&world).unwrap()
-    }
-
-    pub fn from_json(data: &str) -> Result<Self, serde_json::Error> {
-        let serialized_world: SerializedWorld = serde_json::from_str(data)?;
-        let mut chunk = Chunk::new(serialized_world.chunk_corners[0]);
-        for block_kind in Block::iter() {
-            let cubes = serialized_world.cubes_by_kind.get(&block_kind).unwrap();
-            for cube_data in cubes {
-                let x = cube_data[0] as f32;
-                let y = cube_data[1] as f32;
-                let z = cube_data[2] as f32;
-                let neighbors = cube_data[3] as u8;
-                chunk.add_cube(Vector3::new(x, y, z), block_kind, neighbors);
+    /// Encodes the chunk into a compact binary representation.
+    ///
+    /// The data starts with the corner of the chunk, followed by one entry per position of the
+    /// chunk: a single `0` if the position is empty, otherwise the id of the block plus one,
+    /// followed by the number of neighbors of the cube.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.corner_bytes();
+        for cell in self.cells() {
+            match cell {
+                (0, _) => bytes.push(0),
+                (code, neighbors) => bytes.extend_from_slice(&[code, neighbors]),
             }
         }
+        bytes
+    }
+
+    /// Decodes a chunk from the representation computed by `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ChunkDecodingError> {
+        let mut data = bytes.iter().copied();
+        let mut next = || data.next().ok_or(ChunkDecodingError::MissingData);
+
+        let corner = [next()?, next()?, next()?, next()?, next()?, next()?, next()?, next()?];
+        let mut chunk = Self::from_corner_bytes(corner);
+        for index in 0..CHUNK_HEIGHT * CHUNK_SIZE * CHUNK_SIZE {
+            let code = next()?;
+            let neighbors = if code == 0 { 0 } else { next()? };
+            chunk.set_cell(index, (code, neighbors))?;
+        }
+
+        Ok(chunk)
+    }
+
+    /// Encodes the chunk with a palette and a run-length encoding.
+    ///
+    /// Chunks are mostly made of long series of identical positions (the air above the ground, the
+    /// layers of dirt...), which makes this representation much smaller than the one of
+    /// `to_bytes`. The data is made of
+    /// - the corner of the chunk
+    /// - the palette: the number of entries (u16), and for each entry the code of the block and the
+    ///   number of neighbors
+    /// - the runs, until all the positions of the chunk are described: the length of the run (u16)
+    ///   and the index of its entry in the palette (u16).
+    pub fn to_palette_bytes(&self) -> Vec<u8> {
+        let mut palette: Vec<CellCode> = Vec::new();
+        let mut runs: Vec<(u16, u16)> = Vec::new();
+        for cell in self.cells() {
+            let entry = match palette.iter().position(|c| *c == cell) {
+                Some(entry) => entry,
+                None => {
+                    palette.push(cell);
+                    palette.len() - 1
+                }
+            } as u16;
+            match runs.last_mut() {
+                Some((len, last)) if *last == entry => *len += 1,
+                _ => runs.push((1, entry)),
+            }
+        }
+
+        let mut bytes = self.corner_bytes();
+        bytes.extend_from_slice(&(palette.len() as u16).to_le_bytes());
+        for (code, neighbors) in palette {
+            bytes.extend_from_slice(&[code, neighbors]);
+        }
+        for (len, entry) in runs {
+            bytes.extend_from_slice(&len.to_le_bytes());
+            bytes.extend_from_slice(&entry.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Decodes a chunk from the representation computed by `to_palette_bytes`
+    pub fn from_palette_bytes(bytes: &[u8]) -> Result<Self, ChunkDecodingError> {
+        let mut data = bytes.iter().copied();
+        let mut next = || data.next().ok_or(ChunkDecodingError::MissingData);
+
+        let corner = [next()?, next()?, next()?, next()?, next()?, next()?, next()?, next()?];
+        let mut chunk = Self::from_corner_bytes(corner);
+        let palette_len = u16::from_le_bytes([next()?, next()?]);
+        let palette = (0..palette_len)
+            .map(|_| Ok((next()?, next()?)))
+            .collect::<Result<Vec<CellCode>, ChunkDecodingError>>()?;
+
+        let n_cells = CHUNK_HEIGHT * CHUNK_SIZE * CHUNK_SIZE;
+        let mut index = 0;
+        while index < n_cells {
+            let len = u16::from_le_bytes([next()?, next()?]) as usize;
+            let entry = u16::from_le_bytes([next()?, next()?]);
+            let cell = *palette
+                .get(entry as usize)
+                .ok_or(ChunkDecodingError::UnknownPaletteEntry(entry))?;
+            if index + len > n_cells {
+                return Err(ChunkDecodingError::TooManyCubes);
+            }
+            for i in index..index + len {
+                chunk.set_cell(i, cell)?;
+            }
+            index += len;
+        }
+
         Ok(chunk)
     }
+
+    fn corner_bytes(&self) -> Vec<u8> {
+        let mut bytes = (self.corner[0] as i32).to_le_bytes().to_vec();
+        bytes.extend_from_slice(&(self.corner[1] as i32).to_le_bytes());
+        bytes
+    }
+
+    fn from_corner_bytes(bytes: [u8; 8]) -> Self {
+        let x0 = i32::from_le_bytes(bytes[0..4].try_into().unwrap());
+        let z0 = i32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        Chunk::new([x0 as f32, z0 as f32])
+    }
+
+    /// Returns the code of each position of the chunk, sorted by height, then x, then z.
+    /// The ids of the blocks are shifted by one, which cannot overflow since they stop at
+    /// `MAX_BLOCK_ID`.
+    fn cells(&self) -> impl Iterator<Item = CellCode> + '_ {
+        self.cubes.iter().flatten().flatten().map(|cube| match cube {
+            Some(cube) => (cube.block_id() + 1, cube.n_neighbors()),
+            None => (0, 0),
+        })
+    }
+
+    /// Fills the position with the given index in the order of `cells`
+    fn set_cell(
+        &mut self,
+        index: usize,
+        (code, neighbors): CellCode,
+    ) -> Result<(), ChunkDecodingError> {
+        let (k, i, j) = (
+            index / (CHUNK_SIZE * CHUNK_SIZE),
+            (index / CHUNK_SIZE) % CHUNK_SIZE,
+            index % CHUNK_SIZE,
+        );
+        self.cubes[k][i][j] = if code == 0 {
+            None
+        } else {
+            let block =
+                Block::from_id(code - 1).ok_or(ChunkDecodingError::UnknownBlock(code - 1))?;
+            let position = [self.corner[0] + i as f32, k as f32, self.corner[1] + j as f32];
+            Some(Cube::new(position, block, neighbors))
+        };
+        Ok(())
+    }
 }
 
 impl Collidable for Chunk {
@@ -373,8 +545,8 @@ impl Collidable for Chunk {
 #[cfg(test)]
 mod tests {
     use primitives::vector::Vector3;
-    use crate::world::block_kind::Block::GRASS;
-    use crate::world::chunk::{Chunk, CHUNK_HEIGHT, CHUNK_SIZE};
+    use crate::world::block_kind::Block;
+    use crate::world::chunk::{Chunk, ChunkDecodingError, CHUNK_HEIGHT, CHUNK_SIZE};
 
     #[test]
     fn test_bounding_area() {
@@ -394,6 +566,14 @@ mod tests {
         assert!(!chunk.is_in(&Vector3::new(0., 0., 3.)));
     }
 
+    #[test]
+    fn test_corner_of() {
+        let s = CHUNK_SIZE as i32;
+        assert_eq!((0, 0), Chunk::corner_of(&Vector3::new(0., 30., 7.5)));
+        assert_eq!((s, -s), Chunk::corner_of(&Vector3::new(8., 30., -0.5)));
+        assert_eq!((-2 * s, 0), Chunk::corner_of(&Vector3::new(-9., 0., 3.)));
+    }
+
     #[test]
     fn test_free_check_1() {
         let mut chunk = Chunk::new([0., 0.]);
@@ -410,7 +590,7 @@ mod tests {
         }
 
         // Fill the 10-th layer
-        chunk.fill_layer(10, GRASS);
+        chunk.fill_layer(10, Block::GRASS);
 
         // Assert that only positions on the 10.th layer are not free
         for k in 0..CHUNK_HEIGHT {
@@ -433,14 +613,14 @@ mod tests {
     #[test]
     fn test_free_check_2() {
         let mut chunk = Chunk::new([0., 0.]);
-        chunk.fill_layer(9, GRASS);
+        chunk.fill_layer(9, Block::GRASS);
         assert!(chunk.is_position_free_or_transparent(&Vector3::new(4.0, 10.1, 4.0)));
     }
 
     #[test]
     fn test_free_check_3() {
         let mut chunk = Chunk::new([0., 0.]);
-        chunk.fill_layer(0, GRASS);
+        chunk.fill_layer(0, Block::GRASS);
         assert!(!chunk.is_position_free_or_transparent(&Vector3::new(4.0, 0.1, 4.0)));
         assert!(!chunk.is_position_free_or_transparent(&Vector3::new(4.0, 0.5, 4.0)));
         assert!(!chunk.is_position_free_or_transparent(&Vector3::new(4.0, 0.9, 4.0)));
@@ -452,9 +632,9 @@ mod tests {
     #[test]
     fn test_visible_cube_in_one_chunnk() {
         let mut chunk = Chunk::new([0., 0.]);
-        chunk.fill_layer(0, GRASS);
-        chunk.fill_layer(1, GRASS);
-        chunk.fill_layer(2, GRASS);
+        chunk.fill_layer(0, Block::GRASS);
+        chunk.fill_layer(1, Block::GRASS);
+        chunk.fill_layer(2, Block::GRASS);
 
         // Before computing visible cube, we make sure that all cubes are visible
         assert_eq!(chunk.visible_cube_count(), 3 * CHUNK_SIZE * CHUNK_SIZE);
@@ -471,11 +651,48 @@ mod tests {
     }
 
     #[test]
-    fn test_chunk_persistence() {
-        let chunk = Chunk::new_for_demo([3., 4.], 5);
-        let serialized = chunk.to_json();
-        let reconstructed = Chunk::from_json(serialized.as_str()).unwrap();
-        assert_eq!(chunk, reconstructed);
+    fn test_chunk_binary_persistence() {
+        let mut chunk = Chunk::new_for_demo([-16., 24.], 5);
+        chunk.compute_visible_cubes();
+        let bytes = chunk.to_bytes();
+        assert_eq!(chunk, Chunk::from_bytes(&bytes).unwrap());
+
+        // Incomplete data must be refused
+        assert_eq!(
+            Err(ChunkDecodingError::MissingData),
+            Chunk::from_bytes(&bytes[..bytes.len() - 1])
+        );
+    }
+
+    #[test]
+    fn test_chunk_palette_encoding() {
+        let mut chunk = Chunk::new_for_demo([-16., 24.], 5);
+        chunk.compute_visible_cubes();
+        let bytes = chunk.to_palette_bytes();
+        assert_eq!(chunk, Chunk::from_palette_bytes(&bytes).unwrap());
+
+        // The palette encoding is much smaller than the plain one
+        assert!(10 * bytes.len() < chunk.to_bytes().len());
+
+        // Invalid data must be refused
+        assert_eq!(
+            Err(ChunkDecodingError::MissingData),
+            Chunk::from_palette_bytes(&bytes[..bytes.len() - 1])
+        );
+        let mut too_long = bytes.clone();
+        let n = too_long.len();
+        too_long[n - 4] += 1;
+        assert_eq!(
+            Err(ChunkDecodingError::TooManyCubes),
+            Chunk::from_palette_bytes(&too_long)
+        );
+    }
+
+    #[test]
+    fn test_empty_chunk_palette_encoding() {
+        let chunk = Chunk::new([8., 0.]);
+        let bytes = chunk.to_palette_bytes();
+        assert_eq!(chunk, Chunk::from_palette_bytes(&bytes).unwrap());
     }
 
     #[test]
@@ -32,7 +32,7 @@ impl Cube {
     }
 
     pub fn block_id(&self) -> u8 {
-        self.block as u8
+        self.block.id()
     }
 
     pub fn position(&self) -> &Vector3 {
@@ -172,9 +172,10 @@ impl Cube {
     }
 }
 
+/// The entities go through the fluids
 impl Collidable for Cube {
     fn collides(&self, aabb: &AABB) -> bool {
-        self.aabb().collides(&aabb)
+        !self.block.is_fluid() && self.aabb().collides(&aabb)
     }
 
     fn collision_time(
@@ -184,6 +185,9 @@ impl Collidable for Cube {
         target: &AABB,
         velocity: &Vector3,
     ) -> Option<CollisionData> {
+        if self.block.is_fluid() {
+            return None;
+        }
         let cube_aabb = self.aabb();
 
         if aabb.collides(&cube_aabb) {
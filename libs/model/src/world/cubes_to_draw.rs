@@ -1,5 +1,5 @@
 use primitives::render_data::CubeRenderData;
-use crate::world::chunk::Chunk;
+use crate::world::chunk::{Chunk, CHUNK_SIZE};
 use crate::world::cube::Cube;
 use primitives::vector::Vector3;
 
@@ -33,6 +33,8 @@ impl CubesToDraw {
             position: c.position().clone(),
             block_id: c.block_id(),
             is_selected: false,
+            scale: 1.,
+            texture_id: None,
         });
     }
 
@@ -45,6 +47,8 @@ impl CubesToDraw {
                     position: selected.position().clone(),
                     block_id: selected.block_id(),
                     is_selected: true,
+                    scale: 1.,
+                    texture_id: None,
                 });
         }
         let buffer = self.cubes_to_draw.clone();
@@ -69,6 +73,16 @@ impl CubesToDraw {
         }
     }
 
+    /// Removes all the cubes of the chunk with the given corner
+    pub fn remove_chunk(&mut self, corner: [f32; 2]) {
+        let s = CHUNK_SIZE as f32;
+        let is_in_chunk = |p: &Vector3| {
+            p[0] >= corner[0] && p[0] < corner[0] + s && p[2] >= corner[1] && p[2] < corner[1] + s
+        };
+        self.cubes_to_draw.retain(|cube| !is_in_chunk(&cube.position));
+        self.selected_cube_index = None;
+    }
+
     pub fn cubes_to_draw(&self) -> &[CubeRenderData] {
         &self.cubes_to_draw
     }
@@ -94,7 +108,7 @@ impl CubesToDraw {
 #[cfg(test)]
 mod tests {
     use super::CubesToDraw;
-    use crate::world::block_kind::Block::DIRT;
+    use crate::world::block_kind::Block;
     use crate::world::cube::Cube;
     use primitives::vector::Vector3;
 
@@ -102,9 +116,9 @@ mod tests {
     fn test_add_remove_one_cube() {
         let mut cube_to_draw = CubesToDraw::new();
         let mut vec_to_draw = Vec::new();
-        vec_to_draw.push(Cube::new([0., 0., 0.], DIRT, 0));
+        vec_to_draw.push(Cube::new([0., 0., 0.], Block::DIRT, 0));
 
-        cube_to_draw.add_cube(&Cube::new([0., 0., 0.], DIRT, 0));
+        cube_to_draw.add_cube(&Cube::new([0., 0., 0.], Block::DIRT, 0));
 
         assert!(cube_to_draw.cubes_to_draw().len() == 1);
 
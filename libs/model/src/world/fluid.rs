@@ -0,0 +1,237 @@
+use crate::game::actions::Action;
+use crate::game::player::GRAVITY_ACCELERATION_VECTOR;
+use crate::world::block_kind::Block;
+use crate::world::cube::Cube;
+use crate::world::world::World;
+use primitives::vector::Vector3;
+use std::collections::HashSet;
+
+/// Time [s] between two steps of the fluids
+pub const FLUID_TICK: f32 = 0.25;
+
+/// Fraction of the gravity felt by an entity in a fluid, the rest being balanced by buoyancy
+const FLUID_GRAVITY_FACTOR: f32 = 0.2;
+
+/// Rate [1/s] at which a fluid slows down the vertical motion of an entity
+const FLUID_DRAG: f32 = 2.;
+
+/// Maximum speed [cube/s] at which an entity sinks in a fluid
+const MAX_SINKING_SPEED: f32 = 2.;
+
+/// Vertical speed [cube/s] of an entity swimming up
+const SWIMMING_SPEED: f32 = 3.;
+
+/// Fraction of its usual horizontal speed that an entity keeps in a fluid
+pub const FLUID_SPEED_FACTOR: f32 = 0.5;
+
+/// Updates the vertical velocity of an entity in a fluid: it sinks slowly, unless it swims up
+pub fn swim(velocity: &mut Vector3, dt: f32, swimming_up: bool) {
+    *velocity += GRAVITY_ACCELERATION_VECTOR * (FLUID_GRAVITY_FACTOR * dt);
+    velocity[1] *= (1. - FLUID_DRAG * dt).max(0.);
+    if swimming_up {
+        velocity[1] = velocity[1].max(SWIMMING_SPEED);
+    }
+    velocity[1] = velocity[1].max(-MAX_SINKING_SPEED);
+}
+
+/// Returns the fluid that must fill the cell at `at`, given the fluids around it.
+///
+/// A fluid falls into the free cell below it. On a solid ground, it spreads to the free cells
+/// next to it with a lower level, so that it covers a distance given by its level.
+fn expected_fluid(world: &World, at: Vector3) -> Option<Block> {
+    let fluid_at = |pos: Vector3| world.block_at(&pos).and_then(|block| block.fluid());
+
+    if let Some(above) = fluid_at(at + Vector3::unit_y()) {
+        return Some(above.falls_into);
+    }
+
+    let horizontal = [
+        Vector3::unit_x(),
+        Vector3::unit_x().opposite(),
+        Vector3::unit_z(),
+        Vector3::unit_z().opposite(),
+    ];
+    horizontal
+        .into_iter()
+        .map(|direction| at + direction)
+        .filter(|neighbor| {
+            let below = world.block_at(&(*neighbor - Vector3::unit_y()));
+            below.is_some_and(|block| !block.is_fluid())
+        })
+        .filter_map(|neighbor| fluid_at(neighbor)?.flows_into)
+        .max_by_key(|block| block.fluid().map_or(0, |fluid| fluid.level))
+}
+
+/// Returns the action that makes the fluid flow at `at`, if the cell must change.
+/// Only the free cells and the flowing levels of the fluids change: the solid cubes and the sources
+/// of the fluids stay as they are.
+pub fn flow_at(world: &World, at: Vector3) -> Option<Action> {
+    if !world.is_loaded(&at) {
+        return None;
+    }
+    let current = world.block_at(&at);
+    if current.is_some_and(|block| block.fluid().is_none_or(|fluid| fluid.is_source)) {
+        return None;
+    }
+
+    let expected = expected_fluid(world, at);
+    if expected == current {
+        return None;
+    }
+    Some(match expected {
+        Some(block) => Action::Add { at, block },
+        None => Action::Destroy { at },
+    })
+}
+
+/// Makes the fluids of the world flow, on the server.
+///
+/// Only the cells next to a change of the world are updated, so that the fluids placed by the
+/// world generation stay still until a player disturbs them.
+#[derive(Default)]
+pub struct FluidSimulator {
+    /// The cells to update at the next step
+    to_update: HashSet<(i32, i32, i32)>,
+    /// Time [s] since the last step
+    since_last_step: f32,
+}
+
+impl FluidSimulator {
+    pub fn new() -> Self {
+        Self {
+            to_update: HashSet::new(),
+            since_last_step: 0.,
+        }
+    }
+
+    /// The cube at `at` changed: the fluids around it may flow
+    pub fn wake(&mut self, at: Vector3) {
+        for pos in Cube::neighbors_positions(at).into_iter().chain([at]) {
+            self.to_update
+                .insert((pos[0] as i32, pos[1] as i32, pos[2] as i32));
+        }
+    }
+
+    /// Returns true if no fluid is flowing
+    pub fn is_idle(&self) -> bool {
+        self.to_update.is_empty()
+    }
+
+    /// Lets the time pass. Every `FLUID_TICK`, returns the actions that make the fluids flow by one
+    /// cell, computed from the current state of the world. The caller applies them, and wakes the
+    /// changed cells.
+    pub fn step(&mut self, dt: f32, world: &World) -> Vec<Action> {
+        self.since_last_step += dt;
+        if self.since_last_step < FLUID_TICK {
+            return Vec::new();
+        }
+        self.since_last_step = 0.;
+
+        std::mem::take(&mut self.to_update)
+            .into_iter()
+            .map(|(x, y, z)| Vector3::new(x as f32, y as f32, z as f32))
+            .filter_map(|at| flow_at(world, at))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::actions::Action;
+    use crate::world::block_kind::Block;
+    use crate::world::chunk::Chunk;
+    use crate::world::fluid::{swim, FluidSimulator, FLUID_TICK, MAX_SINKING_SPEED};
+    use crate::world::world::World;
+    use primitives::vector::Vector3;
+
+    /// Runs the simulation until the fluids stop flowing
+    fn flow_until_still(world: &mut World, fluids: &mut FluidSimulator) {
+        for _ in 0..100 {
+            for action in fluids.step(FLUID_TICK, world) {
+                world.apply_action(&action);
+                fluids.wake(action.position());
+            }
+            if fluids.is_idle() {
+                return;
+            }
+        }
+        panic!("The fluids never stop flowing");
+    }
+
+    fn flat_world() -> World {
+        let mut chunk = Chunk::new([0., 0.]);
+        chunk.fill_layer(0, Block::STONE);
+        World::new(vec![chunk])
+    }
+
+    #[test]
+    fn test_water_spreads_and_dries_up() {
+        let mut world = flat_world();
+        let mut fluids = FluidSimulator::new();
+        let source = Vector3::new(1., 1., 1.);
+        world.apply_action(&Action::Add {
+            at: source,
+            block: Block::WATER,
+        });
+        fluids.wake(source);
+        flow_until_still(&mut world, &mut fluids);
+
+        let level_at = |world: &World, x: f32| {
+            world
+                .block_at(&Vector3::new(x, 1., 1.))
+                .and_then(|block| block.fluid())
+                .map(|fluid| fluid.level)
+        };
+        assert_eq!(
+            vec![Some(4), Some(5), Some(4), Some(3), Some(2), Some(1), None],
+            (0..7).map(|x| level_at(&world, x as f32)).collect::<Vec<_>>()
+        );
+        // The water does not climb
+        assert_eq!(None, world.block_at(&Vector3::new(1., 2., 1.)));
+
+        // Without its source, the flowing water disappears
+        world.apply_action(&Action::Add {
+            at: source,
+            block: Block::DIRT,
+        });
+        fluids.wake(source);
+        flow_until_still(&mut world, &mut fluids);
+        assert_eq!(None, level_at(&world, 0.));
+        assert_eq!(None, level_at(&world, 3.));
+    }
+
+    #[test]
+    fn test_water_falls() {
+        let mut world = flat_world();
+        let mut fluids = FluidSimulator::new();
+        let source = Vector3::new(4., 5., 4.);
+        world.apply_action(&Action::Add {
+            at: source,
+            block: Block::WATER,
+        });
+        fluids.wake(source);
+        flow_until_still(&mut world, &mut fluids);
+
+        // The water falls straight down, and only spreads once on the ground
+        let falls_into = Block::WATER.fluid().unwrap().falls_into;
+        for y in 1..5 {
+            assert_eq!(Some(falls_into), world.block_at(&Vector3::new(4., y as f32, 4.)));
+        }
+        assert_eq!(None, world.block_at(&Vector3::new(5., 4., 4.)));
+        assert!(world
+            .block_at(&Vector3::new(5., 1., 4.))
+            .is_some_and(|block| block.is_fluid()));
+    }
+
+    #[test]
+    fn test_swimming() {
+        let mut velocity = Vector3::new(0., -20., 0.);
+        for _ in 0..100 {
+            swim(&mut velocity, 0.01, false);
+        }
+        assert!(velocity[1] < 0. && velocity[1] >= -MAX_SINKING_SPEED);
+
+        swim(&mut velocity, 0.01, true);
+        assert!(velocity[1] > 0.);
+    }
+}
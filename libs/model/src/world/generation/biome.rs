@@ -1,4 +1,3 @@
-use std::hash::{Hash, Hasher};
 use std::vec;
 
 use rand::distributions::Open01;
@@ -6,8 +5,7 @@ use rand::rngs::SmallRng;
 use rand::{Rng, RngCore, SeedableRng};
 use crate::world::block_kind::Block;
 use crate::world::chunk::CHUNK_SIZE;
-use super::biomes_def::NUM_BIOMES;
-use super::perlin::{PerlinNoiseConfig, MAX_LEVEL_NOISE};
+use super::perlin::{seeded_hash, PerlinNoiseConfig, MAX_LEVEL_NOISE};
 
 const PROBABILITY_BIOME_CENTER_IN_CHUNK: f32 = 0.05;
 const MAX_NUMBER_LAYER: usize = 8;
@@ -124,17 +122,15 @@ impl BiomeGenerator {
     }
 
     /// For a given chunk, computes if it has a biome center and if it does of what kind and where
-    fn get_chunk_biome_center(seed: u64, chunk_coord: [i64; 2]) -> (Option<[i32; 2]>, u64) {
+    ///
+    /// `n_biomes`: the number of biomes that can be chosen from
+    fn get_chunk_biome_center(
+        seed: u64,
+        n_biomes: u64,
+        chunk_coord: [i64; 2],
+    ) -> (Option<[i32; 2]>, u64) {
         // Generate a seed for deterministic PRNG
-        let mut hasher = std::hash::DefaultHasher::new();
-        // Hash the seed
-        seed.hash(&mut hasher);
-        // Hash the chunk coordinates
-        chunk_coord.hash(&mut hasher);
-        // Combine the hashes into a final value
-        let specific_seed_hash = hasher.finish();
-
-        let mut rng: SmallRng = SmallRng::seed_from_u64(specific_seed_hash);
+        let mut rng: SmallRng = SmallRng::seed_from_u64(seeded_hash(seed, &chunk_coord));
 
         if rng.sample::<f32, Open01>(Open01) < PROBABILITY_BIOME_CENTER_IN_CHUNK {
             let x = rng.next_u64() % 8;
@@ -142,7 +138,7 @@ impl BiomeGenerator {
 
             (
                 Some(Self::get_world_coord(chunk_coord, [x, z])),
-                (rng.next_u64() % NUM_BIOMES),
+                (rng.next_u64() % n_biomes),
             )
         } else {
             (None, u64::MAX)
@@ -179,7 +175,9 @@ impl BiomeGenerator {
     }
 
     /// For a given world pos, look for the nearest biome center and return its type
-    pub fn find_closest_biome(seed: u64, x: i32, z: i32) -> u64 {
+    ///
+    /// `n_biomes`: the number of biomes that can be chosen from
+    pub fn find_closest_biome(seed: u64, n_biomes: u64, x: i32, z: i32) -> u64 {
         let world_pos: [i32; 2] = [x, z];
         let mut current_level: i64 = 0;
 
@@ -198,7 +196,8 @@ impl BiomeGenerator {
                     center_chunk_coord[0] + chunk[0],
                     center_chunk_coord[1] + chunk[1],
                 ];
-                let (center_opt, biome_type) = BiomeGenerator::get_chunk_biome_center(seed, coords);
+                let (center_opt, biome_type) =
+                    BiomeGenerator::get_chunk_biome_center(seed, n_biomes, coords);
 
                 if let Some(center) = center_opt {
                     let diff = [
@@ -260,8 +259,10 @@ mod tests {
         let seed: u64 = 42;
         let coords: [i64; 2] = [0, 2];
 
-        let (center1, biome_type1) = BiomeGenerator::get_chunk_biome_center(seed, coords);
-        let (center2, biome_type2) = BiomeGenerator::get_chunk_biome_center(seed, coords);
+        let (center1, biome_type1) =
+            BiomeGenerator::get_chunk_biome_center(seed, NUM_BIOMES, coords);
+        let (center2, biome_type2) =
+            BiomeGenerator::get_chunk_biome_center(seed, NUM_BIOMES, coords);
 
         assert_eq!(center1, center2);
         assert_eq!(biome_type1, biome_type2)
@@ -300,8 +301,8 @@ mod tests {
         let x: i32 = -1;
         let z: i32 = 10;
 
-        let biome_t1 = BiomeGenerator::find_closest_biome(seed, x, z);
-        let biome_t2 = BiomeGenerator::find_closest_biome(seed, x, z);
+        let biome_t1 = BiomeGenerator::find_closest_biome(seed, NUM_BIOMES, x, z);
+        let biome_t2 = BiomeGenerator::find_closest_biome(seed, NUM_BIOMES, x, z);
 
         assert_eq!(biome_t1, biome_t2);
     }
@@ -11,7 +11,6 @@ use rand::distributions::Open01;
 use rand::rngs::SmallRng;
 use rand::{Rng, SeedableRng};
 use std::collections::HashMap;
-use std::hash::{Hash, Hasher};
 use std::iter::zip;
 
 pub const MAX_LEVEL_NOISE: usize = 5;
@@ -126,18 +125,30 @@ impl PerlinNoise {
     }
 }
 
+/// Combines a seed and a coordinate into a new seed, to be used by a deterministic PRNG.
+///
+/// Unlike `std::hash::DefaultHasher`, whose algorithm may change between Rust releases, the
+/// output of this function is guaranteed to never change: a given seed always generates the same
+/// world.
+pub fn seeded_hash(seed: u64, coord: &[i64; 2]) -> u64 {
+    // Mixing function of splitmix64
+    fn mix(mut z: u64) -> u64 {
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    let mut hash = mix(seed.wrapping_add(0x9e3779b97f4a7c15));
+    for value in coord {
+        hash = mix(hash ^ (*value as u64).wrapping_add(0x9e3779b97f4a7c15));
+    }
+    hash
+}
+
 /// Returns a deterministic random gradient for a given coord and seed
 fn random_gradient(coord: &[i64; 2], seed: u64) -> [f32; 2] {
     // Generate a seed for deterministic PRNG
-    let mut hasher = std::hash::DefaultHasher::new();
-    // Hash the seed
-    seed.hash(&mut hasher);
-    // Hash the chunk coordinates
-    coord.hash(&mut hasher);
-    // Combine the hashes into a final value
-    let specific_seed_hash = hasher.finish();
-
-    let mut rng: SmallRng = SmallRng::seed_from_u64(specific_seed_hash);
+    let mut rng: SmallRng = SmallRng::seed_from_u64(seeded_hash(seed, coord));
 
     // TODO uniform distributions do not yield uniform normalized vectors !
     // Should use either gaussians, or a polar representation
@@ -176,7 +187,6 @@ mod tests {
     }
 
     #[test]
-    #[ignore] // TODO remove this when determinism is ensured
     fn test_reproducible_gradient() {
         let u = random_gradient(&[1, 250], 42);
         let v = random_gradient(&[1, 250], 42);
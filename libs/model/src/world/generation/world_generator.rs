@@ -1,76 +1,143 @@
-use super::biome::BiomeGenerator;
+use super::biome::{BiomeConfig, BiomeGenerator};
 use super::biomes_def::{BASE_BIOME_CONFIG, BIOMES, SINGLE_NOISE_CONFIG};
 use super::perlin::MultiscalePerlinNoise;
 use primitives::vector::Vector3;
-use crate::world::block_kind::Block::{DIRT, GRASS};
+use crate::world::block_kind::Block;
 use crate::world::chunk::{Chunk, CHUNK_FLOOR, CHUNK_SIZE};
 use crate::world::world::World;
+use serde::{Deserialize, Serialize};
 
-/// Class which manages the generation of a new world
-pub struct WorldGenerator {}
+/// Seed used when none is provided by the user
+pub const DEFAULT_SEED: u64 = 42;
+
+/// Kind of terrain of a world, stored with it
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TerrainKind {
+    Random,
+    Flat,
+}
+
+/// What a generator is made of. It is stored with the world, so that the chunks that were never
+/// saved are generated the same way as the saved ones when the world is loaded again.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Level {
+    pub seed: u64,
+    pub terrain: TerrainKind,
+}
+
+/// The different kinds of terrain that the generator can produce
+enum Terrain {
+    /// Hills and biomes, computed from a multiscale perlin noise
+    Random(Box<MultiscalePerlinNoise>),
+    /// A simple, flat grassland
+    Flat,
+}
+
+/// Class which manages the generation of a new world.
+///
+/// The generator works chunk by chunk, which allows the server to create the chunks on demand when
+/// players walk towards areas that were never visited.
+///
+/// The generation is fully deterministic: for a given seed and biome table, a chunk is always
+/// generated the same way, regardless of the order in which the chunks are generated.
+pub struct WorldGenerator {
+    seed: u64,
+    biomes: Vec<BiomeConfig>,
+    terrain: Terrain,
+}
 
 impl WorldGenerator {
-    /// Creates a simple world with hills
-    pub fn create_new_random_world(n_chunks: i32) -> World {
-        //let mut noise = PerlinNoise::new(121, 32.);
-        let seed: u64 = 42;
-        let mut noise = MultiscalePerlinNoise::new(seed, BIOMES[0].noise_config.clone());
+    /// Returns a generator of worlds with hills, using the default biomes
+    pub fn random(seed: u64) -> Self {
+        Self::with_biomes(seed, BIOMES.to_vec())
+    }
+
+    /// Returns a generator of worlds with hills, using the provided table of biomes
+    pub fn with_biomes(seed: u64, biomes: Vec<BiomeConfig>) -> Self {
+        assert!(!biomes.is_empty(), "At least one biome must be provided");
+        let noise = MultiscalePerlinNoise::new(seed, biomes[0].noise_config.clone());
+        Self {
+            seed,
+            biomes,
+            terrain: Terrain::Random(Box::new(noise)),
+        }
+    }
+
+    /// Returns a generator of flat worlds
+    pub fn flat() -> Self {
+        Self {
+            seed: DEFAULT_SEED,
+            biomes: Vec::new(),
+            terrain: Terrain::Flat,
+        }
+    }
+
+    /// Returns the generator of the level, with the default biomes for a random terrain
+    pub fn from_level(level: &Level) -> Self {
+        match level.terrain {
+            TerrainKind::Random => Self::random(level.seed),
+            TerrainKind::Flat => Self::flat(),
+        }
+    }
+
+    /// Returns the seed and the kind of terrain of the generator
+    pub fn level(&self) -> Level {
+        let terrain = match self.terrain {
+            Terrain::Random(_) => TerrainKind::Random,
+            Terrain::Flat => TerrainKind::Flat,
+        };
+        Level {
+            seed: self.seed,
+            terrain,
+        }
+    }
 
-        let s = CHUNK_SIZE as f32;
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the name of the biome of the column at (x, z), or None for a flat world
+    pub fn biome_at(&self, x: f32, z: f32) -> Option<&'static str> {
+        match self.terrain {
+            Terrain::Random(_) => {
+                let n_biomes = self.biomes.len() as u64;
+                let (x, z) = (x.floor() as i32, z.floor() as i32);
+                let biome = BiomeGenerator::find_closest_biome(self.seed, n_biomes, x, z);
+                Some(self.biomes[biome as usize].name)
+            }
+            Terrain::Flat => None,
+        }
+    }
+
+    /// Generates the chunk whose corner is located at the given position.
+    pub fn generate_chunk(&mut self, corner: (i32, i32)) -> Chunk {
+        match &mut self.terrain {
+            Terrain::Random(noise) => Self::random_chunk(self.seed, &self.biomes, noise, corner),
+            Terrain::Flat => Self::flat_chunk(corner),
+        }
+    }
+
+    /// Generates all the chunks of a square of `(2n+1) x (2n+1)` chunks centered on the origin.
+    fn generate_square(&mut self, n_chunks: i32) -> World {
+        let s = CHUNK_SIZE as i32;
         let mut chunks = vec![];
 
         // Yes this is slow, but it will be fine for now
         for i in -n_chunks..n_chunks + 1 {
             for j in -n_chunks..n_chunks + 1 {
-                let x0 = i as f32 * s;
-                let z0 = j as f32 * s;
-                let mut chunk = Chunk::new([x0, z0]);
-
-                // get the height from the perlin noise for each block
-                for x in 0..8 {
-                    for z in 0..8 {
-                        let biome_t: u64 =
-                            BiomeGenerator::find_closest_biome(seed, x + x0 as i32, z + z0 as i32);
-
-                        let biome_config = &BIOMES[biome_t as usize];
-
-                        if SINGLE_NOISE_CONFIG {
-                            noise.change_config(BASE_BIOME_CONFIG);
-                        } else {
-                            noise.change_config(biome_config.noise_config.clone());
-                        }
-
-                        let height = biome_config.terrain_offset
-                            + biome_config.terrain_scale
-                                * noise.at([i as f32 * s + x as f32, j as f32 * s + z as f32]);
-
-                        let cube_height = height.floor() as i32;
-
-                        for y in 0..cube_height {
-                            let block_at_height = biome_config.get_block_at(cube_height - y - 1);
-
-                            if let Some(block) = block_at_height {
-                                chunk.add_cube(
-                                    Vector3::new(
-                                        i as f32 * s + x as f32,
-                                        y as f32,
-                                        j as f32 * s + z as f32,
-                                    ),
-                                    block,
-                                    0,
-                                );
-                            }
-                        }
-                    }
-                }
-
-                chunks.push(chunk);
+                chunks.push(self.generate_chunk((i * s, j * s)));
             }
         }
 
         World::new(chunks)
     }
 
+    /// Creates a simple world with hills
+    pub fn create_new_random_world(seed: u64, n_chunks: i32) -> World {
+        Self::random(seed).generate_square(n_chunks)
+    }
+
     /// Creates a basic, flat world. For now this is a simple, flat
     /// grassland, extending `nchunks` in each direction.
     ///
@@ -78,21 +145,122 @@ impl WorldGenerator {
     /// direction; i.e., (2nchunks + 1) x (2nchunks + 1) chunks will
     /// be created
     pub fn create_new_flat_world(n_chunks: i32) -> World {
-        let s = CHUNK_SIZE as f32;
-        let mut chunks = vec![];
+        Self::flat().generate_square(n_chunks)
+    }
 
-        // Yes this is slow, but it will be fine for now
-        for i in -n_chunks..n_chunks + 1 {
-            for j in -n_chunks..n_chunks + 1 {
-                let mut chunk = Chunk::new([i as f32 * s, j as f32 * s]);
-                for k in 0..CHUNK_FLOOR {
-                    chunk.fill_layer(k, DIRT);
+    fn random_chunk(
+        seed: u64,
+        biomes: &[BiomeConfig],
+        noise: &mut MultiscalePerlinNoise,
+        corner: (i32, i32),
+    ) -> Chunk {
+        let (x0, z0) = corner;
+        let mut chunk = Chunk::new([x0 as f32, z0 as f32]);
+
+        // get the height from the perlin noise for each block
+        for x in 0..CHUNK_SIZE as i32 {
+            for z in 0..CHUNK_SIZE as i32 {
+                let biome_t: u64 = BiomeGenerator::find_closest_biome(
+                    seed,
+                    biomes.len() as u64,
+                    x + x0,
+                    z + z0,
+                );
+
+                let biome_config = &biomes[biome_t as usize];
+
+                if SINGLE_NOISE_CONFIG {
+                    noise.change_config(BASE_BIOME_CONFIG);
+                } else {
+                    noise.change_config(biome_config.noise_config.clone());
+                }
+
+                let height = biome_config.terrain_offset
+                    + biome_config.terrain_scale * noise.at([(x0 + x) as f32, (z0 + z) as f32]);
+
+                let cube_height = height.floor() as i32;
+
+                for y in 0..cube_height {
+                    let block_at_height = biome_config.get_block_at(cube_height - y - 1);
+
+                    if let Some(block) = block_at_height {
+                        chunk.add_cube(
+                            Vector3::new((x0 + x) as f32, y as f32, (z0 + z) as f32),
+                            block,
+                            0,
+                        );
+                    }
                 }
-                chunk.fill_layer(CHUNK_FLOOR, GRASS);
-                chunks.push(chunk);
             }
         }
 
-        World::new(chunks)
+        chunk
+    }
+
+    fn flat_chunk(corner: (i32, i32)) -> Chunk {
+        let mut chunk = Chunk::new([corner.0 as f32, corner.1 as f32]);
+        for k in 0..CHUNK_FLOOR {
+            chunk.fill_layer(k, Block::DIRT);
+        }
+        chunk.fill_layer(CHUNK_FLOOR, Block::GRASS);
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::chunk::CHUNK_SIZE;
+    use crate::world::generation::world_generator::WorldGenerator;
+
+    #[test]
+    fn test_generated_chunk_is_at_corner() {
+        let s = CHUNK_SIZE as i32;
+        let chunk = WorldGenerator::random(42).generate_chunk((-3 * s, 2 * s));
+        assert_eq!(chunk.corner(), [-3. * s as f32, 2. * s as f32]);
+        assert!(chunk.cubes_iter().any(|cube| cube.is_some()));
+    }
+
+    #[test]
+    fn test_generation_does_not_depend_on_order() {
+        let s = CHUNK_SIZE as i32;
+        let corners = [(0, 0), (5 * s, -2 * s), (-12 * s, 7 * s), (s, s)];
+
+        let mut generator1 = WorldGenerator::random(1234);
+        let chunks1: Vec<_> = corners
+            .iter()
+            .map(|corner| generator1.generate_chunk(*corner))
+            .collect();
+
+        // A new generator, which generates the chunks in the reverse order
+        let mut generator2 = WorldGenerator::random(1234);
+        let mut chunks2: Vec<_> = corners
+            .iter()
+            .rev()
+            .map(|corner| generator2.generate_chunk(*corner))
+            .collect();
+        chunks2.reverse();
+
+        assert_eq!(chunks1, chunks2);
+    }
+
+    #[test]
+    fn test_generator_from_level() {
+        let s = CHUNK_SIZE as i32;
+        let mut generator = WorldGenerator::random(5);
+        let mut again = WorldGenerator::from_level(&generator.level());
+        assert_eq!(generator.generate_chunk((s, -s)), again.generate_chunk((s, -s)));
+        let flat = WorldGenerator::flat().level();
+        assert_eq!(flat, WorldGenerator::from_level(&flat).level());
+    }
+
+    #[test]
+    fn test_seeds_generate_different_worlds() {
+        let s = CHUNK_SIZE as i32;
+        let mut generator1 = WorldGenerator::random(1);
+        let mut generator2 = WorldGenerator::random(2);
+        let differ = (0..10).any(|i| {
+            generator1.generate_chunk((i * s, 0)) != generator2.generate_chunk((i * s, 0))
+        });
+        assert!(differ);
     }
 }
@@ -1,37 +1,49 @@
 use crate::collision::aabb::AABB;
 use crate::collision::collidable::{Collidable, CollisionData};
 use crate::game::actions::Action;
+use crate::game::player::{FOREHEAD, PLAYER_HEIGHT, PLAYER_MARGIN};
 use crate::world::block_kind::Block;
-use crate::world::chunk::{Chunk, CHUNK_SIZE};
+use crate::world::chunk::{Chunk, CHUNK_HEIGHT, CHUNK_SIZE};
 use crate::world::cube::Cube;
 use crate::world::cubes_to_draw::CubesToDraw;
-use crate::world::world_serializer::{
-    get_serialize_container, serialize_one_chunk, SerializedWorld,
-};
 use primitives::position::Position;
 use primitives::vector::Vector3;
-use strum::IntoEnumIterator;
 use primitives::render_data::CubeRenderData;
+use std::collections::{HashMap, HashSet};
+
+/// Integer coordinates of the corner of a chunk, see `Chunk::corner_of`
+pub type ChunkCoords = (i32, i32);
 
 pub struct World {
-    /// The list of the chunks currently being displayed
-    chunks: Vec<Chunk>,
+    /// The chunks currently being displayed, indexed by their corner
+    chunks: HashMap<ChunkCoords, Chunk>,
     cubes_to_draw: Option<CubesToDraw>,
+    /// Corners of the chunks that were generated or modified since they were last saved
+    modified_chunks: HashSet<ChunkCoords>,
+    /// Corners of the chunks that were modified since they were generated or loaded, which
+    /// cannot be generated again
+    edited_chunks: HashSet<ChunkCoords>,
 }
 
 impl World {
     pub fn empty() -> Self {
-        let chunks = Vec::new();
         Self {
-            chunks,
+            chunks: HashMap::new(),
             cubes_to_draw: None,
+            modified_chunks: HashSet::new(),
+            edited_chunks: HashSet::new(),
         }
     }
 
     pub fn new(chunks: Vec<Chunk>) -> Self {
         let mut w = Self {
-            chunks,
+            chunks: chunks
+                .into_iter()
+                .map(|chunk| (chunk.integer_corner(), chunk))
+                .collect(),
             cubes_to_draw: None,
+            modified_chunks: HashSet::new(),
+            edited_chunks: HashSet::new(),
         };
 
         w.compute_visible_cubes();
@@ -40,60 +52,101 @@ impl World {
 
     pub fn fill_for_demo(&mut self) {
         let s = CHUNK_SIZE as f32;
-        self.chunks.push(Chunk::new_for_demo([0., 0.], 0));
-        self.chunks.push(Chunk::new_for_demo([s, 0.], 2));
-        self.chunks.push(Chunk::new_for_demo([0., -s], 2));
-        self.chunks.push(Chunk::new_for_demo([0., s], 2));
-        self.chunks.push(Chunk::new_for_demo([-s, 0.], 0));
-        self.chunks.push(Chunk::new_for_demo([-2. * s, 0.], 0));
+        self.insert_chunk(Chunk::new_for_demo([0., 0.], 0));
+        self.insert_chunk(Chunk::new_for_demo([s, 0.], 2));
+        self.insert_chunk(Chunk::new_for_demo([0., -s], 2));
+        self.insert_chunk(Chunk::new_for_demo([0., s], 2));
+        self.insert_chunk(Chunk::new_for_demo([-s, 0.], 0));
+        self.insert_chunk(Chunk::new_for_demo([-2. * s, 0.], 0));
     }
 
     pub fn cubes_near_player(&self, pos: Vector3) -> impl Iterator<Item = &Option<Cube>> {
-        self.chunks
-            .iter()
+        self.chunks_around(&pos)
             .filter(move |chunk| chunk.is_near_player(&pos))
             .flat_map(|chunk| chunk.cubes_iter())
     }
 
     pub fn add_chunk(&mut self, chunk: Chunk) {
-        if self.cubes_to_draw.is_some() {
-            self.cubes_to_draw.as_mut().unwrap().add_chunk(&chunk);
+        if let Some(cubes_to_draw) = self.cubes_to_draw.as_mut() {
+            // A chunk sent again replaces the previous version
+            cubes_to_draw.remove_chunk(chunk.corner());
+            cubes_to_draw.add_chunk(&chunk);
         }
-        self.chunks.push(chunk);
+        self.insert_chunk(chunk);
+    }
+
+    /// Removes the chunk with the given corner from the world, and from the cubes to draw.
+    /// Its modifications are forgotten: it must be saved before if they matter.
+    /// Returns the removed chunk, if it was present.
+    pub fn remove_chunk(&mut self, corner: (i32, i32)) -> Option<Chunk> {
+        let chunk = self.chunks.remove(&corner)?;
+        self.modified_chunks.remove(&corner);
+        self.edited_chunks.remove(&corner);
+        if let Some(cubes_to_draw) = self.cubes_to_draw.as_mut() {
+            cubes_to_draw.remove_chunk(chunk.corner());
+        }
+        Some(chunk)
     }
 
-    pub fn get_chunk(&self, corner: (i32, i32)) -> Option<Chunk> {
-        for chunk in &self.chunks {
-            let tmp = chunk.corner();
-            if tmp[0] == corner.0 as f32 && tmp[1] == corner.1 as f32 {
-                return Some(chunk.clone());
-            }
-        }
-        None
+    /// Adds a chunk that was just generated, and computes the visibility of its cubes, including
+    /// the ones at the border with the chunks already present in the world.
+    /// The chunk is marked as modified, so that it is persisted with the next save.
+    pub fn add_generated_chunk(&mut self, mut chunk: Chunk) {
+        chunk.compute_visible_cubes();
+        let corner = chunk.integer_corner();
+        self.modified_chunks.insert(corner);
+        self.insert_chunk(chunk);
+        self.compute_border_visibility(corner);
     }
 
-    /// Loads a world from a file.
-    pub fn from_file(name: &str) -> Option<Self> {
-        match std::fs::read_to_string(name) {
-            Ok(data) => Some(Self::from_json(data)),
-            Err(err) => {
-                tracing::warn!("Could not read: {name} with error: {err}");
-                None
-            }
-        }
+    /// Returns an iterator over all the chunks of the world
+    pub fn chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.chunks.values()
     }
 
-    /// Saves the current map to the given file
-    pub fn save_to_file(&self, name: &str) {
-        // Note: so far I am using `serde_json` but we will be able to change this in the future.
-        //       There seems to be many options suited for us: https://serde.rs/#data-formats
-        let serialized = self.to_json();
-        match std::fs::write(name, serialized) {
-            Ok(_) => tracing::info!("Map was saved at {name}"),
-            Err(err) => {
-                tracing::error!("Error while saving {name}: {err}")
-            }
-        }
+    /// Returns the chunk that contains the given position
+    pub fn chunk_at(&self, pos: &Vector3) -> Option<&Chunk> {
+        self.chunks.get(&Chunk::corner_of(pos))
+    }
+
+    fn chunk_at_mut(&mut self, pos: &Vector3) -> Option<&mut Chunk> {
+        self.chunks.get_mut(&Chunk::corner_of(pos))
+    }
+
+    /// Returns the chunks whose corner is at most two chunks away from the chunk of the given
+    /// position, i.e. all the chunks for which `Chunk::is_near_player` can be true.
+    fn chunks_around(&self, pos: &Vector3) -> impl Iterator<Item = &Chunk> {
+        let s = CHUNK_SIZE as i32;
+        let (x, z) = Chunk::corner_of(pos);
+        (-2..=2)
+            .flat_map(move |i| (-2..=2).map(move |j| (x + i * s, z + j * s)))
+            .filter_map(|corner| self.chunks.get(&corner))
+    }
+
+    fn insert_chunk(&mut self, chunk: Chunk) {
+        self.chunks.insert(chunk.integer_corner(), chunk);
+    }
+
+    /// Returns the chunks that were generated or modified since the last call to this function.
+    pub fn take_modified_chunks(&mut self) -> Vec<Chunk> {
+        std::mem::take(&mut self.modified_chunks)
+            .into_iter()
+            .filter_map(|corner| self.get_chunk(corner))
+            .collect()
+    }
+
+    pub fn get_chunk(&self, corner: ChunkCoords) -> Option<Chunk> {
+        self.chunks.get(&corner).cloned()
+    }
+
+    /// Returns true if the chunk was generated or modified since it was last saved
+    pub fn is_modified(&self, corner: ChunkCoords) -> bool {
+        self.modified_chunks.contains(&corner)
+    }
+
+    /// Returns true if the chunk was modified since it was generated or loaded
+    pub fn is_edited(&self, corner: ChunkCoords) -> bool {
+        self.edited_chunks.contains(&corner)
     }
 
     /// Returns a list of cube attributes to be drawn on the screen.
@@ -105,7 +158,7 @@ impl World {
         // I have tried to optimize this shit using a custom class that does not re-allocate everything
         // but it does not improve anything ... So let's keep the simple solution of always calling `push`
         let mut positions: Vec<CubeRenderData> = Vec::new();
-        for chunk in &self.chunks {
+        for chunk in self.chunks.values() {
             for layer in chunk.cubes() {
                 for row in layer {
                     for cube in row {
@@ -115,6 +168,8 @@ impl World {
                                     position: c.position().clone(),
                                     block_id: c.block_id(),
                                     is_selected: false,
+                                    scale: 1.,
+                                    texture_id: None,
                                 });
                             }
                         }
@@ -150,30 +205,68 @@ impl World {
 
     /// Returns the block at the given position
     pub fn block_at(&self, pos: &Vector3) -> Option<Block> {
-        for chunk in &self.chunks {
-            if chunk.is_in(pos) {
-                if let Some(cube) = chunk.cube_at(pos) {
-                    return Some(cube.block().clone());
-                }
-            }
-        }
-
-        None
+        self.cube_at(*pos).map(|cube| cube.block().clone())
     }
 
     /// Returns true if there is a cube at this position
     pub fn is_position_free_or_transparent(&self, pos: &Vector3) -> bool {
-        for chunk in &self.chunks {
-            if chunk.is_in(pos) {
-                if !chunk.is_position_free_or_transparent(pos) {
-                    return false;
+        self.chunk_at(pos)
+            .is_none_or(|chunk| chunk.is_position_free_or_transparent(pos))
+    }
+
+    /// Returns true if the position is inside one of the chunks of the world
+    pub fn is_loaded(&self, pos: &Vector3) -> bool {
+        pos[1] >= 0. && pos[1] < CHUNK_HEIGHT as f32 && self.chunk_at(pos).is_some()
+    }
+
+    /// Returns true if the box overlaps a cube of fluid
+    pub fn is_in_fluid(&self, aabb: &AABB) -> bool {
+        let cells = |min: f32, max: f32| (min.floor() as i32)..=(max.floor() as i32);
+        cells(aabb.west(), aabb.east()).any(|x| {
+            cells(aabb.bottom(), aabb.top()).any(|y| {
+                cells(aabb.south(), aabb.north()).any(|z| {
+                    let pos = Vector3::new(x as f32, y as f32, z as f32);
+                    self.block_at(&pos).is_some_and(|block| block.is_fluid())
+                })
+            })
+        })
+    }
+
+    /// Returns where the eyes of a player standing on the ground must be, in the column closest
+    /// to (x, z) which is at most `radius` cubes away. The ground must be loaded and opaque.
+    pub fn safe_spawn_position(&self, x: f32, z: f32, radius: i32) -> Option<Position> {
+        let (x, z) = (x.floor(), z.floor());
+        for r in 0..=radius {
+            let ring = (-r..=r)
+                .flat_map(|i| (-r..=r).map(move |j| (i, j)))
+                .filter(|(i, j)| i.abs() == r || j.abs() == r);
+            for (i, j) in ring {
+                let column = Vector3::new(x + i as f32, 0., z + j as f32);
+                let Some(ground) = self
+                    .chunk_at(&column)
+                    .and_then(|chunk| chunk.highest_cube_at(&column))
+                else {
+                    continue;
+                };
+                if ground.is_transparent() {
+                    continue;
                 }
+                let eyes = ground.position()[1] + 1. + PLAYER_HEIGHT - FOREHEAD + PLAYER_MARGIN;
+                return Some(Position::new_vec(column[0] + 0.5, eyes, column[2] + 0.5));
             }
         }
-        true
+        None
     }
 
+    /// Edits the world. The actions on the chunks that the world does not have are ignored.
     pub fn apply_action(&mut self, action: &Action) {
+        let at = action.position();
+        if self.chunk_at(&at).is_none() {
+            return;
+        }
+        self.modified_chunks.insert(Chunk::corner_of(&at));
+        self.edited_chunks.insert(Chunk::corner_of(&at));
+
         match action {
             Action::Destroy { at } => {
                 let revealed_cubes = self.destroy_cube(at.clone());
@@ -187,6 +280,11 @@ impl World {
                 }
             }
             Action::Add { at, block } => {
+                // The new cube may replace a fluid
+                let replaced = self.cube_at(*at).is_some();
+                if let (true, Some(cubes_to_draw)) = (replaced, self.cubes_to_draw.as_mut()) {
+                    cubes_to_draw.remove_cube(at);
+                }
                 let (cubes_to_destroy, cube) = self.add_cube(at.clone(), block.clone());
                 if self.cubes_to_draw.is_some() {
                     // Add the cube from the rendered cube
@@ -201,21 +299,11 @@ impl World {
     }
 
     fn cube_at_mut(&mut self, pos: Vector3) -> Option<&mut Cube> {
-        for chunk in &mut self.chunks {
-            if chunk.is_in(&pos) {
-                return chunk.cube_at_mut(&pos);
-            }
-        }
-        None
+        self.chunk_at_mut(&pos)?.cube_at_mut(&pos)
     }
 
     pub fn cube_at(&self, pos: Vector3) -> Option<&Cube> {
-        for chunk in &self.chunks {
-            if chunk.is_in(&pos) {
-                return chunk.cube_at(&pos);
-            }
-        }
-        None
+        self.chunk_at(&pos)?.cube_at(&pos)
     }
 
     /// Adds a cube and then recomputes the visibility of the affected cubes (neighbors)
@@ -244,27 +332,17 @@ impl World {
 
     /// Adds a cube without recomputing the visibility
     fn add_cube_unsafe(&mut self, at: Vector3, block: Block, neighbors: u8) {
-        for chunk in &mut self.chunks {
-            if chunk.is_in(&at) {
-                chunk.add_cube(at, block, neighbors);
-            }
+        if let Some(chunk) = self.chunk_at_mut(&at) {
+            chunk.add_cube(at, block, neighbors);
         }
     }
 
     /// Destroy a cube and return the neighboring cubes that need to be rendered
     fn destroy_cube(&mut self, at: Vector3) -> Vec<Cube> {
-        // Find the chunk where the cube is located
-        let mut chunk_index = 0;
-        for i in 0..self.chunks.len() {
-            if self.chunks[i].is_in(&at) {
-                chunk_index = i;
-                break;
-            }
-        }
-
         let mut cubes_to_reveal = Vec::new();
-        // Mark all the neighbors cube as visible
-        if let Some(_cube) = self.chunks[chunk_index].cube_at(&at) {
+        // Mark all the neighbors cube as visible. The transparent cubes were not counted as their
+        // neighbors.
+        if self.cube_at(at).is_some_and(|cube| !cube.is_transparent()) {
             for pos in Cube::neighbors_positions(at) {
                 if let Some(cube_to_toggle) = self.cube_at_mut(pos) {
                     // If the cube was not visible before, add it
@@ -276,15 +354,16 @@ impl World {
             }
         }
 
-        // Shouldn't this be inside the if let ?
-        self.chunks[chunk_index].destroy_cube(at);
+        if let Some(chunk) = self.chunk_at_mut(&at) {
+            chunk.destroy_cube(at);
+        }
         cubes_to_reveal
     }
 
     #[cfg(test)]
     fn visible_cubes_count(&self) -> usize {
         self.chunks
-            .iter()
+            .values()
             .map(|chunk| chunk.visible_cube_count())
             .sum()
     }
@@ -292,97 +371,66 @@ impl World {
     /// Goes through all the cubes in the world, and sets whether the cube is touching air.
     fn compute_visible_cubes(&mut self) {
         // 1. First pass inside each chunk
-        for chunk in &mut self.chunks {
+        for chunk in self.chunks.values_mut() {
             chunk.compute_visible_cubes();
         }
 
         // 2. Handle the borders of each chunk
-        for i in 0..self.chunks.len() {
-            let border = self.chunks[i].border();
-            for index in border {
-                // Count the number of neighbors of this cube
-                let mut count = if let Some(cube_at_border) = self.chunks[i].cube_at_index(index) {
-                    let neighbors = Cube::neighbors_positions(cube_at_border.position().clone());
-                    let count = neighbors
-                        .iter()
-                        .filter(|pos| !self.is_position_free_or_transparent(&pos))
-                        .count();
-                    count as u8
-                } else {
-                    0
-                };
-
-                // If it is the bottommost layer, increase
-                if index.0 == 0 {
-                    count += 1;
-                }
-
-                // Set it
-                // You need to do this separatly than the previous block.
-                if let Some(cube_at_border) = self.chunks[i].cube_at_index_mut(index) {
-                    cube_at_border.set_n_neighbors(count);
-                }
-            }
+        let corners: Vec<ChunkCoords> = self.chunks.keys().copied().collect();
+        for corner in corners {
+            self.compute_border_visibility(corner);
         }
     }
-}
-
-impl World {
-    fn to_json(&self) -> String {
-        // Provide all the chunks corner
-        let chunk_corners: Vec<[f32; 2]> = self.chunks.iter().map(|chunk| chunk.corner()).collect();
-
-        // Provide all the cubes, sorted by kind
-        let mut all_cubes = get_serialize_container();
-        for chunk in &self.chunks {
-            serialize_one_chunk(&mut all_cubes, chunk);
-        }
-
-        let world = SerializedWorld {
-            chunk_corners,
-            cubes_by_kind: all_cubes,
-        };
 
-        serde_json::to_string(&world).unwrap()
-    }
-
-    fn from_json(data: String) -> Self {
-        // If we end up with stack-overflows, we could not read the entire file but instead provide the reader.
-        let serialized_world: SerializedWorld = serde_json::from_str(data.as_str()).unwrap();
-
-        // First, build all the chunks
-        let mut chunks = Vec::new();
-        for corner in serialized_world.chunk_corners {
-            chunks.push(Chunk::new(corner));
-        }
-
-        // Build the world
-        let mut world = Self {
-            chunks,
-            cubes_to_draw: None,
-        };
+    /// Sets the number of neighbors of the cubes at the border of the chunk with the given
+    /// corner, using the chunks that touch it.
+    fn compute_border_visibility(&mut self, corner: ChunkCoords) {
+        let border = self.chunks[&corner].border();
+        for index in border {
+            // Count the number of neighbors of this cube
+            let chunk = &self.chunks[&corner];
+            let mut count = if let Some(cube_at_border) = chunk.cube_at_index(index) {
+                let neighbors = Cube::neighbors_positions(cube_at_border.position().clone());
+                let count = neighbors
+                    .iter()
+                    .filter(|pos| !self.is_position_free_or_transparent(&pos))
+                    .count();
+                count as u8
+            } else {
+                0
+            };
+
+            // If it is the bottommost layer, increase
+            if index.0 == 0 {
+                count += 1;
+            }
 
-        // Fill all the chunks by building all the cubes
-        for block_kind in Block::iter() {
-            let cubes = serialized_world.cubes_by_kind.get(&block_kind).unwrap();
-            for cube_data in cubes {
-                let x = cube_data[0] as f32;
-                let y = cube_data[1] as f32;
-                let z = cube_data[2] as f32;
-                let neighbors = cube_data[3] as u8;
-                world.add_cube_unsafe(Vector3::new(x, y, z), block_kind, neighbors);
+            // Set it
+            // You need to do this separatly than the previous block.
+            if let Some(cube_at_border) = self
+                .chunks
+                .get_mut(&corner)
+                .and_then(|chunk| chunk.cube_at_index_mut(index))
+            {
+                cube_at_border.set_n_neighbors(count);
             }
         }
-
-        world
     }
 }
 
 impl Collidable for World {
     fn collides(&self, aabb: &AABB) -> bool {
-        for chunk in &self.chunks {
-            if chunk.collides(aabb) {
-                return true;
+        // Only the chunks that overlap the box (with a margin of one cube, as cubes extend beyond
+        // their position) need to be checked.
+        let min = Chunk::corner_of(&Vector3::new(aabb.west() - 1., 0., aabb.south() - 1.));
+        let max = Chunk::corner_of(&Vector3::new(aabb.east() + 1., 0., aabb.north() + 1.));
+        for x in (min.0..=max.0).step_by(CHUNK_SIZE) {
+            for z in (min.1..=max.1).step_by(CHUNK_SIZE) {
+                if let Some(chunk) = self.chunks.get(&(x, z)) {
+                    if chunk.collides(aabb) {
+                        return true;
+                    }
+                }
             }
         }
 
@@ -401,8 +449,7 @@ impl Collidable for World {
         let mut acc_time = f32::MAX;
         let mut acc_normal = Vector3::empty();
 
-        // TODO be smarter
-        for chunk in &self.chunks {
+        for chunk in self.chunks_around(&position.pos()) {
             if chunk.is_near_player(&position.pos()) {
                 if let Some(CollisionData { time, normal }) =
                     chunk.collision_time(position, aabb, target, velocity)
@@ -428,11 +475,11 @@ impl Collidable for World {
 
 #[cfg(test)]
 mod tests {
+    use crate::collision::collidable::Collidable;
+    use crate::entity::humanoid::humanoid_aabb;
     use crate::game::actions::Action;
     use crate::world::block_kind::Block;
-    use crate::world::block_kind::Block::GRASS;
     use crate::world::chunk::{Chunk, CHUNK_FLOOR, CHUNK_SIZE};
-    use crate::world::generation::world_generator::WorldGenerator;
     use crate::world::world::World;
     use primitives::vector::Vector3;
 
@@ -441,8 +488,8 @@ mod tests {
         let mut world = World::empty();
         // Adding one chunk
         let s = CHUNK_SIZE as f32;
-        world.chunks.push(Chunk::new_for_demo([-s, 0.], 0));
-        world.chunks[0].print_all_cubes();
+        world.insert_chunk(Chunk::new_for_demo([-s, 0.], 0));
+        world.chunks().for_each(|chunk| chunk.print_all_cubes());
 
         // Assert some positions
         assert!(!world.is_position_free_or_transparent(&Vector3::new(
@@ -476,7 +523,7 @@ mod tests {
     fn test_chunk_collision_2() {
         let mut world = World::empty();
         // Adding one chunk
-        world.chunks.push(Chunk::new_for_demo([0., 0.], 0));
+        world.insert_chunk(Chunk::new_for_demo([0., 0.], 0));
         assert!(world.is_position_free_or_transparent(&Vector3::new(4.0, 10.2, 3.0)));
     }
 
@@ -484,17 +531,17 @@ mod tests {
     fn test_visible_cube_with_two_chunks_that_touch() {
         let mut world = World::empty();
         let mut chunk1 = Chunk::new([0., 0.]);
-        chunk1.fill_layer(0, GRASS);
-        chunk1.fill_layer(1, GRASS);
-        chunk1.fill_layer(2, GRASS);
+        chunk1.fill_layer(0, Block::GRASS);
+        chunk1.fill_layer(1, Block::GRASS);
+        chunk1.fill_layer(2, Block::GRASS);
 
         let mut chunk2 = Chunk::new([CHUNK_SIZE as f32, 0.]);
-        chunk2.fill_layer(0, GRASS);
-        chunk2.fill_layer(1, GRASS);
-        chunk2.fill_layer(2, GRASS);
+        chunk2.fill_layer(0, Block::GRASS);
+        chunk2.fill_layer(1, Block::GRASS);
+        chunk2.fill_layer(2, Block::GRASS);
 
-        world.chunks.push(chunk1);
-        world.chunks.push(chunk2);
+        world.insert_chunk(chunk1);
+        world.insert_chunk(chunk2);
 
         let count1 = world.visible_cubes_count();
         world.compute_visible_cubes();
@@ -504,21 +551,85 @@ mod tests {
         assert_eq!(count1, 2 * 3 * CHUNK_SIZE * CHUNK_SIZE);
     }
 
+    #[test]
+    fn test_chunk_lookup() {
+        let mut world = World::empty();
+        let s = CHUNK_SIZE as f32;
+        let mut chunk = Chunk::new([-s, -s]);
+        chunk.fill_layer(0, Block::GRASS);
+        world.add_chunk(chunk);
+
+        // Positions are mapped to the chunk that contains them, including negative ones
+        assert_eq!(Some(Block::GRASS), world.block_at(&Vector3::new(-0.5, 0., -s)));
+        assert_eq!(Some(Block::GRASS), world.block_at(&Vector3::new(-s, 0.2, -0.1)));
+        assert_eq!(None, world.block_at(&Vector3::new(0., 0., -1.)));
+        assert_eq!(None, world.block_at(&Vector3::new(-1., 0., -s - 1.)));
+        assert!(!world.is_position_free_or_transparent(&Vector3::new(-1., 0., -1.)));
+        assert!(world.is_position_free_or_transparent(&Vector3::new(-1., 1., -1.)));
+        assert!(world.chunk_at(&Vector3::new(-0.1, 0., -0.1)).is_some());
+        assert!(world.chunk_at(&Vector3::new(0.1, 0., 0.1)).is_none());
+    }
+
+    #[test]
+    fn test_remove_chunk() {
+        let mut world = World::empty();
+        let mut chunk1 = Chunk::new([0., 0.]);
+        chunk1.fill_layer(0, Block::GRASS);
+        let mut chunk2 = Chunk::new([CHUNK_SIZE as f32, 0.]);
+        chunk2.fill_layer(0, Block::GRASS);
+        world.add_chunk(chunk1);
+        world.set_cubes_to_draw();
+        world.add_chunk(chunk2);
+        assert_eq!(2 * CHUNK_SIZE * CHUNK_SIZE, world.number_cubes_rendered());
+
+        assert!(world.remove_chunk((CHUNK_SIZE as i32, 0)).is_some());
+        assert!(world.remove_chunk((CHUNK_SIZE as i32, 0)).is_none());
+        assert_eq!(1, world.chunks().count());
+        assert_eq!(CHUNK_SIZE * CHUNK_SIZE, world.number_cubes_rendered());
+        assert!(world.cube_at(Vector3::new(CHUNK_SIZE as f32 + 1., 0., 1.)).is_none());
+    }
+
+    #[test]
+    fn test_safe_spawn_position() {
+        let mut world = World::empty();
+        let mut chunk = Chunk::new([0., 0.]);
+        chunk.fill_layer(0, Block::DIRT);
+        world.add_chunk(chunk);
+        world.apply_action(&Action::Add {
+            at: Vector3::new(2., 1., 2.),
+            block: Block::WATER,
+        });
+
+        // The player stands on top of the highest cube, in the middle of the column
+        let spawn = world.safe_spawn_position(3.2, 5.7, 4).unwrap();
+        assert_eq!(3.5, spawn.x());
+        assert_eq!(5.5, spawn.z());
+        assert!(!world.collides(&humanoid_aabb(&spawn)));
+        assert!(world.collides(&humanoid_aabb(&(&spawn + Vector3::new(0., -0.1, 0.)))));
+
+        // They do not spawn in the water, but next to it
+        let spawn = world.safe_spawn_position(2.5, 2.5, 4).unwrap();
+        assert_eq!(1, (spawn.x() - 2.5).abs().max((spawn.z() - 2.5).abs()) as i32);
+
+        // There is no ground outside of the loaded chunks
+        assert!(world.safe_spawn_position(-20., -20., 4).is_none());
+    }
+
     #[test]
     fn test_visible_cube_with_two_chunks_that_doesnt_touch() {
         let mut world = World::empty();
         let mut chunk1 = Chunk::new([0., 0.]);
-        chunk1.fill_layer(0, GRASS);
-        chunk1.fill_layer(1, GRASS);
-        chunk1.fill_layer(2, GRASS);
+        chunk1.fill_layer(0, Block::GRASS);
+        chunk1.fill_layer(1, Block::GRASS);
+        chunk1.fill_layer(2, Block::GRASS);
 
         let mut chunk2 = Chunk::new([3. * CHUNK_SIZE as f32, 0.]);
-        chunk2.fill_layer(0, GRASS);
-        chunk2.fill_layer(1, GRASS);
-        chunk2.fill_layer(2, GRASS);
+        chunk2.fill_layer(0, Block::GRASS);
+        chunk2.fill_layer(1, Block::GRASS);
+        chunk2.fill_layer(2, Block::GRASS);
 
-        world.chunks.push(chunk1);
-        world.chunks.push(chunk2);
+        world.insert_chunk(chunk1);
+        world.insert_chunk(chunk2);
 
         let count1 = world.visible_cubes_count();
         world.compute_visible_cubes();
@@ -532,10 +643,10 @@ mod tests {
     fn test_visibility_after_deleting_cubes() {
         let mut world = World::empty();
         let mut chunk = Chunk::new([0., 0.]);
-        chunk.fill_layer(0, GRASS);
-        chunk.fill_layer(1, GRASS);
-        chunk.fill_layer(2, GRASS);
-        world.chunks.push(chunk);
+        chunk.fill_layer(0, Block::GRASS);
+        chunk.fill_layer(1, Block::GRASS);
+        chunk.fill_layer(2, Block::GRASS);
+        world.insert_chunk(chunk);
         world.compute_visible_cubes();
 
         let top = Vector3::new(4., 2., 4.);
@@ -544,13 +655,13 @@ mod tests {
 
         // Initially, the cube in the middle is not supposed to be visible
         // Note that the bottommost layer is not showed
-        assert_eq!(world.chunks[0].cube_at(&top).unwrap().is_visible(), true);
+        assert_eq!(world.cube_at(top).unwrap().is_visible(), true);
         assert_eq!(
-            world.chunks[0].cube_at(&middle).unwrap().is_visible(),
+            world.cube_at(middle).unwrap().is_visible(),
             false
         );
         assert_eq!(
-            world.chunks[0].cube_at(&bottom).unwrap().is_visible(),
+            world.cube_at(bottom).unwrap().is_visible(),
             false
         );
 
@@ -558,28 +669,28 @@ mod tests {
         world.apply_action(&Action::Destroy { at: top });
 
         // Assert the cube in the middle is now visible
-        assert_eq!(world.chunks[0].cube_at(&middle).unwrap().is_visible(), true);
+        assert_eq!(world.cube_at(middle).unwrap().is_visible(), true);
 
         // But so far, the sides of `bottom` should not be visible yet
         let one_side = middle + Vector3::unit_x();
         let another_side = middle + Vector3::unit_z();
         assert_eq!(
-            world.chunks[0].cube_at(&one_side).unwrap().is_visible(),
+            world.cube_at(one_side).unwrap().is_visible(),
             false
         );
         assert_eq!(
-            world.chunks[0].cube_at(&another_side).unwrap().is_visible(),
+            world.cube_at(another_side).unwrap().is_visible(),
             false
         );
 
         // But we if delete the middle block, the sides get in contact with air, so they are supposed to be visible.
         world.apply_action(&Action::Destroy { at: middle });
         assert_eq!(
-            world.chunks[0].cube_at(&one_side).unwrap().is_visible(),
+            world.cube_at(one_side).unwrap().is_visible(),
             true
         );
         assert_eq!(
-            world.chunks[0].cube_at(&another_side).unwrap().is_visible(),
+            world.cube_at(another_side).unwrap().is_visible(),
             true
         );
     }
@@ -588,10 +699,10 @@ mod tests {
     fn test_visibility_after_creating_and_deleting_cubes() {
         let mut world = World::empty();
         let mut chunk = Chunk::new([0., 0.]);
-        chunk.fill_layer(0, GRASS);
-        chunk.fill_layer(1, GRASS);
-        chunk.fill_layer(2, GRASS);
-        world.chunks.push(chunk);
+        chunk.fill_layer(0, Block::GRASS);
+        chunk.fill_layer(1, Block::GRASS);
+        chunk.fill_layer(2, Block::GRASS);
+        world.insert_chunk(chunk);
         world.compute_visible_cubes();
 
         let above = Vector3::new(4., 3., 4.);
@@ -604,40 +715,32 @@ mod tests {
         });
 
         // Assert the visibility: the block 'top' should not be rendered anymore
-        assert_eq!(world.chunks[0].cube_at(&above).unwrap().is_visible(), true);
-        assert_eq!(world.chunks[0].cube_at(&top).unwrap().is_visible(), false);
+        assert_eq!(world.cube_at(above).unwrap().is_visible(), true);
+        assert_eq!(world.cube_at(top).unwrap().is_visible(), false);
     }
 
     #[test]
     fn test_visibility_of_bottommost_layer() {
         let mut world = World::empty();
         let mut chunk = Chunk::new([0., 0.]);
-        chunk.fill_layer(0, GRASS);
-        chunk.fill_layer(1, GRASS);
-        chunk.fill_layer(2, GRASS);
-        world.chunks.push(chunk);
+        chunk.fill_layer(0, Block::GRASS);
+        chunk.fill_layer(1, Block::GRASS);
+        chunk.fill_layer(2, Block::GRASS);
+        world.insert_chunk(chunk);
         world.compute_visible_cubes();
         let bottom = Vector3::new(4., 0., 4.);
         assert_eq!(
-            world.chunks[0].cube_at(&bottom).unwrap().is_visible(),
+            world.cube_at(bottom).unwrap().is_visible(),
             false
         );
     }
 
-    #[test]
-    fn test_world_persistence() {
-        let world = WorldGenerator::create_new_random_world(2);
-        let serialized = world.to_json();
-        let reconstructed = World::from_json(serialized);
-        assert_eq!(world.chunks, reconstructed.chunks);
-    }
-
     #[test]
     fn test_cube_iter() {
         let mut world = World::empty();
         let mut chunk = Chunk::new([0., 0.]);
-        chunk.fill_layer(0, GRASS);
-        world.chunks.push(chunk);
+        chunk.fill_layer(0, Block::GRASS);
+        world.insert_chunk(chunk);
 
         let count = world
             .cubes_near_player(Vector3::empty())
@@ -0,0 +1,397 @@
+use crate::server::entity_ids::EntityIdAllocator;
+use crate::server::server_state::ServerState;
+use crate::world::chunk::{Chunk, CHUNK_SIZE};
+use crate::world::generation::world_generator::Level;
+use crate::world::world::World;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Number of chunks along each side of a region: a region file holds `REGION_SIZE * REGION_SIZE`
+/// chunks.
+pub const REGION_SIZE: i32 = 8;
+
+/// First bytes of every region file
+const REGION_MAGIC: &[u8; 4] = b"CRFR";
+const REGION_VERSION: u8 = 1;
+const HEADER_LEN: usize = REGION_MAGIC.len() + 1 + 4 * (REGION_SIZE * REGION_SIZE) as usize;
+
+/// Name of the file holding the state of the players
+const PLAYERS_FILE: &str = "players.json";
+
+/// Name of the file holding the seed and the kind of terrain of the world
+const LEVEL_FILE: &str = "level.json";
+
+/// Number of regions kept in memory, so that their files are not read for each of their chunks
+const MAX_OPEN_REGIONS: usize = 16;
+
+/// Coordinates of a region, in units of regions.
+type RegionCoords = (i32, i32);
+
+/// A region file kept in memory
+struct OpenRegion {
+    /// The binary representation of each chunk of the region, empty if the chunk is absent.
+    /// The chunks are only decoded when they are loaded.
+    chunks: Vec<Vec<u8>>,
+    /// When the region was last used, to close the least recently used one first
+    last_use: u64,
+}
+
+/// Persistence of the world on the disk.
+///
+/// The world is stored in a directory, with one file per region, i.e. per square of
+/// `REGION_SIZE x REGION_SIZE` chunks. This allows to load and save each chunk individually,
+/// without having to read or write the full world.
+///
+/// A region file is made of
+/// - a header: the magic bytes, the version of the format, and for each chunk of the region the
+///   length of its data (0 if the chunk is absent)
+/// - the binary representation of each chunk of the region (see `Chunk::to_bytes`), in the order
+///   of the header.
+///
+/// The state of the players is stored next to the regions, in `players.json`, and the seed and
+/// the kind of terrain of the world in `level.json`.
+///
+/// The last regions used are kept in memory, and written to the disk as soon as one of their
+/// chunks is saved.
+pub struct WorldStorage {
+    dir: PathBuf,
+    regions: HashMap<RegionCoords, OpenRegion>,
+    /// Incremented each time a region is used
+    uses: u64,
+}
+
+impl WorldStorage {
+    /// Opens the world stored in the given directory, which is created if needed.
+    pub fn new(dir: impl AsRef<Path>) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+            regions: HashMap::new(),
+            uses: 0,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Loads the chunk with the given corner. Returns `None` if it was never saved.
+    pub fn load_chunk(&mut self, corner: (i32, i32)) -> io::Result<Option<Chunk>> {
+        let (region, index) = Self::locate(corner);
+        let bytes = &self.open_region(region)?.chunks[index];
+        if bytes.is_empty() {
+            return Ok(None);
+        }
+        Self::decode(region, bytes).map(Some)
+    }
+
+    /// Loads all the chunks stored in the directory
+    pub fn load_world(&self) -> io::Result<World> {
+        let mut chunks = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if let Some(region) = Self::region_from_path(&path) {
+                for bytes in self.read_region(region)?.iter().filter(|bytes| !bytes.is_empty()) {
+                    chunks.push(Self::decode(region, bytes)?);
+                }
+            }
+        }
+        Ok(World::new(chunks))
+    }
+
+    /// Saves the given chunks, replacing the previous version of each of them.
+    /// Each region file that contains one of the chunks is written only once.
+    pub fn save_chunks<'a>(
+        &mut self,
+        chunks: impl IntoIterator<Item = &'a Chunk>,
+    ) -> io::Result<()> {
+        let mut by_region: HashMap<RegionCoords, Vec<&Chunk>> = HashMap::new();
+        for chunk in chunks {
+            let (region, _) = Self::locate(chunk.integer_corner());
+            by_region.entry(region).or_default().push(chunk);
+        }
+
+        for (region, chunks) in by_region {
+            let open = self.open_region(region)?;
+            for chunk in chunks {
+                open.chunks[Self::locate(chunk.integer_corner()).1] = chunk.to_bytes();
+            }
+            let data = Self::encode_region(&open.chunks);
+            self.write_atomically(&self.region_path(region), &data)?;
+        }
+        Ok(())
+    }
+
+    /// Saves the chunks of the world that were generated or modified since the last save.
+    /// Returns the number of chunks written to the disk.
+    pub fn save_modified_chunks(&mut self, world: &mut World) -> io::Result<usize> {
+        let chunks = world.take_modified_chunks();
+        self.save_chunks(&chunks)?;
+        Ok(chunks.len())
+    }
+
+    /// Saves the state of the players
+    pub fn save_server_state(&self, state: &ServerState) -> io::Result<()> {
+        let serialized = serde_json::to_vec(state)?;
+        self.write_atomically(&self.dir.join(PLAYERS_FILE), &serialized)
+    }
+
+    /// Loads the state of the players. Returns `None` if it was never saved.
+    pub fn load_server_state(&self) -> io::Result<Option<ServerState>> {
+        match fs::read(self.dir.join(PLAYERS_FILE)) {
+            Ok(data) => {
+                let mut state: ServerState = serde_json::from_slice(&data)?;
+                // The ids of the known players are not given to the new entities
+                state.set_entity_ids(EntityIdAllocator::new());
+                Ok(Some(state))
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Saves the seed and the kind of terrain of the world
+    pub fn save_level(&self, level: &Level) -> io::Result<()> {
+        let serialized = serde_json::to_vec(level)?;
+        self.write_atomically(&self.dir.join(LEVEL_FILE), &serialized)
+    }
+
+    /// Loads the seed and the kind of terrain of the world. Returns `None` if they were never
+    /// saved.
+    pub fn load_level(&self) -> io::Result<Option<Level>> {
+        match fs::read(self.dir.join(LEVEL_FILE)) {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Writes to a temporary file first, so that a crash never leaves a half-written file
+    fn write_atomically(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(tmp, path)
+    }
+
+    /// Returns the region that contains a chunk, and the index of the chunk within this region
+    fn locate(corner: (i32, i32)) -> (RegionCoords, usize) {
+        let s = CHUNK_SIZE as i32;
+        let (cx, cz) = (corner.0.div_euclid(s), corner.1.div_euclid(s));
+        let region = (cx.div_euclid(REGION_SIZE), cz.div_euclid(REGION_SIZE));
+        let index = cx.rem_euclid(REGION_SIZE) * REGION_SIZE + cz.rem_euclid(REGION_SIZE);
+        (region, index as usize)
+    }
+
+    fn region_path(&self, region: RegionCoords) -> PathBuf {
+        self.dir.join(format!("r.{}.{}.region", region.0, region.1))
+    }
+
+    fn region_from_path(path: &Path) -> Option<RegionCoords> {
+        let name = path.file_name()?.to_str()?;
+        let mut parts = name.strip_prefix("r.")?.strip_suffix(".region")?.split('.');
+        let x = parts.next()?.parse().ok()?;
+        let z = parts.next()?.parse().ok()?;
+        Some((x, z))
+    }
+
+    /// Returns the region kept in memory, which is read from its file if it is not open yet.
+    /// The least recently used region is closed when too many are open.
+    fn open_region(&mut self, region: RegionCoords) -> io::Result<&mut OpenRegion> {
+        self.uses += 1;
+        if !self.regions.contains_key(&region) {
+            let chunks = self.read_region(region)?;
+            if self.regions.len() >= MAX_OPEN_REGIONS {
+                let oldest = self
+                    .regions
+                    .iter()
+                    .min_by_key(|(_, open)| open.last_use)
+                    .map(|(coords, _)| *coords);
+                if let Some(oldest) = oldest {
+                    self.regions.remove(&oldest);
+                }
+            }
+            self.regions.insert(region, OpenRegion { chunks, last_use: 0 });
+        }
+        let open = self.regions.get_mut(&region).unwrap();
+        open.last_use = self.uses;
+        Ok(open)
+    }
+
+    fn decode(region: RegionCoords, bytes: &[u8]) -> io::Result<Chunk> {
+        Chunk::from_bytes(bytes).map_err(|err| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("Region {region:?}: {err}"))
+        })
+    }
+
+    /// Reads the binary representation of all the chunks of a region, in the order of the region
+    fn read_region(&self, region: RegionCoords) -> io::Result<Vec<Vec<u8>>> {
+        let n_chunks = (REGION_SIZE * REGION_SIZE) as usize;
+        let data = match fs::read(self.region_path(region)) {
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![vec![]; n_chunks]),
+            Err(err) => return Err(err),
+        };
+
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        if data.len() < HEADER_LEN || &data[0..4] != REGION_MAGIC {
+            return Err(invalid(format!("{region:?} is not a region file")));
+        }
+        if data[4] != REGION_VERSION {
+            return Err(invalid(format!("Unsupported region version: {}", data[4])));
+        }
+
+        let mut chunks = Vec::with_capacity(n_chunks);
+        let mut offset = HEADER_LEN;
+        for index in 0..n_chunks {
+            let at = REGION_MAGIC.len() + 1 + 4 * index;
+            let len = u32::from_le_bytes(data[at..at + 4].try_into().unwrap()) as usize;
+            let bytes = data
+                .get(offset..offset + len)
+                .ok_or_else(|| invalid(format!("Region {region:?} is truncated")))?;
+            chunks.push(bytes.to_vec());
+            offset += len;
+        }
+        Ok(chunks)
+    }
+
+    /// Returns the content of a region file holding the given chunks
+    fn encode_region(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(REGION_MAGIC);
+        header.push(REGION_VERSION);
+        for bytes in chunks {
+            header.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        }
+        [header, chunks.concat()].concat()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::actions::Action;
+    use crate::server::server_state::ServerState;
+    use crate::world::chunk::CHUNK_SIZE;
+    use crate::world::generation::world_generator::{Level, TerrainKind, WorldGenerator};
+    use crate::world::world::World;
+    use crate::world::world_storage::{WorldStorage, MAX_OPEN_REGIONS, REGION_SIZE};
+    use primitives::vector::Vector3;
+
+    fn temporary_storage(name: &str) -> WorldStorage {
+        let dir = std::env::temp_dir().join(format!("crafty_{name}_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        WorldStorage::new(dir).unwrap()
+    }
+
+    #[test]
+    fn test_world_persistence() {
+        let mut storage = temporary_storage("world_persistence");
+        let world = WorldGenerator::create_new_random_world(42, 2);
+        storage.save_chunks(world.chunks()).unwrap();
+
+        let reconstructed = storage.load_world().unwrap();
+        for chunk in world.chunks() {
+            assert_eq!(Some(chunk), reconstructed.chunks().find(|c| c.corner() == chunk.corner()));
+        }
+        assert_eq!(world.chunks().count(), reconstructed.chunks().count());
+    }
+
+    #[test]
+    fn test_level_persistence() {
+        let storage = temporary_storage("level");
+        assert!(storage.load_level().unwrap().is_none());
+        let level = Level {
+            seed: 7,
+            terrain: TerrainKind::Flat,
+        };
+        storage.save_level(&level).unwrap();
+        assert_eq!(Some(level), storage.load_level().unwrap());
+    }
+
+    #[test]
+    fn test_chunks_are_saved_individually() {
+        let mut storage = temporary_storage("individual_chunks");
+        let s = CHUNK_SIZE as i32;
+        let mut generator = WorldGenerator::flat();
+
+        // Two chunks in different regions
+        let corner1 = (0, 0);
+        let corner2 = (-REGION_SIZE * s, 3 * s);
+        storage.save_chunks(&[generator.generate_chunk(corner1)]).unwrap();
+        assert!(storage.load_chunk(corner1).unwrap().is_some());
+        assert!(storage.load_chunk(corner2).unwrap().is_none());
+
+        storage.save_chunks(&[generator.generate_chunk(corner2)]).unwrap();
+        assert!(storage.load_chunk(corner1).unwrap().is_some());
+        assert_eq!(corner2, storage.load_chunk(corner2).unwrap().unwrap().integer_corner());
+    }
+
+    #[test]
+    fn test_more_regions_than_the_open_ones() {
+        let mut storage = temporary_storage("many_regions");
+        let s = CHUNK_SIZE as i32;
+        let mut generator = WorldGenerator::flat();
+        let corners: Vec<(i32, i32)> = (0..MAX_OPEN_REGIONS as i32 + 4)
+            .map(|i| (i * REGION_SIZE * s, (i % 3) * s))
+            .collect();
+
+        // The regions closed to open new ones are still on the disk
+        for corner in &corners {
+            storage.save_chunks(&[generator.generate_chunk(*corner)]).unwrap();
+        }
+        assert_eq!(MAX_OPEN_REGIONS, storage.regions.len());
+        for corner in &corners {
+            assert_eq!(*corner, storage.load_chunk(*corner).unwrap().unwrap().integer_corner());
+        }
+
+        // Another storage of the same directory reads them as well
+        let mut other = WorldStorage::new(storage.dir()).unwrap();
+        let neighbour = (corners[0].0 + s, corners[0].1);
+        storage.save_chunks(&[generator.generate_chunk(neighbour)]).unwrap();
+        assert!(other.load_chunk(corners[0]).unwrap().is_some());
+        assert!(other.load_chunk(neighbour).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_only_modified_chunks_are_saved() {
+        let mut storage = temporary_storage("modified_chunks");
+        let mut world = World::empty();
+        let mut generator = WorldGenerator::flat();
+        world.add_generated_chunk(generator.generate_chunk((0, 0)));
+        world.add_generated_chunk(generator.generate_chunk((CHUNK_SIZE as i32, 0)));
+
+        // The generated chunks are saved once
+        assert_eq!(2, storage.save_modified_chunks(&mut world).unwrap());
+        assert_eq!(0, storage.save_modified_chunks(&mut world).unwrap());
+
+        // Then, only the chunks affected by an action
+        let at = Vector3::new(1., 3., 1.);
+        world.apply_action(&Action::Destroy { at });
+        assert_eq!(1, storage.save_modified_chunks(&mut world).unwrap());
+        let saved = storage.load_chunk((0, 0)).unwrap().unwrap();
+        assert!(saved.cube_at(&at).is_none());
+    }
+
+    #[test]
+    fn test_server_state_persistence() {
+        let storage = temporary_storage("server_state");
+        assert!(storage.load_server_state().unwrap().is_none());
+
+        let mut state = ServerState::new();
+        let p1 = state.login("arthur".to_string());
+        let p2 = state.login("johan".to_string());
+        storage.save_server_state(&state).unwrap();
+
+        // The players are known, but no longer connected
+        let mut reconstructed = storage.load_server_state().unwrap().unwrap();
+        assert_eq!(0, reconstructed.n_players_connected());
+        let p2_again = reconstructed.login("johan".to_string());
+        assert_eq!(p2.id, p2_again.id);
+        assert_ne!(p1.id, p2_again.id);
+
+        // A new player does not take the id of a known one
+        let p3 = reconstructed.login("arnaud".to_string());
+        assert!(p3.id != p1.id && p3.id != p2.id);
+    }
+}
@@ -1,12 +1,16 @@
-use std::str::from_utf8;
-use model::entity::entity::EntityKind;
+use model::entity::entity::{EntityId, EntityKind};
 use model::game::actions::Action;
-use model::game::attack::EntityAttack;
+use model::game::player_items::{ItemStack, PlayerItems};
+use model::server::action_validation::ActionRejection;
 use primitives::position::Position;
+use primitives::vector::Vector3;
+use model::server::server_state::PlayerState;
 use model::server::server_update::ServerUpdate;
-use model::server::server_update::ServerUpdate::{Attack, LoadChunk, LoggedIn, RegisterEntity, RemoveEntity, SendAction, UpdatePosition};
-use model::world::chunk::Chunk;
-use crate::tcp_message_encoding::{TcpDeserialize, TcpSerialize};
+use model::server::server_update::ServerUpdate::{
+    Death, KnockBack, LoadChunk, LoggedIn, PickUpItem, RegisterEntity, RejectAction, RemoveEntity,
+    Respawn, SendAction, SetItems, UnloadChunk, UpdateHealth, UpdatePosition,
+};
+use crate::tcp_message_encoding::{ChunkEncoding, TcpDeserialize, TcpError, TcpSerialize};
 
 pub mod message_to_server;
 pub mod proxy;
@@ -20,74 +24,124 @@ impl TcpSerialize for ServerUpdate {
     fn to_u8(&self) -> u8 {
         match self {
             LoadChunk(_) => 0,
-            LoggedIn(_, _) => 1,
+            LoggedIn(_) => 1,
             SendAction(_) => 2,
             RegisterEntity(_, _, _) => 3,
             UpdatePosition(_, _) => 4,
-            Attack(_) => 5,
+            KnockBack(_) => 5,
             RemoveEntity(_) => 6,
+            UnloadChunk(_) => 7,
+            RejectAction(_) => 8,
+            PickUpItem(_) => 9,
+            Death(_) => 10,
+            Respawn(_) => 11,
+            UpdateHealth(_, _) => 12,
+            SetItems(_) => 13,
         }
     }
 
     fn to_bytes_representation(&self) -> Vec<u8> {
         // Compute the data inside the message
         match self {
-            LoadChunk(chunk) => chunk.to_json().into_bytes(),
+            LoadChunk(_) => self.to_bytes_with_encoding(ChunkEncoding::Palette),
             SendAction(action) => action.to_bytes(),
-            LoggedIn(id, pos) | UpdatePosition(id, pos) => {
-                let mut bytes = vec![*id];
+            LoggedIn(state) | Respawn(state) => state.to_bytes(),
+            UpdatePosition(id, pos) => {
+                let mut bytes = id.to_be_bytes().to_vec();
                 bytes.extend_from_slice(&pos.to_bytes());
                 bytes
             }
             RegisterEntity(id, entity_kind, pos) => {
-                let mut bytes = vec![*id];
-                bytes.push(entity_kind.to_u8());
+                let mut bytes = id.to_be_bytes().to_vec();
+                bytes.extend_from_slice(&entity_kind.to_bytes());
                 bytes.extend_from_slice(&pos.to_bytes());
                 bytes
             }
-            Attack(attack) => attack.to_bytes(),
+            KnockBack(velocity) => velocity.to_bytes(),
             RemoveEntity(id) => id.to_be_bytes().to_vec(),
+            UnloadChunk((x, z)) => [x.to_le_bytes(), z.to_le_bytes()].concat(),
+            RejectAction(rejection) => rejection.to_bytes(),
+            PickUpItem(stack) => stack.to_bytes(),
+            Death(items) | SetItems(items) => items.to_bytes(),
+            UpdateHealth(id, health) => [id.to_be_bytes().as_slice(), &[*health]].concat(),
+        }
+    }
+
+    fn to_bytes_with_encoding(&self, chunk_encoding: ChunkEncoding) -> Vec<u8> {
+        match self {
+            LoadChunk(chunk) => chunk_encoding.encode(chunk),
+            _ => self.to_bytes_representation(),
         }
     }
 }
 
 impl TcpDeserialize for ServerUpdate {
-    fn parse_bytes_representation(code: u8, bytes_to_parse: &[u8]) -> ServerUpdate {
+    fn parse_bytes_representation(code: u8, bytes_to_parse: &[u8]) -> Result<Self, TcpError> {
+        let truncated = || TcpError::invalid(code, "message is too short");
         match code {
-            0 => {
-                let as_json = from_utf8(bytes_to_parse).unwrap();
-                let chunk = Chunk::from_json(as_json);
-                match chunk {
-                    Ok(chunk) => LoadChunk(chunk),
-                    Err(err) => panic!("Error while parsing a chunk: {err}"),
+            0 => ChunkEncoding::decode(bytes_to_parse).map(|chunk| LoadChunk(Box::new(chunk))),
+            1 => PlayerState::from_bytes(bytes_to_parse)
+                .map(LoggedIn)
+                .map_err(|err| TcpError::invalid(code, err)),
+            2 => Action::from_bytes(bytes_to_parse)
+                .map(SendAction)
+                .ok_or_else(|| TcpError::invalid(code, "invalid action")),
+            3 => match bytes_to_parse {
+                [b0, b1, b2, b3, rest @ ..] => {
+                    let (kind, pos) = EntityKind::from_bytes(rest)
+                        .ok_or_else(|| TcpError::invalid(code, "invalid entity kind"))?;
+                    Ok(RegisterEntity(
+                        EntityId::from_be_bytes([*b0, *b1, *b2, *b3]),
+                        kind,
+                        Position::from_bytes(pos).ok_or_else(truncated)?,
+                    ))
                 }
-            }
-            1 => LoggedIn(
-                bytes_to_parse[0],
-                Position::from_bytes(&bytes_to_parse[1..]),
-            ),
-            2 => {
-                let as_json = from_utf8(bytes_to_parse).unwrap();
-                let action = Action::from_str(as_json);
-                SendAction(action)
-            }
-            3 => RegisterEntity(
-                bytes_to_parse[0],
-                EntityKind::from_u8(bytes_to_parse[1]),
-                Position::from_bytes(&bytes_to_parse[2..]),
-            ),
-            4 => UpdatePosition(
-                bytes_to_parse[0],
-                Position::from_bytes(&bytes_to_parse[1..]),
-            ),
-            5 => Attack(EntityAttack::from_bytes(bytes_to_parse)),
-            6 => RemoveEntity(u32::from_be_bytes([
-                bytes_to_parse[0],
-                bytes_to_parse[1],
-                bytes_to_parse[2],
-                bytes_to_parse[3],
-            ])),
-            _ => panic!("Cannot build server update from code {code}"),
+                _ => Err(truncated()),
+            },
+            4 => match bytes_to_parse {
+                [b0, b1, b2, b3, pos @ ..] => Ok(UpdatePosition(
+                    EntityId::from_be_bytes([*b0, *b1, *b2, *b3]),
+                    Position::from_bytes(pos).ok_or_else(truncated)?,
+                )),
+                _ => Err(truncated()),
+            },
+            5 => Vector3::from_bytes(bytes_to_parse)
+                .map(KnockBack)
+                .ok_or_else(truncated),
+            6 => match bytes_to_parse {
+                [b0, b1, b2, b3] => Ok(RemoveEntity(EntityId::from_be_bytes([*b0, *b1, *b2, *b3]))),
+                _ => Err(truncated()),
+            },
+            7 => match bytes_to_parse {
+                [x0, x1, x2, x3, z0, z1, z2, z3] => Ok(UnloadChunk((
+                    i32::from_le_bytes([*x0, *x1, *x2, *x3]),
+                    i32::from_le_bytes([*z0, *z1, *z2, *z3]),
+                ))),
+                _ => Err(truncated()),
+            },
+            8 => ActionRejection::from_bytes(bytes_to_parse)
+                .map(RejectAction)
+                .map_err(|err| TcpError::invalid(code, err)),
+            9 => ItemStack::from_bytes(bytes_to_parse)
+                .map(PickUpItem)
+                .map_err(|err| TcpError::invalid(code, err)),
+            10 => PlayerItems::from_bytes(bytes_to_parse)
+                .map(Death)
+                .map_err(|err| TcpError::invalid(code, err)),
+            11 => PlayerState::from_bytes(bytes_to_parse)
+                .map(Respawn)
+                .map_err(|err| TcpError::invalid(code, err)),
+            12 => match bytes_to_parse {
+                [b0, b1, b2, b3, health] => Ok(UpdateHealth(
+                    EntityId::from_be_bytes([*b0, *b1, *b2, *b3]),
+                    *health,
+                )),
+                _ => Err(truncated()),
+            },
+            13 => PlayerItems::from_bytes(bytes_to_parse)
+                .map(SetItems)
+                .map_err(|err| TcpError::invalid(code, err)),
+            _ => Err(TcpError::UnknownCode(code)),
         }
     }
 }
@@ -97,15 +151,26 @@ mod tests {
     use model::entity::entity::EntityKind;
     use primitives::position::Position;
     use primitives::vector::Vector3;
+    use model::server::server_state::ServerState;
     use model::server::server_update::ServerUpdate;
-    use model::server::server_update::ServerUpdate::{LoadChunk, LoggedIn, RegisterEntity, RemoveEntity};
+    use model::server::server_update::ServerUpdate::{
+        Death, KnockBack, LoadChunk, LoggedIn, PickUpItem, RegisterEntity, RejectAction,
+        RemoveEntity, Respawn, SetItems, UnloadChunk, UpdateHealth,
+    };
+    use model::game::item::ItemKind;
+    use model::game::player_items::{ItemStack, PlayerItems};
+    use model::server::action_validation::ActionRejection;
+    use model::world::block_kind::Block;
     use model::world::chunk::Chunk;
-    use crate::tcp_message_encoding::{from_tcp_repr, to_tcp_repr, ParseContext};
+    use crate::tcp_message_encoding::{
+        from_tcp_repr, to_tcp_repr, to_tcp_repr_with_encoding, ChunkEncoding, ParseContext,
+        TcpError,
+    };
 
     #[test]
     fn test_load_chunks_encoding_decoding() {
         let chunk = Chunk::new_for_demo([3., 5.], 5);
-        let update = LoadChunk(chunk);
+        let update = LoadChunk(Box::new(chunk));
         let bytes = to_tcp_repr(&update);
         let mut context = ParseContext::new();
         let parsed = from_tcp_repr::<ServerUpdate>(bytes.as_slice(), &mut context).unwrap();
@@ -119,47 +184,162 @@ mod tests {
 
     #[test]
     fn test_response_encoding_decoding() {
-        let update = LoggedIn(113, Position::empty());
+        let update = LoggedIn(ServerState::new().login("arthur".to_string()));
         let bytes = to_tcp_repr(&update);
         let mut context = ParseContext::new();
         let parsed = from_tcp_repr::<ServerUpdate>(bytes.as_slice(), &mut context).unwrap();
 
         // Assert that the two chunks are the same !
         match (&update, &parsed[0]) {
-            (LoggedIn(a, _), LoggedIn(b, _)) => assert_eq!(a, b),
+            (LoggedIn(a), LoggedIn(b)) => assert_eq!(a, b),
             (_, _) => assert!(false),
         }
     }
 
+    #[test]
+    fn test_rejection_encoding_decoding() {
+        let update = RejectAction(ActionRejection {
+            at: Vector3::new(3., 12., -5.),
+            block: Some(Block::SAND),
+            items: PlayerItems::starting_items(),
+        });
+        let bytes = to_tcp_repr(&update);
+        let mut context = ParseContext::new();
+        let parsed = from_tcp_repr::<ServerUpdate>(bytes.as_slice(), &mut context).unwrap();
+
+        match (&update, &parsed[0]) {
+            (RejectAction(a), RejectAction(b)) => assert_eq!(a, b),
+            (_, other) => panic!("Unexpected update: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_death_encoding_decoding() {
+        let update_1 = Death(PlayerItems::starting_items());
+        let update_2 = Respawn(ServerState::new().login("arthur".to_string()));
+        let update_3 = SetItems(PlayerItems::starting_items());
+        let mut bytes = to_tcp_repr(&update_1);
+        bytes.append(&mut to_tcp_repr(&update_2));
+        bytes.append(&mut to_tcp_repr(&update_3));
+
+        let mut context = ParseContext::new();
+        let parsed = from_tcp_repr::<ServerUpdate>(bytes.as_slice(), &mut context).unwrap();
+        match (&update_1, &parsed[0]) {
+            (Death(a), Death(b)) => assert_eq!(a, b),
+            (_, other) => panic!("Unexpected update: {other:?}"),
+        }
+        match (&update_2, &parsed[1]) {
+            (Respawn(a), Respawn(b)) => assert_eq!(a, b),
+            (_, other) => panic!("Unexpected update: {other:?}"),
+        }
+        match (&update_3, &parsed[2]) {
+            (SetItems(a), SetItems(b)) => assert_eq!(a, b),
+            (_, other) => panic!("Unexpected update: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_health_encoding_decoding() {
+        let update_1 = KnockBack(Vector3::new(-4., 2.5, 0.3));
+        let update_2 = UpdateHealth(300, 7);
+        let mut bytes = to_tcp_repr(&update_1);
+        bytes.append(&mut to_tcp_repr(&update_2));
+
+        let mut context = ParseContext::new();
+        let parsed = from_tcp_repr::<ServerUpdate>(bytes.as_slice(), &mut context).unwrap();
+        match (&update_1, &parsed[0]) {
+            (KnockBack(a), KnockBack(b)) => assert_eq!(a, b),
+            (_, other) => panic!("Unexpected update: {other:?}"),
+        }
+        match &parsed[1] {
+            UpdateHealth(id, health) => assert_eq!((300, 7), (*id, *health)),
+            other => panic!("Unexpected update: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_dropped_item_encoding_decoding() {
+        let stack = ItemStack::new(ItemKind::STONE_SWORD.into());
+        let update_1 = RegisterEntity(
+            70_000,
+            EntityKind::DroppedItem(stack.item),
+            Position::from_pos(Vector3::new(-3., 2., 34.5)),
+        );
+        let update_2 = PickUpItem(stack);
+        let mut bytes = to_tcp_repr(&update_1);
+        bytes.append(&mut to_tcp_repr(&update_2));
+
+        let mut context = ParseContext::new();
+        let parsed = from_tcp_repr::<ServerUpdate>(bytes.as_slice(), &mut context).unwrap();
+        match (&update_1, &parsed[0]) {
+            (RegisterEntity(id1, kind1, pos1), RegisterEntity(id2, kind2, pos2)) => {
+                assert_eq!((id1, kind1, pos1), (id2, kind2, pos2))
+            }
+            (_, other) => panic!("Unexpected update: {other:?}"),
+        }
+        match &parsed[1] {
+            PickUpItem(parsed) => assert_eq!(&stack, parsed),
+            other => panic!("Unexpected update: {other:?}"),
+        }
+
+        // An unknown item is rejected
+        let mut bytes = to_tcp_repr(&update_1);
+        bytes[11] = 200;
+        assert!(matches!(
+            from_tcp_repr::<ServerUpdate>(bytes.as_slice(), &mut ParseContext::new()),
+            Err(TcpError::InvalidContent { code: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn test_falling_block_encoding_decoding() {
+        let update = RegisterEntity(
+            101,
+            EntityKind::FallingBlock(Block::SAND),
+            Position::from_pos(Vector3::new(4., 11.5, -7.)),
+        );
+        let bytes = to_tcp_repr(&update);
+        let parsed = from_tcp_repr::<ServerUpdate>(bytes.as_slice(), &mut ParseContext::new());
+        match (&update, &parsed.unwrap()[0]) {
+            (RegisterEntity(id1, kind1, pos1), RegisterEntity(id2, kind2, pos2)) => {
+                assert_eq!((id1, kind1, pos1), (id2, kind2, pos2))
+            }
+            (_, other) => panic!("Unexpected update: {other:?}"),
+        }
+    }
+
     #[test]
     fn test_parse_multiple_updates_at_one() {
         let chunk1 = Chunk::new_for_demo([3., 5.], 5);
         let chunk2 = Chunk::new_for_demo([31., -52.], 10);
 
-        let update_1 = LoadChunk(chunk1);
-        let update_2 = LoadChunk(chunk2);
-        let update_3 = LoggedIn(113, Position::empty());
+        let update_1 = LoadChunk(Box::new(chunk1));
+        let update_2 = LoadChunk(Box::new(chunk2));
+        let update_3 = LoggedIn(ServerState::new().login("arthur".to_string()));
         let update_4 = RegisterEntity(
             113,
             EntityKind::Monster1,
             Position::from_pos(Vector3::new(-3., 2., 34.532)),
         );
         let update_5 = RemoveEntity(258);
+        let update_6 = UnloadChunk((-8, 16));
 
         let mut bytes1 = to_tcp_repr(&update_1);
         let mut bytes2 = to_tcp_repr(&update_2);
         let mut bytes3 = to_tcp_repr(&update_3);
         let mut bytes4 = to_tcp_repr(&update_4);
         let mut bytes5 = to_tcp_repr(&update_5);
+        let mut bytes6 = to_tcp_repr(&update_6);
 
         bytes1.append(&mut bytes2);
         bytes1.append(&mut bytes3);
         bytes1.append(&mut bytes4);
         bytes1.append(&mut bytes5);
+        bytes1.append(&mut bytes6);
 
         let mut context = ParseContext::new();
         let parsed = from_tcp_repr::<ServerUpdate>(bytes1.as_slice(), &mut context).unwrap();
-        assert_eq!(5, parsed.len());
+        assert_eq!(6, parsed.len());
 
         match (&update_1, &parsed[0]) {
             (LoadChunk(a), LoadChunk(b)) => assert_eq!(a, b),
@@ -172,7 +352,7 @@ mod tests {
         }
 
         match (&update_3, &parsed[2]) {
-            (LoggedIn(a, _), LoggedIn(b, _)) => assert_eq!(a, b),
+            (LoggedIn(a), LoggedIn(b)) => assert_eq!(a, b),
             (_, _) => assert!(false),
         }
 
@@ -188,13 +368,17 @@ mod tests {
             (RemoveEntity(id0), RemoveEntity(id1)) => assert_eq!(id0, id1),
             (_, _) => assert!(false),
         }
+        match (&update_6, &parsed[5]) {
+            (UnloadChunk(corner0), UnloadChunk(corner1)) => assert_eq!(corner0, corner1),
+            (_, other) => panic!("Unexpected update: {other:?}"),
+        }
     }
 
     #[test]
     fn test_one_message_sent_over_mutliple_packet() {
         let chunk1 = Chunk::new_for_demo([3., 5.], 5);
-        let update_1 = LoadChunk(chunk1);
-        let bytes1 = to_tcp_repr(&update_1);
+        let update_1 = LoadChunk(Box::new(chunk1));
+        let bytes1 = to_tcp_repr_with_encoding(&update_1, ChunkEncoding::Plain);
 
         let packet1 = &bytes1[0..500];
         let packet2 = &bytes1[500..1500];
@@ -1,51 +1,106 @@
 use std::str::from_utf8;
 use model::game::actions::Action;
 use model::game::attack::EntityAttack;
+use model::game::crafting::{grid_from_bytes, grid_to_bytes, CraftingGrid};
+use model::game::player_items::PlayerItems;
 use primitives::position::Position;
-use crate::message_to_server::MessageToServer::{Attack, Login, OnNewAction, OnNewPosition, SpawnRequest};
-use crate::tcp_message_encoding::{TcpDeserialize, TcpSerialize};
+use crate::message_to_server::MessageToServer::{
+    ArrangeItems, Attack, Craft, Login, OnNewAction, OnNewPosition, Respawn, SaveWorld, SelectItem,
+    SpawnRequest, ThrowItem,
+};
+use crate::tcp_message_encoding::{TcpDeserialize, TcpError, TcpSerialize};
 
 /// List of message that can be exchanged between to the server from the client
 #[derive(Debug, PartialEq)]
 pub enum MessageToServer {
-    /// Ask the server to log in a new player with a given identifer
-    Login(String),
+    /// Ask the server to log in a new player with a given identifer, and the number of chunks that
+    /// the player would like to see in each direction
+    Login(String, u8),
     OnNewPosition(Position),
     OnNewAction(Action),
     Attack(EntityAttack),
     SpawnRequest(Position),
+    /// The player moved their items between the slots, without adding or removing any
+    ArrangeItems(Box<PlayerItems>),
+    /// The player throws one item of the stack they are holding
+    ThrowItem,
+    /// The dead player asks to come back to life
+    Respawn,
+    /// The player holds the given slot of their item bar
+    SelectItem(u8),
+    /// The player crafted the recipe of the grid
+    Craft(CraftingGrid),
+    /// Ask the server to save its world now
+    SaveWorld,
 }
 
 impl TcpSerialize for MessageToServer {
     fn to_u8(&self) -> u8 {
         match self {
-            Login(_) => 0,
+            Login(_, _) => 0,
             OnNewPosition(_) => 1,
             OnNewAction(_) => 2,
             Attack(_) => 3,
             SpawnRequest(_) => 4,
+            ArrangeItems(_) => 5,
+            ThrowItem => 6,
+            Respawn => 7,
+            SelectItem(_) => 8,
+            Craft(_) => 9,
+            SaveWorld => 10,
         }
     }
 
     fn to_bytes_representation(&self) -> Vec<u8> {
         match self {
-            Login(name) => name.clone().into_bytes(),
+            Login(name, view_distance) => [&[*view_distance], name.as_bytes()].concat(),
             OnNewPosition(pos) | SpawnRequest(pos) => pos.to_bytes(),
             OnNewAction(action) => action.to_bytes(),
             Attack(attack) => attack.to_bytes(),
+            ArrangeItems(items) => items.to_bytes(),
+            ThrowItem | Respawn | SaveWorld => vec![],
+            SelectItem(index) => vec![*index],
+            Craft(grid) => grid_to_bytes(grid),
         }
     }
 }
 
 impl TcpDeserialize for MessageToServer {
-    fn parse_bytes_representation(code: u8, bytes_to_parse: &[u8]) -> Self {
+    fn parse_bytes_representation(code: u8, bytes_to_parse: &[u8]) -> Result<Self, TcpError> {
+        let truncated = || TcpError::invalid(code, "message is too short");
         match code {
-            0 => Login(from_utf8(bytes_to_parse).unwrap().to_string()),
-            1 => OnNewPosition(Position::from_bytes(bytes_to_parse)),
-            2 => OnNewAction(Action::from_str(from_utf8(bytes_to_parse).unwrap())),
-            3 => Attack(EntityAttack::from_bytes(bytes_to_parse)),
-            4 => SpawnRequest(Position::from_bytes(bytes_to_parse)),
-            _ => panic!("Cannot build message to server from code {code}"),
+            0 => match bytes_to_parse {
+                [view_distance, name @ ..] => from_utf8(name)
+                    .map(|name| Login(name.to_string(), *view_distance))
+                    .map_err(|err| TcpError::invalid(code, err)),
+                _ => Err(truncated()),
+            },
+            1 => Position::from_bytes(bytes_to_parse)
+                .map(OnNewPosition)
+                .ok_or_else(truncated),
+            2 => Action::from_bytes(bytes_to_parse)
+                .map(OnNewAction)
+                .ok_or_else(|| TcpError::invalid(code, "invalid action")),
+            3 => EntityAttack::from_bytes(bytes_to_parse)
+                .map(Attack)
+                .ok_or_else(truncated),
+            4 => Position::from_bytes(bytes_to_parse)
+                .map(SpawnRequest)
+                .ok_or_else(truncated),
+            5 => PlayerItems::from_bytes(bytes_to_parse)
+                .map(|items| ArrangeItems(Box::new(items)))
+                .map_err(|err| TcpError::invalid(code, err)),
+            6 => Ok(ThrowItem),
+            7 => Ok(Respawn),
+            8 => match bytes_to_parse {
+                [index] => Ok(SelectItem(*index)),
+                _ => Err(truncated()),
+            },
+            9 => grid_from_bytes(bytes_to_parse)
+                .map(Craft)
+                .map_err(|err| TcpError::invalid(code, err)),
+            10 => Ok(SaveWorld),
+            _ => Err(TcpError::UnknownCode(code)),
         }
     }
 }
@@ -55,8 +110,13 @@ mod tests {
     use primitives::position::Position;
     use primitives::vector::Vector3;
     use crate::message_to_server::MessageToServer;
-    use crate::message_to_server::MessageToServer::{Login, OnNewPosition};
-    use crate::tcp_message_encoding::{from_tcp_repr, to_tcp_repr, ParseContext};
+    use crate::message_to_server::MessageToServer::{ArrangeItems, Login, OnNewPosition};
+    use model::game::player_items::PlayerItems;
+    use crate::tcp_message_encoding::{from_tcp_repr, to_tcp_repr, ParseContext, TcpError};
+    use model::game::actions::Action;
+    use model::game::attack::EntityAttack;
+    use model::game::item::Item;
+    use model::world::block_kind::Block;
 
     fn test_integrity(m: MessageToServer) {
         let bytes = to_tcp_repr(&m);
@@ -67,9 +127,61 @@ mod tests {
 
     #[test]
     fn test_message_integrity() {
-        test_integrity(Login("arthur".to_string()));
+        test_integrity(Login("arthur".to_string(), 4));
         test_integrity(OnNewPosition(Position::new_vec(1.0, 1.0, 1.0)));
         test_integrity(OnNewPosition(Position::new_vec(-1.0, 2.0, 100.012)));
+        test_integrity(ArrangeItems(Box::new(PlayerItems::starting_items())));
+        test_integrity(MessageToServer::SelectItem(3));
+        let log = Some(Item::Block(Block::OAKLOG));
+        test_integrity(MessageToServer::Craft([[log, None, log], [None; 3], [None, log, None]]));
+        test_integrity(MessageToServer::ThrowItem);
+        test_integrity(MessageToServer::Respawn);
+        test_integrity(MessageToServer::SaveWorld);
+        test_integrity(MessageToServer::OnNewAction(Action::Destroy {
+            at: Vector3::new(1., 2., 3.),
+        }));
+        let mut attack = EntityAttack::new(70_000);
+        attack.set_strength(4);
+        test_integrity(MessageToServer::Attack(attack));
+    }
+
+    fn parse(bytes: &[u8]) -> Result<Vec<MessageToServer>, TcpError> {
+        from_tcp_repr(bytes, &mut ParseContext::new())
+    }
+
+    #[test]
+    fn test_malformed_messages_are_rejected() {
+        // Unknown code
+        assert_eq!(Err(TcpError::UnknownCode(200)), parse(&[200, 0, 0, 0, 0]));
+
+        // Position with missing bytes
+        let mut bytes = to_tcp_repr(&OnNewPosition(Position::new_vec(1., 2., 3.)));
+        bytes[1] -= 1;
+        bytes.pop();
+        assert!(matches!(parse(&bytes), Err(TcpError::InvalidContent { code: 1, .. })));
+
+        // Name which is not valid UTF-8
+        assert!(matches!(
+            parse(&[0, 3, 0, 0, 0, 4, 0xC3, 0x28]),
+            Err(TcpError::InvalidContent { code: 0, .. })
+        ));
+
+        // Login without a view distance
+        assert!(matches!(parse(&[0, 0, 0, 0, 0]), Err(TcpError::InvalidContent { code: 0, .. })));
+
+        // Absurd length
+        assert!(matches!(parse(&[0, 255, 255, 255, 255]), Err(TcpError::LengthError(_))));
+    }
+
+    #[test]
+    fn test_message_received_byte_by_byte() {
+        let message = OnNewPosition(Position::new_vec(-4., 2., 12.5));
+        let mut context = ParseContext::new();
+        let mut parsed = Vec::new();
+        for byte in to_tcp_repr(&message) {
+            parsed.extend(from_tcp_repr::<MessageToServer>(&[byte], &mut context).unwrap());
+        }
+        assert_eq!(vec![message], parsed);
     }
 
     fn test_multiple_messages(messages: &[MessageToServer]) {
@@ -95,7 +207,7 @@ mod tests {
             OnNewPosition(Position::from_pos(p2)),
         ]);
         test_multiple_messages(&[
-            Login("hey".to_string()),
+            Login("hey".to_string(), 12),
             OnNewPosition(Position::from_pos(p1)),
         ]);
     }
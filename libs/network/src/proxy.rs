@@ -1,15 +1,30 @@
 use model::game::actions::Action;
 use model::game::attack::EntityAttack;
+use model::game::crafting::CraftingGrid;
+use model::game::player_items::PlayerItems;
 use primitives::position::Position;
 use model::server::server_update::ServerUpdate;
 
 /// Defines the interface that a client use to communicate with the game server
 pub trait Proxy {
-    fn login(&mut self, name: String);
+    /// Logs in the player, who would like to see `view_distance` chunks in each direction
+    fn login(&mut self, name: String, view_distance: u8);
     fn send_position_update(&mut self, position: Position);
     fn on_new_action(&mut self, action: Action);
     fn on_new_attack(&mut self, attack: EntityAttack);
     fn request_to_spawn(&mut self, position: Position);
+    /// Holds another slot of the item bar
+    fn select_item(&mut self, index: usize);
+    /// Crafts the recipe of the grid, with ingredients taken from the items of the player
+    fn craft(&mut self, grid: CraftingGrid);
+    /// Moves the items of the player between their slots
+    fn arrange_items(&mut self, items: PlayerItems);
+    /// Throws one item of the stack held by the player
+    fn throw_item(&mut self);
+    /// Asks the server to bring the dead player back to life
+    fn respawn(&mut self);
+    /// Asks the server to save its world now, if it has a storage
+    fn save_world(&mut self);
     fn consume_server_updates(&mut self) -> Vec<ServerUpdate>;
     /// Returns the delay to wait for at startup
     fn loading_delay(&self) -> u64;
@@ -1,6 +1,9 @@
 use std::sync::{Arc, Mutex};
 use model::game::actions::Action;
+use model::entity::entity::EntityId;
 use model::game::attack::EntityAttack;
+use model::game::crafting::CraftingGrid;
+use model::game::player_items::PlayerItems;
 use primitives::position::Position;
 use model::server::game_server::GameServer;
 use model::server::server_update::ServerUpdate;
@@ -8,7 +11,7 @@ use crate::proxy::Proxy;
 
 pub struct SinglePlayerProxy {
     server: Arc<Mutex<GameServer>>,
-    client_id: usize,
+    client_id: EntityId,
 }
 
 impl SinglePlayerProxy {
@@ -21,8 +24,8 @@ impl SinglePlayerProxy {
 }
 
 impl Proxy for SinglePlayerProxy {
-    fn login(&mut self, name: String) {
-        self.client_id = self.server.lock().unwrap().login(name);
+    fn login(&mut self, name: String, view_distance: u8) {
+        self.client_id = self.server.lock().unwrap().login(name, view_distance);
     }
 
     fn send_position_update(&mut self, position: Position) {
@@ -40,13 +43,46 @@ impl Proxy for SinglePlayerProxy {
     }
 
     fn on_new_attack(&mut self, attack: EntityAttack) {
-        self.server.lock().unwrap().on_new_attack(attack);
+        self.server
+            .lock()
+            .unwrap()
+            .on_new_attack(self.client_id, attack);
     }
 
     fn request_to_spawn(&mut self, position: Position) {
         self.server.lock().unwrap().spawn_monster(position);
     }
 
+    fn select_item(&mut self, index: usize) {
+        self.server
+            .lock()
+            .unwrap()
+            .on_select_item(self.client_id, index);
+    }
+
+    fn craft(&mut self, grid: CraftingGrid) {
+        self.server.lock().unwrap().on_craft(self.client_id, grid);
+    }
+
+    fn arrange_items(&mut self, items: PlayerItems) {
+        self.server
+            .lock()
+            .unwrap()
+            .on_arrange_items(self.client_id, items);
+    }
+
+    fn throw_item(&mut self) {
+        self.server.lock().unwrap().on_throw_item(self.client_id);
+    }
+
+    fn respawn(&mut self) {
+        self.server.lock().unwrap().on_respawn(self.client_id);
+    }
+
+    fn save_world(&mut self) {
+        self.server.lock().unwrap().save_world();
+    }
+
     fn consume_server_updates(&mut self) -> Vec<ServerUpdate> {
         self.server.lock().unwrap().consume_updates(self.client_id)
     }
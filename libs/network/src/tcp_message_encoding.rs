@@ -1,18 +1,129 @@
+use model::server::server_update::{RESPONSE_ERROR, RESPONSE_OK};
+use model::world::chunk::{Chunk, ChunkDecodingError};
 use std::fmt::{Display, Formatter};
 
-#[derive(Debug)]
-enum TcpError {
-    LengthError,
+/// Version of the protocol. It must be increased each time the encoding of a message changes, so
+/// that a client and a server that do not speak the same language refuse to communicate.
+pub const PROTOCOL_VERSION: u16 = 13;
+
+/// First bytes sent by a client when connecting to a server
+const HANDSHAKE_MAGIC: &[u8; 4] = b"CRFT";
+
+/// Length of the handshake sent by the client: the magic bytes, the version of the protocol, the
+/// chunk encodings it supports and the digest of its definitions
+pub const HANDSHAKE_REQUEST_LEN: usize = HANDSHAKE_MAGIC.len() + 11;
+
+/// Length of the answer of the server to the handshake: the status, the version of the protocol
+/// and the chunk encoding it will use, or the reason why it refused the connection
+pub const HANDSHAKE_RESPONSE_LEN: usize = 4;
+
+/// Reasons sent by the server which refuses a handshake
+const REJECTED_VERSION: u8 = 0;
+const REJECTED_INVALID: u8 = 1;
+const REJECTED_DEFINITIONS: u8 = 2;
+
+/// Length of the header of each message: the code and the length of the message
+const HEADER_LEN: usize = 5;
+
+/// Messages bigger than this are considered as malformed
+const MAX_MESSAGE_LEN: usize = 1 << 24;
+
+#[derive(Debug, PartialEq)]
+pub enum TcpError {
+    /// The header of a message announces an invalid length
+    LengthError(usize),
+    /// The code of a message is not part of the protocol
+    UnknownCode(u8),
+    /// The content of a message could not be decoded
+    InvalidContent { code: u8, reason: String },
+    /// A chunk could not be decoded
+    InvalidChunk(ChunkDecodingError),
+    /// A chunk is encoded in a way that is not part of the protocol
+    UnknownChunkEncoding(u8),
+    /// The peer did not start the communication with a handshake
+    InvalidHandshake,
+    /// The client and the server do not use the same version of the protocol
+    VersionMismatch { client: u16, server: u16 },
+    /// The client and the server do not have the same blocks, items or recipes
+    DefinitionsMismatch,
 }
 
 impl Display for TcpError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Error while communicating over TCP: {:?}", self)
+        match self {
+            TcpError::LengthError(len) => write!(f, "Invalid message length: {len}"),
+            TcpError::UnknownCode(code) => write!(f, "Unknown message code: {code}"),
+            TcpError::InvalidContent { code, reason } => {
+                write!(f, "Invalid content for message {code}: {reason}")
+            }
+            TcpError::InvalidChunk(err) => write!(f, "Invalid chunk: {err}"),
+            TcpError::UnknownChunkEncoding(code) => write!(f, "Unknown chunk encoding: {code}"),
+            TcpError::InvalidHandshake => write!(f, "Invalid handshake"),
+            TcpError::VersionMismatch { client, server } => write!(
+                f,
+                "Client uses version {client} of the protocol, but the server uses version {server}"
+            ),
+            TcpError::DefinitionsMismatch => write!(
+                f,
+                "Client and server do not have the same definitions of blocks, items and recipes"
+            ),
+        }
     }
 }
 
 impl std::error::Error for TcpError {}
 
+impl TcpError {
+    /// Returns an error telling that the content of a message could not be decoded
+    pub fn invalid(code: u8, reason: impl ToString) -> Self {
+        TcpError::InvalidContent {
+            code,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// The ways of encoding the chunks sent by the server.
+/// The client tells which ones it supports during the handshake, and the server picks the best one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChunkEncoding {
+    /// One or two bytes per position of the chunk (see `Chunk::to_bytes`)
+    Plain = 0,
+    /// Palette and run-length encoding (see `Chunk::to_palette_bytes`)
+    Palette = 1,
+}
+
+impl ChunkEncoding {
+    /// All the encodings, from the least to the most efficient
+    pub const ALL: [ChunkEncoding; 2] = [ChunkEncoding::Plain, ChunkEncoding::Palette];
+
+    fn from_u8(code: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|encoding| *encoding as u8 == code)
+    }
+
+    /// Encodes the chunk, prefixed by the encoding used so that the receiver knows how to decode it
+    pub fn encode(&self, chunk: &Chunk) -> Vec<u8> {
+        let data = match self {
+            ChunkEncoding::Plain => chunk.to_bytes(),
+            ChunkEncoding::Palette => chunk.to_palette_bytes(),
+        };
+        [vec![*self as u8], data].concat()
+    }
+
+    /// Decodes a chunk encoded by `encode`, whatever the encoding used
+    pub fn decode(bytes: &[u8]) -> Result<Chunk, TcpError> {
+        let (code, data) = bytes
+            .split_first()
+            .ok_or(TcpError::InvalidChunk(ChunkDecodingError::MissingData))?;
+        let chunk = match Self::from_u8(*code) {
+            Some(ChunkEncoding::Plain) => Chunk::from_bytes(data),
+            Some(ChunkEncoding::Palette) => Chunk::from_palette_bytes(data),
+            None => return Err(TcpError::UnknownChunkEncoding(*code)),
+        };
+        chunk.map_err(TcpError::InvalidChunk)
+    }
+}
+
 /// A trait that an enum or a struct implement to be shared over the network.
 /// This trait can be used by `to_tcp_repr` to encode a message on our custom protocol.
 pub trait TcpSerialize {
@@ -20,21 +131,38 @@ pub trait TcpSerialize {
     fn to_u8(&self) -> u8;
     /// Returns the vector of bytes representing the object.
     fn to_bytes_representation(&self) -> Vec<u8>;
+    /// Returns the vector of bytes representing the object, using the given encoding for the
+    /// chunks. Only the types which contain chunks need to override it.
+    fn to_bytes_with_encoding(&self, _chunk_encoding: ChunkEncoding) -> Vec<u8> {
+        self.to_bytes_representation()
+    }
 }
 
 /// A trait that an enum or a struct implement to be shared over the network.
 /// This trait can be used by `to_tcp_repr` to decode a message on our custom protocol.
-pub trait TcpDeserialize {
-    fn parse_bytes_representation(code: u8, bytes_to_parse: &[u8]) -> Self;
+pub trait TcpDeserialize: Sized {
+    /// Decodes a message. This must never panic, whatever the bytes received.
+    fn parse_bytes_representation(code: u8, bytes_to_parse: &[u8]) -> Result<Self, TcpError>;
 }
 
 /// Given an object that can be serialized to our TCP protocol,
 /// returns the bytes message to be sent over the network
 pub fn to_tcp_repr<T: TcpSerialize>(object: &T) -> Vec<u8> {
-    let mut data = object.to_bytes_representation();
+    frame(object.to_u8(), object.to_bytes_representation())
+}
 
+/// Same as `to_tcp_repr`, using the chunk encoding negotiated with the peer
+pub fn to_tcp_repr_with_encoding<T: TcpSerialize>(
+    object: &T,
+    chunk_encoding: ChunkEncoding,
+) -> Vec<u8> {
+    frame(object.to_u8(), object.to_bytes_with_encoding(chunk_encoding))
+}
+
+/// Prefixes the data of a message with its header
+fn frame(code: u8, mut data: Vec<u8>) -> Vec<u8> {
     // First bytes contains the type
-    let mut data_to_send = vec![object.to_u8()];
+    let mut data_to_send = vec![code];
 
     // Second 4-bytes contain the length of the message
     let len = data.len() as u32;
@@ -47,105 +175,193 @@ pub fn to_tcp_repr<T: TcpSerialize>(object: &T) -> Vec<u8> {
     data_to_send
 }
 
+/// Holds the bytes received that do not yet form a complete message
 pub struct ParseContext {
     bytes: Vec<u8>,
-    code: u8,
-    len: usize,
 }
 
 impl ParseContext {
     pub fn new() -> Self {
-        Self {
-            bytes: vec![],
-            len: 0,
-            code: 0,
-        }
-    }
-
-    fn flush(&mut self) {
-        self.bytes = vec![];
-        self.len = 0;
-    }
-
-    fn store(&mut self, data: &[u8]) {
-        self.bytes.extend_from_slice(data)
+        Self { bytes: vec![] }
     }
 
-    fn is_empty(&self) -> bool {
-        self.bytes.is_empty()
-    }
-
-    fn set_message_len(&mut self, len: usize) {
-        self.len = len;
-    }
-
-    fn remaining_length_to_read(&self) -> usize {
-        self.len - self.bytes.len()
-    }
-
-    fn set_code(&mut self, code: u8) {
-        self.code = code;
+    /// Returns the code and the length of the message starting at the given offset, if its full
+    /// header was received.
+    fn header_at(&self, start: usize) -> Option<(u8, usize)> {
+        let header = self.bytes.get(start..start + HEADER_LEN)?;
+        let len = u32::from_le_bytes(header[1..].try_into().unwrap()) as usize;
+        Some((header[0], len))
     }
 }
 
+/// Decodes all the complete messages contained in the bytes received so far.
+/// The bytes of an incomplete message are kept in the context, until the next call.
 pub fn from_tcp_repr<T: TcpDeserialize>(
     bytes: &[u8],
     context: &mut ParseContext,
-) -> Result<Vec<T>, Box<dyn std::error::Error>> {
+) -> Result<Vec<T>, TcpError> {
+    context.bytes.extend_from_slice(bytes);
+
     let mut to_return = vec![];
     let mut start = 0;
+    while let Some((code, len)) = context.header_at(start) {
+        if len > MAX_MESSAGE_LEN {
+            return Err(TcpError::LengthError(len));
+        }
 
-    loop {
-        if bytes.len() < start + 5 {
+        // Wait for the rest of the message
+        let end = start + HEADER_LEN + len;
+        if end > context.bytes.len() {
             break;
         }
 
-        if context.is_empty() {
-            // It means it's a new message to be read.
-            // Read the header
-            // - type of the enum
-            // - length of the message being sent
-            let length_bytes: [u8; 4] = bytes
-                .get(start + 1..start + 5)
-                .ok_or(TcpError::LengthError)?
-                .try_into()?;
-            let len = u32::from_le_bytes(length_bytes) as usize;
-            let code = bytes[start];
-            context.set_code(code);
-            context.set_message_len(len);
-            start += 5;
-        }
+        to_return.push(T::parse_bytes_representation(
+            code,
+            &context.bytes[start + HEADER_LEN..end],
+        )?);
+        start = end;
+    }
 
-        let remaining = context.remaining_length_to_read();
+    context.bytes.drain(..start);
+    Ok(to_return)
+}
 
-        // This line is interesting for debugging.
-        // println!("[TCP] start = {start}, len = {}, size = {}, remaining = {}, code = {}", context.len, bytes.len(), remaining, context.code);
+/// Returns the bytes that a client must send first when connecting to a server, announcing the
+/// chunk encodings it supports and the digest of its definitions (see `definitions_digest`)
+pub fn handshake_request(supported: &[ChunkEncoding], digest: u64) -> Vec<u8> {
+    let mask = supported
+        .iter()
+        .fold(0_u8, |mask, encoding| mask | (1 << *encoding as u8));
+    [
+        HANDSHAKE_MAGIC.as_slice(),
+        &PROTOCOL_VERSION.to_le_bytes(),
+        &[mask],
+        &digest.to_le_bytes(),
+    ]
+    .concat()
+}
 
-        if start + remaining > bytes.len() {
-            // This means that the message sent was too small to be sent over 1 byte
-            // So we have to wait for the next message
-            context.store(&bytes[start..]);
+/// Checks the handshake received by the server, whose definitions have the given digest.
+/// Returns the answer to send to the client, and the chunk encoding to use if the connection can
+/// go on.
+pub fn answer_handshake(request: &[u8], digest: u64) -> (Vec<u8>, Result<ChunkEncoding, TcpError>) {
+    let result = match request.strip_prefix(HANDSHAKE_MAGIC.as_slice()) {
+        Some([v0, v1, mask, client_digest @ ..]) if client_digest.len() == 8 => {
+            let client = u16::from_le_bytes([*v0, *v1]);
+            if client != PROTOCOL_VERSION {
+                Err(TcpError::VersionMismatch {
+                    client,
+                    server: PROTOCOL_VERSION,
+                })
+            } else if client_digest != digest.to_le_bytes() {
+                Err(TcpError::DefinitionsMismatch)
+            } else {
+                // Pick the most efficient encoding supported by the client
+                ChunkEncoding::ALL
+                    .into_iter()
+                    .rev()
+                    .find(|encoding| mask & (1 << *encoding as u8) != 0)
+                    .ok_or(TcpError::InvalidHandshake)
+            }
+        }
+        _ => Err(TcpError::InvalidHandshake),
+    };
 
-            // Wait for next message
-            break;
-        } else {
-            context.store(&bytes[start..start + remaining]);
+    let (status, detail) = match &result {
+        Ok(encoding) => (RESPONSE_OK, *encoding as u8),
+        Err(TcpError::VersionMismatch { .. }) => (RESPONSE_ERROR, REJECTED_VERSION),
+        Err(TcpError::DefinitionsMismatch) => (RESPONSE_ERROR, REJECTED_DEFINITIONS),
+        Err(_) => (RESPONSE_ERROR, REJECTED_INVALID),
+    };
+    let version = PROTOCOL_VERSION.to_le_bytes();
+    (vec![status, version[0], version[1], detail], result)
+}
+
+/// Checks the answer of the server to the handshake of the client.
+/// Returns the chunk encoding chosen by the server.
+pub fn check_handshake_answer(answer: &[u8]) -> Result<ChunkEncoding, TcpError> {
+    match answer {
+        [RESPONSE_OK, _, _, encoding] => {
+            ChunkEncoding::from_u8(*encoding).ok_or(TcpError::InvalidHandshake)
         }
+        [RESPONSE_ERROR, v0, v1, REJECTED_VERSION] => Err(TcpError::VersionMismatch {
+            client: PROTOCOL_VERSION,
+            server: u16::from_le_bytes([*v0, *v1]),
+        }),
+        [RESPONSE_ERROR, _, _, REJECTED_DEFINITIONS] => Err(TcpError::DefinitionsMismatch),
+        _ => Err(TcpError::InvalidHandshake),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tcp_message_encoding::{
+        answer_handshake, check_handshake_answer, handshake_request, ChunkEncoding, TcpError,
+        PROTOCOL_VERSION,
+    };
+    use model::world::chunk::Chunk;
 
-        // Once we arrive here, we know that we can parse 1 message.
+    #[test]
+    fn test_handshake() {
+        let (answer, result) = answer_handshake(&handshake_request(&ChunkEncoding::ALL, 42), 42);
+        assert_eq!(Ok(ChunkEncoding::Palette), result);
+        assert_eq!(Ok(ChunkEncoding::Palette), check_handshake_answer(&answer));
+    }
+
+    #[test]
+    fn test_handshake_with_other_definitions() {
+        let (answer, result) = answer_handshake(&handshake_request(&ChunkEncoding::ALL, 42), 43);
+        assert_eq!(Err(TcpError::DefinitionsMismatch), result);
+        assert_eq!(Err(TcpError::DefinitionsMismatch), check_handshake_answer(&answer));
+    }
 
-        // Depending on the type of the enum, parse correctly the content
-        let parsed = T::parse_bytes_representation(context.code, &context.bytes);
-        to_return.push(parsed);
-        context.flush();
+    #[test]
+    fn test_handshake_negotiates_chunk_encoding() {
+        let request = handshake_request(&[ChunkEncoding::Plain], 0);
+        let (answer, result) = answer_handshake(&request, 0);
+        assert_eq!(Ok(ChunkEncoding::Plain), result);
+        assert_eq!(Ok(ChunkEncoding::Plain), check_handshake_answer(&answer));
 
-        // Increase the counter, in the case that there are several messaages to be parsed
-        // in the current packet.
-        start += remaining;
-        if start >= bytes.len() {
-            break;
+        // A client must support at least one encoding
+        let (answer, result) = answer_handshake(&handshake_request(&[], 0), 0);
+        assert_eq!(Err(TcpError::InvalidHandshake), result);
+        assert_eq!(Err(TcpError::InvalidHandshake), check_handshake_answer(&answer));
+    }
+
+    #[test]
+    fn test_chunk_encodings() {
+        let mut chunk = Chunk::new_for_demo([16., -8.], 3);
+        chunk.compute_visible_cubes();
+        for encoding in ChunkEncoding::ALL {
+            let bytes = encoding.encode(&chunk);
+            assert_eq!(Ok(chunk.clone()), ChunkEncoding::decode(&bytes));
         }
+        assert!(ChunkEncoding::decode(&[42, 0, 0]).is_err());
     }
 
-    Ok(to_return)
+    #[test]
+    fn test_handshake_with_old_client() {
+        // An old client also has other definitions, but the version is checked first
+        let mut request = handshake_request(&ChunkEncoding::ALL, 1);
+        request[4..6].copy_from_slice(&(PROTOCOL_VERSION - 1).to_le_bytes());
+
+        let expected = TcpError::VersionMismatch {
+            client: PROTOCOL_VERSION - 1,
+            server: PROTOCOL_VERSION,
+        };
+        let (answer, result) = answer_handshake(&request, 0);
+        assert_eq!(Err(expected), result);
+        let expected = TcpError::VersionMismatch {
+            client: PROTOCOL_VERSION,
+            server: PROTOCOL_VERSION,
+        };
+        assert_eq!(Err(expected), check_handshake_answer(&answer));
+    }
+
+    #[test]
+    fn test_invalid_handshake() {
+        let (answer, result) = answer_handshake(b"GET / HTTP/1.1\r\n", 0);
+        assert_eq!(Err(TcpError::InvalidHandshake), result);
+        assert_eq!(Err(TcpError::InvalidHandshake), check_handshake_answer(&answer));
+    }
 }
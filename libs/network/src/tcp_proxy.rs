@@ -6,12 +6,19 @@ use std::sync::{mpsc, Arc, Mutex};
 use std::{io, thread};
 use model::game::actions::Action;
 use model::game::attack::EntityAttack;
+use model::game::crafting::CraftingGrid;
+use model::game::definitions::definitions_digest;
+use model::game::player_items::PlayerItems;
 use primitives::position::Position;
 use model::server::server_update::ServerUpdate;
 use tracing::{error, info};
 use crate::message_to_server::MessageToServer;
 use crate::proxy::Proxy;
-use crate::tcp_message_encoding::{from_tcp_repr, to_tcp_repr, ParseContext};
+use crate::tcp_message_encoding::{
+    check_handshake_answer, from_tcp_repr, handshake_request, to_tcp_repr, ChunkEncoding,
+    ParseContext, HANDSHAKE_RESPONSE_LEN,
+};
+use crate::tcp_server::write_all_nonblocking;
 
 /// Function that handles the thread that
 /// - sends messages to server
@@ -29,20 +36,36 @@ fn handle_stream_with_server(
     loop {
         // Continuously read the bytes received by the server
         match stream.read(&mut data) {
-            Ok(size) => {
-                for update in from_tcp_repr(&data[0..size], &mut context).unwrap() {
-                    proxy.lock().unwrap().push_server_update(update);
-                }
+            Ok(0) => {
+                error!("The server closed the connection");
+                return;
             }
+            Ok(size) => match from_tcp_repr(&data[0..size], &mut context) {
+                Ok(updates) => {
+                    for update in updates {
+                        proxy.lock().unwrap().push_server_update(update);
+                    }
+                }
+                Err(err) => {
+                    error!("Invalid data received from the server, disconnecting: {err}");
+                    return;
+                }
+            },
             Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
-            Err(e) => error!("Failed to receive data: {}", e),
+            Err(e) => {
+                error!("Failed to receive data, disconnecting: {}", e);
+                return;
+            }
         }
 
         // Try to read if the WorldRenderer tried to communicate something to the server
         match updates_receiver.try_recv() {
             Ok(message) => {
                 // Send the message to the server
-                stream.write(to_tcp_repr(&message).as_slice()).unwrap();
+                if let Err(e) = write_all_nonblocking(&mut stream, &to_tcp_repr(&message)) {
+                    error!("Failed to send data, disconnecting: {}", e);
+                    return;
+                }
             }
             Err(_) => {}
         }
@@ -67,12 +90,9 @@ impl TcpProxy {
         }));
 
         // Start a stream on a new thread
-        match TcpStream::connect(server_address) {
+        match Self::connect(server_address) {
             Ok(stream) => {
                 info!("Successfully connected to server");
-                stream
-                    .set_nonblocking(true)
-                    .expect("Cannot set non-blocking");
                 let new_proxy = proxy.clone();
                 thread::spawn(move || handle_stream_with_server(stream, new_proxy, rx));
             }
@@ -84,6 +104,20 @@ impl TcpProxy {
         proxy
     }
 
+    /// Connects to the server and checks that it uses the same version of the protocol and the same
+    /// definitions
+    fn connect(server_address: &str) -> Result<TcpStream, Box<dyn std::error::Error>> {
+        let mut stream = TcpStream::connect(server_address)?;
+        stream.write_all(&handshake_request(&ChunkEncoding::ALL, definitions_digest()))?;
+        let mut answer = [0_u8; HANDSHAKE_RESPONSE_LEN];
+        stream.read_exact(&mut answer)?;
+        let chunk_encoding = check_handshake_answer(&answer)?;
+        info!("Chunks are received with the encoding {chunk_encoding:?}");
+
+        stream.set_nonblocking(true)?;
+        Ok(stream)
+    }
+
     /// Adds a server update to be read by the client
     pub fn push_server_update(&mut self, update: ServerUpdate) {
         self.pending_updates.push_back(update);
@@ -91,8 +125,11 @@ impl TcpProxy {
 }
 
 impl Proxy for TcpProxy {
-    fn login(&mut self, name: String) {
-        match self.updates_transmitter.send(MessageToServer::Login(name)) {
+    fn login(&mut self, name: String, view_distance: u8) {
+        match self
+            .updates_transmitter
+            .send(MessageToServer::Login(name, view_distance))
+        {
             Ok(_) => {}
             Err(err) => panic!("Error while logging in: {err}"),
         }
@@ -138,6 +175,54 @@ impl Proxy for TcpProxy {
         }
     }
 
+    fn select_item(&mut self, index: usize) {
+        match self
+            .updates_transmitter
+            .send(MessageToServer::SelectItem(index as u8))
+        {
+            Ok(_) => {}
+            Err(err) => error!("Error while sending: {err}"),
+        }
+    }
+
+    fn craft(&mut self, grid: CraftingGrid) {
+        match self.updates_transmitter.send(MessageToServer::Craft(grid)) {
+            Ok(_) => {}
+            Err(err) => error!("Error while sending: {err}"),
+        }
+    }
+
+    fn arrange_items(&mut self, items: PlayerItems) {
+        match self
+            .updates_transmitter
+            .send(MessageToServer::ArrangeItems(Box::new(items)))
+        {
+            Ok(_) => {}
+            Err(err) => error!("Error while sending: {err}"),
+        }
+    }
+
+    fn throw_item(&mut self) {
+        match self.updates_transmitter.send(MessageToServer::ThrowItem) {
+            Ok(_) => {}
+            Err(err) => error!("Error while sending: {err}"),
+        }
+    }
+
+    fn respawn(&mut self) {
+        match self.updates_transmitter.send(MessageToServer::Respawn) {
+            Ok(_) => {}
+            Err(err) => error!("Error while sending: {err}"),
+        }
+    }
+
+    fn save_world(&mut self) {
+        match self.updates_transmitter.send(MessageToServer::SaveWorld) {
+            Ok(_) => {}
+            Err(err) => error!("Error while sending: {err}"),
+        }
+    }
+
     fn consume_server_updates(&mut self) -> Vec<ServerUpdate> {
         // TODO change the API to get something that complies more with the circular buffer
         //      for instance returning an iterator that consumes the front of the queue ?
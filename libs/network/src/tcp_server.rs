@@ -3,10 +3,15 @@ use std::net::{Shutdown, TcpListener, TcpStream};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use std::{io, thread};
+use model::game::definitions::definitions_digest;
+use model::entity::entity::EntityId;
 use model::server::game_server::GameServer;
 use tracing::{error, info, warn};
 use crate::message_to_server::MessageToServer;
-use crate::tcp_message_encoding::{from_tcp_repr, to_tcp_repr, ParseContext};
+use crate::tcp_message_encoding::{
+    answer_handshake, from_tcp_repr, to_tcp_repr_with_encoding, ChunkEncoding, ParseContext,
+    HANDSHAKE_REQUEST_LEN,
+};
 
 pub struct TcpServer {}
 
@@ -19,8 +24,14 @@ impl TcpServer {
             .expect("Cannot set non-blocking");
         info!("Server is running: {}", listener.local_addr().unwrap());
 
-        // Accept connections and process them, spawning a new thread for each one
+        let mut clients = Vec::new();
+
+        // Accept connections and process them, spawning a new thread for each one, until the
+        // server is asked to shut down.
         for stream in listener.incoming() {
+            if game.lock().unwrap().is_shutting_down() {
+                break;
+            }
             match stream {
                 Ok(stream) => {
                     info!("New connection: {}", stream.peer_addr().unwrap());
@@ -32,11 +43,12 @@ impl TcpServer {
                     // Create a new thread that will handle the connection with this client
                     // Note that each client must be able to send messages back to the world
                     let new_game = game.clone();
-                    thread::spawn(move || handle_client(stream, new_game));
+                    clients.push(thread::spawn(move || handle_client(stream, new_game)));
                 }
                 Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
                     // In the non-blocking mode, this branch is called when the server is not receiving any new connections
                     // Read: https://doc.rust-lang.org/std/net/struct.TcpListener.html#method.set_nonblocking
+                    thread::sleep(Duration::from_millis(10));
                     continue;
                 }
                 Err(e) => {
@@ -48,10 +60,71 @@ impl TcpServer {
 
         // close the socket server
         drop(listener);
+
+        // Wait for all the connections to be closed
+        for client in clients {
+            let _ = client.join();
+        }
+        info!("Server stopped");
+    }
+}
+
+/// Reads the handshake of the client, and answers it.
+/// The connection must be closed if the client does not use the same version of the protocol, or
+/// does not have the same definitions.
+/// Returns the encoding to use for the chunks sent to this client.
+fn accept_handshake(stream: &mut TcpStream) -> Result<ChunkEncoding, Box<dyn std::error::Error>> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    let mut request = [0_u8; HANDSHAKE_REQUEST_LEN];
+    stream.read_exact(&mut request)?;
+    let (answer, result) = answer_handshake(&request, definitions_digest());
+    stream.write_all(&answer)?;
+    let chunk_encoding = result?;
+
+    stream.set_read_timeout(None)?;
+    stream.set_nonblocking(true)?;
+    Ok(chunk_encoding)
+}
+
+/// Writes all the bytes on a non-blocking stream, waiting whenever the socket is full.
+pub(crate) fn write_all_nonblocking(stream: &mut TcpStream, mut bytes: &[u8]) -> io::Result<()> {
+    while !bytes.is_empty() {
+        match stream.write(bytes) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => bytes = &bytes[n..],
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(1))
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    stream.flush()
+}
+
+/// Logs out the client, if it is logged in, and closes the connection
+fn disconnect(stream: &TcpStream, game: &Mutex<GameServer>, client_id: Option<EntityId>) {
+    if let Some(id) = client_id {
+        game.lock().unwrap().logout(id)
+    }
+    info!("Trying to safely shutdown...");
+    match stream.shutdown(Shutdown::Both) {
+        Ok(_) => info!("   ... Shutdown successfull"),
+        Err(err) => error!("   ... Error while closing socket: {err}"),
     }
 }
 
 fn handle_client(mut stream: TcpStream, game: Arc<Mutex<GameServer>>) {
+    let chunk_encoding = match accept_handshake(&mut stream) {
+        Ok(chunk_encoding) => chunk_encoding,
+        Err(err) => {
+            warn!("Handshake failed, closing the connection: {err}");
+            let _ = stream.shutdown(Shutdown::Both);
+            return;
+        }
+    };
+
     let mut data = [0_u8; 2_usize.pow(10)];
     let mut client_id = None;
     let mut context = ParseContext::new();
@@ -61,17 +134,29 @@ fn handle_client(mut stream: TcpStream, game: Arc<Mutex<GameServer>>) {
     // loop.
     loop {
         match stream.read(&mut data) {
+            Ok(0) => {
+                info!("Client {client_id:?} closed the connection");
+                disconnect(&stream, &game, client_id);
+                return;
+            }
             Ok(size) => {
                 // Read the messages sent by the client
                 match from_tcp_repr::<MessageToServer>(&data[0..size], &mut context) {
                     Ok(messages) => {
                         // For each message, create a response and send it to the client.
                         for message in messages {
+                            // Nothing can be done before the client is logged in
+                            let is_login = matches!(message, MessageToServer::Login(_, _));
+                            if client_id.is_none() && !is_login {
+                                warn!("Ignoring message sent before logging in: {message:?}");
+                                continue;
+                            }
+
                             match message {
-                                MessageToServer::Login(name) => {
-                                    let id = game.lock().unwrap().login(name) as u8;
+                                MessageToServer::Login(name, view_distance) => {
+                                    let id = game.lock().unwrap().login(name, view_distance);
                                     // The thread memorizes
-                                    client_id = Some(id as usize);
+                                    client_id = Some(id);
                                 }
                                 MessageToServer::OnNewPosition(new_pos) => {
                                     game.lock()
@@ -87,24 +172,42 @@ fn handle_client(mut stream: TcpStream, game: Arc<Mutex<GameServer>>) {
                                         .on_new_action(client_id.unwrap(), action);
                                 }
                                 MessageToServer::Attack(attack) => {
-                                    game.lock().unwrap().on_new_attack(attack);
+                                    game.lock()
+                                        .unwrap()
+                                        .on_new_attack(client_id.unwrap(), attack);
                                 }
                                 MessageToServer::SpawnRequest(position) => {
                                     game.lock().unwrap().spawn_monster(position);
                                 }
+                                MessageToServer::SelectItem(index) => {
+                                    game.lock()
+                                        .unwrap()
+                                        .on_select_item(client_id.unwrap(), index as usize);
+                                }
+                                MessageToServer::Craft(grid) => {
+                                    game.lock().unwrap().on_craft(client_id.unwrap(), grid);
+                                }
+                                MessageToServer::ArrangeItems(items) => {
+                                    game.lock()
+                                        .unwrap()
+                                        .on_arrange_items(client_id.unwrap(), *items);
+                                }
+                                MessageToServer::ThrowItem => {
+                                    game.lock().unwrap().on_throw_item(client_id.unwrap());
+                                }
+                                MessageToServer::Respawn => {
+                                    game.lock().unwrap().on_respawn(client_id.unwrap());
+                                }
+                                MessageToServer::SaveWorld => {
+                                    info!("Client {client_id:?} asked to save the world");
+                                    game.lock().unwrap().save_world();
+                                }
                             };
                         }
                     }
-                    Err(_) => {
-                        warn!("Error while communicating with client: {client_id:?}");
-                        if let Some(id) = client_id {
-                            game.lock().unwrap().logout(id)
-                        }
-                        info!("Trying to safely shutdown...");
-                        match stream.shutdown(Shutdown::Write) {
-                            Ok(_) => info!("   ... Shutdown successfull"),
-                            Err(err) => error!("   ... Error while closing socket: {err}"),
-                        }
+                    Err(err) => {
+                        warn!("Error while communicating with client {client_id:?}: {err}");
+                        disconnect(&stream, &game, client_id);
                         return;
                     }
                 }
@@ -112,12 +215,9 @@ fn handle_client(mut stream: TcpStream, game: Arc<Mutex<GameServer>>) {
             Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
                 // Read: https://doc.rust-lang.org/std/net/struct.TcpListener.html#method.set_nonblocking
             }
-            Err(_) => {
-                error!(
-                    "An error occurred, terminating connection with {}",
-                    stream.peer_addr().unwrap()
-                );
-                stream.shutdown(Shutdown::Both).unwrap();
+            Err(e) => {
+                error!("Error while receiving data from client {client_id:?}: {e}");
+                disconnect(&stream, &game, client_id);
                 return;
             }
         }
@@ -125,23 +225,73 @@ fn handle_client(mut stream: TcpStream, game: Arc<Mutex<GameServer>>) {
         // Check if the server has some updates to send to the client, and if so forward them !
         if let Some(id) = client_id {
             let updates = game.lock().unwrap().consume_updates(id);
-            if updates.len() > 0 {
-                for update in &updates {
-                    let msg = to_tcp_repr(update);
-                    match stream.write_all(msg.as_slice()) {
-                        Ok(_) => {}
-                        Err(e) => {
-                            error!("Error while sending message to client {client_id:?}: {e}");
-                            return;
-                        }
-                    }
-                    stream.flush().unwrap();
-
-                    if update.is_heavy() {
-                        thread::sleep(Duration::from_millis(50));
-                    }
+            if !updates.is_empty() {
+                let msg: Vec<u8> = updates
+                    .iter()
+                    .flat_map(|update| to_tcp_repr_with_encoding(update, chunk_encoding))
+                    .collect();
+                if let Err(e) = write_all_nonblocking(&mut stream, &msg) {
+                    error!("Error while sending message to client {client_id:?}: {e}");
+                    disconnect(&stream, &game, client_id);
+                    return;
                 }
             }
         }
+
+        // Once all the updates were sent, close the connection if the server is stopping
+        if game.lock().unwrap().is_shutting_down() {
+            info!("Closing the connection with client {client_id:?}");
+            disconnect(&stream, &game, client_id);
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::message_to_server::MessageToServer;
+    use crate::tcp_message_encoding::{
+        handshake_request, to_tcp_repr, ChunkEncoding, HANDSHAKE_RESPONSE_LEN,
+    };
+    use crate::tcp_server::handle_client;
+    use model::game::definitions::definitions_digest;
+    use model::server::game_server::GameServer;
+    use model::server::server_update::ServerUpdate;
+    use model::world::world::World;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_client_closing_the_connection_is_logged_out() {
+        let game = Arc::new(Mutex::new(GameServer::new(World::empty())));
+        let other = game.lock().unwrap().login("other".to_string(), 1);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let (done_tx, done_rx) = mpsc::channel();
+        let server_game = game.clone();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            handle_client(stream, server_game);
+            done_tx.send(()).unwrap();
+        });
+
+        let mut client = TcpStream::connect(address).unwrap();
+        let request = handshake_request(&ChunkEncoding::ALL, definitions_digest());
+        client.write_all(&request).unwrap();
+        client.read_exact(&mut [0; HANDSHAKE_RESPONSE_LEN]).unwrap();
+        let login = MessageToServer::Login("client".to_string(), 1);
+        client.write_all(&to_tcp_repr(&login)).unwrap();
+        drop(client);
+
+        // The thread of the client ends, and the other players see them leave
+        done_rx
+            .recv_timeout(Duration::from_secs(5))
+            .expect("The thread of the client is still running");
+        let updates = game.lock().unwrap().consume_updates(other);
+        assert!(updates.iter().any(|update| matches!(update, ServerUpdate::RemoveEntity(_))));
     }
 }
@@ -1,9 +1,9 @@
-use crate::vector::Vector3;
+use crate::vector::{read_floats, Vector3};
+use serde::{Deserialize, Serialize};
 use std::ops::{Add, AddAssign};
-use std::str::from_utf8;
 
 /// Position of an entity in a 3D world
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Position {
     pos: Vector3,
     yaw: f32,
@@ -19,14 +19,6 @@ impl Position {
         }
     }
 
-    pub fn spawn_position(y: f32) -> Self {
-        Self {
-            pos: Vector3::new(0., y, 0.),
-            yaw: 0.,
-            pitch: 0.,
-        }
-    }
-
     pub fn new(pos: Vector3, yaw: f32, pitch: f32) -> Self {
         Self { pos, yaw, pitch }
     }
@@ -56,35 +48,36 @@ impl Position {
         Vector3::new(self.yaw.cos(), 0., self.yaw.sin())
     }
 
+    /// Returns the normalized direction in which the entity looks
+    pub fn direction(&self) -> Vector3 {
+        Vector3::new(
+            self.yaw.cos() * self.pitch.cos(),
+            self.pitch.sin(),
+            self.yaw.sin() * self.pitch.cos(),
+        )
+    }
+
     pub fn ground_direction_right(&self) -> Vector3 {
         Vector3::new(self.yaw.sin(), 0., -self.yaw.cos())
     }
 
+    /// Encodes the position as 5 little-endian floats: x, y, z, yaw and pitch
     pub fn to_bytes(&self) -> Vec<u8> {
-        format!(
-            "{},{},{},{},{}",
-            self.pos.x(),
-            self.pos.y(),
-            self.pos.z(),
-            self.yaw,
-            self.pitch
-        )
-        .into_bytes()
+        let mut bytes = self.pos.to_bytes();
+        bytes.extend_from_slice(&self.yaw.to_le_bytes());
+        bytes.extend_from_slice(&self.pitch.to_le_bytes());
+        bytes
     }
 
-    pub fn from_bytes(bytes_to_parse: &[u8]) -> Self {
-        let text = from_utf8(bytes_to_parse).unwrap();
-        let mut iter = text.split(',');
-        let x = iter.next().unwrap().parse::<f32>().unwrap();
-        let y = iter.next().unwrap().parse::<f32>().unwrap();
-        let z = iter.next().unwrap().parse::<f32>().unwrap();
-        let yaw = iter.next().unwrap().parse::<f32>().unwrap();
-        let pitch = iter.next().unwrap().parse::<f32>().unwrap();
-        Self {
+    /// Decodes the representation computed by `to_bytes`.
+    /// Returns `None` if there are not enough bytes.
+    pub fn from_bytes(bytes_to_parse: &[u8]) -> Option<Self> {
+        let [x, y, z, yaw, pitch] = read_floats::<5>(bytes_to_parse)?;
+        Some(Self {
             pos: Vector3::new(x, y, z),
             yaw,
             pitch,
-        }
+        })
     }
 
     pub fn yaw(&self) -> f32 {
@@ -4,6 +4,10 @@ pub struct CubeRenderData {
     pub position: crate::vector::Vector3,
     pub block_id: u8,
     pub is_selected: bool,
+    /// Length of the edges of the cube, whose lowest corner is at `position`
+    pub scale: f32,
+    /// Layer of the texture array drawn on all the faces, instead of the textures of the block
+    pub texture_id: Option<i32>,
 }
 
 /// Backend-agnostic representation of an entity part to render
@@ -13,6 +17,8 @@ pub struct EntityRenderData {
     pub body_part_id: u8,
     pub monster_type: u8,
     pub scale: [f32; 3],
+    /// Tints the part in red, when the entity was just hurt
+    pub is_hurt: bool,
 }
 
 /// Backend-agnostic representation of a rectangle to render
@@ -26,7 +32,8 @@ pub struct RectRenderData {
     // TODO: I don't really like the fact that this `Rect` shader has this meta data to represent a font...
     pub is_font: bool,
     pub font_coords: Option<[f32; 2]>,
-    pub block_id: Option<i8>,
+    /// Layer of the texture array drawn in the rectangle (see `Item::texture_id`)
+    pub texture_id: Option<i32>,
 }
 
 impl RectRenderData {
@@ -52,7 +59,7 @@ impl RectRenderData {
             color,
             is_font: false,
             font_coords: None,
-            block_id: None,
+            texture_id: None,
         }
     }
 
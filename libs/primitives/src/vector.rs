@@ -1,7 +1,6 @@
 use serde::{Deserialize, Serialize};
 use std::fmt::{Debug, Formatter};
 use std::ops::{Add, AddAssign, Div, Index, IndexMut, Mul, Sub, SubAssign};
-use std::str::from_utf8;
 
 /// A vector in 3 coordinates
 ///
@@ -110,24 +109,44 @@ impl Vector3 {
         Vector3::new(self.x.floor(), self.y.floor(), self.z.floor())
     }
 
+    /// Encodes the vector as 3 little-endian floats
     pub fn to_bytes(&self) -> Vec<u8> {
-        format!("{},{},{}", self.x, self.y, self.z).into_bytes()
+        [self.x, self.y, self.z]
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
     }
 
-    pub fn from_bytes(bytes_to_parse: &[u8]) -> Self {
-        let text = from_utf8(bytes_to_parse).unwrap();
-        let mut pos = Vector3::empty();
-        for (i, part) in text.split(',').enumerate() {
-            pos[i] = part.parse::<f32>().unwrap();
-        }
-        pos
+    /// Decodes the representation computed by `to_bytes`.
+    /// Returns `None` if there are not enough bytes.
+    pub fn from_bytes(bytes_to_parse: &[u8]) -> Option<Self> {
+        let mut floats = read_floats::<3>(bytes_to_parse)?.into_iter();
+        Some(Self::new(floats.next()?, floats.next()?, floats.next()?))
+    }
+}
+
+/// Reads `N` little-endian floats at the start of the given bytes
+pub(crate) fn read_floats<const N: usize>(bytes: &[u8]) -> Option<[f32; N]> {
+    let mut floats = [0.; N];
+    for (i, float) in floats.iter_mut().enumerate() {
+        *float = f32::from_le_bytes(bytes.get(4 * i..4 * i + 4)?.try_into().ok()?);
     }
+    Some(floats)
 }
 
 #[cfg(test)]
 mod tests {
     use crate::vector::Vector3;
 
+    #[test]
+    fn test_bytes_representation() {
+        let v = Vector3::new(1.5, -2.25, 1000.125);
+        let bytes = v.to_bytes();
+        assert_eq!(12, bytes.len());
+        assert_eq!(Some(v), Vector3::from_bytes(&bytes));
+        assert_eq!(None, Vector3::from_bytes(&bytes[..11]));
+    }
+
     #[test]
     fn test_line_to() {
         let p0 = Vector3::new(0., 0., 0.);